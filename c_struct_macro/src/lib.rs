@@ -56,6 +56,35 @@ pub fn from_bytes_derive(input: proc_macro::TokenStream) -> proc_macro::TokenStr
         }
     });
 
+    let try_read_fields_bytes = fields.iter().map(|field| {
+        let ty = &field.ty;
+        let ident = field
+            .ident
+            .as_ref()
+            .expect("All fields must have an identifier");
+        let field_name = ident.to_string();
+        let read = if ty.to_token_stream().to_string() == "bool" {
+            quote! { safe_transmute::transmute_bool_pedantic(slice).unwrap()[0] }
+        } else {
+            quote! { transmute_field(slice) }
+        };
+        quote! {
+            #ident: {
+                let size = std::mem::size_of::<#ty>();
+                if bytes.len() < offset + size {
+                    return Err(TryFromBytesError {
+                        field: #field_name,
+                        offset,
+                        len: bytes.len(),
+                    });
+                }
+                let slice = &bytes[offset..(offset + size)];
+                offset += size;
+                #read
+            }
+        }
+    });
+
     let read_fields = fields.iter().map(|field| {
         let ty = &field.ty;
         let ident = field
@@ -121,6 +150,15 @@ pub fn from_bytes_derive(input: proc_macro::TokenStream) -> proc_macro::TokenStr
                 }
             }
 
+            /// Same as `from_bytes`, but returns an error naming the first field that doesn't
+            /// fit in `bytes` instead of panicking
+            pub fn try_from_bytes(bytes: &[u8]) -> Result<Self, TryFromBytesError> {
+                let mut offset = 0;
+                Ok(#name {
+                    #(#try_read_fields_bytes),*
+                })
+            }
+
             fn from_reader<T: Read>(reader: &mut T) -> Self {
                 #name {
                     #(#read_fields),*
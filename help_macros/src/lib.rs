@@ -84,6 +84,29 @@ pub fn construct_bytecode_instructions(input: proc_macro::TokenStream) -> proc_m
         }
     });
 
+    //operands indexing the string table are the ones named like string_table_index or
+    //pattern_string_index, and the ones indexing the function table function_table_index
+    let string_operands_tokens = operand_match_arms(&enum_name, &data, |name| {
+        name.contains("string") && name.ends_with("index")
+    });
+    let function_operands_tokens =
+        operand_match_arms(&enum_name, &data, |name| name == "function_table_index");
+
+    let operands_tokens = data.variants.iter().map(|variant| {
+        let variant_name = &variant.ident;
+        let fields: Vec<_> = variant
+            .fields
+            .iter()
+            .filter_map(|field| field.ident.as_ref())
+            .collect();
+        let names = fields.iter().map(|field| field.to_string());
+        quote! {
+            #enum_name::#variant_name { #(#fields),* } => {
+                vec![#((#names, Operand::from(*#fields))),*]
+            }
+        }
+    });
+
     proc_macro::TokenStream::from(quote! {
         impl InstructionSet for #enum_name {
             fn get_bytecode_size(opcode: u8) -> u8 {
@@ -100,6 +123,53 @@ pub fn construct_bytecode_instructions(input: proc_macro::TokenStream) -> proc_m
                     _ => panic!("Unhandled opcode: {}", opcode)
                 }
             }
+
+            fn string_operands(&self) -> Vec<u32> {
+                match self {
+                    #(#string_operands_tokens)*
+                    _ => Vec::new()
+                }
+            }
+
+            fn function_operands(&self) -> Vec<u32> {
+                match self {
+                    #(#function_operands_tokens)*
+                    _ => Vec::new()
+                }
+            }
+
+            fn operands(&self) -> Vec<(&'static str, Operand)> {
+                match self {
+                    #(#operands_tokens),*
+                }
+            }
         }
     })
 }
+
+//`Enum::Variant { a, b, .. } => vec![u32::from(*a), u32::from(*b)],` for every variant that has
+//fields whose name matches `is_operand`
+fn operand_match_arms(
+    enum_name: &syn::Ident,
+    data: &syn::DataEnum,
+    is_operand: impl Fn(&str) -> bool,
+) -> Vec<proc_macro2::TokenStream> {
+    data.variants
+        .iter()
+        .filter_map(|variant| {
+            let variant_name = &variant.ident;
+            let fields: Vec<_> = variant
+                .fields
+                .iter()
+                .filter_map(|field| field.ident.as_ref())
+                .filter(|name| is_operand(&name.to_string()))
+                .collect();
+            if fields.is_empty() {
+                return None;
+            }
+            Some(quote! {
+                #enum_name::#variant_name { #(#fields),*, .. } => vec![#(u32::from(*#fields)),*],
+            })
+        })
+        .collect()
+}
@@ -0,0 +1,51 @@
+#![feature(test)]
+
+extern crate test;
+
+use hermes_dec::{decompile_all, parse, DecompileOptions};
+use test::Bencher;
+
+#[path = "../tests/common/mod.rs"]
+mod common;
+
+const FUNCTIONS: usize = 64;
+const PROPERTIES: usize = 100;
+
+//FUNCTIONS functions, each reading PROPERTIES properties of the global object
+fn many_functions_bundle() -> Vec<u8> {
+    let names: Vec<String> = (0..PROPERTIES).map(|i| format!("property{i}")).collect();
+    let strings: Vec<&str> = names.iter().map(String::as_str).collect();
+    let mut bytecode = vec![48, 0]; //GetGlobalObject r0
+    for i in 0..PROPERTIES {
+        //GetById r1, r0, cache i, string i
+        bytecode.extend_from_slice(&[53, 1, 0, i as u8]);
+        bytecode.extend_from_slice(&(i as u16).to_le_bytes());
+    }
+    bytecode.extend_from_slice(&[90, 1]); //Ret r1
+    let functions: Vec<common::TestFunction> = (0..FUNCTIONS)
+        .map(|_| common::TestFunction {
+            name: 0,
+            param_count: 0,
+            bytecode: &bytecode,
+        })
+        .collect();
+    common::bundle_with_functions(&strings, &functions)
+}
+
+fn decompile_with_jobs(b: &mut Bencher, jobs: usize) {
+    let buf = many_functions_bundle();
+    let file = parse(&buf).unwrap();
+    let options = DecompileOptions::default();
+    b.iter(|| decompile_all(&file, &buf, &options, jobs));
+}
+
+#[bench]
+fn decompile_all_one_job(b: &mut Bencher) {
+    decompile_with_jobs(b, 1);
+}
+
+#[bench]
+fn decompile_all_all_cores(b: &mut Bencher) {
+    let jobs = std::thread::available_parallelism().map_or(1, |n| n.get());
+    decompile_with_jobs(b, jobs);
+}
@@ -0,0 +1,44 @@
+#![feature(test)]
+
+extern crate test;
+
+use hermes_dec::{decompile_function, parse};
+use test::Bencher;
+
+#[path = "../tests/common/mod.rs"]
+mod common;
+
+const PROPERTIES: usize = 200;
+
+//reads PROPERTIES properties of the global object, each named by its own string
+fn property_heavy_bundle() -> Vec<u8> {
+    let names: Vec<String> = (0..PROPERTIES).map(|i| format!("property{i}")).collect();
+    let strings: Vec<&str> = names.iter().map(String::as_str).collect();
+    let mut bytecode = vec![48, 0]; //GetGlobalObject r0
+    for i in 0..PROPERTIES {
+        //GetById r1, r0, cache i, string i
+        bytecode.extend_from_slice(&[53, 1, 0, i as u8]);
+        bytecode.extend_from_slice(&(i as u16).to_le_bytes());
+    }
+    bytecode.extend_from_slice(&[90, 1]); //Ret r1
+    common::bundle_with_function(&strings, 0, 0, &bytecode)
+}
+
+#[bench]
+fn get_string(b: &mut Bencher) {
+    let buf = property_heavy_bundle();
+    let file = parse(&buf).unwrap();
+    b.iter(|| {
+        (0..PROPERTIES as u32)
+            .filter_map(|index| file.get_string(index))
+            .map(str::len)
+            .sum::<usize>()
+    });
+}
+
+#[bench]
+fn decompile_property_reads(b: &mut Bencher) {
+    let buf = property_heavy_bundle();
+    let file = parse(&buf).unwrap();
+    b.iter(|| decompile_function(&file, &buf, 0).unwrap());
+}
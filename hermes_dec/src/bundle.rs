@@ -0,0 +1,38 @@
+use std::{fs::File, io::Read, ops::Deref, path::Path};
+
+/// The bytes of a bundle, either memory-mapped or read into memory.
+pub enum Bundle {
+    #[cfg(feature = "mmap")]
+    Mapped(memmap2::Mmap),
+    Read(Vec<u8>),
+}
+
+impl Deref for Bundle {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        match self {
+            #[cfg(feature = "mmap")]
+            Self::Mapped(mmap) => mmap,
+            Self::Read(buf) => buf,
+        }
+    }
+}
+
+/// Loads the bundle at `path`.
+///
+/// With the `mmap` feature the file is memory-mapped, which avoids keeping a copy of large
+/// bundles in memory. If mapping fails (or without the feature) the whole file is read instead.
+pub fn load<P: AsRef<Path>>(path: P) -> std::io::Result<Bundle> {
+    let mut file = File::open(path)?;
+    #[cfg(feature = "mmap")]
+    {
+        //the file must not be modified while it's mapped, same as with any other mmap user
+        if let Ok(mmap) = unsafe { memmap2::Mmap::map(&file) } {
+            return Ok(Bundle::Mapped(mmap));
+        }
+    }
+    let mut buf = Vec::new();
+    file.read_to_end(&mut buf)?;
+    Ok(Bundle::Read(buf))
+}
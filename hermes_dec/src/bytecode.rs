@@ -3,6 +3,64 @@ use std::io::Read;
 pub mod v93;
 
 pub trait InstructionSet {
+    /// Size of the operands of `opcode`, not counting the opcode byte itself
     fn get_bytecode_size(opcode: u8) -> u8;
     fn read_opcode<R: Read>(reader: &mut R) -> Self;
+    /// String table indices among the operands, e.g. the property name of `GetById`
+    fn string_operands(&self) -> Vec<u32>;
+    /// Function table indices among the operands, e.g. the closure `CreateClosure` creates
+    fn function_operands(&self) -> Vec<u32>;
+    /// Every operand in the order they are encoded in, with the name of its field
+    fn operands(&self) -> Vec<(&'static str, Operand)>;
+}
+
+/// The value of an operand, by the type it's encoded as
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Operand {
+    Unsigned(u32),
+    Signed(i32),
+    Double(f64),
+    Bool(bool),
+}
+
+impl From<u8> for Operand {
+    fn from(value: u8) -> Self {
+        Self::Unsigned(value.into())
+    }
+}
+
+impl From<u16> for Operand {
+    fn from(value: u16) -> Self {
+        Self::Unsigned(value.into())
+    }
+}
+
+impl From<u32> for Operand {
+    fn from(value: u32) -> Self {
+        Self::Unsigned(value)
+    }
+}
+
+impl From<i8> for Operand {
+    fn from(value: i8) -> Self {
+        Self::Signed(value.into())
+    }
+}
+
+impl From<i32> for Operand {
+    fn from(value: i32) -> Self {
+        Self::Signed(value)
+    }
+}
+
+impl From<f64> for Operand {
+    fn from(value: f64) -> Self {
+        Self::Double(value)
+    }
+}
+
+impl From<bool> for Operand {
+    fn from(value: bool) -> Self {
+        Self::Bool(value)
+    }
 }
@@ -1,6 +1,7 @@
-use super::InstructionSet;
+use super::{InstructionSet, Operand};
 use byteorder::{LittleEndian, ReadBytesExt};
 use help_macros::ByteCodeInstructions;
+use serde::Serialize;
 use std::io::Read;
 
 pub static JS_BUILTINS: [&str; 52] = [
@@ -59,7 +60,8 @@ pub static JS_BUILTINS: [&str; 52] = [
 ];
 
 #[repr(C)]
-#[derive(ByteCodeInstructions, Debug, Clone)]
+#[derive(ByteCodeInstructions, Debug, Clone, Serialize)]
+#[serde(tag = "opcode")]
 pub enum Instruction {
     Unreachable,
     NewObjectWithBuffer {
@@ -970,53 +972,65 @@ pub enum Instruction {
         arg1_reg: u8,
         arg2_reg: u8,
     },
+    //the asm.js heap opcodes, where the heap is a typed array and the index a byte address
     Loadi8 {
         dst_reg: u8,
-        _unused_reg: u8,
+        heap_reg: u8,
         heap_index_reg: u8,
     },
     Loadu8 {
         dst_reg: u8,
-        _unused_reg: u8,
+        heap_reg: u8,
         heap_index_reg: u8,
     },
     Loadi16 {
         dst_reg: u8,
-        _unused_reg: u8,
+        heap_reg: u8,
         heap_index_reg: u8,
     },
     Loadu16 {
         dst_reg: u8,
-        _unused_reg: u8,
+        heap_reg: u8,
         heap_index_reg: u8,
     },
     Loadi32 {
         dst_reg: u8,
-        _unused_reg: u8,
+        heap_reg: u8,
         heap_index_reg: u8,
     },
     Loadu32 {
         dst_reg: u8,
-        _unused_reg: u8,
+        heap_reg: u8,
         heap_index_reg: u8,
     },
     Store8 {
-        _unused_reg: u8,
+        heap_reg: u8,
         heap_index_reg: u8,
         value_reg: u8,
     },
     Store16 {
-        _unused_reg: u8,
+        heap_reg: u8,
         heap_index_reg: u8,
         value_reg: u8,
     },
     Store32 {
-        _unused_reg: u8,
+        heap_reg: u8,
         heap_index_reg: u8,
         value_reg: u8,
     },
 }
 
+impl Instruction {
+    /// The opcode's name, e.g. `Add`
+    pub fn name(&self) -> String {
+        let debug = format!("{self:?}");
+        match debug.find([' ', '{']) {
+            Some(end) => debug[..end].to_string(),
+            None => debug,
+        }
+    }
+}
+
 impl std::fmt::Display for Instruction {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
         write!(f, "{self:?}")
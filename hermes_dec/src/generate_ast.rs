@@ -1,18 +1,28 @@
-use std::collections::VecDeque;
+use std::{
+    borrow::Cow,
+    cell::{Cell, RefCell},
+    collections::{HashSet, VecDeque},
+    ops::Range,
+    rc::Rc,
+};
 
 use petgraph::{
     graph::EdgeReference,
     stable_graph::NodeIndex,
-    visit::{Bfs, Dfs, DfsPostOrder, EdgeRef, VisitMap},
+    visit::{Dfs, DfsPostOrder, EdgeRef, VisitMap},
     Graph,
 };
-use swc_common::DUMMY_SP;
+use swc_common::{
+    comments::{Comment, CommentKind, Comments, SingleThreadedComments},
+    BytePos, Span, DUMMY_SP,
+};
 use swc_ecma_ast::{
-    ArrayLit, AssignExpr, AssignOp, BinExpr, BinaryOp, BlockStmt, Bool, CallExpr, Callee,
-    ComputedPropName, CondExpr, ContinueStmt, DebuggerStmt, DoWhileStmt, Expr, ExprOrSpread,
-    ExprStmt, Ident, IfStmt, KeyValueProp, Lit, MemberExpr, MemberProp, NewExpr, Null, Number,
-    ObjectLit, ParenExpr, PatOrExpr, Prop, PropName, PropOrSpread, ReturnStmt, Stmt, Str,
-    ThrowStmt, UnaryExpr, UnaryOp, UpdateExpr, UpdateOp, WhileStmt,
+    ArrayLit, AssignExpr, AssignOp, BinExpr, BinaryOp, BlockStmt, Bool, BreakStmt, CallExpr,
+    Callee, ComputedPropName, CondExpr, ContinueStmt, DebuggerStmt, DoWhileStmt, EmptyStmt, Expr,
+    ExprOrSpread, ExprStmt, ForInStmt, ForOfStmt, ForStmt, Ident, IfStmt, KeyValueProp,
+    LabeledStmt, Lit, MemberExpr, MemberProp, NewExpr, Null, Number, ObjectLit, ParenExpr,
+    PatOrExpr, Prop, PropName, PropOrSpread, Regex, ReturnStmt, Stmt, Str, ThrowStmt, TryStmt,
+    UnaryExpr, UnaryOp, UpdateExpr, UpdateOp, WhileStmt,
 };
 
 use crate::{
@@ -23,17 +33,353 @@ use crate::{
 enum AstGeneratorStage {
     BeginProcessBlock,
     LoopCheck,
+    //waiting for the body of the loop in pending_loop
+    LoopBody,
     IfCheck,
+    //waiting for the consequent of the if in pending_if
+    IfAlt,
+    //waiting for the alternate of the if in pending_if
+    IfJoin,
     AfterIf,
+    //waiting for the protected block of the try in pending_try
+    TryBlock,
+    //waiting for the finally block of the try in pending_try
+    TryFinalizer,
     ProcessingDone,
 }
 
+//what populate_next_stage wants done next
+enum Progress<'a> {
+    Continue,
+    //generate this block's statements, then hand them back in child_result
+    Call(Box<BlockGenerator<'a>>),
+    Done,
+}
+
+//a loop whose body is being generated
+struct PendingLoop {
+    label: LoopLabel,
+    test: Expr,
+    is_do_while: bool,
+    //where the code after the loop starts, None if nothing after it is reached through it
+    exit: Option<NodeIndex>,
+    //the statements of a while loop's condition block, which run again before every continue
+    header: Vec<Stmt>,
+}
+
+/// What form a loop takes, going by its header (the block every iteration starts with), its
+/// latches (the blocks jumping back to the header) and its body (the blocks reaching a latch
+/// without passing through the header again). The first form that fits is taken.
+enum LoopShape {
+    /// `do { ... } while (test)`: the only latch ends in a conditional jump with one edge back to
+    /// the header and one out of the body. Other jumps back have to go through the latch, as
+    /// a continue in a do..while runs the test
+    PostTest {
+        test: Expr,
+        latch: NodeIndex,
+        exit: NodeIndex,
+    },
+    /// `while (test) { ... }`: the header ends in a conditional jump with one edge into the body
+    /// and one out of it. Latches are continues, whichever way they jump back
+    PreTest {
+        test: Expr,
+        body: NodeIndex,
+        exit: NodeIndex,
+    },
+    /// `while (true) { ... }`: there is no test, the loop is left by breaking out of it to the
+    /// earliest block after it, or by returning or throwing
+    Endless { exit: Option<NodeIndex> },
+}
+
+//a try whose blocks are being generated
+struct PendingTry {
+    region: TryFinally,
+    block: Option<Vec<Stmt>>,
+}
+
+//an if whose branches are being generated
+struct PendingIf {
+    test: Expr,
+    join: Option<NodeIndex>,
+    alt: NodeIndex,
+    cons: Option<(Vec<Stmt>, bool)>,
+}
+
+/// A limit [`Budget`] puts on generating a function
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Limit {
+    /// Control flow nested deeper than `max_depth`
+    Depth,
+    /// More than `max_nodes` blocks generated
+    Nodes,
+}
+
+impl std::fmt::Display for Limit {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Depth => write!(f, "nesting depth"),
+            Self::Nodes => write!(f, "block count"),
+        }
+    }
+}
+
+/// Bounds the work of generating a function, so unusual control flow can't overflow the stack
+/// (the passes and the emitter recurse into every level of nesting) or run for too long
+/// (blocks after ifs that don't rejoin get generated once per path reaching them).
+/// Once a limit is exceeded generators sharing the budget don't start on any more blocks.
+pub struct Budget {
+    max_depth: usize,
+    max_nodes: usize,
+    nodes: Cell<usize>,
+    exceeded: Cell<Option<Limit>>,
+}
+
+impl Budget {
+    pub fn new(max_depth: usize, max_nodes: usize) -> Self {
+        Self {
+            max_depth,
+            max_nodes,
+            nodes: Cell::new(0),
+            exceeded: Cell::new(None),
+        }
+    }
+
+    /// The limit that stopped generation, if any
+    pub fn exceeded(&self) -> Option<Limit> {
+        self.exceeded.get()
+    }
+
+    //accounts for generating a block at `depth`, returns false if over budget
+    fn spend(&self, depth: usize) -> bool {
+        self.nodes.set(self.nodes.get() + 1);
+        if self.exceeded.get().is_none() {
+            if depth > self.max_depth {
+                self.exceeded.set(Some(Limit::Depth));
+            } else if self.nodes.get() > self.max_nodes {
+                self.exceeded.set(Some(Limit::Nodes));
+            }
+        }
+        self.exceeded.get().is_none()
+    }
+}
+
+/// What a [`Diagnostic`] is about
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DiagnosticKind {
+    /// An instruction with no translation yet, emitted as an `unsupported("Name")` call
+    UnsupportedOpcode,
+    /// A jump structured control flow couldn't express, emitted as a `goto(offset)` call
+    UnstructuredJump,
+    /// A regexp literal with flags JS doesn't accept, emitted as it is
+    InvalidRegExpFlags,
+    /// A string index past the end of the string table, emitted as `__string{index}`
+    MissingString,
+}
+
+impl std::fmt::Display for DiagnosticKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::UnsupportedOpcode => write!(f, "unsupported opcode"),
+            Self::UnstructuredJump => write!(f, "unstructured jump"),
+            Self::InvalidRegExpFlags => write!(f, "invalid regexp flags"),
+            Self::MissingString => write!(f, "missing string"),
+        }
+    }
+}
+
+/// A place where the generated code doesn't faithfully match the bytecode
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub kind: DiagnosticKind,
+    pub function_id: usize,
+    /// Offset of the instruction from the start of the function's bytecode
+    pub offset: u32,
+    pub message: String,
+}
+
+impl std::fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "f{} @{:#X}: {}: {}",
+            self.function_id, self.offset, self.kind, self.message
+        )
+    }
+}
+
+/// Collects the [`Diagnostic`]s reported while generating function `function_id`
+pub struct Diagnostics {
+    function_id: usize,
+    reported: RefCell<Vec<Diagnostic>>,
+}
+
+impl Diagnostics {
+    pub fn new(function_id: usize) -> Self {
+        Self {
+            function_id,
+            reported: RefCell::new(Vec::new()),
+        }
+    }
+
+    pub fn into_vec(self) -> Vec<Diagnostic> {
+        self.reported.into_inner()
+    }
+
+    fn report(&self, kind: DiagnosticKind, offset: u32, message: String) {
+        self.reported.borrow_mut().push(Diagnostic {
+            kind,
+            function_id: self.function_id,
+            offset,
+            message,
+        });
+    }
+}
+
+#[derive(Clone)]
+struct LoopLabel {
+    label: String,
+    continue_block: Option<NodeIndex>,
+    break_block: Option<NodeIndex>,
+    //set when a nested loop jumps to this loop, so the loop statement needs the label
+    is_used: Rc<Cell<bool>>,
+}
+
+/// What the statements of a function are generated from
+#[derive(Clone, Copy)]
+pub struct FunctionBody<'a> {
+    pub f: &'a BytecodeFile,
+    pub cfg: &'a Graph<Vec<usize>, bool>,
+    pub instructions: &'a [InstructionInfo<Instruction>],
+    /// Number of registers of the function, call arguments are passed in the last ones
+    pub frame_size: u32,
+    /// The exception handlers generated as `try { } finally { }`
+    pub try_finally: &'a [TryFinally],
+}
+
+/// An exception handler generated as `try { } finally { }`: one that runs the finally block
+/// and throws what it caught again. The finally block is generated from the handler's copy of
+/// it, and the copy Hermes puts after the protected code, run when that doesn't throw, is
+/// skipped. Blocks of the cfg have to start at `start`, `end`, `finally.start` and `after`,
+/// and the handler's `Catch` at `finally.start - 1` has to be in it even though no jump
+/// leads there.
+#[derive(Clone, Debug)]
+pub struct TryFinally {
+    /// Index of the first protected instruction
+    pub start: usize,
+    /// Index of the instruction right past the protected ones
+    pub end: usize,
+    /// The instructions of the handler between its `Catch` and the `Throw` rethrowing
+    pub finally: Range<usize>,
+    /// The register the handler catches into
+    pub exception_reg: u8,
+    /// Index of the instruction after the copy of the finally block the protected code runs
+    /// into, None if the instructions there aren't a copy of it
+    pub after: Option<usize>,
+}
+
+/// Generates the statements of a function from its cfg.
+///
+/// Every nested loop body and if branch is generated by its own [`BlockGenerator`]. They are
+/// kept on a stack instead of calling each other, so deeply nested control flow takes heap
+/// rather than call stack.
 pub struct AstGenerator<'a> {
+    //the generator of the function's entry block first, then the ones generating the
+    //statements of a block of the one below them, with what they generated so far
+    frames: Vec<(Box<BlockGenerator<'a>>, Vec<Stmt>)>,
+}
+
+impl<'a> AstGenerator<'a> {
+    pub fn new(
+        body: FunctionBody<'a>,
+        node: NodeIndex, //if this isn't correct cfg node - this is an error
+        is_do_while_first_block: bool,
+        while_cond_block: Option<NodeIndex>,
+        do_while_cond_block: Option<NodeIndex>,
+    ) -> Self {
+        let root = BlockGenerator::new(
+            body,
+            node,
+            is_do_while_first_block,
+            while_cond_block,
+            do_while_cond_block,
+        );
+        Self {
+            frames: vec![(Box::new(root), Vec::new())],
+        }
+    }
+
+    /// Emits ProfilePoint instructions as empty statements with a `/* profile point N */` comment
+    /// added to `comments`, instead of dropping them
+    pub fn keep_profile_points(mut self, comments: &'a SingleThreadedComments) -> Self {
+        self.frames[0].0.profile_points = Some(comments);
+        self
+    }
+
+    /// Adds a trailing `// @0x1A Add` comment to `comments` for every statement with the offset
+    /// and name of the instruction it was generated from
+    pub fn annotate(mut self, comments: &'a SingleThreadedComments) -> Self {
+        self.frames[0].0.annotations = Some(comments);
+        self
+    }
+
+    /// Adds a trailing `/* cache index N */` comment to `comments` for every property read
+    /// by id, with the index of the inline cache slot the instruction uses
+    pub fn show_cache_indices(mut self, comments: &'a SingleThreadedComments) -> Self {
+        self.frames[0].0.cache_indices = Some(comments);
+        self
+    }
+
+    /// Refers to closures by the names `names` gives their functions, see [`function_ident`]
+    pub fn function_names(mut self, names: &'a dyn Fn(u32) -> Option<String>) -> Self {
+        self.frames[0].0.function_names = Some(names);
+        self
+    }
+
+    /// Reports what couldn't be translated faithfully to `diagnostics`
+    pub fn diagnostics(mut self, diagnostics: &'a Diagnostics) -> Self {
+        self.frames[0].0.diagnostics = Some(diagnostics);
+        self
+    }
+
+    /// Stops generating more blocks once `budget` is exceeded, leaving what was generated
+    pub fn budget(mut self, budget: &'a Budget) -> Self {
+        self.frames[0].0.budget = Some(budget);
+        self
+    }
+}
+
+impl Iterator for AstGenerator<'_> {
+    type Item = Stmt;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let is_root = self.frames.len() == 1;
+            let (generator, generated) = self.frames.last_mut().unwrap();
+            match generator.step() {
+                Step::Yield(stmt) if is_root => return Some(stmt),
+                Step::Yield(stmt) => generated.push(stmt),
+                Step::Call(child) => self.frames.push((child, Vec::new())),
+                Step::Done if is_root => return None,
+                Step::Done => {
+                    let (child, generated) = self.frames.pop().unwrap();
+                    let (generator, _) = self.frames.last_mut().unwrap();
+                    generator.child_result = Some((generated, child.is_last_instruction_return));
+                }
+            }
+        }
+    }
+}
+
+enum Step<'a> {
+    Yield(Stmt),
+    Call(Box<BlockGenerator<'a>>),
+    Done,
+}
+
+struct BlockGenerator<'a> {
     stmt_queue: VecDeque<Stmt>,
 
-    f: &'a BytecodeFile,
-    cfg: &'a Graph<Vec<usize>, bool>,
-    instructions: &'a [InstructionInfo<Instruction>],
+    body: FunctionBody<'a>,
     node: NodeIndex,
     is_do_while_first_block: bool,
     while_cond_block: Option<NodeIndex>,
@@ -41,17 +387,45 @@ pub struct AstGenerator<'a> {
 
     after_if_node: Option<NodeIndex>,
     stage: AstGeneratorStage,
+    //the statements of this block's instructions, held back until we know whether the block
+    //starts a loop, as a do..while generates them inside the loop instead
+    block_stmts: Vec<Stmt>,
+
+    chained_iterator: Option<Box<BlockGenerator<'a>>>,
+    pending_loop: Option<PendingLoop>,
+    pending_if: Option<PendingIf>,
+    pending_try: Option<PendingTry>,
+    //the statements of the block generated for pending_loop or pending_if, and whether the
+    //last of them returns
+    child_result: Option<(Vec<Stmt>, bool)>,
 
-    chained_iterator: Option<Box<AstGenerator<'a>>>,
+    //nodes that enclosing and preceding generators are already emitting
+    //reaching one of them again means the cfg couldn't be structured (e.g. irreducible loop)
+    path: HashSet<NodeIndex>,
+    //enclosing loops, innermost last
+    loops: Vec<LoopLabel>,
+    //where the branches of enclosing ifs reconverge, reaching one ends the branch
+    stop_nodes: Vec<NodeIndex>,
+    //where ProfilePoint instructions are written as comments, if they are kept
+    profile_points: Option<&'a SingleThreadedComments>,
+    //where the offset and name of the instruction each statement came from are written, if annotating
+    annotations: Option<&'a SingleThreadedComments>,
+    //where the inline cache indices of property reads are written, if showing them
+    cache_indices: Option<&'a SingleThreadedComments>,
+    //the names closures are referred to by, if functions are named
+    function_names: Option<&'a dyn Fn(u32) -> Option<String>>,
+    //where what couldn't be translated faithfully is reported, if anywhere
+    diagnostics: Option<&'a Diagnostics>,
+    //how many generators enclose this one
+    depth: usize,
+    budget: Option<&'a Budget>,
 
     is_last_instruction_return: bool,
 }
 
-impl<'a> AstGenerator<'a> {
-    pub fn new(
-        f: &'a BytecodeFile,
-        cfg: &'a Graph<Vec<usize>, bool>,
-        instructions: &'a [InstructionInfo<Instruction>],
+impl<'a> BlockGenerator<'a> {
+    fn new(
+        body: FunctionBody<'a>,
         node: NodeIndex, //if this isn't correct cfg node - this is an error
         is_do_while_first_block: bool,
         while_cond_block: Option<NodeIndex>,
@@ -59,24 +433,360 @@ impl<'a> AstGenerator<'a> {
     ) -> Self {
         Self {
             stmt_queue: VecDeque::new(),
-            f,
-            cfg,
-            instructions,
+            body,
             node,
             is_do_while_first_block,
             while_cond_block,
             do_while_cond_block,
             after_if_node: None,
             stage: AstGeneratorStage::BeginProcessBlock,
+            block_stmts: Vec::new(),
             chained_iterator: None,
+            pending_loop: None,
+            pending_if: None,
+            pending_try: None,
+            child_result: None,
+
+            path: HashSet::new(),
+            loops: Vec::new(),
+            stop_nodes: Vec::new(),
+            profile_points: None,
+            annotations: None,
+            cache_indices: None,
+            function_names: None,
+            diagnostics: None,
+            depth: 0,
+            budget: None,
 
             is_last_instruction_return: false,
         }
     }
 
-    fn populate_next_stage(&mut self) -> bool {
+    fn child(
+        &self,
+        node: NodeIndex,
+        is_do_while_first_block: bool,
+        while_cond_block: Option<NodeIndex>,
+        do_while_cond_block: Option<NodeIndex>,
+    ) -> Self {
+        let mut generator = Self::new(
+            self.body,
+            node,
+            is_do_while_first_block,
+            while_cond_block,
+            do_while_cond_block,
+        );
+        generator.path = self.path.clone();
+        generator.path.insert(self.node);
+        generator.loops = self.loops.clone();
+        generator.stop_nodes = self.stop_nodes.clone();
+        generator.profile_points = self.profile_points;
+        generator.annotations = self.annotations;
+        generator.cache_indices = self.cache_indices;
+        generator.function_names = self.function_names;
+        generator.diagnostics = self.diagnostics;
+        generator.depth = self.depth + 1;
+        generator.budget = self.budget;
+        generator
+    }
+
+    //the generator of the block following this one, which replaces it at the same depth
+    fn chained(
+        &self,
+        node: NodeIndex,
+        while_cond_block: Option<NodeIndex>,
+        do_while_cond_block: Option<NodeIndex>,
+    ) -> Self {
+        let mut generator = self.child(node, false, while_cond_block, do_while_cond_block);
+        generator.depth = self.depth;
+        generator
+    }
+
+    fn loop_body(
+        &self,
+        loop_label: &LoopLabel,
+        node: NodeIndex,
+        is_do_while_first_block: bool,
+        while_cond_block: Option<NodeIndex>,
+        do_while_cond_block: Option<NodeIndex>,
+    ) -> Self {
+        let mut body = self.child(
+            node,
+            is_do_while_first_block,
+            while_cond_block,
+            do_while_cond_block,
+        );
+        body.loops.push(loop_label.clone());
+        //falling off the end of the body continues the loop instead of leaving the if around it
+        body.stop_nodes.clear();
+        body
+    }
+
+    //the targets of the true and false edges of a block ending in a conditional jump
+    fn branches(&self, node: NodeIndex) -> Option<(NodeIndex, NodeIndex)> {
+        let mut tru = None;
+        let mut fals = None;
+        for edge in self
+            .body
+            .cfg
+            .edges_directed(node, petgraph::Direction::Outgoing)
+        {
+            if *edge.weight() {
+                tru = Some(edge.target());
+            } else {
+                fals = Some(edge.target());
+            }
+        }
+        tru.zip(fals)
+    }
+
+    //the blocks of the loop this block heads: the latches and the blocks reaching them without
+    //going through this block, out of the ones only reachable through it
+    fn natural_loop(
+        &self,
+        latches: &[NodeIndex],
+        dominated: &HashSet<NodeIndex>,
+    ) -> HashSet<NodeIndex> {
+        let mut body = HashSet::from([self.node]);
+        let mut stack = latches.to_vec();
+        while let Some(node) = stack.pop() {
+            if !body.insert(node) {
+                continue;
+            }
+            stack.extend(
+                self.body
+                    .cfg
+                    .neighbors_directed(node, petgraph::Direction::Incoming)
+                    .filter(|prev| dominated.contains(prev)),
+            );
+        }
+        body
+    }
+
+    fn loop_shape(&self, latches: &[NodeIndex], body: &HashSet<NodeIndex>) -> LoopShape {
+        let test = |node: NodeIndex| {
+            let last = self.body.cfg.node_weight(node).unwrap().last().unwrap();
+            jump_inst_to_test(&self.body.instructions[*last].instruction)
+        };
+        if let [latch] = *latches {
+            match self.branches(latch) {
+                Some((tru, exit)) if tru == self.node && !body.contains(&exit) => {
+                    return LoopShape::PostTest {
+                        test: test(latch),
+                        latch,
+                        exit,
+                    };
+                }
+                Some((exit, fals)) if fals == self.node && !body.contains(&exit) => {
+                    return LoopShape::PostTest {
+                        test: negate(test(latch)),
+                        latch,
+                        exit,
+                    };
+                }
+                _ => (),
+            }
+        }
+        if let Some((tru, fals)) = self.branches(self.node) {
+            match (body.contains(&tru), body.contains(&fals)) {
+                (false, true) => {
+                    return LoopShape::PreTest {
+                        test: negate(test(self.node)),
+                        body: fals,
+                        exit: tru,
+                    };
+                }
+                (true, false) => {
+                    return LoopShape::PreTest {
+                        test: test(self.node),
+                        body: tru,
+                        exit: fals,
+                    };
+                }
+                _ => (),
+            }
+        }
+        let exit = body
+            .iter()
+            .flat_map(|node| self.body.cfg.neighbors(*node))
+            .filter(|next| !body.contains(next))
+            .min_by_key(|next| self.body.cfg.node_weight(*next).unwrap()[0]);
+        LoopShape::Endless { exit }
+    }
+
+    fn block_starting_at(&self, index: usize) -> Option<NodeIndex> {
+        self.body
+            .cfg
+            .node_indices()
+            .find(|node| self.body.cfg[*node][0] == index)
+    }
+
+    //the try statement this block starts, if it's generated as one: the protected code has to
+    //either run into the copy of the finally block or never get past it
+    fn try_starting_here(&self) -> Option<&'a TryFinally> {
+        let first_index = self.body.cfg.node_weight(self.node).unwrap()[0];
+        self.body.try_finally.iter().find(|region| {
+            region.start == first_index
+                && (region.after.is_some() || self.block_starting_at(region.end).is_none())
+        })
+    }
+
+    //the protected block of a try this block starts, which carries on from the statements
+    //already generated for this block
+    fn try_block(&self, region: &TryFinally, block_stmts: Vec<Stmt>) -> Self {
+        let mut block = self.child(
+            self.node,
+            self.is_do_while_first_block,
+            self.while_cond_block,
+            self.do_while_cond_block,
+        );
+        block.stmt_queue.extend(block_stmts);
+        block.stage = AstGeneratorStage::IfCheck;
+        block.stop_nodes.extend(self.block_starting_at(region.end));
+        block
+    }
+
+    fn branch(&self, node: NodeIndex, join: Option<NodeIndex>) -> Self {
+        let mut branch = self.child(node, false, self.while_cond_block, self.do_while_cond_block);
+        branch.stop_nodes.extend(join);
+        branch
+    }
+
+    //blocks a branch can't continue past, since generating them emits a jump instead
+    fn is_jump_boundary(&self, node: NodeIndex) -> bool {
+        node == self.node
+            || self.path.contains(&node)
+            || self.while_cond_block == Some(node)
+            || self
+                .loops
+                .iter()
+                .any(|l| l.continue_block == Some(node) || l.break_block == Some(node))
+    }
+
+    //blocks where a branch ends by falling off the end of its statements
+    fn is_fall_off_boundary(&self, node: NodeIndex) -> bool {
+        self.stop_nodes.contains(&node) || self.do_while_cond_block == Some(node)
+    }
+
+    //the first block both branches of an if reach, if every other way out of the branches is a
+    //return, throw or explicit jump, so the branches can end there instead of each emitting it
+    fn find_join(&self, tru: NodeIndex, fals: NodeIndex) -> Option<NodeIndex> {
+        let reachable = |start: NodeIndex| {
+            let mut order = Vec::new();
+            let mut queue = VecDeque::from([start]);
+            let mut seen = HashSet::from([start]);
+            while let Some(node) = queue.pop_front() {
+                if self.is_jump_boundary(node) || self.stop_nodes.contains(&node) {
+                    continue;
+                }
+                order.push(node);
+                if self.do_while_cond_block == Some(node) {
+                    continue;
+                }
+                for next in self.body.cfg.neighbors(node) {
+                    if seen.insert(next) {
+                        queue.push_back(next);
+                    }
+                }
+            }
+            order
+        };
+        let from_tru = reachable(tru);
+        let from_fals = reachable(fals);
+        let join = from_tru
+            .iter()
+            .zip(&from_fals)
+            .flat_map(|(a, b)| [*a, *b])
+            .chain(from_tru.iter().skip(from_fals.len()).copied())
+            .chain(from_fals.iter().skip(from_tru.len()).copied())
+            .find(|node| from_tru.contains(node) && from_fals.contains(node))?;
+
+        //a branch that could fall off somewhere else would continue at the join instead
+        let mut stack = vec![tru, fals];
+        let mut seen = HashSet::from([tru, fals]);
+        while let Some(node) = stack.pop() {
+            if node == join || self.is_jump_boundary(node) {
+                continue;
+            }
+            if self.is_fall_off_boundary(node) {
+                return None;
+            }
+            for next in self.body.cfg.neighbors(node) {
+                if seen.insert(next) {
+                    stack.push(next);
+                }
+            }
+        }
+        Some(join)
+    }
+
+    fn push_if(&mut self, test: Expr, cons: Vec<Stmt>, alt: Vec<Stmt>) {
+        let (test, cons, alt) = match (cons.is_empty(), alt.is_empty()) {
+            (true, true) => return,
+            (true, false) => (negate(test), alt, Vec::new()),
+            _ => (test, cons, alt),
+        };
+        let block = |stmts| {
+            Box::new(Stmt::Block(BlockStmt {
+                span: DUMMY_SP,
+                stmts,
+            }))
+        };
+        self.stmt_queue.push_back(Stmt::If(IfStmt {
+            span: DUMMY_SP,
+            test: Box::new(test),
+            cons: block(cons),
+            alt: (!alt.is_empty()).then(|| block(alt)),
+        }));
+    }
+
+    //if the current block is where an enclosing loop continues or breaks to, we emit that jump instead of the block
+    //jumps to anything but the innermost loop need the label of the loop
+    fn loop_jump(&self) -> Option<Stmt> {
+        //a while (true) loop continues to the block its body starts with, generated here
+        if self.is_do_while_first_block {
+            return None;
+        }
+        for (depth, loop_label) in self.loops.iter().enumerate().rev() {
+            let is_continue = loop_label.continue_block == Some(self.node);
+            if !is_continue && loop_label.break_block != Some(self.node) {
+                continue;
+            }
+            let label = if depth == self.loops.len() - 1 {
+                None
+            } else {
+                loop_label.is_used.set(true);
+                Some(Ident::new(loop_label.label.as_str().into(), DUMMY_SP))
+            };
+            return Some(if is_continue {
+                Stmt::Continue(ContinueStmt {
+                    span: DUMMY_SP,
+                    label,
+                })
+            } else {
+                Stmt::Break(BreakStmt {
+                    span: DUMMY_SP,
+                    label,
+                })
+            });
+        }
+        None
+    }
+
+    fn populate_next_stage(&mut self) -> Progress<'a> {
         match self.stage {
             AstGeneratorStage::BeginProcessBlock => {
+                if self.budget.is_some_and(|budget| !budget.spend(self.depth)) {
+                    self.stage = AstGeneratorStage::ProcessingDone;
+                    return Progress::Done;
+                }
+
+                if self.stop_nodes.contains(&self.node) {
+                    //the enclosing if emits this block after both of its branches
+                    self.stage = AstGeneratorStage::ProcessingDone;
+                    return Progress::Continue;
+                }
+
                 if let Some(while_cond_block) = self.while_cond_block {
                     //this means that we got redirected through a jump back to a while loop condition
                     //we don't need to decompile anything at this point so we just return a "continue;"
@@ -86,13 +796,63 @@ impl<'a> AstGenerator<'a> {
                             label: None,
                         }));
                         self.stage = AstGeneratorStage::ProcessingDone;
-                        return false;
+                        return Progress::Continue;
+                    }
+                }
+
+                if let Some(jump) = self.loop_jump() {
+                    self.stmt_queue.push_back(jump);
+                    self.stage = AstGeneratorStage::ProcessingDone;
+                    return Progress::Continue;
+                }
+
+                if self.path.contains(&self.node) && !self.is_do_while_first_block {
+                    //we can't express this jump with structured control flow so we fall back to a goto
+                    //to the offset of the block instead of generating it again forever
+                    let first_index = self.body.cfg.node_weight(self.node).unwrap()[0];
+                    let offset = self.body.instructions[first_index].offset;
+                    if let Some(diagnostics) = self.diagnostics {
+                        diagnostics.report(
+                            DiagnosticKind::UnstructuredJump,
+                            offset,
+                            format!("jump to {offset:#X} emitted as a goto"),
+                        );
                     }
+                    self.stmt_queue.push_back(Stmt::Expr(ExprStmt {
+                        span: DUMMY_SP,
+                        expr: Box::new(Expr::Call(CallExpr {
+                            span: DUMMY_SP,
+                            callee: Callee::Expr(Box::new(Expr::Ident(Ident {
+                                span: DUMMY_SP,
+                                sym: "goto".into(),
+                                optional: false,
+                            }))),
+                            args: vec![ExprOrSpread {
+                                spread: None,
+                                expr: Box::new(Expr::Lit(Lit::Num(Number {
+                                    span: DUMMY_SP,
+                                    value: f64::from(offset),
+                                    raw: None,
+                                }))),
+                            }],
+                            type_args: None,
+                        })),
+                    }));
+                    self.stage = AstGeneratorStage::ProcessingDone;
+                    return Progress::Continue;
                 }
 
-                self.stmt_queue.append(
-                    &mut simple_instructions_to_ast(self.f, self.cfg, self.node, self.instructions)
-                        .into(),
+                self.block_stmts = simple_instructions_to_ast(
+                    self.body.f,
+                    self.body.cfg,
+                    self.node,
+                    self.body.instructions,
+                    self.body.frame_size,
+                    self.profile_points,
+                    self.annotations,
+                    self.cache_indices,
+                    self.function_names,
+                    self.diagnostics,
                 );
 
                 if self.do_while_cond_block.is_some()
@@ -100,169 +860,170 @@ impl<'a> AstGenerator<'a> {
                 {
                     //we reached the end of a do..while loop statement so we just put decompiled statements in that block into stmts
                     //and then don't check for loops as it'll throw us in an infinite loop
+                    self.stmt_queue
+                        .extend(std::mem::take(&mut self.block_stmts));
                     self.stage = AstGeneratorStage::ProcessingDone;
-                    return false;
+                    return Progress::Continue;
                 }
                 self.stage = AstGeneratorStage::LoopCheck;
-                true
+                Progress::Continue
             }
             AstGeneratorStage::LoopCheck => {
-                let indecies = self.cfg.node_weight(self.node).unwrap();
-                let flow_index = indecies.last().unwrap();
+                let block_stmts = std::mem::take(&mut self.block_stmts);
                 let incoming_edges = self
+                    .body
                     .cfg
                     .edges_directed(self.node, petgraph::Direction::Incoming)
                     .collect::<Vec<EdgeReference<'_, bool>>>();
-                if !self.is_do_while_first_block && incoming_edges.len() >= 2 {
+                //the header of a loop is also entered from before it, unless the function
+                //starts with the loop
+                let is_entry = self.node == NodeIndex::new(0);
+                let min_incoming = if is_entry { 1 } else { 2 };
+                if !self.is_do_while_first_block && incoming_edges.len() >= min_incoming {
                     //is_do_while_first_block -> prevent going into a loop
                     //either loop or "if target"
                     let edges_from = incoming_edges
                         .iter()
                         .map(|e| e.source())
                         .collect::<Vec<NodeIndex>>();
-                    let mut dfs = DfsPostOrder::new(self.cfg, self.node);
-                    {
-                        let mut dfs_a = Dfs::new(self.cfg, NodeIndex::new(0));
+                    let mut dfs = DfsPostOrder::new(self.body.cfg, self.node);
+                    if !is_entry {
+                        let mut dfs_a = Dfs::new(self.body.cfg, NodeIndex::new(0));
                         dfs_a.discovered.visit(self.node);
-                        while let Some(node) = dfs_a.next(self.cfg) {
+                        while let Some(node) = dfs_a.next(self.body.cfg) {
                             dfs.discovered.visit(node);
                             dfs.finished.visit(node);
                         }
                     }
 
-                    let mut is_loop = false;
-                    let mut possible_loop_condition_index = None;
-                    while let Some(node) = dfs.next(self.cfg) {
+                    //the dfs only reaches the blocks that can't be reached without this one
+                    let mut dominated = HashSet::new();
+                    let mut latches = Vec::new();
+                    while let Some(node) = dfs.next(self.body.cfg) {
+                        dominated.insert(node);
                         if edges_from.contains(&node) {
-                            is_loop = true;
-                            possible_loop_condition_index = Some(node);
-                            break;
+                            latches.push(node);
                         }
                     }
 
-                    if is_loop {
-                        let cond_index = self
-                            .cfg
-                            .node_weight(possible_loop_condition_index.unwrap())
-                            .unwrap()
-                            .last()
-                            .unwrap();
-                        let mut skip_do_while_check = false;
-                        if let Instruction::Jmp { .. } = &self.instructions[*cond_index].instruction
-                        {
-                            skip_do_while_check = true; //can't be do_while if ends with jmp
-                        }
-                        let (index, loop_cond_index) = if skip_do_while_check {
-                            (*flow_index, self.node)
-                        } else {
-                            (*cond_index, possible_loop_condition_index.unwrap())
+                    if !latches.is_empty() {
+                        let body = self.natural_loop(&latches, &dominated);
+                        let loop_label = |continue_block, break_block| LoopLabel {
+                            label: format!("loop{}", self.node.index()),
+                            continue_block,
+                            break_block,
+                            is_used: Rc::new(Cell::new(false)),
                         };
-
-                        let cond = jump_inst_to_test(&self.instructions[index].instruction);
-                        let outgoing_edges = self
-                            .cfg
-                            .edges_directed(loop_cond_index, petgraph::Direction::Outgoing)
-                            .collect::<Vec<EdgeReference<'_, bool>>>();
-                        let (tru, fals) = {
-                            let mut tru = None;
-                            let mut fals = None;
-                            for edge in &outgoing_edges {
-                                if *edge.weight() {
-                                    tru = Some(edge);
-                                } else {
-                                    fals = Some(edge);
-                                }
+                        let (label, body, pending) = match self.loop_shape(&latches, &body) {
+                            LoopShape::PostTest { test, latch, exit } => {
+                                let label = loop_label(None, Some(exit));
+                                //the body starts with this block, generating its statements
+                                //again
+                                let body =
+                                    self.loop_body(&label, self.node, true, None, Some(latch));
+                                (label, body, (test, true, Some(exit), Vec::new()))
                             }
-                            (tru.unwrap(), fals.unwrap())
-                        };
-                        if tru.target() == self.node {
-                            //do..while
-                            let body = AstGenerator::new(
-                                self.f,
-                                self.cfg,
-                                self.instructions,
-                                self.node,
-                                true,
-                                None,
-                                Some(possible_loop_condition_index.unwrap()),
-                            )
-                            .collect::<Vec<Stmt>>();
-                            if indecies.len() > 1 {
-                                //add_inside_while(&mut body, &stmts)
+                            LoopShape::PreTest { test, body, exit } => {
+                                let label = loop_label(Some(self.node), Some(exit));
+                                let body = self.loop_body(
+                                    &label,
+                                    body,
+                                    false,
+                                    Some(self.node),
+                                    self.do_while_cond_block,
+                                );
+                                //the header runs before the loop and before every continue of it
+                                self.stmt_queue.extend(block_stmts.iter().cloned());
+                                (label, body, (test, false, Some(exit), block_stmts))
                             }
-                            self.stmt_queue.push_back(Stmt::DoWhile(DoWhileStmt {
-                                span: DUMMY_SP,
-                                test: Box::new(Expr::Paren(ParenExpr {
-                                    span: DUMMY_SP,
-                                    expr: Box::new(cond),
-                                })),
-                                body: Box::new(Stmt::Block(BlockStmt {
+                            LoopShape::Endless { exit } => {
+                                let label = loop_label(Some(self.node), exit);
+                                //the body starts with this block like a do..while's
+                                let body = self.loop_body(
+                                    &label,
+                                    self.node,
+                                    true,
+                                    None,
+                                    self.do_while_cond_block,
+                                );
+                                let test = Expr::Lit(Lit::Bool(Bool {
                                     span: DUMMY_SP,
-                                    stmts: body,
-                                })),
-                            }));
-                            self.chained_iterator = Some(Box::new(AstGenerator::new(
-                                self.f,
-                                self.cfg,
-                                self.instructions,
-                                fals.target(),
-                                false,
-                                None,
-                                None,
-                            )));
-                        } else {
-                            //while..do
-                            let mut body = AstGenerator::new(
-                                self.f,
-                                self.cfg,
-                                self.instructions,
-                                fals.target(),
-                                false,
-                                Some(self.node),
-                                self.do_while_cond_block,
-                            )
-                            .collect::<Vec<Stmt>>();
-                            if indecies.len() > 1 {
-                                add_inside_while(&mut body, &self.stmt_queue)
+                                    value: true,
+                                }));
+                                (label, body, (test, false, exit, Vec::new()))
                             }
-                            self.stmt_queue.push_back(Stmt::While(WhileStmt {
-                                span: DUMMY_SP,
-                                test: Box::new(Expr::Unary(UnaryExpr {
-                                    span: DUMMY_SP,
-                                    op: UnaryOp::Bang,
-                                    arg: Box::new(Expr::Paren(ParenExpr {
-                                        span: DUMMY_SP,
-                                        expr: Box::new(cond),
-                                    })),
-                                })),
-                                body: Box::new(Stmt::Block(BlockStmt {
-                                    span: DUMMY_SP,
-                                    stmts: body,
-                                })),
-                            }));
-                            self.chained_iterator = Some(Box::new(AstGenerator::new(
-                                self.f,
-                                self.cfg,
-                                self.instructions,
-                                tru.target(),
-                                false,
-                                None,
-                                self.do_while_cond_block,
-                            )));
-                        }
-
-                        self.stage = AstGeneratorStage::ProcessingDone;
-                        return true;
+                        };
+                        let (test, is_do_while, exit, header) = pending;
+                        self.pending_loop = Some(PendingLoop {
+                            label,
+                            test,
+                            is_do_while,
+                            exit,
+                            header,
+                        });
+                        self.stage = AstGeneratorStage::LoopBody;
+                        return Progress::Call(Box::new(body));
                     }
                 }
 
+                if let Some(region) = self.try_starting_here() {
+                    let block = self.try_block(region, block_stmts);
+                    self.pending_try = Some(PendingTry {
+                        region: region.clone(),
+                        block: None,
+                    });
+                    self.stage = AstGeneratorStage::TryBlock;
+                    return Progress::Call(Box::new(block));
+                }
+
+                self.stmt_queue.extend(block_stmts);
                 self.stage = AstGeneratorStage::IfCheck;
-                true
+                Progress::Continue
+            }
+            AstGeneratorStage::LoopBody => {
+                let (mut body, _) = self.child_result.take().unwrap();
+                let pending = self.pending_loop.take().unwrap();
+                let loop_stmt = if pending.is_do_while {
+                    Stmt::DoWhile(DoWhileStmt {
+                        span: DUMMY_SP,
+                        test: Box::new(pending.test),
+                        body: Box::new(Stmt::Block(BlockStmt {
+                            span: DUMMY_SP,
+                            stmts: body,
+                        })),
+                    })
+                } else {
+                    if !pending.header.is_empty() {
+                        add_inside_while(&mut body, &pending.header, &pending.label.label)
+                    }
+                    Stmt::While(WhileStmt {
+                        span: DUMMY_SP,
+                        test: Box::new(pending.test),
+                        body: Box::new(Stmt::Block(BlockStmt {
+                            span: DUMMY_SP,
+                            stmts: body,
+                        })),
+                    })
+                };
+                self.stmt_queue
+                    .push_back(label_loop(&pending.label, loop_stmt));
+                let do_while_cond_block = if pending.is_do_while {
+                    None
+                } else {
+                    self.do_while_cond_block
+                };
+                if let Some(exit) = pending.exit {
+                    self.chained_iterator =
+                        Some(Box::new(self.chained(exit, None, do_while_cond_block)));
+                }
+                self.stage = AstGeneratorStage::ProcessingDone;
+                Progress::Continue
             }
             AstGeneratorStage::IfCheck => {
-                let indecies = self.cfg.node_weight(self.node).unwrap();
+                let indecies = self.body.cfg.node_weight(self.node).unwrap();
                 let flow_index = indecies.last().unwrap();
                 let outgoing_edges = self
+                    .body
                     .cfg
                     .edges_directed(self.node, petgraph::Direction::Outgoing)
                     .collect::<Vec<EdgeReference<'_, bool>>>();
@@ -282,150 +1043,19 @@ impl<'a> AstGenerator<'a> {
                         (tru.unwrap(), fals.unwrap())
                     };
 
-                    let mut skip_else_false = false;
-                    let mut skip_else_true = false;
-                    {
-                        let mut bfs = Bfs::new(self.cfg, fals.target());
-                        while let Some(node) = bfs.next(self.cfg) {
-                            if tru.target() == node {
-                                skip_else_false = true;
-                                break;
-                            }
-                        }
-
-                        //other way around
-                        if !skip_else_false {
-                            let mut bfs = Bfs::new(self.cfg, tru.target());
-                            while let Some(node) = bfs.next(self.cfg) {
-                                if fals.target() == node {
-                                    skip_else_true = true;
-                                    break;
-                                }
-                            }
-                        }
-                    }
-
-                    if skip_else_false {
-                        self.stmt_queue.push_back(Stmt::If(IfStmt {
-                            span: DUMMY_SP,
-                            test: Box::new(jump_inst_to_test(
-                                &self.instructions[*flow_index].instruction,
-                            )),
-                            cons: Box::new(Stmt::Block(BlockStmt {
-                                span: DUMMY_SP,
-                                stmts: AstGenerator::new(
-                                    self.f,
-                                    self.cfg,
-                                    self.instructions,
-                                    tru.target(),
-                                    false,
-                                    self.while_cond_block,
-                                    self.do_while_cond_block,
-                                )
-                                .collect(),
-                            })),
-                            alt: None,
-                        }));
-                        self.after_if_node = Some(fals.target());
-                        self.stage = AstGeneratorStage::AfterIf;
-                    } else if skip_else_true {
-                        self.stmt_queue.push_back(Stmt::If(IfStmt {
-                            span: DUMMY_SP,
-                            test: Box::new(Expr::Unary(UnaryExpr {
-                                //revert the if condition
-                                span: DUMMY_SP,
-                                op: UnaryOp::Bang,
-                                arg: Box::new(Expr::Paren(ParenExpr {
-                                    span: DUMMY_SP,
-                                    expr: Box::new(jump_inst_to_test(
-                                        &self.instructions[*flow_index].instruction,
-                                    )),
-                                })),
-                            })),
-                            cons: Box::new(Stmt::Block(BlockStmt {
-                                span: DUMMY_SP,
-                                stmts: AstGenerator::new(
-                                    self.f,
-                                    self.cfg,
-                                    self.instructions,
-                                    fals.target(),
-                                    false,
-                                    self.while_cond_block,
-                                    self.do_while_cond_block,
-                                )
-                                .collect(),
-                            })),
-                            alt: None,
-                        }));
-                        self.after_if_node = Some(tru.target());
-                        self.stage = AstGeneratorStage::AfterIf;
-                    } else {
-                        let mut cons_gen = AstGenerator::new(
-                            self.f,
-                            self.cfg,
-                            self.instructions,
-                            tru.target(),
-                            false,
-                            self.while_cond_block,
-                            self.do_while_cond_block,
-                        );
-                        let cons_stmts = (&mut cons_gen).collect();
-                        if cons_gen.is_last_instruction_return {
-                            self.stmt_queue.push_back(Stmt::If(IfStmt {
-                                span: DUMMY_SP,
-                                test: Box::new(jump_inst_to_test(
-                                    &self.instructions[*flow_index].instruction,
-                                )),
-                                cons: Box::new(Stmt::Block(BlockStmt {
-                                    span: DUMMY_SP,
-                                    stmts: cons_stmts,
-                                })),
-                                alt: None,
-                            }));
-                            self.chained_iterator = Some(Box::new(AstGenerator::new(
-                                self.f,
-                                self.cfg,
-                                self.instructions,
-                                fals.target(),
-                                false,
-                                self.while_cond_block,
-                                self.do_while_cond_block,
-                            )));
-                        } else {
-                            self.stmt_queue.push_back(Stmt::If(IfStmt {
-                                span: DUMMY_SP,
-                                test: Box::new(jump_inst_to_test(
-                                    &self.instructions[*flow_index].instruction,
-                                )),
-                                cons: Box::new(Stmt::Block(BlockStmt {
-                                    span: DUMMY_SP,
-                                    stmts: cons_stmts,
-                                })),
-                                alt: Some(Box::new(Stmt::Block(BlockStmt {
-                                    span: DUMMY_SP,
-                                    stmts: AstGenerator::new(
-                                        self.f,
-                                        self.cfg,
-                                        self.instructions,
-                                        fals.target(),
-                                        false,
-                                        self.while_cond_block,
-                                        self.do_while_cond_block,
-                                    )
-                                    .collect(),
-                                }))),
-                            }));
-                        }
-
-                        self.stage = AstGeneratorStage::ProcessingDone;
-                    }
+                    let test = jump_inst_to_test(&self.body.instructions[*flow_index].instruction);
+                    let join = self.find_join(tru.target(), fals.target());
+                    self.pending_if = Some(PendingIf {
+                        test,
+                        join,
+                        alt: fals.target(),
+                        cons: None,
+                    });
+                    self.stage = AstGeneratorStage::IfAlt;
+                    return Progress::Call(Box::new(self.branch(tru.target(), join)));
                 } else if outgoing_edges.len() == 1 {
-                    self.chained_iterator = Some(Box::new(AstGenerator::new(
-                        self.f,
-                        self.cfg,
-                        self.instructions,
+                    self.chained_iterator = Some(Box::new(self.chained(
                         outgoing_edges[0].target(),
-                        false,
                         self.while_cond_block,
                         self.do_while_cond_block,
                     )));
@@ -433,51 +1063,186 @@ impl<'a> AstGenerator<'a> {
                 } else {
                     self.stage = AstGeneratorStage::ProcessingDone;
                 }
-                true
+                Progress::Continue
+            }
+            AstGeneratorStage::IfAlt => {
+                let pending = self.pending_if.as_mut().unwrap();
+                pending.cons = self.child_result.take();
+                let (alt, join) = (pending.alt, pending.join);
+                self.stage = AstGeneratorStage::IfJoin;
+                Progress::Call(Box::new(self.branch(alt, join)))
+            }
+            AstGeneratorStage::IfJoin => {
+                let (alt_stmts, alt_returns) = self.child_result.take().unwrap();
+                let PendingIf {
+                    test,
+                    join,
+                    cons: Some((cons_stmts, cons_returns)),
+                    ..
+                } = self.pending_if.take().unwrap()
+                else {
+                    unreachable!()
+                };
+                if join.is_some() {
+                    //both branches end where they reconverge, which is emitted once after the if
+                    self.push_if(test, cons_stmts, alt_stmts);
+                    self.after_if_node = join;
+                    self.stage = AstGeneratorStage::AfterIf;
+                } else {
+                    //a branch that returns doesn't need the other one nested in an else,
+                    //so the shorter of them goes inside the if to keep early returns flat
+                    let alt_first =
+                        alt_returns && (!cons_returns || alt_stmts.len() < cons_stmts.len());
+                    if alt_first {
+                        self.push_if(negate(test), alt_stmts, Vec::new());
+                        self.stmt_queue.extend(cons_stmts);
+                    } else if cons_returns {
+                        self.push_if(test, cons_stmts, Vec::new());
+                        self.stmt_queue.extend(alt_stmts);
+                    } else {
+                        self.push_if(test, cons_stmts, alt_stmts);
+                    }
+                    self.stage = AstGeneratorStage::ProcessingDone;
+                }
+                Progress::Continue
             }
             AstGeneratorStage::AfterIf => {
                 if let Some(after_if_node) = self.after_if_node {
-                    self.chained_iterator = Some(Box::new(AstGenerator::new(
-                        self.f,
-                        self.cfg,
-                        self.instructions,
+                    self.chained_iterator = Some(Box::new(self.chained(
                         after_if_node,
-                        false,
                         self.while_cond_block,
                         self.do_while_cond_block,
                     )));
                 }
                 self.stage = AstGeneratorStage::ProcessingDone;
-                true
+                Progress::Continue
             }
-            AstGeneratorStage::ProcessingDone => false,
+            AstGeneratorStage::TryBlock => {
+                let (block, _) = self.child_result.take().unwrap();
+                let pending = self.pending_try.as_mut().unwrap();
+                pending.block = Some(block);
+                let finally = pending.region.finally.start;
+                self.stage = AstGeneratorStage::TryFinalizer;
+                match self.block_starting_at(finally) {
+                    Some(finally) => {
+                        Progress::Call(Box::new(self.child(finally, false, None, None)))
+                    }
+                    None => {
+                        self.child_result = Some((Vec::new(), false));
+                        Progress::Continue
+                    }
+                }
+            }
+            AstGeneratorStage::TryFinalizer => {
+                let (mut finalizer, _) = self.child_result.take().unwrap();
+                let PendingTry {
+                    region,
+                    block: Some(block),
+                } = self.pending_try.take().unwrap()
+                else {
+                    unreachable!()
+                };
+                //the handler ends throwing what it caught again, as the finally statement does
+                if let Some(Stmt::Throw(ThrowStmt { arg, .. })) = finalizer.last() {
+                    if is_register(arg, region.exception_reg) {
+                        finalizer.pop();
+                    }
+                }
+                self.stmt_queue.push_back(Stmt::Try(Box::new(TryStmt {
+                    span: DUMMY_SP,
+                    block: BlockStmt {
+                        span: DUMMY_SP,
+                        stmts: block,
+                    },
+                    handler: None,
+                    finalizer: Some(BlockStmt {
+                        span: DUMMY_SP,
+                        stmts: finalizer,
+                    }),
+                })));
+                if let Some(after) = region.after.and_then(|after| self.block_starting_at(after)) {
+                    self.chained_iterator = Some(Box::new(self.chained(
+                        after,
+                        self.while_cond_block,
+                        self.do_while_cond_block,
+                    )));
+                }
+                self.stage = AstGeneratorStage::ProcessingDone;
+                Progress::Continue
+            }
+            AstGeneratorStage::ProcessingDone => Progress::Done,
         }
     }
 }
 
-impl Iterator for AstGenerator<'_> {
-    type Item = Stmt;
-
-    fn next(&mut self) -> Option<Self::Item> {
-        if let Some(item) = self.stmt_queue.pop_front() {
-            match item {
-                Stmt::Return(_) => {
-                    self.is_last_instruction_return = true;
-                }
-                Stmt::Throw(_) => {
-                    self.is_last_instruction_return = true;
+impl<'a> BlockGenerator<'a> {
+    fn step(&mut self) -> Step<'a> {
+        loop {
+            if let Some(item) = self.stmt_queue.pop_front() {
+                match item {
+                    Stmt::Return(_) => {
+                        self.is_last_instruction_return = true;
+                    }
+                    Stmt::Throw(_) => {
+                        self.is_last_instruction_return = true;
+                    }
+                    Stmt::Break(_) | Stmt::Continue(_) => {
+                        self.is_last_instruction_return = true;
+                    }
+                    _ => (),
                 }
-                _ => (),
+                return Step::Yield(item);
             }
-            Some(item)
-        } else if self.populate_next_stage() {
-            self.next()
-        } else if let Some(_) = &mut self.chained_iterator {
-            *self = *self.chained_iterator.take().unwrap();
-            self.next()
-        } else {
-            None
+            match self.populate_next_stage() {
+                Progress::Continue => (),
+                Progress::Call(child) => return Step::Call(child),
+                Progress::Done => match self.chained_iterator.take() {
+                    Some(chained) => *self = *chained,
+                    None => return Step::Done,
+                },
+            }
+        }
+    }
+}
+
+fn label_loop(loop_label: &LoopLabel, loop_stmt: Stmt) -> Stmt {
+    if loop_label.is_used.get() {
+        Stmt::Labeled(LabeledStmt {
+            span: DUMMY_SP,
+            label: Ident::new(loop_label.label.as_str().into(), DUMMY_SP),
+            body: Box::new(loop_stmt),
+        })
+    } else {
+        loop_stmt
+    }
+}
+
+//the emitter writes the tree as it is, so operands binding looser than `!` need explicit parens
+fn negate(test: Expr) -> Expr {
+    match test {
+        Expr::Unary(UnaryExpr {
+            op: UnaryOp::Bang,
+            arg,
+            ..
+        }) => match *arg {
+            Expr::Paren(ParenExpr { expr, .. }) => *expr,
+            arg => arg,
+        },
+        test @ (Expr::Bin(_) | Expr::Cond(_) | Expr::Assign(_) | Expr::Seq(_)) => {
+            Expr::Unary(UnaryExpr {
+                span: DUMMY_SP,
+                op: UnaryOp::Bang,
+                arg: Box::new(Expr::Paren(ParenExpr {
+                    span: DUMMY_SP,
+                    expr: Box::new(test),
+                })),
+            })
         }
+        test => Expr::Unary(UnaryExpr {
+            span: DUMMY_SP,
+            op: UnaryOp::Bang,
+            arg: Box::new(test),
+        }),
     }
 }
 
@@ -1469,61 +2234,753 @@ fn jump_inst_to_test(instruction: &Instruction) -> Expr {
         } => {
             return Expr::Bin(BinExpr {
                 span: DUMMY_SP,
-                op: BinaryOp::NotEqEq,
-                left: Box::new(Expr::Ident(Ident {
-                    span: DUMMY_SP,
-                    sym: format!("r{arg1_value_reg}").as_str().into(),
-                    optional: false,
-                })),
-                right: Box::new(Expr::Ident(Ident {
+                op: BinaryOp::NotEqEq,
+                left: Box::new(Expr::Ident(Ident {
+                    span: DUMMY_SP,
+                    sym: format!("r{arg1_value_reg}").as_str().into(),
+                    optional: false,
+                })),
+                right: Box::new(Expr::Ident(Ident {
+                    span: DUMMY_SP,
+                    sym: format!("r{arg2_value_reg}").as_str().into(),
+                    optional: false,
+                })),
+            })
+        }
+        _ => panic!("got a non-jump: {instruction:?}"),
+    }
+}
+
+//puts `to_add` before every continue of the while loop labeled `label` in its `body`, unlabeled
+//continues in loops nested in the body continue those loops instead
+fn add_inside_while(body: &mut Vec<Stmt>, to_add: &[Stmt], label: &str) {
+    add_before_continues(body, to_add, label, false);
+}
+
+fn continues_loop(stmt: &Stmt, label: &str, in_nested_loop: bool) -> bool {
+    match stmt {
+        Stmt::Continue(ContinueStmt { label: None, .. }) => !in_nested_loop,
+        Stmt::Continue(ContinueStmt {
+            label: Some(continued),
+            ..
+        }) => *continued.sym == *label,
+        _ => false,
+    }
+}
+
+fn add_before_continues(stmts: &mut Vec<Stmt>, to_add: &[Stmt], label: &str, in_nested_loop: bool) {
+    let mut i = 0;
+    while i < stmts.len() {
+        if continues_loop(&stmts[i], label, in_nested_loop) {
+            stmts.splice(i..i, to_add.iter().cloned());
+            i += to_add.len();
+        } else {
+            add_before_continues_in_stmt(&mut stmts[i], to_add, label, in_nested_loop);
+        }
+        i += 1;
+    }
+}
+
+//a continue that is a statement of its own, like an if's branch, becomes a block
+fn add_before_continue_stmt(stmt: &mut Stmt, to_add: &[Stmt], label: &str, in_nested_loop: bool) {
+    if continues_loop(stmt, label, in_nested_loop) {
+        let mut stmts = to_add.to_vec();
+        stmts.push(std::mem::replace(
+            stmt,
+            Stmt::Empty(EmptyStmt { span: DUMMY_SP }),
+        ));
+        *stmt = Stmt::Block(BlockStmt {
+            span: DUMMY_SP,
+            stmts,
+        });
+    } else {
+        add_before_continues_in_stmt(stmt, to_add, label, in_nested_loop);
+    }
+}
+
+fn add_before_continues_in_stmt(
+    stmt: &mut Stmt,
+    to_add: &[Stmt],
+    label: &str,
+    in_nested_loop: bool,
+) {
+    match stmt {
+        Stmt::Block(block) => add_before_continues(&mut block.stmts, to_add, label, in_nested_loop),
+        Stmt::Labeled(labeled) => {
+            add_before_continue_stmt(&mut labeled.body, to_add, label, in_nested_loop)
+        }
+        Stmt::If(if_stmt) => {
+            add_before_continue_stmt(&mut if_stmt.cons, to_add, label, in_nested_loop);
+            if let Some(alt) = &mut if_stmt.alt {
+                add_before_continue_stmt(alt, to_add, label, in_nested_loop);
+            }
+        }
+        Stmt::With(with) => add_before_continue_stmt(&mut with.body, to_add, label, in_nested_loop),
+        Stmt::Switch(switch) => {
+            for case in &mut switch.cases {
+                add_before_continues(&mut case.cons, to_add, label, in_nested_loop);
+            }
+        }
+        Stmt::Try(try_stmt) => {
+            add_before_continues(&mut try_stmt.block.stmts, to_add, label, in_nested_loop);
+            if let Some(handler) = &mut try_stmt.handler {
+                add_before_continues(&mut handler.body.stmts, to_add, label, in_nested_loop);
+            }
+            if let Some(finalizer) = &mut try_stmt.finalizer {
+                add_before_continues(&mut finalizer.stmts, to_add, label, in_nested_loop);
+            }
+        }
+        //only continues labeled with this loop's label get out of nested loops
+        Stmt::While(WhileStmt { body, .. })
+        | Stmt::DoWhile(DoWhileStmt { body, .. })
+        | Stmt::For(ForStmt { body, .. })
+        | Stmt::ForIn(ForInStmt { body, .. })
+        | Stmt::ForOf(ForOfStmt { body, .. }) => {
+            add_before_continue_stmt(body, to_add, label, true)
+        }
+        _ => (),
+    }
+}
+
+//the value last assigned to `reg` in `stmts`, Some(None) if it was last modified some other way (r0++, r0 += 1)
+fn last_assigned_value(stmts: &[Stmt], reg: u8) -> Option<Option<&Expr>> {
+    let reg_name = format!("r{reg}");
+    for stmt in stmts.iter().rev() {
+        let Stmt::Expr(ExprStmt { expr, .. }) = stmt else {
+            continue;
+        };
+        let (target, value) = match &**expr {
+            Expr::Assign(AssignExpr {
+                op,
+                left: PatOrExpr::Expr(left),
+                right,
+                ..
+            }) => (left, Some(&**right).filter(|_| *op == AssignOp::Assign)),
+            Expr::Update(UpdateExpr { arg, .. }) => (arg, None),
+            _ => continue,
+        };
+        let Expr::Ident(ident) = &**target else {
+            continue;
+        };
+        if *ident.sym == *reg_name {
+            return Some(value);
+        }
+    }
+    None
+}
+
+//rA[rB] where rB was last assigned a string literal that's a valid identifier can be written as rA.foo
+//we only look at statements already generated for the current block
+fn index_reg_to_member_prop(stmts: &[Stmt], index_reg: u8) -> MemberProp {
+    match last_assigned_value(stmts, index_reg) {
+        Some(Some(Expr::Lit(Lit::Str(s)))) if is_valid_identifier(&s.value) => {
+            return MemberProp::Ident(Ident::new(s.value.clone(), DUMMY_SP));
+        }
+        //rA[rB] where rB was last assigned a number is rA[3]
+        Some(Some(index @ Expr::Lit(Lit::Num(_)))) => {
+            return MemberProp::Computed(ComputedPropName {
+                span: DUMMY_SP,
+                expr: Box::new(index.clone()),
+            });
+        }
+        _ => (),
+    }
+    MemberProp::Computed(ComputedPropName {
+        span: DUMMY_SP,
+        expr: Box::new(Expr::Ident(Ident::new(
+            format!("r{index_reg}").as_str().into(),
+            DUMMY_SP,
+        ))),
+    })
+}
+
+//registers a caller keeps free at the end of its frame, after the arguments, for the callee's
+//frame header (StackFrameLayout::CallerExtraRegistersAtEnd in hermes)
+const CALL_EXTRA_REGISTERS: u32 = 4;
+
+//arguments of Call/Construct are passed counting down from the last register before the
+//CALL_EXTRA_REGISTERS: argument i, `this` being argument 0, is in frame_size - 1 - CALL_EXTRA_REGISTERS - i
+fn frame_arguments(frame_size: u32, arguments_len: u32) -> Vec<ExprOrSpread> {
+    let last_argument_reg = frame_size.checked_sub(1 + CALL_EXTRA_REGISTERS);
+    (0..arguments_len)
+        .map_while(|i| last_argument_reg?.checked_sub(i))
+        .map(|reg| ExprOrSpread {
+            spread: None,
+            expr: Box::new(Expr::Ident(Ident::new(
+                format!("r{reg}").as_str().into(),
+                DUMMY_SP,
+            ))),
+        })
+        .collect()
+}
+
+//rD = rC.bind(this)(...args)
+fn call_stmt(dst_reg: u8, closure: Box<Expr>, arguments: Vec<ExprOrSpread>) -> Stmt {
+    let mut arguments = arguments.into_iter();
+    let this = arguments.next().unwrap_or_else(|| ExprOrSpread {
+        spread: None,
+        expr: Box::new(Expr::Ident(Ident::new("undefined".into(), DUMMY_SP))),
+    });
+    Stmt::Expr(ExprStmt {
+        span: DUMMY_SP,
+        expr: Box::new(Expr::Assign(AssignExpr {
+            span: DUMMY_SP,
+            op: AssignOp::Assign,
+            left: PatOrExpr::Expr(Box::new(Expr::Ident(Ident {
+                span: DUMMY_SP,
+                sym: format!("r{dst_reg}").as_str().into(),
+                optional: false,
+            }))),
+            right: Box::new(Expr::Call(CallExpr {
+                span: DUMMY_SP,
+                callee: Callee::Expr(Box::new(Expr::Call(CallExpr {
+                    span: DUMMY_SP,
+                    callee: Callee::Expr(Box::new(Expr::Member(MemberExpr {
+                        span: DUMMY_SP,
+                        obj: closure,
+                        prop: MemberProp::Ident(Ident {
+                            span: DUMMY_SP,
+                            sym: "bind".into(),
+                            optional: false,
+                        }),
+                    }))),
+                    args: vec![this],
+                    type_args: None,
+                }))),
+                args: arguments.collect(),
+                type_args: None,
+            })),
+        })),
+    })
+}
+
+//rD = new rC(this, ...args), the this argument is removed by collapse_constructor_calls
+fn construct_stmt(dst_reg: u8, closure_reg: u8, arguments: Vec<ExprOrSpread>) -> Stmt {
+    Stmt::Expr(ExprStmt {
+        span: DUMMY_SP,
+        expr: Box::new(Expr::Assign(AssignExpr {
+            span: DUMMY_SP,
+            op: AssignOp::Assign,
+            left: PatOrExpr::Expr(Box::new(Expr::Ident(Ident {
+                span: DUMMY_SP,
+                sym: format!("r{dst_reg}").as_str().into(),
+                optional: false,
+            }))),
+            right: Box::new(Expr::New(NewExpr {
+                span: DUMMY_SP,
+                callee: Box::new(Expr::Ident(Ident {
                     span: DUMMY_SP,
-                    sym: format!("r{arg2_value_reg}").as_str().into(),
+                    sym: format!("r{closure_reg}").as_str().into(),
                     optional: false,
                 })),
-            })
-        }
-        _ => panic!("got a non-jump: {instruction:?}"),
+                args: Some(arguments),
+                type_args: None,
+            })),
+        })),
+    })
+}
+
+fn builtin_expr(builtin_number: u8) -> Expr {
+    let builtin = *JS_BUILTINS.get(builtin_number as usize).unwrap();
+    if builtin.contains('.') {
+        let mut s = builtin.split('.');
+        Expr::Member(MemberExpr {
+            span: DUMMY_SP,
+            obj: Box::new(Expr::Ident(Ident {
+                span: DUMMY_SP,
+                sym: s.next().unwrap().into(),
+                optional: false,
+            })),
+            prop: MemberProp::Ident(Ident {
+                span: DUMMY_SP,
+                sym: s.next().unwrap().into(),
+                optional: false,
+            }),
+        })
+    } else {
+        Expr::Ident(Ident {
+            span: DUMMY_SP,
+            sym: builtin.into(),
+            optional: false,
+        })
     }
 }
 
-fn add_inside_while(body: &mut Vec<Stmt>, to_add: &VecDeque<Stmt>) {
-    let mut i = 0;
-    //println!("{}", body.len());
-    while i < body.len() {
-        let stmt = &mut body[i];
-        match stmt {
-            Stmt::Continue(_) => {
-                for i1 in 0..to_add.len() {
-                    body.insert(i + i1, to_add[i1].clone())
-                }
-                i += to_add.len();
+//builtins ignore `this`, so it's dropped from the arguments. `f(...a)` is compiled to
+//arraySpread(rT, rS, rI), which appends rS to rT starting at index rI and returns the new
+//length, and apply(rF, rT, this), which calls rF with the elements of rT (or constructs it when
+//no this is passed), so both are turned back into spreads
+fn call_builtin_stmt(dst_reg: u8, builtin_number: u8, arguments: Vec<ExprOrSpread>) -> Stmt {
+    let arguments = arguments.get(1..).unwrap_or_default();
+    let spread = |array: &ExprOrSpread| ExprOrSpread {
+        spread: Some(DUMMY_SP),
+        expr: array.expr.clone(),
+    };
+    match (JS_BUILTINS.get(builtin_number as usize), arguments) {
+        //rD = rT.push(...rS)
+        (Some(&"arraySpread"), [target, source, _next_index]) => assign_stmt(
+            dst_reg,
+            Box::new(Expr::Call(CallExpr {
+                span: DUMMY_SP,
+                callee: Callee::Expr(member_expr(target.expr.clone(), "push")),
+                args: vec![spread(source)],
+                type_args: None,
+            })),
+        ),
+        (Some(&"apply"), [function, array, this]) => call_stmt(
+            dst_reg,
+            function.expr.clone(),
+            vec![this.clone(), spread(array)],
+        ),
+        (Some(&"apply"), [function, array]) => assign_stmt(
+            dst_reg,
+            Box::new(Expr::New(NewExpr {
+                span: DUMMY_SP,
+                callee: function.expr.clone(),
+                args: Some(vec![spread(array)]),
+                type_args: None,
+            })),
+        ),
+        _ => assign_stmt(
+            dst_reg,
+            Box::new(Expr::Call(CallExpr {
+                span: DUMMY_SP,
+                callee: Callee::Expr(Box::new(builtin_expr(builtin_number))),
+                args: arguments.to_vec(),
+                type_args: None,
+            })),
+        ),
+    }
+}
+
+/// What `LoadConstEmpty` loads: the VM's marker for a `let`, `const` or class binding that isn't
+/// initialized yet, which `ThrowIfEmpty` checks for. It isn't `undefined`, a binding can be
+/// initialized to that, so it's written as this name, which no JS defines
+pub const EMPTY: &str = "__empty";
+
+fn reg_expr(reg: u8) -> Box<Expr> {
+    Box::new(Expr::Ident(Ident::new(
+        format!("r{reg}").as_str().into(),
+        DUMMY_SP,
+    )))
+}
+
+fn is_register(expr: &Expr, reg: u8) -> bool {
+    matches!(expr, Expr::Ident(ident) if *ident.sym == *format!("r{reg}"))
+}
+
+fn member_expr(obj: Box<Expr>, prop: &str) -> Box<Expr> {
+    Box::new(Expr::Member(MemberExpr {
+        span: DUMMY_SP,
+        obj,
+        prop: MemberProp::Ident(Ident::new(prop.into(), DUMMY_SP)),
+    }))
+}
+
+fn assign_stmt(dst_reg: u8, value: Box<Expr>) -> Stmt {
+    Stmt::Expr(ExprStmt {
+        span: DUMMY_SP,
+        expr: Box::new(Expr::Assign(AssignExpr {
+            span: DUMMY_SP,
+            op: AssignOp::Assign,
+            left: PatOrExpr::Expr(reg_expr(dst_reg)),
+            right: value,
+        })),
+    })
+}
+
+/// The name function `function_id` is emitted with, `f12` or `render_f12` for one declared as
+/// `render`. The id stays in the name, as functions of a bundle often share names
+pub fn function_ident(name: Option<&str>, function_id: u32) -> String {
+    match name {
+        Some(name) => format!("{name}_f{function_id}"),
+        None => format!("f{function_id}"),
+    }
+}
+
+//rD = fN, see function_ident
+fn closure_stmt(dst_reg: u8, function_table_index: u32, name: Option<&str>) -> Stmt {
+    assign_stmt(
+        dst_reg,
+        Box::new(Expr::Ident(Ident::new(
+            function_ident(name, function_table_index).as_str().into(),
+            DUMMY_SP,
+        ))),
+    )
+}
+
+//new Int16Array(rH.buffer)[rI >> 1], the element of the heap the VM reads at byte address rI:
+//it casts the heap's data to the element type and drops the address bits below its size
+fn heap_element_expr(view: &str, heap_reg: u8, address_reg: u8, shift: u8) -> Box<Expr> {
+    let index = match shift {
+        0 => reg_expr(address_reg),
+        _ => Box::new(Expr::Bin(BinExpr {
+            span: DUMMY_SP,
+            op: BinaryOp::RShift,
+            left: reg_expr(address_reg),
+            right: Box::new(Expr::Lit(Lit::Num(number_literal(f64::from(shift))))),
+        })),
+    };
+    Box::new(Expr::Member(MemberExpr {
+        span: DUMMY_SP,
+        obj: Box::new(Expr::New(NewExpr {
+            span: DUMMY_SP,
+            callee: Box::new(Expr::Ident(Ident::new(view.into(), DUMMY_SP))),
+            args: Some(vec![ExprOrSpread {
+                spread: None,
+                expr: member_expr(reg_expr(heap_reg), "buffer"),
+            }]),
+            type_args: None,
+        })),
+        prop: MemberProp::Computed(ComputedPropName {
+            span: DUMMY_SP,
+            expr: index,
+        }),
+    }))
+}
+
+//new Int16Array(rH.buffer)[rI >> 1] = rV, the typed array truncates the value like the VM
+fn heap_store_stmt(view: &str, heap_reg: u8, address_reg: u8, shift: u8, value_reg: u8) -> Stmt {
+    Stmt::Expr(ExprStmt {
+        span: DUMMY_SP,
+        expr: Box::new(Expr::Assign(AssignExpr {
+            span: DUMMY_SP,
+            op: AssignOp::Assign,
+            left: PatOrExpr::Expr(heap_element_expr(view, heap_reg, address_reg, shift)),
+            right: reg_expr(value_reg),
+        })),
+    })
+}
+
+//the compiled regexp in the bundle's regexp table is only needed to run it, the literal is
+//written from its source
+fn regex_literal(
+    pattern: &str,
+    flags: &str,
+    offset: u32,
+    diagnostics: Option<&Diagnostics>,
+) -> Regex {
+    let mut seen = String::new();
+    for flag in flags.chars() {
+        if !"gimsuy".contains(flag) || seen.contains(flag) {
+            if let Some(diagnostics) = diagnostics {
+                diagnostics.report(
+                    DiagnosticKind::InvalidRegExpFlags,
+                    offset,
+                    format!("invalid regexp flags \"{flags}\""),
+                );
             }
-            Stmt::If(stmt) => {
-                if let Stmt::Block(b) = &mut *stmt.cons {
-                    add_inside_while(&mut b.stmts, to_add);
-                }
-                if let Some(o) = &mut stmt.alt {
-                    if let Stmt::Block(b) = &mut **o {
-                        add_inside_while(&mut b.stmts, to_add);
-                    }
-                }
+            break;
+        }
+        seen.push(flag);
+    }
+    Regex {
+        span: DUMMY_SP,
+        //what new RegExp("").source is, as // would start a comment
+        exp: if pattern.is_empty() { "(?:)" } else { pattern }.into(),
+        flags: flags.into(),
+    }
+}
+
+//unsupported("Name"), standing in for an instruction with no translation, reported as
+//UnsupportedOpcode
+fn unsupported(info: &InstructionInfo<Instruction>, diagnostics: Option<&Diagnostics>) -> Stmt {
+    let name = info.instruction.name();
+    if let Some(diagnostics) = diagnostics {
+        diagnostics.report(
+            DiagnosticKind::UnsupportedOpcode,
+            info.offset,
+            format!("{name} has no translation yet"),
+        );
+    }
+    unsupported_stmt(&name)
+}
+
+//the string at `index` of the string table, one past its end is reported as MissingString
+//and named after its index
+fn table_string<'a>(
+    f: &'a BytecodeFile,
+    index: u32,
+    info: &InstructionInfo<Instruction>,
+    diagnostics: Option<&Diagnostics>,
+) -> Cow<'a, str> {
+    match f.get_string(index) {
+        Some(string) => Cow::Borrowed(string),
+        None => {
+            if let Some(diagnostics) = diagnostics {
+                diagnostics.report(
+                    DiagnosticKind::MissingString,
+                    info.offset,
+                    format!("string {index} isn't in the string table"),
+                );
             }
-            Stmt::Expr(_) => (),
-            Stmt::Return(_) => (),
-            _ => unimplemented!("{:?}", stmt),
+            Cow::Owned(format!("__string{index}"))
         }
-        i += 1;
     }
 }
 
+fn unsupported_stmt(name: &str) -> Stmt {
+    Stmt::Expr(ExprStmt {
+        span: DUMMY_SP,
+        expr: Box::new(Expr::Call(CallExpr {
+            span: DUMMY_SP,
+            callee: Callee::Expr(Box::new(Expr::Ident(Ident::new(
+                "unsupported".into(),
+                DUMMY_SP,
+            )))),
+            args: vec![ExprOrSpread {
+                spread: None,
+                expr: Box::new(Expr::Lit(Lit::Str(Str {
+                    span: DUMMY_SP,
+                    value: name.into(),
+                    raw: None,
+                }))),
+            }],
+            type_args: None,
+        })),
+    })
+}
+
+fn bool_expr(value: bool) -> Box<Expr> {
+    Box::new(Expr::Lit(Lit::Bool(Bool {
+        span: DUMMY_SP,
+        value,
+    })))
+}
+
+//Object.defineProperty(rO, rK, {descriptor})
+fn define_property_stmt(obj_reg: u8, key_reg: u8, descriptor: Vec<(&str, Box<Expr>)>) -> Stmt {
+    let props = descriptor
+        .into_iter()
+        .map(|(key, value)| {
+            PropOrSpread::Prop(Box::new(Prop::KeyValue(KeyValueProp {
+                key: PropName::Ident(Ident::new(key.into(), DUMMY_SP)),
+                value,
+            })))
+        })
+        .collect();
+    Stmt::Expr(ExprStmt {
+        span: DUMMY_SP,
+        expr: Box::new(Expr::Call(CallExpr {
+            span: DUMMY_SP,
+            callee: Callee::Expr(member_expr(
+                Box::new(Expr::Ident(Ident::new("Object".into(), DUMMY_SP))),
+                "defineProperty",
+            )),
+            args: [
+                reg_expr(obj_reg),
+                reg_expr(key_reg),
+                Box::new(Expr::Object(ObjectLit {
+                    span: DUMMY_SP,
+                    props,
+                })),
+            ]
+            .into_iter()
+            .map(|expr| ExprOrSpread { spread: None, expr })
+            .collect(),
+            type_args: None,
+        })),
+    })
+}
+
+//rI = rS[Symbol.iterator](), the array fast path (rI holding an index) isn't observable from JS
+fn iterator_begin_stmt(dst_reg: u8, source_reg: u8) -> Stmt {
+    assign_stmt(
+        dst_reg,
+        Box::new(Expr::Call(CallExpr {
+            span: DUMMY_SP,
+            callee: Callee::Expr(Box::new(Expr::Member(MemberExpr {
+                span: DUMMY_SP,
+                obj: reg_expr(source_reg),
+                prop: MemberProp::Computed(ComputedPropName {
+                    span: DUMMY_SP,
+                    expr: member_expr(
+                        Box::new(Expr::Ident(Ident::new("Symbol".into(), DUMMY_SP))),
+                        "iterator",
+                    ),
+                }),
+            }))),
+            args: vec![],
+            type_args: None,
+        })),
+    )
+}
+
+//rD = rI.next(); rI = rD.done ? undefined : rI; rD = rD.value;
+//IteratorNext sets the iterator register to undefined once it's done, which is what loops check
+fn iterator_next_stmts(dst_reg: u8, iterator_reg: u8) -> [Stmt; 3] {
+    [
+        assign_stmt(
+            dst_reg,
+            Box::new(Expr::Call(CallExpr {
+                span: DUMMY_SP,
+                callee: Callee::Expr(member_expr(reg_expr(iterator_reg), "next")),
+                args: vec![],
+                type_args: None,
+            })),
+        ),
+        assign_stmt(
+            iterator_reg,
+            Box::new(Expr::Cond(CondExpr {
+                span: DUMMY_SP,
+                test: member_expr(reg_expr(dst_reg), "done"),
+                cons: Box::new(Expr::Ident(Ident::new("undefined".into(), DUMMY_SP))),
+                alt: reg_expr(iterator_reg),
+            })),
+        ),
+        assign_stmt(dst_reg, member_expr(reg_expr(dst_reg), "value")),
+    ]
+}
+
+//rI === undefined || rI.return == null || rI.return();
+fn iterator_close_stmt(iterator_reg: u8) -> Stmt {
+    let or = |left, right| {
+        Box::new(Expr::Bin(BinExpr {
+            span: DUMMY_SP,
+            op: BinaryOp::LogicalOr,
+            left,
+            right,
+        }))
+    };
+    Stmt::Expr(ExprStmt {
+        span: DUMMY_SP,
+        expr: or(
+            or(
+                Box::new(Expr::Bin(BinExpr {
+                    span: DUMMY_SP,
+                    op: BinaryOp::EqEqEq,
+                    left: reg_expr(iterator_reg),
+                    right: Box::new(Expr::Ident(Ident::new("undefined".into(), DUMMY_SP))),
+                })),
+                Box::new(Expr::Bin(BinExpr {
+                    span: DUMMY_SP,
+                    op: BinaryOp::EqEq,
+                    left: member_expr(reg_expr(iterator_reg), "return"),
+                    right: Box::new(Expr::Lit(Lit::Null(Null { span: DUMMY_SP }))),
+                })),
+            ),
+            Box::new(Expr::Call(CallExpr {
+                span: DUMMY_SP,
+                callee: Callee::Expr(member_expr(reg_expr(iterator_reg), "return")),
+                args: vec![],
+                type_args: None,
+            })),
+        ),
+    })
+}
+
+/// `value` as an expression, NaN and the infinities have no literals and are written as the
+/// globals holding them, `-Infinity` being a negation
+fn double_expr(value: f64) -> Expr {
+    let global = |name: &str| Expr::Ident(Ident::new(name.into(), DUMMY_SP));
+    if value.is_nan() {
+        global("NaN")
+    } else if value == f64::INFINITY {
+        global("Infinity")
+    } else if value == f64::NEG_INFINITY {
+        Expr::Unary(UnaryExpr {
+            span: DUMMY_SP,
+            op: UnaryOp::Minus,
+            arg: Box::new(global("Infinity")),
+        })
+    } else {
+        Expr::Lit(Lit::Num(number_literal(value)))
+    }
+}
+
+/// A number literal written the way JS would convert finite `value` to a string: the shortest
+/// digits that read back as `value`, in exponent form below 1e-6 and from 1e21 on, e.g. `1e+21`
+pub fn number_literal(value: f64) -> Number {
+    let raw = if value == 0.0 {
+        if value.is_sign_negative() { "-0" } else { "0" }.to_string()
+    } else {
+        //d.ddde-x holding the shortest round-tripping digits
+        let exponential = format!("{:e}", value.abs());
+        let (mantissa, exponent) = exponential.split_once('e').unwrap();
+        let digits = mantissa.replace('.', "");
+        let k = digits.len() as i32;
+        //where the decimal point goes relative to the digits
+        let n = exponent.parse::<i32>().unwrap() + 1;
+        let magnitude = if k <= n && n <= 21 {
+            digits + &"0".repeat((n - k) as usize)
+        } else if 0 < n && n <= 21 {
+            format!("{}.{}", &digits[..n as usize], &digits[n as usize..])
+        } else if -6 < n && n <= 0 {
+            format!("0.{}{digits}", "0".repeat(-n as usize))
+        } else {
+            let mantissa = match digits.split_at(1) {
+                (first, "") => first.to_string(),
+                (first, rest) => format!("{first}.{rest}"),
+            };
+            format!(
+                "{mantissa}e{}{}",
+                if n > 0 { "+" } else { "-" },
+                (n - 1).abs()
+            )
+        };
+        let sign = if value < 0.0 { "-" } else { "" };
+        format!("{sign}{magnitude}")
+    };
+    Number {
+        span: DUMMY_SP,
+        value,
+        raw: Some(raw.as_str().into()),
+    }
+}
+
+pub fn is_valid_identifier(s: &str) -> bool {
+    let mut chars = s.chars();
+    match chars.next() {
+        Some(c) if c.is_ascii_alphabetic() || c == '_' || c == '$' => (),
+        _ => return false,
+    }
+    chars.all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '$')
+}
+
+/// Whether the generator has no translation for `instruction` yet: translating it reports
+/// [`DiagnosticKind::UnsupportedOpcode`] and emits an `unsupported("Name")` call
+pub fn is_unsupported(f: &BytecodeFile, instruction: Instruction) -> bool {
+    let instructions = [InstructionInfo {
+        offset: 0,
+        instruction,
+    }];
+    let mut cfg = Graph::new();
+    let node = cfg.add_node(vec![0]);
+    let diagnostics = Diagnostics::new(0);
+    simple_instructions_to_ast(
+        f,
+        &cfg,
+        node,
+        &instructions,
+        0,
+        None,
+        None,
+        None,
+        None,
+        Some(&diagnostics),
+    );
+    diagnostics
+        .into_vec()
+        .iter()
+        .any(|diagnostic| diagnostic.kind == DiagnosticKind::UnsupportedOpcode)
+}
+
+#[allow(clippy::too_many_arguments)]
 fn simple_instructions_to_ast(
     f: &BytecodeFile,
     cfg: &Graph<Vec<usize>, bool>,
     node: NodeIndex,
     instructions: &[InstructionInfo<Instruction>],
+    frame_size: u32,
+    profile_points: Option<&SingleThreadedComments>,
+    annotations: Option<&SingleThreadedComments>,
+    cache_indices: Option<&SingleThreadedComments>,
+    function_names: Option<&dyn Fn(u32) -> Option<String>>,
+    diagnostics: Option<&Diagnostics>,
 ) -> Vec<Stmt> {
     let mut stmts = Vec::new();
     for index in cfg.node_weight(node).unwrap() {
+        let first_stmt = stmts.len();
         match &instructions[*index].instruction {
             Instruction::Mov { dst_reg, src_reg } => stmts.push(Stmt::Expr(ExprStmt {
                 span: DUMMY_SP,
@@ -1858,11 +3315,13 @@ fn simple_instructions_to_ast(
                         })),
                         prop: MemberProp::Ident(Ident {
                             span: DUMMY_SP,
-                            sym: f
-                                .get_string(u32::from(*string_table_index))
-                                .unwrap()
-                                .as_str()
-                                .into(),
+                            sym: table_string(
+                                f,
+                                u32::from(*string_table_index),
+                                &instructions[*index],
+                                diagnostics,
+                            )
+                            .into(),
                             optional: false,
                         }),
                     })),
@@ -1892,11 +3351,13 @@ fn simple_instructions_to_ast(
                         })),
                         prop: MemberProp::Ident(Ident {
                             span: DUMMY_SP,
-                            sym: f
-                                .get_string(u32::from(*string_table_index))
-                                .unwrap()
-                                .as_str()
-                                .into(),
+                            sym: table_string(
+                                f,
+                                u32::from(*string_table_index),
+                                &instructions[*index],
+                                diagnostics,
+                            )
+                            .into(),
                             optional: false,
                         }),
                     })),
@@ -1921,11 +3382,13 @@ fn simple_instructions_to_ast(
                         })),
                         prop: MemberProp::Ident(Ident {
                             span: DUMMY_SP,
-                            sym: f
-                                .get_string(u32::from(*string_table_index))
-                                .unwrap()
-                                .as_str()
-                                .into(),
+                            sym: table_string(
+                                f,
+                                u32::from(*string_table_index),
+                                &instructions[*index],
+                                diagnostics,
+                            )
+                            .into(),
                             optional: false,
                         }),
                     }))),
@@ -1954,7 +3417,6 @@ fn simple_instructions_to_ast(
                         value: f
                             .get_string(u32::from(*string_table_index))
                             .unwrap_or_default()
-                            .as_str()
                             .into(),
                         raw: None,
                     }))),
@@ -1970,11 +3432,7 @@ fn simple_instructions_to_ast(
                         sym: format!("r{dst_reg}").as_str().into(),
                         optional: false,
                     }))),
-                    right: Box::new(Expr::Lit(Lit::Num(Number {
-                        span: DUMMY_SP,
-                        value: f64::from(*value),
-                        raw: None,
-                    }))),
+                    right: Box::new(Expr::Lit(Lit::Num(number_literal(f64::from(*value))))),
                 })),
             })),
             Instruction::LoadConstZero { dst_reg } => stmts.push(Stmt::Expr(ExprStmt {
@@ -2161,14 +3619,27 @@ fn simple_instructions_to_ast(
                     })),
                 })),
             })),
-            Instruction::Ret { value_reg } => stmts.push(Stmt::Return(ReturnStmt {
-                span: DUMMY_SP,
-                arg: Some(Box::new(Expr::Ident(Ident {
-                    span: DUMMY_SP,
-                    sym: format!("r{value_reg}").as_str().into(),
-                    optional: false,
-                }))),
-            })),
+            Instruction::Ret { value_reg } => {
+                let returns_undefined = match last_assigned_value(&stmts, *value_reg) {
+                    Some(Some(Expr::Ident(ident))) => &*ident.sym == "undefined",
+                    //registers start out undefined, and a block ending in a return is only
+                    //ever entered once, so one never written in the entry block is undefined
+                    None => node.index() == 0,
+                    Some(_) => false,
+                };
+                stmts.push(Stmt::Return(ReturnStmt {
+                    span: DUMMY_SP,
+                    arg: if returns_undefined {
+                        None
+                    } else {
+                        Some(Box::new(Expr::Ident(Ident {
+                            span: DUMMY_SP,
+                            sym: format!("r{value_reg}").as_str().into(),
+                            optional: false,
+                        })))
+                    },
+                }))
+            }
             Instruction::GetEnvironment {
                 dst_reg,
                 num_environments,
@@ -2191,11 +3662,11 @@ fn simple_instructions_to_ast(
                         }))),
                         args: vec![ExprOrSpread {
                             spread: None,
-                            expr: Box::new(Expr::Ident(Ident {
+                            expr: Box::new(Expr::Lit(Lit::Num(Number {
                                 span: DUMMY_SP,
-                                sym: format!("r{num_environments}").as_str().into(),
-                                optional: false,
-                            })),
+                                value: f64::from(*num_environments),
+                                raw: None,
+                            }))),
                         }],
                         type_args: None,
                     })),
@@ -2283,7 +3754,22 @@ fn simple_instructions_to_ast(
                     })),
                 })),
             })),
-            Instruction::Unreachable => (),
+            Instruction::Unreachable => stmts.push(Stmt::Throw(ThrowStmt {
+                span: DUMMY_SP,
+                arg: Box::new(Expr::New(NewExpr {
+                    span: DUMMY_SP,
+                    callee: Box::new(Expr::Ident(Ident::new("Error".into(), DUMMY_SP))),
+                    args: Some(vec![ExprOrSpread {
+                        spread: None,
+                        expr: Box::new(Expr::Lit(Lit::Str(Str {
+                            span: DUMMY_SP,
+                            value: "unreachable".into(),
+                            raw: None,
+                        }))),
+                    }]),
+                    type_args: None,
+                })),
+            })),
             Instruction::NewObjectWithBuffer {
                 dst_reg,
                 size_hint: _,
@@ -3433,7 +4919,9 @@ fn simple_instructions_to_ast(
                     })),
                 })),
             })),
-            Instruction::GetNewTarget { dst_reg: _ } => todo!(),
+            Instruction::GetNewTarget { dst_reg: _ } => {
+                stmts.push(unsupported(&instructions[*index], diagnostics))
+            }
             Instruction::CreateEnvironment { dst_reg } => stmts.push(Stmt::Expr(ExprStmt {
                 span: DUMMY_SP,
                 expr: Box::new(Expr::Assign(AssignExpr {
@@ -3471,7 +4959,13 @@ fn simple_instructions_to_ast(
                             })),
                             prop: MemberProp::Ident(Ident {
                                 span: DUMMY_SP,
-                                sym: f.get_string(*string_table_index).unwrap().as_str().into(),
+                                sym: table_string(
+                                    f,
+                                    *string_table_index,
+                                    &instructions[*index],
+                                    diagnostics,
+                                )
+                                .into(),
                                 optional: false,
                             }),
                         }))),
@@ -3507,7 +5001,13 @@ fn simple_instructions_to_ast(
                         })),
                         prop: MemberProp::Ident(Ident {
                             span: DUMMY_SP,
-                            sym: f.get_string(*string_table_index).unwrap().as_str().into(),
+                            sym: table_string(
+                                f,
+                                *string_table_index,
+                                &instructions[*index],
+                                diagnostics,
+                            )
+                            .into(),
                             optional: false,
                         }),
                     })),
@@ -3537,11 +5037,13 @@ fn simple_instructions_to_ast(
                         })),
                         prop: MemberProp::Ident(Ident {
                             span: DUMMY_SP,
-                            sym: f
-                                .get_string(u32::from(*string_table_index))
-                                .unwrap()
-                                .as_str()
-                                .into(),
+                            sym: table_string(
+                                f,
+                                u32::from(*string_table_index),
+                                &instructions[*index],
+                                diagnostics,
+                            )
+                            .into(),
                             optional: false,
                         }),
                     })),
@@ -3571,7 +5073,13 @@ fn simple_instructions_to_ast(
                         })),
                         prop: MemberProp::Ident(Ident {
                             span: DUMMY_SP,
-                            sym: f.get_string(*string_table_index).unwrap().as_str().into(),
+                            sym: table_string(
+                                f,
+                                *string_table_index,
+                                &instructions[*index],
+                                diagnostics,
+                            )
+                            .into(),
                             optional: false,
                         }),
                     })),
@@ -3596,7 +5104,13 @@ fn simple_instructions_to_ast(
                         })),
                         prop: MemberProp::Ident(Ident {
                             span: DUMMY_SP,
-                            sym: f.get_string(*string_table_index).unwrap().as_str().into(),
+                            sym: table_string(
+                                f,
+                                *string_table_index,
+                                &instructions[*index],
+                                diagnostics,
+                            )
+                            .into(),
                             optional: false,
                         }),
                     }))),
@@ -3626,11 +5140,13 @@ fn simple_instructions_to_ast(
                         })),
                         prop: MemberProp::Ident(Ident {
                             span: DUMMY_SP,
-                            sym: f
-                                .get_string(u32::from(*string_table_index))
-                                .unwrap()
-                                .as_str()
-                                .into(),
+                            sym: table_string(
+                                f,
+                                u32::from(*string_table_index),
+                                &instructions[*index],
+                                diagnostics,
+                            )
+                            .into(),
                             optional: false,
                         }),
                     }))),
@@ -3660,7 +5176,13 @@ fn simple_instructions_to_ast(
                         })),
                         prop: MemberProp::Ident(Ident {
                             span: DUMMY_SP,
-                            sym: f.get_string(*string_table_index).unwrap().as_str().into(),
+                            sym: table_string(
+                                f,
+                                *string_table_index,
+                                &instructions[*index],
+                                diagnostics,
+                            )
+                            .into(),
                             optional: false,
                         }),
                     }))),
@@ -3690,11 +5212,13 @@ fn simple_instructions_to_ast(
                         })),
                         prop: MemberProp::Ident(Ident {
                             span: DUMMY_SP,
-                            sym: f
-                                .get_string(u32::from(*string_table_index))
-                                .unwrap()
-                                .as_str()
-                                .into(),
+                            sym: table_string(
+                                f,
+                                u32::from(*string_table_index),
+                                &instructions[*index],
+                                diagnostics,
+                            )
+                            .into(),
                             optional: false,
                         }),
                     }))),
@@ -3723,11 +5247,13 @@ fn simple_instructions_to_ast(
                         })),
                         prop: MemberProp::Ident(Ident {
                             span: DUMMY_SP,
-                            sym: f
-                                .get_string(u32::from(*string_table_index))
-                                .unwrap()
-                                .as_str()
-                                .into(),
+                            sym: table_string(
+                                f,
+                                u32::from(*string_table_index),
+                                &instructions[*index],
+                                diagnostics,
+                            )
+                            .into(),
                             optional: false,
                         }),
                     }))),
@@ -3756,7 +5282,13 @@ fn simple_instructions_to_ast(
                         })),
                         prop: MemberProp::Ident(Ident {
                             span: DUMMY_SP,
-                            sym: f.get_string(*string_table_index).unwrap().as_str().into(),
+                            sym: table_string(
+                                f,
+                                *string_table_index,
+                                &instructions[*index],
+                                diagnostics,
+                            )
+                            .into(),
                             optional: false,
                         }),
                     }))),
@@ -3771,12 +5303,12 @@ fn simple_instructions_to_ast(
                 dst_obj_reg: _,
                 value_reg: _,
                 string_table_index: _,
-            } => todo!(),
+            } => stmts.push(unsupported(&instructions[*index], diagnostics)),
             Instruction::PutNewOwnNEByIdLong {
                 dst_obj_reg: _,
                 value_reg: _,
                 string_table_index: _,
-            } => todo!(),
+            } => stmts.push(unsupported(&instructions[*index], diagnostics)),
             Instruction::PutOwnByIndex {
                 dst_obj_reg,
                 value_reg,
@@ -3877,81 +5409,18 @@ fn simple_instructions_to_ast(
                         })),
                     }))
                 } else {
-                    stmts.push(Stmt::Expr(ExprStmt {
-                        span: DUMMY_SP,
-                        expr: Box::new(Expr::Call(CallExpr {
-                            span: DUMMY_SP,
-                            callee: Callee::Expr(Box::new(Expr::Member(MemberExpr {
-                                span: DUMMY_SP,
-                                obj: Box::new(Expr::Ident(Ident {
-                                    span: DUMMY_SP,
-                                    sym: "Object".into(),
-                                    optional: false,
-                                })),
-                                prop: MemberProp::Ident(Ident {
-                                    span: DUMMY_SP,
-                                    sym: "defineProperty".into(),
-                                    optional: false,
-                                }),
-                            }))),
-                            args: vec![
-                                ExprOrSpread {
-                                    spread: None,
-                                    expr: Box::new(Expr::Ident(Ident {
-                                        span: DUMMY_SP,
-                                        sym: format!("r{dst_obj_reg}").as_str().into(),
-                                        optional: false,
-                                    })),
-                                },
-                                ExprOrSpread {
-                                    spread: None,
-                                    expr: Box::new(Expr::Ident(Ident {
-                                        span: DUMMY_SP,
-                                        sym: format!("r{property_name_reg}").as_str().into(),
-                                        optional: false,
-                                    })),
-                                },
-                                ExprOrSpread {
-                                    spread: None,
-                                    expr: Box::new(Expr::Object(ObjectLit {
-                                        span: DUMMY_SP,
-                                        props: vec![
-                                            PropOrSpread::Prop(Box::new(Prop::KeyValue(
-                                                KeyValueProp {
-                                                    key: PropName::Ident(Ident {
-                                                        span: DUMMY_SP,
-                                                        sym: "value".into(),
-                                                        optional: false,
-                                                    }),
-                                                    value: Box::new(Expr::Ident(Ident {
-                                                        span: DUMMY_SP,
-                                                        sym: format!("r{value_reg}")
-                                                            .as_str()
-                                                            .into(),
-                                                        optional: false,
-                                                    })),
-                                                },
-                                            ))),
-                                            PropOrSpread::Prop(Box::new(Prop::KeyValue(
-                                                KeyValueProp {
-                                                    key: PropName::Ident(Ident {
-                                                        span: DUMMY_SP,
-                                                        sym: "enumerable".into(),
-                                                        optional: false,
-                                                    }),
-                                                    value: Box::new(Expr::Lit(Lit::Bool(Bool {
-                                                        span: DUMMY_SP,
-                                                        value: false,
-                                                    }))),
-                                                },
-                                            ))),
-                                        ],
-                                    })),
-                                },
-                            ],
-                            type_args: None,
-                        })),
-                    }))
+                    //an own data property is writable and configurable whether it's enumerable
+                    //or not, which defineProperty would otherwise default to false
+                    stmts.push(define_property_stmt(
+                        *dst_obj_reg,
+                        *property_name_reg,
+                        vec![
+                            ("value", reg_expr(*value_reg)),
+                            ("writable", bool_expr(true)),
+                            ("enumerable", bool_expr(*enumerable)),
+                            ("configurable", bool_expr(true)),
+                        ],
+                    ))
                 }
             }
             Instruction::DelById {
@@ -3980,11 +5449,13 @@ fn simple_instructions_to_ast(
                             })),
                             prop: MemberProp::Ident(Ident {
                                 span: DUMMY_SP,
-                                sym: f
-                                    .get_string(u32::from(*string_table_index))
-                                    .unwrap()
-                                    .as_str()
-                                    .into(),
+                                sym: table_string(
+                                    f,
+                                    u32::from(*string_table_index),
+                                    &instructions[*index],
+                                    diagnostics,
+                                )
+                                .into(),
                                 optional: false,
                             }),
                         })),
@@ -4017,7 +5488,13 @@ fn simple_instructions_to_ast(
                             })),
                             prop: MemberProp::Ident(Ident {
                                 span: DUMMY_SP,
-                                sym: f.get_string(*string_table_index).unwrap().as_str().into(),
+                                sym: table_string(
+                                    f,
+                                    *string_table_index,
+                                    &instructions[*index],
+                                    diagnostics,
+                                )
+                                .into(),
                                 optional: false,
                             }),
                         })),
@@ -4045,14 +5522,7 @@ fn simple_instructions_to_ast(
                             sym: format!("r{obj_reg}").as_str().into(),
                             optional: false,
                         })),
-                        prop: MemberProp::Computed(ComputedPropName {
-                            span: DUMMY_SP,
-                            expr: Box::new(Expr::Ident(Ident {
-                                span: DUMMY_SP,
-                                sym: format!("r{index_reg}").as_str().into(),
-                                optional: false,
-                            })),
-                        }),
+                        prop: index_reg_to_member_prop(&stmts, *index_reg),
                     })),
                 })),
             })),
@@ -4072,14 +5542,7 @@ fn simple_instructions_to_ast(
                             sym: format!("r{dst_obj_reg}").as_str().into(),
                             optional: false,
                         })),
-                        prop: MemberProp::Computed(ComputedPropName {
-                            span: DUMMY_SP,
-                            expr: Box::new(Expr::Ident(Ident {
-                                span: DUMMY_SP,
-                                sym: format!("r{index_reg}").as_str().into(),
-                                optional: false,
-                            })),
-                        }),
+                        prop: index_reg_to_member_prop(&stmts, *index_reg),
                     }))),
                     right: Box::new(Expr::Ident(Ident {
                         span: DUMMY_SP,
@@ -4107,110 +5570,39 @@ fn simple_instructions_to_ast(
                         op: UnaryOp::Delete,
                         arg: Box::new(Expr::Member(MemberExpr {
                             span: DUMMY_SP,
-                            obj: Box::new(Expr::Ident(Ident {
-                                span: DUMMY_SP,
-                                sym: format!("r{obj_reg}").as_str().into(),
-                                optional: false,
-                            })),
-                            prop: MemberProp::Computed(ComputedPropName {
-                                span: DUMMY_SP,
-                                expr: Box::new(Expr::Ident(Ident {
-                                    span: DUMMY_SP,
-                                    sym: format!("r{index_reg}").as_str().into(),
-                                    optional: false,
-                                })),
-                            }),
-                        })),
-                    })),
-                })),
-            })),
-            Instruction::PutOwnGetterSetterByVal {
-                obj_reg,
-                property_name_reg,
-                getter_closure_reg,
-                setter_closure_reg,
-                enumerable,
-            } => stmts.push(Stmt::Expr(ExprStmt {
-                span: DUMMY_SP,
-                expr: Box::new(Expr::Call(CallExpr {
-                    span: DUMMY_SP,
-                    callee: Callee::Expr(Box::new(Expr::Member(MemberExpr {
-                        span: DUMMY_SP,
-                        obj: Box::new(Expr::Ident(Ident {
-                            span: DUMMY_SP,
-                            sym: "Object".into(),
-                            optional: false,
-                        })),
-                        prop: MemberProp::Ident(Ident {
-                            span: DUMMY_SP,
-                            sym: "defineProperty".into(),
-                            optional: false,
-                        }),
-                    }))),
-                    args: vec![
-                        ExprOrSpread {
-                            spread: None,
-                            expr: Box::new(Expr::Ident(Ident {
+                            obj: Box::new(Expr::Ident(Ident {
                                 span: DUMMY_SP,
                                 sym: format!("r{obj_reg}").as_str().into(),
                                 optional: false,
                             })),
-                        },
-                        ExprOrSpread {
-                            spread: None,
-                            expr: Box::new(Expr::Ident(Ident {
-                                span: DUMMY_SP,
-                                sym: format!("r{property_name_reg}").as_str().into(),
-                                optional: false,
-                            })),
-                        },
-                        ExprOrSpread {
-                            spread: None,
-                            expr: Box::new(Expr::Object(ObjectLit {
+                            prop: MemberProp::Computed(ComputedPropName {
                                 span: DUMMY_SP,
-                                props: vec![
-                                    PropOrSpread::Prop(Box::new(Prop::KeyValue(KeyValueProp {
-                                        key: PropName::Ident(Ident {
-                                            span: DUMMY_SP,
-                                            sym: "get".into(),
-                                            optional: false,
-                                        }),
-                                        value: Box::new(Expr::Ident(Ident {
-                                            span: DUMMY_SP,
-                                            sym: format!("r{getter_closure_reg}").as_str().into(),
-                                            optional: false,
-                                        })),
-                                    }))),
-                                    PropOrSpread::Prop(Box::new(Prop::KeyValue(KeyValueProp {
-                                        key: PropName::Ident(Ident {
-                                            span: DUMMY_SP,
-                                            sym: "set".into(),
-                                            optional: false,
-                                        }),
-                                        value: Box::new(Expr::Ident(Ident {
-                                            span: DUMMY_SP,
-                                            sym: format!("r{setter_closure_reg}").as_str().into(),
-                                            optional: false,
-                                        })),
-                                    }))),
-                                    PropOrSpread::Prop(Box::new(Prop::KeyValue(KeyValueProp {
-                                        key: PropName::Ident(Ident {
-                                            span: DUMMY_SP,
-                                            sym: "enumerable".into(),
-                                            optional: false,
-                                        }),
-                                        value: Box::new(Expr::Lit(Lit::Bool(Bool {
-                                            span: DUMMY_SP,
-                                            value: *enumerable,
-                                        }))),
-                                    }))),
-                                ],
-                            })),
-                        },
-                    ],
-                    type_args: None,
+                                expr: Box::new(Expr::Ident(Ident {
+                                    span: DUMMY_SP,
+                                    sym: format!("r{index_reg}").as_str().into(),
+                                    optional: false,
+                                })),
+                            }),
+                        })),
+                    })),
                 })),
             })),
+            Instruction::PutOwnGetterSetterByVal {
+                obj_reg,
+                property_name_reg,
+                getter_closure_reg,
+                setter_closure_reg,
+                enumerable,
+            } => stmts.push(define_property_stmt(
+                *obj_reg,
+                *property_name_reg,
+                vec![
+                    ("get", reg_expr(*getter_closure_reg)),
+                    ("set", reg_expr(*setter_closure_reg)),
+                    ("enumerable", bool_expr(*enumerable)),
+                    ("configurable", bool_expr(true)),
+                ],
+            )),
             Instruction::GetPNameList {
                 dst_reg,
                 obj_reg,
@@ -4350,123 +5742,66 @@ fn simple_instructions_to_ast(
                 dst_reg,
                 closure_reg,
                 arguments_len,
-            } => {
-                let mut arguments = Vec::new();
-                for s in &stmts[stmts.len() - *arguments_len as usize..stmts.len()] {
-                    if let Stmt::Expr(s) = s {
-                        if let Expr::Assign(s) = &*s.expr {
-                            arguments.push(ExprOrSpread {
-                                spread: None,
-                                expr: Box::new(Expr::Ident(s.left.as_ident().unwrap().clone())),
-                            });
-                        }
-                    }
-                }
-                stmts.push(Stmt::Expr(ExprStmt {
-                    span: DUMMY_SP,
-                    expr: Box::new(Expr::Assign(AssignExpr {
-                        span: DUMMY_SP,
-                        op: AssignOp::Assign,
-                        left: PatOrExpr::Expr(Box::new(Expr::Ident(Ident {
-                            span: DUMMY_SP,
-                            sym: format!("r{dst_reg}").as_str().into(),
-                            optional: false,
-                        }))),
-                        right: Box::new(Expr::Call(CallExpr {
-                            span: DUMMY_SP,
-                            callee: Callee::Expr(Box::new(Expr::Call(CallExpr {
-                                span: DUMMY_SP,
-                                callee: Callee::Expr(Box::new(Expr::Member(MemberExpr {
-                                    span: DUMMY_SP,
-                                    obj: Box::new(Expr::Ident(Ident {
-                                        span: DUMMY_SP,
-                                        sym: format!("r{closure_reg}").as_str().into(),
-                                        optional: false,
-                                    })),
-                                    prop: MemberProp::Ident(Ident {
-                                        span: DUMMY_SP,
-                                        sym: "bind".into(),
-                                        optional: false,
-                                    }),
-                                }))),
-                                args: vec![arguments[0].clone()],
-                                type_args: None,
-                            }))),
-                            args: arguments[1..].to_vec(),
-                            type_args: None,
-                        })),
-                    })),
-                }));
-            }
+            } => stmts.push(call_stmt(
+                *dst_reg,
+                reg_expr(*closure_reg),
+                frame_arguments(frame_size, u32::from(*arguments_len)),
+            )),
             Instruction::Construct {
                 dst_reg,
                 closure_reg,
                 arguments_len,
-            } => {
-                let mut arguments = Vec::new();
-                for s in &stmts[stmts.len() - *arguments_len as usize..stmts.len()] {
-                    if let Stmt::Expr(s) = s {
-                        if let Expr::Assign(s) = &*s.expr {
-                            arguments.push(ExprOrSpread {
-                                spread: None,
-                                expr: Box::new(Expr::Ident(s.left.as_ident().unwrap().clone())),
-                            });
-                        }
-                    }
-                }
-                stmts.push(Stmt::Expr(ExprStmt {
-                    span: DUMMY_SP,
-                    expr: Box::new(Expr::Assign(AssignExpr {
-                        span: DUMMY_SP,
-                        op: AssignOp::Assign,
-                        left: PatOrExpr::Expr(Box::new(Expr::Ident(Ident {
-                            span: DUMMY_SP,
-                            sym: format!("r{dst_reg}").as_str().into(),
-                            optional: false,
-                        }))),
-                        right: Box::new(Expr::New(NewExpr {
-                            span: DUMMY_SP,
-                            callee: Box::new(Expr::Ident(Ident {
-                                span: DUMMY_SP,
-                                sym: format!("r{closure_reg}").as_str().into(),
-                                optional: false,
-                            })),
-                            args: Some(arguments),
-                            type_args: None,
-                        })),
-                    })),
-                }))
-            }
+            } => stmts.push(construct_stmt(
+                *dst_reg,
+                *closure_reg,
+                frame_arguments(frame_size, u32::from(*arguments_len)),
+            )),
             Instruction::CallDirect {
                 dst_reg: _,
                 arguments_len: _,
                 function_table_index: _,
-            } => todo!(),
+            } => stmts.push(unsupported(&instructions[*index], diagnostics)),
             Instruction::CallLong {
-                dst_reg: _,
-                closure_reg: _,
-                arguments_len: _,
-            } => todo!(),
+                dst_reg,
+                closure_reg,
+                arguments_len,
+            } => stmts.push(call_stmt(
+                *dst_reg,
+                reg_expr(*closure_reg),
+                frame_arguments(frame_size, *arguments_len),
+            )),
             Instruction::ConstructLong {
-                dst_reg: _,
-                closure_reg: _,
-                arguments_len: _,
-            } => todo!(),
+                dst_reg,
+                closure_reg,
+                arguments_len,
+            } => stmts.push(construct_stmt(
+                *dst_reg,
+                *closure_reg,
+                frame_arguments(frame_size, *arguments_len),
+            )),
             Instruction::CallDirectLongIndex {
                 dst_reg: _,
                 arguments_len: _,
                 function_table_index: _,
-            } => todo!(),
+            } => stmts.push(unsupported(&instructions[*index], diagnostics)),
             Instruction::CallBuiltin {
-                dst_reg: _,
-                builtin_number: _,
-                arguments_len: _,
-            } => todo!(),
+                dst_reg,
+                builtin_number,
+                arguments_len,
+            } => stmts.push(call_builtin_stmt(
+                *dst_reg,
+                *builtin_number,
+                frame_arguments(frame_size, u32::from(*arguments_len)),
+            )),
             Instruction::CallBuiltinLong {
-                dst_reg: _,
-                builtin_number: _,
-                arguments_len: _,
-            } => todo!(),
+                dst_reg,
+                builtin_number,
+                arguments_len,
+            } => stmts.push(call_builtin_stmt(
+                *dst_reg,
+                *builtin_number,
+                frame_arguments(frame_size, *arguments_len),
+            )),
             Instruction::GetBuiltinClosure {
                 dst_reg,
                 builtin_number,
@@ -4480,34 +5815,12 @@ fn simple_instructions_to_ast(
                         sym: format!("r{dst_reg}").as_str().into(),
                         optional: false,
                     }))),
-                    right: Box::new({
-                        let builtin = *JS_BUILTINS.get(*builtin_number as usize).unwrap();
-                        if builtin.contains('.') {
-                            let mut s = builtin.split('.');
-                            Expr::Member(MemberExpr {
-                                span: DUMMY_SP,
-                                obj: Box::new(Expr::Ident(Ident {
-                                    span: DUMMY_SP,
-                                    sym: s.next().unwrap().into(),
-                                    optional: false,
-                                })),
-                                prop: MemberProp::Ident(Ident {
-                                    span: DUMMY_SP,
-                                    sym: s.next().unwrap().into(),
-                                    optional: false,
-                                }),
-                            })
-                        } else {
-                            Expr::Ident(Ident {
-                                span: DUMMY_SP,
-                                sym: builtin.into(),
-                                optional: false,
-                            })
-                        }
-                    }),
+                    right: Box::new(builtin_expr(*builtin_number)),
                 })),
             })),
-            Instruction::Catch { dst_reg: _ } => todo!(),
+            Instruction::Catch { dst_reg: _ } => {
+                stmts.push(unsupported(&instructions[*index], diagnostics))
+            }
             Instruction::DirectEval { dst_reg, value_reg } => stmts.push(Stmt::Expr(ExprStmt {
                 span: DUMMY_SP,
                 expr: Box::new(Expr::Assign(AssignExpr {
@@ -4546,60 +5859,107 @@ fn simple_instructions_to_ast(
                 })),
             })),
             Instruction::ThrowIfEmpty {
-                dst_reg: _,
-                checked_value_reg: _,
-            } => todo!(),
+                dst_reg,
+                checked_value_reg,
+            } => {
+                //if (rC === __empty) throw new ReferenceError(...); rD = rC
+                stmts.push(Stmt::If(IfStmt {
+                    span: DUMMY_SP,
+                    test: Box::new(Expr::Bin(BinExpr {
+                        span: DUMMY_SP,
+                        op: BinaryOp::EqEqEq,
+                        left: reg_expr(*checked_value_reg),
+                        right: Box::new(Expr::Ident(Ident::new(EMPTY.into(), DUMMY_SP))),
+                    })),
+                    cons: Box::new(Stmt::Throw(ThrowStmt {
+                        span: DUMMY_SP,
+                        arg: Box::new(Expr::New(NewExpr {
+                            span: DUMMY_SP,
+                            callee: Box::new(Expr::Ident(Ident::new(
+                                "ReferenceError".into(),
+                                DUMMY_SP,
+                            ))),
+                            args: Some(vec![ExprOrSpread {
+                                spread: None,
+                                expr: Box::new(Expr::Lit(Lit::Str(Str {
+                                    span: DUMMY_SP,
+                                    value: "accessing an uninitialized variable".into(),
+                                    raw: None,
+                                }))),
+                            }]),
+                            type_args: None,
+                        })),
+                    })),
+                    alt: None,
+                }));
+                stmts.push(assign_stmt(*dst_reg, reg_expr(*checked_value_reg)));
+            }
             Instruction::Debugger => stmts.push(Stmt::Debugger(DebuggerStmt { span: DUMMY_SP })),
             Instruction::AsyncBreakCheck => (),
             Instruction::ProfilePoint {
-                function_local_profile_point_index: _,
-            } => (),
+                function_local_profile_point_index,
+            } => {
+                if let Some(comments) = profile_points {
+                    //comments are looked up by position so every profile point needs its own,
+                    //position 0 is reserved for spans without one
+                    let pos = BytePos(instructions[*index].offset + 1);
+                    comments.add_leading(
+                        pos,
+                        Comment {
+                            kind: CommentKind::Block,
+                            span: DUMMY_SP,
+                            text: format!(" profile point {function_local_profile_point_index} ")
+                                .into(),
+                        },
+                    );
+                    stmts.push(Stmt::Empty(EmptyStmt {
+                        span: Span::new(pos, pos, Default::default()),
+                    }));
+                }
+            }
+            //generator and async functions are decompiled like any other, so their closures are
+            //referenced the same way
             Instruction::CreateClosure {
                 dst_reg,
                 current_environment_reg: _,
                 function_table_index,
-            } => stmts.push(Stmt::Expr(ExprStmt {
-                span: DUMMY_SP,
-                expr: Box::new(Expr::Assign(AssignExpr {
-                    span: DUMMY_SP,
-                    op: AssignOp::Assign,
-                    left: PatOrExpr::Expr(Box::new(Expr::Ident(Ident {
-                        span: DUMMY_SP,
-                        sym: format!("r{dst_reg}").as_str().into(),
-                        optional: false,
-                    }))),
-                    right: Box::new(Expr::Ident(Ident {
-                        span: DUMMY_SP,
-                        sym: format!("f{function_table_index}").as_str().into(),
-                        optional: false,
-                    })),
-                })),
-            })),
-            Instruction::CreateClosureLongIndex {
-                dst_reg: _,
+            }
+            | Instruction::CreateGeneratorClosure {
+                dst_reg,
                 current_environment_reg: _,
-                function_table_index: _,
-            } => todo!(),
-            Instruction::CreateGeneratorClosure {
-                dst_reg: _,
+                function_table_index,
+            }
+            | Instruction::CreateAsyncClosure {
+                dst_reg,
                 current_environment_reg: _,
-                function_table_index: _,
-            } => todo!(),
-            Instruction::CreateGeneratorClosureLongIndex {
-                dst_reg: _,
+                function_table_index,
+            } => {
+                let function_id = u32::from(*function_table_index);
+                let name = function_names.and_then(|names| names(function_id));
+                stmts.push(closure_stmt(*dst_reg, function_id, name.as_deref()))
+            }
+            Instruction::CreateClosureLongIndex {
+                dst_reg,
                 current_environment_reg: _,
-                function_table_index: _,
-            } => todo!(),
-            Instruction::CreateAsyncClosure {
-                dst_reg: _,
+                function_table_index,
+            }
+            | Instruction::CreateGeneratorClosureLongIndex {
+                dst_reg,
                 current_environment_reg: _,
-                function_table_index: _,
-            } => todo!(),
-            Instruction::CreateAsyncClosureLongIndex {
-                dst_reg: _,
+                function_table_index,
+            }
+            | Instruction::CreateAsyncClosureLongIndex {
+                dst_reg,
                 current_environment_reg: _,
-                function_table_index: _,
-            } => todo!(),
+                function_table_index,
+            } => {
+                let name = function_names.and_then(|names| names(*function_table_index));
+                stmts.push(closure_stmt(
+                    *dst_reg,
+                    *function_table_index,
+                    name.as_deref(),
+                ))
+            }
             Instruction::CreateThis {
                 dst_reg,
                 prototype_reg,
@@ -4757,11 +6117,7 @@ fn simple_instructions_to_ast(
                         sym: format!("r{dst_reg}").as_str().into(),
                         optional: false,
                     }))),
-                    right: Box::new(Expr::Lit(Lit::Num(Number {
-                        span: DUMMY_SP,
-                        value: f64::from(*value),
-                        raw: None,
-                    }))),
+                    right: Box::new(Expr::Lit(Lit::Num(number_literal(f64::from(*value))))),
                 })),
             })),
             Instruction::LoadConstDouble { dst_reg, value } => stmts.push(Stmt::Expr(ExprStmt {
@@ -4774,11 +6130,7 @@ fn simple_instructions_to_ast(
                         sym: format!("r{dst_reg}").as_str().into(),
                         optional: false,
                     }))),
-                    right: Box::new(Expr::Lit(Lit::Num(Number {
-                        span: DUMMY_SP,
-                        value: *value,
-                        raw: None,
-                    }))),
+                    right: Box::new(double_expr(*value)),
                 })),
             })),
             Instruction::LoadConstBigInt {
@@ -4803,12 +6155,12 @@ fn simple_instructions_to_ast(
                 })),
             }))*/
             {
-                todo!()
+                stmts.push(unsupported(&instructions[*index], diagnostics))
             }
             Instruction::LoadConstBigIntLongIndex {
                 dst_reg: _,
                 bigint_table_index: _,
-            } => todo!(),
+            } => stmts.push(unsupported(&instructions[*index], diagnostics)),
             Instruction::LoadConstStringLongIndex {
                 dst_reg,
                 string_table_index,
@@ -4827,28 +6179,15 @@ fn simple_instructions_to_ast(
                         value: f
                             .get_string(*string_table_index)
                             .unwrap_or_default()
-                            .as_str()
                             .into(),
                         raw: None,
                     }))),
                 })),
             })),
-            Instruction::LoadConstEmpty { dst_reg } => stmts.push(Stmt::Expr(ExprStmt {
-                span: DUMMY_SP,
-                expr: Box::new(Expr::Assign(AssignExpr {
-                    span: DUMMY_SP,
-                    op: AssignOp::Assign,
-                    left: PatOrExpr::Expr(Box::new(Expr::Ident(Ident::new(
-                        format!("r{dst_reg}").as_str().into(),
-                        DUMMY_SP,
-                    )))),
-                    right: Box::new(Expr::Ident(Ident {
-                        span: DUMMY_SP,
-                        sym: "undefined".into(),
-                        optional: false,
-                    })),
-                })),
-            })),
+            Instruction::LoadConstEmpty { dst_reg } => stmts.push(assign_stmt(
+                *dst_reg,
+                Box::new(Expr::Ident(Ident::new(EMPTY.into(), DUMMY_SP))),
+            )),
             Instruction::CoerceThisNS {
                 dst_reg,
                 this_value_reg,
@@ -4918,7 +6257,7 @@ fn simple_instructions_to_ast(
             Instruction::ToNumeric {
                 dst_reg: _,
                 value_reg: _,
-            } => todo!(),
+            } => stmts.push(unsupported(&instructions[*index], diagnostics)),
             Instruction::ToInt32 { dst_reg, value_reg } => stmts.push(Stmt::Expr(ExprStmt {
                 span: DUMMY_SP,
                 expr: Box::new(Expr::Assign(AssignExpr {
@@ -5051,47 +6390,59 @@ fn simple_instructions_to_ast(
                 })),
             })),
             Instruction::CreateRegExp {
-                dst_reg: _,
-                pattern_string_index: _,
-                flags_string_index: _,
+                dst_reg,
+                pattern_string_index,
+                flags_string_index,
                 regexp_table_index: _,
-            } => todo!(),
+            } => stmts.push(assign_stmt(
+                *dst_reg,
+                Box::new(Expr::Lit(Lit::Regex(regex_literal(
+                    f.get_string(*pattern_string_index).unwrap_or_default(),
+                    f.get_string(*flags_string_index).unwrap_or_default(),
+                    instructions[*index].offset,
+                    diagnostics,
+                )))),
+            )),
             Instruction::SwitchImm {
                 value_reg: _,
                 relative_jump_table_offset: _,
                 relative_default_jump_offset: _,
                 min_value: _,
                 max_value: _,
-            } => todo!(),
-            Instruction::StartGenerator => todo!(),
+            } => stmts.push(unsupported(&instructions[*index], diagnostics)),
+            Instruction::StartGenerator => {
+                stmts.push(unsupported(&instructions[*index], diagnostics))
+            }
             Instruction::ResumeGenerator {
                 dst_result_reg: _,
                 is_return: _,
-            } => todo!(),
-            Instruction::CompleteGenerator => todo!(),
+            } => stmts.push(unsupported(&instructions[*index], diagnostics)),
+            Instruction::CompleteGenerator => {
+                stmts.push(unsupported(&instructions[*index], diagnostics))
+            }
             Instruction::CreateGenerator {
                 dst_reg: _,
                 current_environment_reg: _,
                 function_table_index: _,
-            } => todo!(),
+            } => stmts.push(unsupported(&instructions[*index], diagnostics)),
             Instruction::CreateGeneratorLongIndex {
                 dst_reg: _,
                 current_environment_reg: _,
                 function_table_index: _,
-            } => todo!(),
+            } => stmts.push(unsupported(&instructions[*index], diagnostics)),
             Instruction::IteratorBegin {
-                dst_reg: _,
-                source_reg: _,
-            } => todo!(),
+                dst_reg,
+                source_reg,
+            } => stmts.push(iterator_begin_stmt(*dst_reg, *source_reg)),
             Instruction::IteratorNext {
-                dst_reg: _,
-                iterator_or_index_reg: _,
+                dst_reg,
+                iterator_or_index_reg,
                 source_reg: _,
-            } => todo!(),
+            } => stmts.extend(iterator_next_stmts(*dst_reg, *iterator_or_index_reg)),
             Instruction::IteratorClose {
-                iterator_or_index_reg: _,
+                iterator_or_index_reg,
                 ignore_inner_exception: _,
-            } => todo!(),
+            } => stmts.push(iterator_close_stmt(*iterator_or_index_reg)),
 
             Instruction::Jmp { relative_offset: _ } => (),
             Instruction::JmpLong { relative_offset: _ } => (),
@@ -5119,8 +6470,12 @@ fn simple_instructions_to_ast(
                 relative_offset: _,
                 check_value_reg: _,
             } => (),
-            Instruction::SaveGenerator { relative_offset: _ } => todo!(),
-            Instruction::SaveGeneratorLong { relative_offset: _ } => todo!(),
+            Instruction::SaveGenerator { relative_offset: _ } => {
+                stmts.push(unsupported(&instructions[*index], diagnostics))
+            }
+            Instruction::SaveGeneratorLong { relative_offset: _ } => {
+                stmts.push(unsupported(&instructions[*index], diagnostics))
+            }
             Instruction::JLess {
                 relative_offset: _,
                 arg1_value_reg: _,
@@ -5326,74 +6681,192 @@ fn simple_instructions_to_ast(
                 dst_reg: _,
                 arg1_reg: _,
                 arg2_reg: _,
-            } => todo!(),
+            } => stmts.push(unsupported(&instructions[*index], diagnostics)),
             Instruction::Sub32 {
                 dst_reg: _,
                 arg1_reg: _,
                 arg2_reg: _,
-            } => todo!(),
+            } => stmts.push(unsupported(&instructions[*index], diagnostics)),
             Instruction::Mul32 {
                 dst_reg: _,
                 arg1_reg: _,
                 arg2_reg: _,
-            } => todo!(),
+            } => stmts.push(unsupported(&instructions[*index], diagnostics)),
             Instruction::Divi32 {
                 dst_reg: _,
                 arg1_reg: _,
                 arg2_reg: _,
-            } => todo!(),
+            } => stmts.push(unsupported(&instructions[*index], diagnostics)),
             Instruction::Divu32 {
                 dst_reg: _,
                 arg1_reg: _,
                 arg2_reg: _,
-            } => todo!(),
+            } => stmts.push(unsupported(&instructions[*index], diagnostics)),
             Instruction::Loadi8 {
-                dst_reg: _,
-                _unused_reg,
-                heap_index_reg: _,
-            } => todo!(),
+                dst_reg,
+                heap_reg,
+                heap_index_reg,
+            } => stmts.push(assign_stmt(
+                *dst_reg,
+                heap_element_expr("Int8Array", *heap_reg, *heap_index_reg, 0),
+            )),
             Instruction::Loadu8 {
-                dst_reg: _,
-                _unused_reg,
-                heap_index_reg: _,
-            } => todo!(),
+                dst_reg,
+                heap_reg,
+                heap_index_reg,
+            } => stmts.push(assign_stmt(
+                *dst_reg,
+                heap_element_expr("Uint8Array", *heap_reg, *heap_index_reg, 0),
+            )),
             Instruction::Loadi16 {
-                dst_reg: _,
-                _unused_reg,
-                heap_index_reg: _,
-            } => todo!(),
+                dst_reg,
+                heap_reg,
+                heap_index_reg,
+            } => stmts.push(assign_stmt(
+                *dst_reg,
+                heap_element_expr("Int16Array", *heap_reg, *heap_index_reg, 1),
+            )),
             Instruction::Loadu16 {
-                dst_reg: _,
-                _unused_reg,
-                heap_index_reg: _,
-            } => todo!(),
+                dst_reg,
+                heap_reg,
+                heap_index_reg,
+            } => stmts.push(assign_stmt(
+                *dst_reg,
+                heap_element_expr("Uint16Array", *heap_reg, *heap_index_reg, 1),
+            )),
             Instruction::Loadi32 {
-                dst_reg: _,
-                _unused_reg,
-                heap_index_reg: _,
-            } => todo!(),
+                dst_reg,
+                heap_reg,
+                heap_index_reg,
+            } => stmts.push(assign_stmt(
+                *dst_reg,
+                heap_element_expr("Int32Array", *heap_reg, *heap_index_reg, 2),
+            )),
             Instruction::Loadu32 {
-                dst_reg: _,
-                _unused_reg,
-                heap_index_reg: _,
-            } => todo!(),
+                dst_reg,
+                heap_reg,
+                heap_index_reg,
+            } => stmts.push(assign_stmt(
+                *dst_reg,
+                heap_element_expr("Uint32Array", *heap_reg, *heap_index_reg, 2),
+            )),
             Instruction::Store8 {
-                _unused_reg,
-                heap_index_reg: _,
-                value_reg: _,
-            } => todo!(),
+                heap_reg,
+                heap_index_reg,
+                value_reg,
+            } => stmts.push(heap_store_stmt(
+                "Int8Array",
+                *heap_reg,
+                *heap_index_reg,
+                0,
+                *value_reg,
+            )),
             Instruction::Store16 {
-                _unused_reg,
-                heap_index_reg: _,
-                value_reg: _,
-            } => todo!(),
+                heap_reg,
+                heap_index_reg,
+                value_reg,
+            } => stmts.push(heap_store_stmt(
+                "Int16Array",
+                *heap_reg,
+                *heap_index_reg,
+                1,
+                *value_reg,
+            )),
             Instruction::Store32 {
-                _unused_reg,
-                heap_index_reg: _,
-                value_reg: _,
-            } => todo!(),
+                heap_reg,
+                heap_index_reg,
+                value_reg,
+            } => stmts.push(heap_store_stmt(
+                "Int32Array",
+                *heap_reg,
+                *heap_index_reg,
+                2,
+                *value_reg,
+            )),
+        }
+        if let Some(comments) = cache_indices {
+            if let Some(cache_index) = property_cache_index(&instructions[*index].instruction) {
+                add_trailing_comment(
+                    &mut stmts[first_stmt..],
+                    instructions[*index].offset,
+                    comments,
+                    CommentKind::Block,
+                    format!(" cache index {cache_index} "),
+                );
+            }
+        }
+        if let Some(comments) = annotations {
+            annotate(&mut stmts[first_stmt..], &instructions[*index], comments);
         }
     }
 
     stmts
 }
+
+fn annotate(
+    stmts: &mut [Stmt],
+    instruction: &InstructionInfo<Instruction>,
+    comments: &SingleThreadedComments,
+) {
+    add_trailing_comment(
+        stmts,
+        instruction.offset,
+        comments,
+        CommentKind::Line,
+        format!(
+            " @{:#X} {}",
+            instruction.offset,
+            instruction.instruction.name()
+        ),
+    );
+}
+
+//the inline cache slot of reads of a property by id
+fn property_cache_index(instruction: &Instruction) -> Option<u8> {
+    match instruction {
+        Instruction::GetByIdShort { cache_index, .. }
+        | Instruction::GetById { cache_index, .. }
+        | Instruction::GetByIdLong { cache_index, .. }
+        | Instruction::TryGetById { cache_index, .. }
+        | Instruction::TryGetByIdLong { cache_index, .. } => Some(*cache_index),
+        _ => None,
+    }
+}
+
+//adds a comment after the first of the statements generated from the instruction at `offset`
+fn add_trailing_comment(
+    stmts: &mut [Stmt],
+    offset: u32,
+    comments: &SingleThreadedComments,
+    kind: CommentKind,
+    text: String,
+) {
+    let Some(first) = stmts.first_mut() else {
+        return;
+    };
+    //comments are looked up by position, position 0 is reserved for spans without one
+    let pos = BytePos(offset + 1);
+    let span = Span::new(pos, pos, Default::default());
+    match first {
+        Stmt::Expr(ExprStmt {
+            span: stmt_span, ..
+        })
+        | Stmt::Return(ReturnStmt {
+            span: stmt_span, ..
+        })
+        | Stmt::Throw(ThrowStmt {
+            span: stmt_span, ..
+        })
+        | Stmt::Debugger(DebuggerStmt { span: stmt_span })
+        | Stmt::Empty(EmptyStmt { span: stmt_span }) => *stmt_span = span,
+        _ => return,
+    }
+    comments.add_trailing(
+        pos,
+        Comment {
+            kind,
+            span: DUMMY_SP,
+            text: text.into(),
+        },
+    );
+}
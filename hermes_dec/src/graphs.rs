@@ -1,13 +1,39 @@
 use std::collections::{HashMap, HashSet};
+use std::fmt;
 
+use petgraph::dot::{Config, Dot};
 use petgraph::visit::{Dfs, EdgeRef};
 use petgraph::{stable_graph::NodeIndex, Directed, Graph};
 
 use crate::{bytecode::v93::Instruction, hermes_file_reader::InstructionInfo};
 
+/// A jump whose target isn't the start of a decoded instruction, which means either the
+/// operands were decoded with the wrong size or the bytecode is malformed
+#[derive(Debug)]
+pub struct InvalidJumpTarget {
+    /// Offset of the jump instruction
+    pub offset: u32,
+    /// Offset the jump lands on
+    pub target: u32,
+}
+
+impl fmt::Display for InvalidJumpTarget {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "jump at offset {} targets offset {} which isn't the start of an instruction",
+            self.offset, self.target
+        )
+    }
+}
+
+impl std::error::Error for InvalidJumpTarget {}
+
+/// Builds a graph with a node per instruction and an edge per possible transfer of control.
+/// Conditional jumps have a `true` edge to their target and a `false` edge to the next instruction.
 pub fn construct_flow_graph(
     instructions: &[InstructionInfo<Instruction>],
-) -> Graph<(), bool, Directed, u32> {
+) -> Result<Graph<(), bool, Directed, u32>, InvalidJumpTarget> {
     let mut flow_graph: Graph<(), bool, Directed, u32> = Graph::new();
     for _ in instructions {
         flow_graph.add_node(());
@@ -24,28 +50,22 @@ pub fn construct_flow_graph(
             Instruction::Jmp { relative_offset } => {
                 flow_graph.add_edge(
                     NodeIndex::new(instruction_index),
-                    NodeIndex::new(
-                        get_instruction_by_offset(
-                            instructions,
-                            instruction_index,
-                            i32::from(*relative_offset),
-                        )
-                        .unwrap(),
-                    ),
+                    NodeIndex::new(jump_target(
+                        instructions,
+                        instruction_index,
+                        i32::from(*relative_offset),
+                    )?),
                     false,
                 );
             }
             Instruction::JmpLong { relative_offset } => {
                 flow_graph.add_edge(
                     NodeIndex::new(instruction_index),
-                    NodeIndex::new(
-                        get_instruction_by_offset(
-                            instructions,
-                            instruction_index,
-                            *relative_offset,
-                        )
-                        .unwrap(),
-                    ),
+                    NodeIndex::new(jump_target(
+                        instructions,
+                        instruction_index,
+                        *relative_offset,
+                    )?),
                     false,
                 );
             }
@@ -55,14 +75,11 @@ pub fn construct_flow_graph(
             } => {
                 flow_graph.add_edge(
                     NodeIndex::new(instruction_index),
-                    NodeIndex::new(
-                        get_instruction_by_offset(
-                            instructions,
-                            instruction_index,
-                            i32::from(*relative_offset),
-                        )
-                        .unwrap(),
-                    ),
+                    NodeIndex::new(jump_target(
+                        instructions,
+                        instruction_index,
+                        i32::from(*relative_offset),
+                    )?),
                     true,
                 );
                 if instruction_index < instructions.len() - 1 {
@@ -79,14 +96,11 @@ pub fn construct_flow_graph(
             } => {
                 flow_graph.add_edge(
                     NodeIndex::new(instruction_index),
-                    NodeIndex::new(
-                        get_instruction_by_offset(
-                            instructions,
-                            instruction_index,
-                            *relative_offset,
-                        )
-                        .unwrap(),
-                    ),
+                    NodeIndex::new(jump_target(
+                        instructions,
+                        instruction_index,
+                        *relative_offset,
+                    )?),
                     true,
                 );
                 if instruction_index < instructions.len() - 1 {
@@ -103,14 +117,11 @@ pub fn construct_flow_graph(
             } => {
                 flow_graph.add_edge(
                     NodeIndex::new(instruction_index),
-                    NodeIndex::new(
-                        get_instruction_by_offset(
-                            instructions,
-                            instruction_index,
-                            i32::from(*relative_offset),
-                        )
-                        .unwrap(),
-                    ),
+                    NodeIndex::new(jump_target(
+                        instructions,
+                        instruction_index,
+                        i32::from(*relative_offset),
+                    )?),
                     true,
                 );
                 if instruction_index < instructions.len() - 1 {
@@ -127,14 +138,11 @@ pub fn construct_flow_graph(
             } => {
                 flow_graph.add_edge(
                     NodeIndex::new(instruction_index),
-                    NodeIndex::new(
-                        get_instruction_by_offset(
-                            instructions,
-                            instruction_index,
-                            *relative_offset,
-                        )
-                        .unwrap(),
-                    ),
+                    NodeIndex::new(jump_target(
+                        instructions,
+                        instruction_index,
+                        *relative_offset,
+                    )?),
                     true,
                 );
                 if instruction_index < instructions.len() - 1 {
@@ -151,14 +159,11 @@ pub fn construct_flow_graph(
             } => {
                 flow_graph.add_edge(
                     NodeIndex::new(instruction_index),
-                    NodeIndex::new(
-                        get_instruction_by_offset(
-                            instructions,
-                            instruction_index,
-                            i32::from(*relative_offset),
-                        )
-                        .unwrap(),
-                    ),
+                    NodeIndex::new(jump_target(
+                        instructions,
+                        instruction_index,
+                        i32::from(*relative_offset),
+                    )?),
                     true,
                 );
                 if instruction_index < instructions.len() - 1 {
@@ -175,14 +180,11 @@ pub fn construct_flow_graph(
             } => {
                 flow_graph.add_edge(
                     NodeIndex::new(instruction_index),
-                    NodeIndex::new(
-                        get_instruction_by_offset(
-                            instructions,
-                            instruction_index,
-                            *relative_offset,
-                        )
-                        .unwrap(),
-                    ),
+                    NodeIndex::new(jump_target(
+                        instructions,
+                        instruction_index,
+                        *relative_offset,
+                    )?),
                     true,
                 );
                 if instruction_index < instructions.len() - 1 {
@@ -200,14 +202,11 @@ pub fn construct_flow_graph(
             } => {
                 flow_graph.add_edge(
                     NodeIndex::new(instruction_index),
-                    NodeIndex::new(
-                        get_instruction_by_offset(
-                            instructions,
-                            instruction_index,
-                            i32::from(*relative_offset),
-                        )
-                        .unwrap(),
-                    ),
+                    NodeIndex::new(jump_target(
+                        instructions,
+                        instruction_index,
+                        i32::from(*relative_offset),
+                    )?),
                     true,
                 );
                 if instruction_index < instructions.len() - 1 {
@@ -225,14 +224,11 @@ pub fn construct_flow_graph(
             } => {
                 flow_graph.add_edge(
                     NodeIndex::new(instruction_index),
-                    NodeIndex::new(
-                        get_instruction_by_offset(
-                            instructions,
-                            instruction_index,
-                            *relative_offset,
-                        )
-                        .unwrap(),
-                    ),
+                    NodeIndex::new(jump_target(
+                        instructions,
+                        instruction_index,
+                        *relative_offset,
+                    )?),
                     true,
                 );
                 if instruction_index < instructions.len() - 1 {
@@ -250,14 +246,11 @@ pub fn construct_flow_graph(
             } => {
                 flow_graph.add_edge(
                     NodeIndex::new(instruction_index),
-                    NodeIndex::new(
-                        get_instruction_by_offset(
-                            instructions,
-                            instruction_index,
-                            i32::from(*relative_offset),
-                        )
-                        .unwrap(),
-                    ),
+                    NodeIndex::new(jump_target(
+                        instructions,
+                        instruction_index,
+                        i32::from(*relative_offset),
+                    )?),
                     true,
                 );
                 if instruction_index < instructions.len() - 1 {
@@ -275,14 +268,11 @@ pub fn construct_flow_graph(
             } => {
                 flow_graph.add_edge(
                     NodeIndex::new(instruction_index),
-                    NodeIndex::new(
-                        get_instruction_by_offset(
-                            instructions,
-                            instruction_index,
-                            *relative_offset,
-                        )
-                        .unwrap(),
-                    ),
+                    NodeIndex::new(jump_target(
+                        instructions,
+                        instruction_index,
+                        *relative_offset,
+                    )?),
                     true,
                 );
                 if instruction_index < instructions.len() - 1 {
@@ -300,14 +290,11 @@ pub fn construct_flow_graph(
             } => {
                 flow_graph.add_edge(
                     NodeIndex::new(instruction_index),
-                    NodeIndex::new(
-                        get_instruction_by_offset(
-                            instructions,
-                            instruction_index,
-                            i32::from(*relative_offset),
-                        )
-                        .unwrap(),
-                    ),
+                    NodeIndex::new(jump_target(
+                        instructions,
+                        instruction_index,
+                        i32::from(*relative_offset),
+                    )?),
                     true,
                 );
                 if instruction_index < instructions.len() - 1 {
@@ -325,14 +312,11 @@ pub fn construct_flow_graph(
             } => {
                 flow_graph.add_edge(
                     NodeIndex::new(instruction_index),
-                    NodeIndex::new(
-                        get_instruction_by_offset(
-                            instructions,
-                            instruction_index,
-                            *relative_offset,
-                        )
-                        .unwrap(),
-                    ),
+                    NodeIndex::new(jump_target(
+                        instructions,
+                        instruction_index,
+                        *relative_offset,
+                    )?),
                     true,
                 );
                 if instruction_index < instructions.len() - 1 {
@@ -350,14 +334,11 @@ pub fn construct_flow_graph(
             } => {
                 flow_graph.add_edge(
                     NodeIndex::new(instruction_index),
-                    NodeIndex::new(
-                        get_instruction_by_offset(
-                            instructions,
-                            instruction_index,
-                            i32::from(*relative_offset),
-                        )
-                        .unwrap(),
-                    ),
+                    NodeIndex::new(jump_target(
+                        instructions,
+                        instruction_index,
+                        i32::from(*relative_offset),
+                    )?),
                     true,
                 );
                 if instruction_index < instructions.len() - 1 {
@@ -375,14 +356,11 @@ pub fn construct_flow_graph(
             } => {
                 flow_graph.add_edge(
                     NodeIndex::new(instruction_index),
-                    NodeIndex::new(
-                        get_instruction_by_offset(
-                            instructions,
-                            instruction_index,
-                            *relative_offset,
-                        )
-                        .unwrap(),
-                    ),
+                    NodeIndex::new(jump_target(
+                        instructions,
+                        instruction_index,
+                        *relative_offset,
+                    )?),
                     true,
                 );
                 if instruction_index < instructions.len() - 1 {
@@ -400,14 +378,11 @@ pub fn construct_flow_graph(
             } => {
                 flow_graph.add_edge(
                     NodeIndex::new(instruction_index),
-                    NodeIndex::new(
-                        get_instruction_by_offset(
-                            instructions,
-                            instruction_index,
-                            i32::from(*relative_offset),
-                        )
-                        .unwrap(),
-                    ),
+                    NodeIndex::new(jump_target(
+                        instructions,
+                        instruction_index,
+                        i32::from(*relative_offset),
+                    )?),
                     true,
                 );
                 if instruction_index < instructions.len() - 1 {
@@ -425,14 +400,11 @@ pub fn construct_flow_graph(
             } => {
                 flow_graph.add_edge(
                     NodeIndex::new(instruction_index),
-                    NodeIndex::new(
-                        get_instruction_by_offset(
-                            instructions,
-                            instruction_index,
-                            *relative_offset,
-                        )
-                        .unwrap(),
-                    ),
+                    NodeIndex::new(jump_target(
+                        instructions,
+                        instruction_index,
+                        *relative_offset,
+                    )?),
                     true,
                 );
                 if instruction_index < instructions.len() - 1 {
@@ -450,14 +422,11 @@ pub fn construct_flow_graph(
             } => {
                 flow_graph.add_edge(
                     NodeIndex::new(instruction_index),
-                    NodeIndex::new(
-                        get_instruction_by_offset(
-                            instructions,
-                            instruction_index,
-                            i32::from(*relative_offset),
-                        )
-                        .unwrap(),
-                    ),
+                    NodeIndex::new(jump_target(
+                        instructions,
+                        instruction_index,
+                        i32::from(*relative_offset),
+                    )?),
                     true,
                 );
                 if instruction_index < instructions.len() - 1 {
@@ -475,14 +444,11 @@ pub fn construct_flow_graph(
             } => {
                 flow_graph.add_edge(
                     NodeIndex::new(instruction_index),
-                    NodeIndex::new(
-                        get_instruction_by_offset(
-                            instructions,
-                            instruction_index,
-                            *relative_offset,
-                        )
-                        .unwrap(),
-                    ),
+                    NodeIndex::new(jump_target(
+                        instructions,
+                        instruction_index,
+                        *relative_offset,
+                    )?),
                     true,
                 );
                 if instruction_index < instructions.len() - 1 {
@@ -500,14 +466,11 @@ pub fn construct_flow_graph(
             } => {
                 flow_graph.add_edge(
                     NodeIndex::new(instruction_index),
-                    NodeIndex::new(
-                        get_instruction_by_offset(
-                            instructions,
-                            instruction_index,
-                            i32::from(*relative_offset),
-                        )
-                        .unwrap(),
-                    ),
+                    NodeIndex::new(jump_target(
+                        instructions,
+                        instruction_index,
+                        i32::from(*relative_offset),
+                    )?),
                     true,
                 );
                 if instruction_index < instructions.len() - 1 {
@@ -525,14 +488,11 @@ pub fn construct_flow_graph(
             } => {
                 flow_graph.add_edge(
                     NodeIndex::new(instruction_index),
-                    NodeIndex::new(
-                        get_instruction_by_offset(
-                            instructions,
-                            instruction_index,
-                            *relative_offset,
-                        )
-                        .unwrap(),
-                    ),
+                    NodeIndex::new(jump_target(
+                        instructions,
+                        instruction_index,
+                        *relative_offset,
+                    )?),
                     true,
                 );
                 if instruction_index < instructions.len() - 1 {
@@ -550,14 +510,11 @@ pub fn construct_flow_graph(
             } => {
                 flow_graph.add_edge(
                     NodeIndex::new(instruction_index),
-                    NodeIndex::new(
-                        get_instruction_by_offset(
-                            instructions,
-                            instruction_index,
-                            i32::from(*relative_offset),
-                        )
-                        .unwrap(),
-                    ),
+                    NodeIndex::new(jump_target(
+                        instructions,
+                        instruction_index,
+                        i32::from(*relative_offset),
+                    )?),
                     true,
                 );
                 if instruction_index < instructions.len() - 1 {
@@ -575,14 +532,11 @@ pub fn construct_flow_graph(
             } => {
                 flow_graph.add_edge(
                     NodeIndex::new(instruction_index),
-                    NodeIndex::new(
-                        get_instruction_by_offset(
-                            instructions,
-                            instruction_index,
-                            *relative_offset,
-                        )
-                        .unwrap(),
-                    ),
+                    NodeIndex::new(jump_target(
+                        instructions,
+                        instruction_index,
+                        *relative_offset,
+                    )?),
                     true,
                 );
                 if instruction_index < instructions.len() - 1 {
@@ -600,14 +554,11 @@ pub fn construct_flow_graph(
             } => {
                 flow_graph.add_edge(
                     NodeIndex::new(instruction_index),
-                    NodeIndex::new(
-                        get_instruction_by_offset(
-                            instructions,
-                            instruction_index,
-                            i32::from(*relative_offset),
-                        )
-                        .unwrap(),
-                    ),
+                    NodeIndex::new(jump_target(
+                        instructions,
+                        instruction_index,
+                        i32::from(*relative_offset),
+                    )?),
                     true,
                 );
                 if instruction_index < instructions.len() - 1 {
@@ -625,14 +576,11 @@ pub fn construct_flow_graph(
             } => {
                 flow_graph.add_edge(
                     NodeIndex::new(instruction_index),
-                    NodeIndex::new(
-                        get_instruction_by_offset(
-                            instructions,
-                            instruction_index,
-                            *relative_offset,
-                        )
-                        .unwrap(),
-                    ),
+                    NodeIndex::new(jump_target(
+                        instructions,
+                        instruction_index,
+                        *relative_offset,
+                    )?),
                     true,
                 );
                 if instruction_index < instructions.len() - 1 {
@@ -650,14 +598,11 @@ pub fn construct_flow_graph(
             } => {
                 flow_graph.add_edge(
                     NodeIndex::new(instruction_index),
-                    NodeIndex::new(
-                        get_instruction_by_offset(
-                            instructions,
-                            instruction_index,
-                            i32::from(*relative_offset),
-                        )
-                        .unwrap(),
-                    ),
+                    NodeIndex::new(jump_target(
+                        instructions,
+                        instruction_index,
+                        i32::from(*relative_offset),
+                    )?),
                     true,
                 );
                 if instruction_index < instructions.len() - 1 {
@@ -675,14 +620,11 @@ pub fn construct_flow_graph(
             } => {
                 flow_graph.add_edge(
                     NodeIndex::new(instruction_index),
-                    NodeIndex::new(
-                        get_instruction_by_offset(
-                            instructions,
-                            instruction_index,
-                            *relative_offset,
-                        )
-                        .unwrap(),
-                    ),
+                    NodeIndex::new(jump_target(
+                        instructions,
+                        instruction_index,
+                        *relative_offset,
+                    )?),
                     true,
                 );
                 if instruction_index < instructions.len() - 1 {
@@ -700,14 +642,11 @@ pub fn construct_flow_graph(
             } => {
                 flow_graph.add_edge(
                     NodeIndex::new(instruction_index),
-                    NodeIndex::new(
-                        get_instruction_by_offset(
-                            instructions,
-                            instruction_index,
-                            i32::from(*relative_offset),
-                        )
-                        .unwrap(),
-                    ),
+                    NodeIndex::new(jump_target(
+                        instructions,
+                        instruction_index,
+                        i32::from(*relative_offset),
+                    )?),
                     true,
                 );
                 if instruction_index < instructions.len() - 1 {
@@ -725,14 +664,11 @@ pub fn construct_flow_graph(
             } => {
                 flow_graph.add_edge(
                     NodeIndex::new(instruction_index),
-                    NodeIndex::new(
-                        get_instruction_by_offset(
-                            instructions,
-                            instruction_index,
-                            *relative_offset,
-                        )
-                        .unwrap(),
-                    ),
+                    NodeIndex::new(jump_target(
+                        instructions,
+                        instruction_index,
+                        *relative_offset,
+                    )?),
                     true,
                 );
                 if instruction_index < instructions.len() - 1 {
@@ -750,14 +686,11 @@ pub fn construct_flow_graph(
             } => {
                 flow_graph.add_edge(
                     NodeIndex::new(instruction_index),
-                    NodeIndex::new(
-                        get_instruction_by_offset(
-                            instructions,
-                            instruction_index,
-                            i32::from(*relative_offset),
-                        )
-                        .unwrap(),
-                    ),
+                    NodeIndex::new(jump_target(
+                        instructions,
+                        instruction_index,
+                        i32::from(*relative_offset),
+                    )?),
                     true,
                 );
                 if instruction_index < instructions.len() - 1 {
@@ -775,14 +708,11 @@ pub fn construct_flow_graph(
             } => {
                 flow_graph.add_edge(
                     NodeIndex::new(instruction_index),
-                    NodeIndex::new(
-                        get_instruction_by_offset(
-                            instructions,
-                            instruction_index,
-                            *relative_offset,
-                        )
-                        .unwrap(),
-                    ),
+                    NodeIndex::new(jump_target(
+                        instructions,
+                        instruction_index,
+                        *relative_offset,
+                    )?),
                     true,
                 );
                 if instruction_index < instructions.len() - 1 {
@@ -800,14 +730,11 @@ pub fn construct_flow_graph(
             } => {
                 flow_graph.add_edge(
                     NodeIndex::new(instruction_index),
-                    NodeIndex::new(
-                        get_instruction_by_offset(
-                            instructions,
-                            instruction_index,
-                            i32::from(*relative_offset),
-                        )
-                        .unwrap(),
-                    ),
+                    NodeIndex::new(jump_target(
+                        instructions,
+                        instruction_index,
+                        i32::from(*relative_offset),
+                    )?),
                     true,
                 );
                 if instruction_index < instructions.len() - 1 {
@@ -825,14 +752,11 @@ pub fn construct_flow_graph(
             } => {
                 flow_graph.add_edge(
                     NodeIndex::new(instruction_index),
-                    NodeIndex::new(
-                        get_instruction_by_offset(
-                            instructions,
-                            instruction_index,
-                            *relative_offset,
-                        )
-                        .unwrap(),
-                    ),
+                    NodeIndex::new(jump_target(
+                        instructions,
+                        instruction_index,
+                        *relative_offset,
+                    )?),
                     true,
                 );
                 if instruction_index < instructions.len() - 1 {
@@ -850,14 +774,11 @@ pub fn construct_flow_graph(
             } => {
                 flow_graph.add_edge(
                     NodeIndex::new(instruction_index),
-                    NodeIndex::new(
-                        get_instruction_by_offset(
-                            instructions,
-                            instruction_index,
-                            i32::from(*relative_offset),
-                        )
-                        .unwrap(),
-                    ),
+                    NodeIndex::new(jump_target(
+                        instructions,
+                        instruction_index,
+                        i32::from(*relative_offset),
+                    )?),
                     true,
                 );
                 if instruction_index < instructions.len() - 1 {
@@ -875,14 +796,11 @@ pub fn construct_flow_graph(
             } => {
                 flow_graph.add_edge(
                     NodeIndex::new(instruction_index),
-                    NodeIndex::new(
-                        get_instruction_by_offset(
-                            instructions,
-                            instruction_index,
-                            *relative_offset,
-                        )
-                        .unwrap(),
-                    ),
+                    NodeIndex::new(jump_target(
+                        instructions,
+                        instruction_index,
+                        *relative_offset,
+                    )?),
                     true,
                 );
                 if instruction_index < instructions.len() - 1 {
@@ -900,14 +818,11 @@ pub fn construct_flow_graph(
             } => {
                 flow_graph.add_edge(
                     NodeIndex::new(instruction_index),
-                    NodeIndex::new(
-                        get_instruction_by_offset(
-                            instructions,
-                            instruction_index,
-                            i32::from(*relative_offset),
-                        )
-                        .unwrap(),
-                    ),
+                    NodeIndex::new(jump_target(
+                        instructions,
+                        instruction_index,
+                        i32::from(*relative_offset),
+                    )?),
                     true,
                 );
                 if instruction_index < instructions.len() - 1 {
@@ -925,14 +840,11 @@ pub fn construct_flow_graph(
             } => {
                 flow_graph.add_edge(
                     NodeIndex::new(instruction_index),
-                    NodeIndex::new(
-                        get_instruction_by_offset(
-                            instructions,
-                            instruction_index,
-                            *relative_offset,
-                        )
-                        .unwrap(),
-                    ),
+                    NodeIndex::new(jump_target(
+                        instructions,
+                        instruction_index,
+                        *relative_offset,
+                    )?),
                     true,
                 );
                 if instruction_index < instructions.len() - 1 {
@@ -950,14 +862,11 @@ pub fn construct_flow_graph(
             } => {
                 flow_graph.add_edge(
                     NodeIndex::new(instruction_index),
-                    NodeIndex::new(
-                        get_instruction_by_offset(
-                            instructions,
-                            instruction_index,
-                            i32::from(*relative_offset),
-                        )
-                        .unwrap(),
-                    ),
+                    NodeIndex::new(jump_target(
+                        instructions,
+                        instruction_index,
+                        i32::from(*relative_offset),
+                    )?),
                     true,
                 );
                 if instruction_index < instructions.len() - 1 {
@@ -975,14 +884,11 @@ pub fn construct_flow_graph(
             } => {
                 flow_graph.add_edge(
                     NodeIndex::new(instruction_index),
-                    NodeIndex::new(
-                        get_instruction_by_offset(
-                            instructions,
-                            instruction_index,
-                            *relative_offset,
-                        )
-                        .unwrap(),
-                    ),
+                    NodeIndex::new(jump_target(
+                        instructions,
+                        instruction_index,
+                        *relative_offset,
+                    )?),
                     true,
                 );
                 if instruction_index < instructions.len() - 1 {
@@ -1000,14 +906,11 @@ pub fn construct_flow_graph(
             } => {
                 flow_graph.add_edge(
                     NodeIndex::new(instruction_index),
-                    NodeIndex::new(
-                        get_instruction_by_offset(
-                            instructions,
-                            instruction_index,
-                            i32::from(*relative_offset),
-                        )
-                        .unwrap(),
-                    ),
+                    NodeIndex::new(jump_target(
+                        instructions,
+                        instruction_index,
+                        i32::from(*relative_offset),
+                    )?),
                     true,
                 );
                 if instruction_index < instructions.len() - 1 {
@@ -1025,14 +928,11 @@ pub fn construct_flow_graph(
             } => {
                 flow_graph.add_edge(
                     NodeIndex::new(instruction_index),
-                    NodeIndex::new(
-                        get_instruction_by_offset(
-                            instructions,
-                            instruction_index,
-                            *relative_offset,
-                        )
-                        .unwrap(),
-                    ),
+                    NodeIndex::new(jump_target(
+                        instructions,
+                        instruction_index,
+                        *relative_offset,
+                    )?),
                     true,
                 );
                 if instruction_index < instructions.len() - 1 {
@@ -1050,14 +950,11 @@ pub fn construct_flow_graph(
             } => {
                 flow_graph.add_edge(
                     NodeIndex::new(instruction_index),
-                    NodeIndex::new(
-                        get_instruction_by_offset(
-                            instructions,
-                            instruction_index,
-                            i32::from(*relative_offset),
-                        )
-                        .unwrap(),
-                    ),
+                    NodeIndex::new(jump_target(
+                        instructions,
+                        instruction_index,
+                        i32::from(*relative_offset),
+                    )?),
                     true,
                 );
                 if instruction_index < instructions.len() - 1 {
@@ -1075,14 +972,11 @@ pub fn construct_flow_graph(
             } => {
                 flow_graph.add_edge(
                     NodeIndex::new(instruction_index),
-                    NodeIndex::new(
-                        get_instruction_by_offset(
-                            instructions,
-                            instruction_index,
-                            *relative_offset,
-                        )
-                        .unwrap(),
-                    ),
+                    NodeIndex::new(jump_target(
+                        instructions,
+                        instruction_index,
+                        *relative_offset,
+                    )?),
                     true,
                 );
                 if instruction_index < instructions.len() - 1 {
@@ -1100,14 +994,11 @@ pub fn construct_flow_graph(
             } => {
                 flow_graph.add_edge(
                     NodeIndex::new(instruction_index),
-                    NodeIndex::new(
-                        get_instruction_by_offset(
-                            instructions,
-                            instruction_index,
-                            i32::from(*relative_offset),
-                        )
-                        .unwrap(),
-                    ),
+                    NodeIndex::new(jump_target(
+                        instructions,
+                        instruction_index,
+                        i32::from(*relative_offset),
+                    )?),
                     true,
                 );
                 if instruction_index < instructions.len() - 1 {
@@ -1125,14 +1016,11 @@ pub fn construct_flow_graph(
             } => {
                 flow_graph.add_edge(
                     NodeIndex::new(instruction_index),
-                    NodeIndex::new(
-                        get_instruction_by_offset(
-                            instructions,
-                            instruction_index,
-                            *relative_offset,
-                        )
-                        .unwrap(),
-                    ),
+                    NodeIndex::new(jump_target(
+                        instructions,
+                        instruction_index,
+                        *relative_offset,
+                    )?),
                     true,
                 );
                 if instruction_index < instructions.len() - 1 {
@@ -1150,14 +1038,11 @@ pub fn construct_flow_graph(
             } => {
                 flow_graph.add_edge(
                     NodeIndex::new(instruction_index),
-                    NodeIndex::new(
-                        get_instruction_by_offset(
-                            instructions,
-                            instruction_index,
-                            i32::from(*relative_offset),
-                        )
-                        .unwrap(),
-                    ),
+                    NodeIndex::new(jump_target(
+                        instructions,
+                        instruction_index,
+                        i32::from(*relative_offset),
+                    )?),
                     true,
                 );
                 if instruction_index < instructions.len() - 1 {
@@ -1175,14 +1060,11 @@ pub fn construct_flow_graph(
             } => {
                 flow_graph.add_edge(
                     NodeIndex::new(instruction_index),
-                    NodeIndex::new(
-                        get_instruction_by_offset(
-                            instructions,
-                            instruction_index,
-                            *relative_offset,
-                        )
-                        .unwrap(),
-                    ),
+                    NodeIndex::new(jump_target(
+                        instructions,
+                        instruction_index,
+                        *relative_offset,
+                    )?),
                     true,
                 );
                 if instruction_index < instructions.len() - 1 {
@@ -1195,6 +1077,8 @@ pub fn construct_flow_graph(
             }
             Instruction::Ret { value_reg: _ } => {}
             Instruction::Throw { value_reg: _ } => {}
+            //the compiler proved execution never gets past it
+            Instruction::Unreachable => {}
             _ => {
                 if instruction_index < instructions.len() - 1 {
                     flow_graph.add_edge(
@@ -1208,53 +1092,80 @@ pub fn construct_flow_graph(
         instruction_index += 1;
     }
 
-    flow_graph
+    Ok(flow_graph)
 }
 
+/// Groups the instructions of `flow_graph` into basic blocks, each node holding the indices of
+/// its instructions in order. A block only ends where control flow can't simply fall through
+/// to the next instruction: before a jump target reached from two or more places, after a
+/// branch or a return, and at a jump back to an instruction already in a block. So every
+/// maximal run of instructions with a single predecessor and a single successor is one node.
 pub fn construct_cfg<N, E: Copy>(
     flow_graph: &Graph<N, E, Directed, u32>,
+) -> Graph<Vec<usize>, E, Directed, u32> {
+    construct_cfg_with(flow_graph, &[], &HashSet::new())
+}
+
+/// Same as [`construct_cfg`], also grouping the instructions reachable from `roots`, like the
+/// code of an exception handler, which no jump leads to. Blocks also start at the instructions
+/// in `leaders`, so the code between them can be generated apart.
+pub fn construct_cfg_with<N, E: Copy>(
+    flow_graph: &Graph<N, E, Directed, u32>,
+    roots: &[usize],
+    leaders: &HashSet<usize>,
 ) -> Graph<Vec<usize>, E, Directed, u32> {
     let mut cfg: Graph<Vec<usize>, E, Directed, u32> = Graph::new();
+    if flow_graph.node_count() == 0 {
+        return cfg;
+    }
 
     let mut current_block = Vec::new();
-    let mut dfs = Dfs::new(flow_graph, NodeIndex::new(0));
+    let mut dfs = Dfs::empty(flow_graph);
     let mut visited = HashSet::new();
-    while let Some(vertex) = dfs.next(flow_graph) {
-        visited.insert(vertex);
+    for root in std::iter::once(0).chain(roots.iter().copied()) {
+        if root >= flow_graph.node_count() || visited.contains(&NodeIndex::new(root)) {
+            continue;
+        }
+        dfs.move_to(NodeIndex::new(root));
+        while let Some(vertex) = dfs.next(flow_graph) {
+            visited.insert(vertex);
 
-        let num_edges_incoming = flow_graph
-            .edges_directed(vertex, petgraph::Direction::Incoming)
-            .count();
-        let num_edges_outgoing = flow_graph
-            .edges_directed(vertex, petgraph::Direction::Outgoing)
-            .count();
-        //can't be 0 unless end of a function(which we don't care about)
+            let num_edges_incoming = flow_graph
+                .edges_directed(vertex, petgraph::Direction::Incoming)
+                .count();
+            let num_edges_outgoing = flow_graph
+                .edges_directed(vertex, petgraph::Direction::Outgoing)
+                .count();
+            //can't be 0 unless end of a function(which we don't care about)
 
-        if num_edges_incoming >= 2 && !current_block.is_empty() {
-            cfg.add_node(current_block);
-            current_block = Vec::new();
-        }
+            if (num_edges_incoming >= 2 || leaders.contains(&vertex.index()))
+                && !current_block.is_empty()
+            {
+                cfg.add_node(current_block);
+                current_block = Vec::new();
+            }
 
-        current_block.push(vertex.index());
+            current_block.push(vertex.index());
 
-        if num_edges_outgoing >= 2 {
-            //if
-            cfg.add_node(current_block);
-            current_block = Vec::new();
-        } else if num_edges_outgoing == 0 {
-            cfg.add_node(current_block);
-            current_block = Vec::new();
-        } else {
-            //1
-            if visited.contains(
-                &flow_graph
-                    .edges_directed(vertex, petgraph::Direction::Outgoing)
-                    .next()
-                    .unwrap()
-                    .target(),
-            ) {
+            if num_edges_outgoing >= 2 {
+                //if
+                cfg.add_node(current_block);
+                current_block = Vec::new();
+            } else if num_edges_outgoing == 0 {
                 cfg.add_node(current_block);
                 current_block = Vec::new();
+            } else {
+                //1
+                if visited.contains(
+                    &flow_graph
+                        .edges_directed(vertex, petgraph::Direction::Outgoing)
+                        .next()
+                        .unwrap()
+                        .target(),
+                ) {
+                    cfg.add_node(current_block);
+                    current_block = Vec::new();
+                }
             }
         }
     }
@@ -1288,6 +1199,71 @@ pub fn construct_cfg<N, E: Copy>(
     cfg
 }
 
+/// Renders the per-instruction flow graph with every node labeled by its offset and instruction.
+pub fn flow_graph_to_dot(
+    flow_graph: &Graph<(), bool, Directed, u32>,
+    instructions: &[InstructionInfo<Instruction>],
+) -> String {
+    format!(
+        "{:?}",
+        Dot::with_attr_getters(
+            flow_graph,
+            &[Config::NodeNoLabel],
+            &|_, _| String::new(),
+            &|_, (index, _)| instructions_dot_label(instructions, &[index.index()]),
+        )
+    )
+}
+
+/// Renders the CFG with every basic block labeled by the offsets and instructions it contains.
+pub fn cfg_to_dot(
+    cfg: &Graph<Vec<usize>, bool, Directed, u32>,
+    instructions: &[InstructionInfo<Instruction>],
+) -> String {
+    format!(
+        "{:?}",
+        Dot::with_attr_getters(
+            cfg,
+            &[Config::NodeNoLabel],
+            &|_, _| String::new(),
+            &|_, (_, indecies)| instructions_dot_label(instructions, indecies),
+        )
+    )
+}
+
+fn instructions_dot_label(
+    instructions: &[InstructionInfo<Instruction>],
+    indecies: &[usize],
+) -> String {
+    let mut label = String::from("shape = box label = \"");
+    for index in indecies {
+        let instruction_info = &instructions[*index];
+        //"\l" ends a left-justified line in dot
+        label.push_str(&format!(
+            "{}: {}\\l",
+            instruction_info.offset, instruction_info.instruction
+        ));
+    }
+    label.push_str("\" ");
+    label
+}
+
+//like in Hermes, the offset is relative to the start of the jump instruction, not to the
+//instruction after it
+fn jump_target(
+    instructions: &[InstructionInfo<Instruction>],
+    instruction_index: usize,
+    relative_offset: i32,
+) -> Result<usize, InvalidJumpTarget> {
+    get_instruction_by_offset(instructions, instruction_index, relative_offset).ok_or_else(|| {
+        let offset = instructions[instruction_index].offset;
+        InvalidJumpTarget {
+            offset,
+            target: offset.wrapping_add_signed(relative_offset),
+        }
+    })
+}
+
 fn get_instruction_by_offset(
     instructions: &[InstructionInfo<Instruction>],
     mut current_instruction_index: usize,
@@ -1300,8 +1276,8 @@ fn get_instruction_by_offset(
         if instructions[current_instruction_index].offset == end_offset {
             return Some(current_instruction_index);
         }
-        if current_instruction_index == 0 {
-            //prevent overflow
+        if current_instruction_index == 0 && relative_offset < 0 {
+            //prevent overflow, forward jumps from the first instruction still have to be searched
             break;
         }
         current_instruction_index =
@@ -1,6 +1,14 @@
+//! Parsing of Hermes bytecode bundles (`index.android.bundle`).
+//!
+//! [`BytecodeFile`] holds the tables of a bundle but not the function bodies; those are read
+//! lazily from the same bytes through a [`FunctionHeader`] (or the compact [`SmallFuncHeader`]
+//! stored in [`BytecodeFile::function_headers`]), which is why most methods take a reader.
+
 use std::{
-    io::{Cursor, Read, Seek},
-    os::raw::c_char, collections::HashMap,
+    collections::BTreeMap,
+    io::{Cursor, Read, Seek, SeekFrom},
+    marker::PhantomData,
+    os::raw::c_char,
 };
 
 use bitfield_struct::bitfield;
@@ -9,6 +17,7 @@ use c_struct_macro::FromBytes;
 use lazy_static::lazy_static;
 
 use safe_transmute::TriviallyTransmutable;
+use serde::Serialize;
 
 use crate::bytecode::InstructionSet;
 
@@ -26,19 +35,19 @@ fn transmute_field<T: TriviallyTransmutable>(slice: &[u8]) -> T {
         size,
         "Input bytes must have the same size as the target struct"
     );
+    let mut v = slice.to_vec();
     if *IS_BIG_ENDIAN {
-        let mut v = vec![0; size];
-        v[..].clone_from_slice(slice);
         v.reverse();
-        return safe_transmute::transmute_one_pedantic::<T>(v.as_slice()).unwrap();
-    } else {
-        return safe_transmute::transmute_one_pedantic::<T>(slice).unwrap();
     }
+    //fields of a struct read from an arbitrary slice usually aren't aligned
+    //SAFETY: `v` holds exactly `size` bytes and `T` is valid for any bit pattern
+    unsafe { std::ptr::read_unaligned(v.as_ptr().cast::<T>()) }
 }
 
 const MAGIC: u64 = 0x1F19_03C1_03BC_1FC6; //TODO
 const SHA1_NUM_BYTES: usize = 20;
-static SUPPORTED_VERSIONS: [u32; 1] = [93];
+/// Bytecode versions the instruction set and the file layout are known to match
+pub static SUPPORTED_VERSIONS: [u32; 1] = [93];
 
 #[bitfield(u8)]
 pub struct BytecodeOptions {
@@ -86,9 +95,10 @@ struct BytecodeFileFooter {
     file_hash: [u8; SHA1_NUM_BYTES],
 }
 
+/// Which of calling and constructing a function is a `TypeError`
 #[repr(u8)]
-#[derive(Debug)]
-enum Prohibit {
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Prohibit {
     Call = 0,
     Construct = 1,
     None = 2,
@@ -138,6 +148,25 @@ impl From<u128> for FunctionHeaderFlags {
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FunctionKind {
+    /// Has bytecode, and can be invoked in every way but the prohibited one
+    Bytecode(Prohibit),
+    /// Has no bytecode in the bundle, like a function left to be compiled lazily
+    NoBytecode,
+}
+
+impl std::fmt::Display for FunctionKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Bytecode(Prohibit::None) => write!(f, "bytecode"),
+            Self::Bytecode(Prohibit::Call) => write!(f, "bytecode, not callable"),
+            Self::Bytecode(Prohibit::Construct) => write!(f, "bytecode, not constructible"),
+            Self::NoBytecode => write!(f, "no bytecode"),
+        }
+    }
+}
+
 #[allow(dead_code)]
 enum FunctionHeaderFlag {
     Prohibits(Prohibit),
@@ -145,12 +174,17 @@ enum FunctionHeaderFlag {
     Flags(u8),
 }
 
+/// A region of a function protected by a handler, offsets are relative to the start of
+/// the function's bytecode
 #[repr(C)]
-#[derive(FromBytes, Clone, Copy, Debug)]
+#[derive(FromBytes, Clone, Copy, Debug, PartialEq, Eq)]
 pub struct ExceptionHandlerInfo {
-    start: u32,
-    end: u32,
-    target: u32
+    /// Offset of the first instruction the handler protects
+    pub start: u32,
+    /// Offset right past the last instruction the handler protects
+    pub end: u32,
+    /// Offset of the handler, starting with the `Catch` instruction
+    pub target: u32,
 }
 
 #[repr(C)]
@@ -173,6 +207,48 @@ pub struct FunctionHeader {
 }
 
 impl FunctionHeader {
+    /// Offset of the function's bytecode from the start of the bundle
+    pub fn offset(&self) -> u32 {
+        self.offset
+    }
+
+    pub fn param_count(&self) -> u32 {
+        self.param_count
+    }
+
+    pub fn bytecode_size_in_bytes(&self) -> u32 {
+        self.bytecode_size_in_bytes
+    }
+
+    /// Index of the function's name in the string table, see [`BytecodeFile::get_string`]
+    pub fn function_name(&self) -> u32 {
+        self.function_name
+    }
+
+    /// Number of registers the function uses
+    pub fn frame_size(&self) -> u32 {
+        self.frame_size
+    }
+
+    /// Same as [`frame_size`](Self::frame_size), registers are numbered `0..register_count()`
+    pub fn register_count(&self) -> usize {
+        self.frame_size as usize
+    }
+
+    /// Whether the function's source was in strict mode, by `"use strict"` or by being a
+    /// class or module body
+    pub fn strict_mode(&self) -> bool {
+        self.flags.strict_mode()
+    }
+
+    pub fn kind(&self) -> FunctionKind {
+        if self.bytecode_size_in_bytes == 0 {
+            FunctionKind::NoBytecode
+        } else {
+            FunctionKind::Bytecode(self.flags.prohibit_invoke())
+        }
+    }
+
     pub fn read_bytecode<R: Seek + Read>(&self, reader: &mut R) -> Result<Vec<u8>, std::io::Error> {
         //let previous_offset = reader.stream_position()?;
         reader.seek(std::io::SeekFrom::Start(u64::from(self.offset)))?;
@@ -182,23 +258,23 @@ impl FunctionHeader {
         Ok(v)
     }
 
+    /// Seeks to the function's bytecode first, so functions can be read from the same reader
+    /// in any order, wherever the bundle stores their bodies
     pub fn disassemble_function<T: InstructionSet + Clone, R: Seek + Read>(
         &self,
         reader: &mut R,
-    ) -> Result<Vec<InstructionInfo<T>>, std::io::Error> {
+    ) -> Result<Vec<InstructionInfo<T>>, BytecodeError> {
         let bytecode = self.read_bytecode(reader)?;
-        let mut bytecode_cursor = Cursor::new(&bytecode);
-        let mut instructions = Vec::new();
-        while !bytecode_cursor.is_empty() {
-            let offset = bytecode_cursor.position() as u32;
-            let opcode = T::read_opcode(&mut bytecode_cursor);
-            //println!("{:?}", opcode);
-            instructions.push(InstructionInfo {
-                offset,
-                instruction: opcode,
-            });
-        }
-        Ok(instructions)
+        decode_instructions(&bytecode)
+    }
+
+    /// Decodes the function's instructions one at a time as they're iterated, instead of
+    /// collecting all of them like [`disassemble_function`](Self::disassemble_function)
+    pub fn instructions<'r, T: InstructionSet + Clone, R: Seek + Read>(
+        &self,
+        reader: &'r mut R,
+    ) -> Instructions<'r, T, R> {
+        Instructions::new(reader, u64::from(self.offset), self.bytecode_size_in_bytes)
     }
 
     pub fn read_exception_handlers<R: Seek + Read>(&self, reader: &mut R) -> Result<Option<Vec<ExceptionHandlerInfo>>, std::io::Error> {
@@ -256,6 +332,28 @@ impl SmallFuncHeader {
         Ok(r)
     }
 
+    /// Returns the full header, which is stored separately when the function didn't fit in the small one.
+    pub fn full_header<R: Seek + Read>(
+        &self,
+        reader: &mut R,
+    ) -> Result<FunctionHeader, std::io::Error> {
+        if self.flags().overflowed() {
+            return self.read_large_header(reader);
+        }
+        Ok(FunctionHeader {
+            offset: self.offset(),
+            param_count: self.param_count(),
+            bytecode_size_in_bytes: self.bytecode_size_in_bytes(),
+            function_name: self.function_name(),
+            info_offset: self.info_offset(),
+            frame_size: self.frame_size(),
+            environment_size: self.environment_size() as u32,
+            highest_read_cache_index: self.highest_read_cache_index(),
+            highest_write_cache_index: self.highest_write_cache_index(),
+            flags: self.flags(),
+        })
+    }
+
     pub fn read_bytecode<R: Seek + Read>(
         &self,
         reader: &mut R,
@@ -271,30 +369,26 @@ impl SmallFuncHeader {
         Ok(Some(v))
     }
 
+    /// Same as [`FunctionHeader::disassemble_function`], the reader can be anywhere
     pub fn disassemble_function<T: InstructionSet + std::fmt::Debug + Clone, R: Seek + Read>(
         &self,
         reader: &mut R,
-    ) -> Result<Vec<InstructionInfo<T>>, std::io::Error> {
+    ) -> Result<Vec<InstructionInfo<T>>, BytecodeError> {
         if self.flags().overflowed() {
             self.read_large_header(reader)?.disassemble_function(reader)
         } else {
             let bytecode = self.read_bytecode(reader)?.unwrap();
-            let mut bytecode_cursor = Cursor::new(&bytecode);
-            let mut instructions = Vec::new();
-            while !bytecode_cursor.is_empty() {
-                let offset = bytecode_cursor.position() as u32;
-                let opcode = T::read_opcode(&mut bytecode_cursor);
-                //println!("{:?}", opcode);
-                instructions.push(InstructionInfo {
-                    offset,
-                    instruction: opcode,
-                });
-            }
-            Ok(instructions)
+            decode_instructions(&bytecode)
         }
     }
 
     pub fn read_exception_handlers<R: Seek + Read>(&self, reader: &mut R) -> Result<Option<Vec<ExceptionHandlerInfo>>, std::io::Error> {
+        //the small header's info offset is truncated, the large one's isn't
+        if self.flags().overflowed() {
+            return self
+                .read_large_header(reader)?
+                .read_exception_handlers(reader);
+        }
         if !self.flags().has_exception_handler() {
             return Ok(None);
         }
@@ -308,9 +402,163 @@ impl SmallFuncHeader {
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug)]
+pub enum BytecodeError {
+    Io(std::io::Error),
+    /// The last instruction doesn't end where the function's bytecode does,
+    /// `actual` is where it would have ended
+    SizeMismatch {
+        expected: u32,
+        actual: u32,
+    },
+    /// String `index` ends at `end`, past the end of the `storage_size` bytes of string storage
+    StringOutOfBounds {
+        index: u32,
+        end: u64,
+        storage_size: usize,
+    },
+    /// String `index` is in overflow entry `overflow_index`, where `expected` is the next unused
+    /// one. The compiler adds overflow entries in string order, so any other index is corrupt
+    OverflowEntryMismatch {
+        index: u32,
+        overflow_index: u32,
+        expected: u32,
+    },
+}
+
+impl std::fmt::Display for BytecodeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Io(e) => write!(f, "{e}"),
+            Self::SizeMismatch { expected, actual } => write!(
+                f,
+                "instructions take {actual} bytes but the function is {expected} bytes long"
+            ),
+            Self::StringOutOfBounds {
+                index,
+                end,
+                storage_size,
+            } => write!(
+                f,
+                "string {index} ends at {end}, past the {storage_size} bytes of string storage"
+            ),
+            Self::OverflowEntryMismatch {
+                index,
+                overflow_index,
+                expected,
+            } => write!(
+                f,
+                "string {index} is in overflow entry {overflow_index}, expected entry {expected}"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for BytecodeError {}
+
+impl From<std::io::Error> for BytecodeError {
+    fn from(value: std::io::Error) -> Self {
+        Self::Io(value)
+    }
+}
+
+/// Returned by the `try_from_bytes` constructors when `bytes` ends before `field` does
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TryFromBytesError {
+    pub field: &'static str,
+    /// Where `field` starts in the input
+    pub offset: usize,
+    pub len: usize,
+}
+
+impl std::fmt::Display for TryFromBytesError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "field `{}` at offset {} doesn't fit in {} bytes",
+            self.field, self.offset, self.len
+        )
+    }
+}
+
+impl std::error::Error for TryFromBytesError {}
+
+fn decode_instructions<T: InstructionSet + Clone>(
+    bytecode: &[u8],
+) -> Result<Vec<InstructionInfo<T>>, BytecodeError> {
+    Instructions::new(&mut Cursor::new(bytecode), 0, bytecode.len() as u32).collect()
+}
+
+/// Iterator over the instructions of a function, see [`FunctionHeader::instructions`].
+/// Decoding stops after the first error.
+pub struct Instructions<'r, T, R> {
+    reader: &'r mut R,
+    //where the function's bytecode starts in the reader
+    start: u64,
+    size: u32,
+    offset: u32,
+    is_positioned: bool,
+    instruction_set: PhantomData<T>,
+}
+
+impl<'r, T: InstructionSet + Clone, R: Seek + Read> Instructions<'r, T, R> {
+    fn new(reader: &'r mut R, start: u64, size: u32) -> Self {
+        Self {
+            reader,
+            start,
+            size,
+            offset: 0,
+            is_positioned: false,
+            instruction_set: PhantomData,
+        }
+    }
+
+    fn decode_next(&mut self) -> Result<InstructionInfo<T>, BytecodeError> {
+        if !self.is_positioned {
+            self.reader.seek(SeekFrom::Start(self.start))?;
+            self.is_positioned = true;
+        }
+        let offset = self.offset;
+        let opcode = self.reader.read_u8()?;
+        //check before reading so a truncated instruction is an error instead of a failed read
+        let size = 1 + u32::from(T::get_bytecode_size(opcode));
+        if offset + size > self.size {
+            return Err(BytecodeError::SizeMismatch {
+                expected: self.size,
+                actual: offset + size,
+            });
+        }
+        let mut bytes = vec![opcode; size as usize];
+        self.reader.read_exact(&mut bytes[1..])?;
+        self.offset += size;
+        Ok(InstructionInfo {
+            offset,
+            instruction: T::read_opcode(&mut Cursor::new(bytes)),
+        })
+    }
+}
+
+impl<T: InstructionSet + Clone, R: Seek + Read> Iterator for Instructions<'_, T, R> {
+    type Item = Result<InstructionInfo<T>, BytecodeError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.offset >= self.size {
+            return None;
+        }
+        let instruction = self.decode_next();
+        if instruction.is_err() {
+            self.offset = self.size;
+        }
+        Some(instruction)
+    }
+}
+
+/// A decoded instruction and its offset from the start of the function's bytecode.
+/// Serializes to the offset next to the fields of the instruction.
+#[derive(Debug, Clone, Serialize)]
 pub struct InstructionInfo<T: InstructionSet + Clone> {
     pub offset: u32,
+    #[serde(flatten)]
     pub instruction: T,
 }
 
@@ -324,6 +572,9 @@ pub struct SmallStringTableEntry {
     length: u32,
 }
 
+/// The length of a [`SmallStringTableEntry`] whose string is in the overflow table
+pub const OVERFLOW_STRING_LENGTH: u32 = (1 << 8) - 1;
+
 #[repr(u8)]
 #[derive(Debug)]
 pub enum StringKind {
@@ -369,6 +620,10 @@ pub struct RegExpTableEntry {
     length: u32,
 }
 
+/// A parsed bundle.
+///
+/// Function bodies aren't part of it, read them with [`SmallFuncHeader::disassemble_function`]
+/// passing a reader over the same bytes the file was parsed from.
 #[derive(Debug)]
 pub struct BytecodeFile {
     pub header: BytecodeFileHeader,
@@ -389,7 +644,10 @@ pub struct BytecodeFile {
     pub cjs_module_table_static: Option<Vec<(u32, u32)>>,
     pub function_source_table: Vec<(u32, u32)>,
 
-    pub exception_handler_map: HashMap<usize, Vec<ExceptionHandlerInfo>>
+    /// Exception handlers of every function that has some, ordered by function id
+    pub exception_handler_map: BTreeMap<usize, Vec<ExceptionHandlerInfo>>,
+    //every string of the string table decoded up front, so get_string can lend them out
+    strings: Vec<String>,
 }
 
 #[allow(dead_code)]
@@ -398,8 +656,9 @@ impl BytecodeFile {
         let mut offset = 0;
         let header = {
             let size = std::mem::size_of::<BytecodeFileHeader>();
-
-            BytecodeFileHeader::from_bytes(&bytes[offset..offset + size])
+            let header = BytecodeFileHeader::from_bytes(&bytes[offset..offset + size]);
+            offset += size;
+            header
         };
         if header.magic != MAGIC {
             println!(
@@ -471,12 +730,10 @@ impl BytecodeFile {
             let mut v = Vec::with_capacity(header.overflow_string_count as usize);
             for _ in 0..header.overflow_string_count {
                 let size = std::mem::size_of::<OverflowStringTableEntry>();
-                v.push(unsafe {
-                    <OverflowStringTableEntry as From<u64>>::from(
-                        safe_transmute::base::from_bytes_pedantic(&bytes[offset..offset + size])
-                            .unwrap(),
-                    )
-                });
+                //the table is only 4 byte aligned in the file
+                v.push(<OverflowStringTableEntry as From<u64>>::from(
+                    u64::from_le_bytes(bytes[offset..offset + size].try_into().unwrap()),
+                ));
                 offset += size;
             }
             v
@@ -619,7 +876,7 @@ impl BytecodeFile {
             v
         };
         let exception_handler_map = {
-            let mut map = HashMap::new();
+            let mut map = BTreeMap::new();
             for i in 0..function_headers.len() {
                 if let Some(handlers) = function_headers[i].read_exception_handlers(&mut Cursor::new(bytes)).unwrap() {
                     map.insert(i, handlers);
@@ -627,7 +884,7 @@ impl BytecodeFile {
             }
             map
         };
-        Self {
+        let mut file = Self {
             header,
             function_headers,
             string_table_entries, //ALL TODO's
@@ -646,8 +903,11 @@ impl BytecodeFile {
             cjs_module_table_static,
             function_source_table,
 
-            exception_handler_map
-        }
+            exception_handler_map,
+            strings: Vec::new(),
+        };
+        file.strings = file.decode_strings();
+        file
     }
 
     pub fn from_reader<T: Read + Seek>(reader: &mut T) -> Result<Self, std::io::Error> {
@@ -815,7 +1075,7 @@ impl BytecodeFile {
             v
         };
         let exception_handler_map = {
-            let mut map = HashMap::new();
+            let mut map = BTreeMap::new();
             for i in 0..function_headers.len() {
                 if let Some(handlers) = function_headers[i].read_exception_handlers(reader).unwrap() {
                     map.insert(i, handlers);
@@ -823,7 +1083,7 @@ impl BytecodeFile {
             }
             map
         };
-        Ok(Self {
+        let mut file = Self {
             header,
             function_headers,
             string_table_entries, //ALL TODO's
@@ -842,23 +1102,152 @@ impl BytecodeFile {
             cjs_module_table_static,
             function_source_table,
 
-            exception_handler_map
-        })
+            exception_handler_map,
+            strings: Vec::new(),
+        };
+        file.strings = file.decode_strings();
+        Ok(file)
     }
 
-    pub fn get_string(&self, index: u32) -> Option<String> {
-        let entry = &self.string_table_entries[index as usize];
-        if entry.length() == 0 {
-            return None;
+    /// The exception handlers of function `function_id` in the order the VM looks them up,
+    /// innermost first. Empty for functions without any, or that don't exist
+    pub fn exception_handlers(&self, function_id: usize) -> &[ExceptionHandlerInfo] {
+        self.exception_handler_map
+            .get(&function_id)
+            .map_or(&[], Vec::as_slice)
+    }
+
+    /// SHA-1 of the source the bundle was compiled from, which identifies a build of the bundle
+    pub fn source_hash(&self) -> [u8; SHA1_NUM_BYTES] {
+        self.header.source_hash
+    }
+
+    /// Whether the bundle's bytecode version is one of [`SUPPORTED_VERSIONS`]. Other versions
+    /// still parse, but their opcodes and tables may not mean what the decompiler assumes
+    pub fn is_supported_version(&self) -> bool {
+        SUPPORTED_VERSIONS.contains(&self.header.version)
+    }
+
+    /// Number of functions in the bundle, valid function ids are `0..function_count()`
+    pub fn function_count(&self) -> usize {
+        self.function_headers.len()
+    }
+
+    /// Returns the full header of function `function_id`, or None if there is no such function
+    pub fn function_header<R: Seek + Read>(
+        &self,
+        function_id: usize,
+        reader: &mut R,
+    ) -> Result<Option<FunctionHeader>, std::io::Error> {
+        match self.function_headers.get(function_id) {
+            Some(header) => header.full_header(reader).map(Some),
+            None => Ok(None),
         }
-        let begin_offset = entry.offset() as usize;
-        let end_offset = begin_offset + entry.length() as usize;
-        Some(
-            self.string_storage[begin_offset..end_offset]
+    }
+
+    /// Returns `(module_id, function_id)` for every CommonJS module registered in the bundle.
+    ///
+    /// Statically resolved tables store module ids directly, otherwise a module's id is
+    /// its position in the table.
+    pub fn cjs_modules(&self) -> Vec<(u32, u32)> {
+        if self.header.cjs_module_count == 0 {
+            return Vec::new();
+        }
+        match (&self.cjs_module_table_static, &self.cjs_module_table) {
+            (Some(table), _) => table.clone(),
+            (None, Some(table)) => table
                 .iter()
-                .map(|c| *c as u8 as char)
-                .collect::<String>(),
-        )
+                .enumerate()
+                .map(|(module_id, (_, function_id))| (module_id as u32, *function_id))
+                .collect(),
+            (None, None) => Vec::new(),
+        }
+    }
+
+    /// The source text the bundle keeps for function `function_id`, which the compiler records
+    /// for functions whose `toString` has to show it. None for most functions
+    pub fn function_source(&self, function_id: usize) -> Option<&str> {
+        self.function_source_table
+            .iter()
+            .find(|(id, _)| *id as usize == function_id)
+            .and_then(|(_, string_id)| self.get_string(*string_id))
+    }
+
+    /// Checks that every string lies within the string storage and that overflow entries are
+    /// used in order, as the compiler writes them.
+    ///
+    /// Obfuscated or patched bundles can have string tables that [`Self::get_string`] reads
+    /// garbage from, this tells them apart before decompiling.
+    pub fn validate_string_table(&self) -> Result<(), BytecodeError> {
+        let mut next_overflow = 0;
+        for (index, entry) in self.string_table_entries.iter().enumerate() {
+            let index = index as u32;
+            let (offset, length) = if entry.length() == OVERFLOW_STRING_LENGTH {
+                let overflow_index = entry.offset();
+                let overflow = self
+                    .string_table_overflow_entries
+                    .get(overflow_index as usize)
+                    .filter(|_| overflow_index == next_overflow)
+                    .ok_or(BytecodeError::OverflowEntryMismatch {
+                        index,
+                        overflow_index,
+                        expected: next_overflow,
+                    })?;
+                next_overflow += 1;
+                (overflow.offset(), overflow.length())
+            } else {
+                (entry.offset(), entry.length())
+            };
+            //utf16 lengths count code units
+            let unit_size = if entry.is_utf16() == 1 { 2 } else { 1 };
+            let end = u64::from(offset) + u64::from(length) * unit_size;
+            if end > self.string_storage.len() as u64 {
+                return Err(BytecodeError::StringOutOfBounds {
+                    index,
+                    end,
+                    storage_size: self.string_storage.len(),
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns string `index` of the string table, None for empty strings and indices past it
+    pub fn get_string(&self, index: u32) -> Option<&str> {
+        self.strings
+            .get(index as usize)
+            .map(String::as_str)
+            .filter(|s| !s.is_empty())
+    }
+
+    //empty for strings outside the string storage, see validate_string_table
+    fn decode_strings(&self) -> Vec<String> {
+        self.string_table_entries
+            .iter()
+            .map(|entry| {
+                //strings too long for the small entry's length are stored in the overflow table,
+                //and the small entry's offset is their index in it
+                let (offset, length) = if entry.length() == OVERFLOW_STRING_LENGTH {
+                    match self
+                        .string_table_overflow_entries
+                        .get(entry.offset() as usize)
+                    {
+                        Some(overflow) => (overflow.offset(), overflow.length()),
+                        None => return String::new(),
+                    }
+                } else {
+                    (entry.offset(), entry.length())
+                };
+                let begin_offset = offset as usize;
+                let end_offset = begin_offset + length as usize;
+                self.string_storage
+                    .get(begin_offset..end_offset)
+                    .unwrap_or_default()
+                    .iter()
+                    .map(|c| *c as u8 as char)
+                    .collect()
+            })
+            .collect()
     }
 
     /*
@@ -0,0 +1,3971 @@
+#![feature(cursor_remaining)]
+
+use generate_ast::{AstGenerator, Budget, Diagnostics, FunctionBody, TryFinally};
+use petgraph::dot::Dot;
+use petgraph::stable_graph::NodeIndex;
+use petgraph::visit::Dfs;
+use petgraph::Graph;
+use std::any::Any;
+use std::collections::{BTreeSet, HashMap};
+use std::fmt;
+use std::io::Cursor;
+use std::io::Write;
+use std::mem::Discriminant;
+use std::ops::{Range, RangeBounds};
+use std::panic::AssertUnwindSafe;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use swc_common::comments::SingleThreadedComments;
+use swc_common::sync::Lrc;
+use swc_common::FilePathMapping;
+use swc_common::SourceMap;
+use swc_ecma_ast::{
+    ArrowExpr, BindingIdent, BlockStmtOrExpr, Decl, Expr, ExprStmt, FnDecl, FnExpr, Ident, Lit,
+    Module, Param, Pat, Program, Script, Stmt, Str,
+};
+use swc_ecma_codegen::text_writer::JsWriter;
+
+use swc_common::DUMMY_SP;
+use swc_ecma_ast::BlockStmt;
+use swc_ecma_ast::EsVersion;
+use swc_ecma_ast::Function;
+use swc_ecma_codegen::Emitter;
+
+use crate::bytecode::v93::Instruction;
+use crate::bytecode::{InstructionSet, Operand};
+
+mod bundle;
+pub mod bytecode;
+mod generate_ast;
+pub mod graphs;
+pub mod hermes_file_reader;
+mod passes;
+
+pub use bundle::{load, Bundle};
+pub use generate_ast::{Diagnostic, DiagnosticKind, Limit};
+pub use graphs::{
+    cfg_to_dot, construct_cfg, construct_cfg_with, construct_flow_graph, flow_graph_to_dot,
+    InvalidJumpTarget,
+};
+pub use hermes_file_reader::{
+    BytecodeError, BytecodeFile, BytecodeFileHeader, ExceptionHandlerInfo, FunctionHeader,
+    FunctionKind, InstructionInfo, Instructions, Prohibit, SmallFuncHeader, TryFromBytesError,
+    SUPPORTED_VERSIONS,
+};
+
+#[derive(Debug)]
+pub enum DecompileError {
+    InvalidFunctionId(usize),
+    InvalidModuleId(u32),
+    /// The function has no bytecode to decompile, see [`FunctionKind::NoBytecode`]
+    NoBytecode(usize),
+    Io(std::io::Error),
+    Bytecode(BytecodeError),
+    InvalidJumpTarget(InvalidJumpTarget),
+    /// Decompiling gave up after exceeding one of the limits in [`DecompileOptions`].
+    /// `partial` holds what was generated until then
+    LimitExceeded {
+        limit: Limit,
+        partial: String,
+    },
+    /// The decompiler panicked on the function, e.g. on an instruction it can't translate yet.
+    /// Only [`decompile_all`] catches panics, so one function can't end the whole run
+    Panicked(String),
+}
+
+impl fmt::Display for DecompileError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InvalidFunctionId(function_id) => write!(f, "no function with id {function_id}"),
+            Self::InvalidModuleId(module_id) => write!(f, "no module with id {module_id}"),
+            Self::NoBytecode(function_id) => {
+                write!(f, "function {function_id} has no bytecode to decompile")
+            }
+            Self::Io(e) => write!(f, "{e}"),
+            Self::Bytecode(e) => write!(f, "{e}"),
+            Self::InvalidJumpTarget(e) => write!(f, "{e}"),
+            Self::LimitExceeded { limit, .. } => {
+                write!(f, "gave up after exceeding the {limit} limit")
+            }
+            Self::Panicked(message) => write!(f, "decompiler panicked: {message}"),
+        }
+    }
+}
+
+impl std::error::Error for DecompileError {}
+
+impl From<std::io::Error> for DecompileError {
+    fn from(value: std::io::Error) -> Self {
+        Self::Io(value)
+    }
+}
+
+impl From<BytecodeError> for DecompileError {
+    fn from(value: BytecodeError) -> Self {
+        Self::Bytecode(value)
+    }
+}
+
+impl From<InvalidJumpTarget> for DecompileError {
+    fn from(value: InvalidJumpTarget) -> Self {
+        Self::InvalidJumpTarget(value)
+    }
+}
+
+#[derive(clap::ValueEnum, Clone, Copy)]
+pub enum GraphKind {
+    /// One node per instruction
+    Flow,
+    /// Instructions merged into basic blocks
+    Cfg,
+}
+
+/// What a decompiled function is written as
+#[derive(clap::ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Emit {
+    /// JavaScript source
+    #[default]
+    Js,
+    /// The generated SWC `Program`, serialized to JSON on a single line
+    AstJson,
+}
+
+/// Writes the graphviz rendering of either the per-instruction flow graph or the reduced CFG.
+fn write_graph_dot<W: Write>(
+    instructions: &[InstructionInfo<Instruction>],
+    kind: GraphKind,
+    output: &mut W,
+) -> Result<(), DecompileError> {
+    let flow_graph = construct_flow_graph(instructions)?;
+    match kind {
+        GraphKind::Flow => writeln!(output, "{}", flow_graph_to_dot(&flow_graph, instructions))?,
+        GraphKind::Cfg => {
+            let cfg = construct_cfg(&flow_graph);
+            writeln!(output, "{}", cfg_to_dot(&cfg, instructions))?
+        }
+    }
+    Ok(())
+}
+
+/// Parses a bundle. Keep `buf` around, functions are read from it on demand.
+pub fn parse(buf: &[u8]) -> Result<BytecodeFile, DecompileError> {
+    Ok(BytecodeFile::from_reader(&mut Cursor::new(buf))?)
+}
+
+/// Reads and decodes the bytecode of function `function_id`.
+pub fn disassemble_function(
+    file: &BytecodeFile,
+    buf: &[u8],
+    function_id: usize,
+) -> Result<Vec<InstructionInfo<Instruction>>, DecompileError> {
+    let header = file
+        .function_headers
+        .get(function_id)
+        .ok_or(DecompileError::InvalidFunctionId(function_id))?;
+    if header.full_header(&mut Cursor::new(buf))?.kind() == FunctionKind::NoBytecode {
+        return Err(DecompileError::NoBytecode(function_id));
+    }
+    Ok(header.disassemble_function::<Instruction, Cursor<&[u8]>>(&mut Cursor::new(buf))?)
+}
+
+/// The decoded instructions of function `function_id` as a json array of objects holding the
+/// offset, the opcode name and the operands of each instruction, e.g.
+/// `{"offset": 0, "opcode": "LoadConstZero", "dst_reg": 0}`.
+///
+/// Offsets are relative to the start of the function, or positions in the file when `absolute`.
+/// Only the instructions whose offset lies in `offsets` are listed, the whole function is
+/// still decoded so the listing matches what it would be without a range.
+pub fn instructions_json(
+    file: &BytecodeFile,
+    buf: &[u8],
+    function_id: usize,
+    absolute: bool,
+    offsets: impl RangeBounds<u32>,
+) -> Result<String, DecompileError> {
+    let mut instructions = disassemble_function(file, buf, function_id)?;
+    if absolute {
+        //disassemble_function already checked the function exists
+        let header = file
+            .function_header(function_id, &mut Cursor::new(buf))?
+            .unwrap();
+        for info in &mut instructions {
+            info.offset += header.offset();
+        }
+    }
+    instructions.retain(|info| offsets.contains(&info.offset));
+    Ok(serde_json::to_string_pretty(&instructions).map_err(std::io::Error::from)?)
+}
+
+/// The decoded instructions of function `function_id` as text an assembler can read back, one
+/// instruction per line, e.g. `0x10: GetById r0, r1, 3, "foo"@42`. Offsets, the ones being
+/// listed and the jump targets alike, are as for [`instructions_json`]. The grammar is
+///
+/// ```text
+/// line     = offset ": " opcode [" " operand (", " operand)*]
+/// offset   = "0x" hex digits, uppercase
+/// opcode   = the opcode's name, e.g. GetById
+/// operand  = register | string | function | bigint | target | integer | double | bool
+/// register = "r" digits
+/// string   = json string "@" digits            the string and its index in the string table
+/// function = [identifier "_"] "f" digits      the function's id, named like in decompiled code
+/// bigint   = "bigint@" digits                  the index in the bigint table
+/// target   = offset                            where a jump or switch table is
+/// integer  = ["-"] digits
+/// double   = ["-"] digits ("." digits ["e" ["-"] digits] | "e" ["-"] digits) | "NaN" | "inf" | "-inf"
+/// bool     = "true" | "false"
+/// ```
+///
+/// Operands are in the order they are encoded in.
+pub fn instructions_listing(
+    file: &BytecodeFile,
+    buf: &[u8],
+    function_id: usize,
+    absolute: bool,
+    offsets: impl RangeBounds<u32>,
+) -> Result<String, DecompileError> {
+    let instructions = disassemble_function(file, buf, function_id)?;
+    let base = if absolute {
+        //disassemble_function already checked the function exists
+        file.function_header(function_id, &mut Cursor::new(buf))?
+            .unwrap()
+            .offset()
+    } else {
+        0
+    };
+    let mut listing = String::new();
+    for info in &instructions {
+        let offset = base + info.offset;
+        if !offsets.contains(&offset) {
+            continue;
+        }
+        let operands: Vec<String> = info
+            .instruction
+            .operands()
+            .into_iter()
+            .map(|(name, operand)| listing_operand(file, buf, offset, name, operand))
+            .collect();
+        listing.push_str(&format!("{offset:#X}: {}", info.instruction.name()));
+        if !operands.is_empty() {
+            listing.push_str(&format!(" {}", operands.join(", ")));
+        }
+        listing.push('\n');
+    }
+    Ok(listing)
+}
+
+//an operand of the instruction at `offset` as instructions_listing writes it
+fn listing_operand(
+    file: &BytecodeFile,
+    buf: &[u8],
+    offset: u32,
+    name: &str,
+    operand: Operand,
+) -> String {
+    let value = match operand {
+        Operand::Unsigned(value) => i64::from(value),
+        Operand::Signed(value) => i64::from(value),
+        Operand::Double(value) => return format!("{value:?}"),
+        Operand::Bool(value) => return value.to_string(),
+    };
+    if name.ends_with("_reg") {
+        format!("r{value}")
+    } else if name.starts_with("relative_") {
+        format!("{:#X}", i64::from(offset) + value)
+    } else if name.contains("string") && name.ends_with("index") {
+        let string = file.get_string(value as u32).unwrap_or_default();
+        format!("{}@{value}", serde_json::Value::from(string))
+    } else if name == "function_table_index" {
+        let name = function_name(file, buf, value as usize);
+        generate_ast::function_ident(name.as_deref(), value as u32)
+    } else if name == "bigint_table_index" {
+        format!("bigint@{value}")
+    } else {
+        value.to_string()
+    }
+}
+
+/// What a handler does with the exception it catches
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HandlerKind {
+    Catch,
+    /// Runs cleanup code and throws the exception again, the handler `try { } finally { }`
+    /// compiles to. The cleanup is also copied after the protected code for when it doesn't
+    /// throw
+    Finally,
+}
+
+impl fmt::Display for HandlerKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Catch => write!(f, "catch"),
+            Self::Finally => write!(f, "finally"),
+        }
+    }
+}
+
+//the register a finally handler catches into and the instructions reachable from its Catch,
+//None if the handler at `target` isn't one: some way out of them doesn't throw what was caught
+fn finally_handler(
+    instructions: &[InstructionInfo<Instruction>],
+    flow_graph: &Graph<(), bool>,
+    target: usize,
+) -> Option<(u8, BTreeSet<usize>)> {
+    let Some(Instruction::Catch { dst_reg }) = instructions.get(target).map(|i| &i.instruction)
+    else {
+        return None;
+    };
+    let mut reachable = BTreeSet::new();
+    let mut dfs = Dfs::new(flow_graph, NodeIndex::new(target));
+    while let Some(node) = dfs.next(flow_graph) {
+        reachable.insert(node.index());
+        let is_exit = flow_graph.neighbors(node).next().is_none();
+        let rethrows = matches!(
+            instructions[node.index()].instruction,
+            Instruction::Throw { value_reg } if value_reg == *dst_reg
+        );
+        if is_exit && !rethrows {
+            return None;
+        }
+    }
+    Some((*dst_reg, reachable))
+}
+
+fn handler_kind(
+    instructions: &[InstructionInfo<Instruction>],
+    flow_graph: &Graph<(), bool>,
+    target: usize,
+) -> HandlerKind {
+    match finally_handler(instructions, flow_graph, target) {
+        Some(_) => HandlerKind::Finally,
+        None => HandlerKind::Catch,
+    }
+}
+
+//the handlers of a function that can be generated as `try { } finally { }`, see TryFinally
+fn try_finally_regions(
+    handlers: &[ExceptionHandlerInfo],
+    instructions: &[InstructionInfo<Instruction>],
+    flow_graph: &Graph<(), bool>,
+) -> Vec<TryFinally> {
+    let index_of = |offset: u32| {
+        match instructions.binary_search_by_key(&offset, |info| info.offset) {
+            Ok(index) => Some(index),
+            //a region reaching the end of the function
+            Err(index) if index == instructions.len() => Some(index),
+            Err(_) => None,
+        }
+    };
+    handlers
+        .iter()
+        .filter_map(|handler| {
+            let start = index_of(handler.start)?;
+            let end = index_of(handler.end).filter(|end| start < *end)?;
+            let target = index_of(handler.target)?;
+            let (exception_reg, reachable) = finally_handler(instructions, flow_graph, target)?;
+            //the finally block is generated from the handler's copy of it, which has to run
+            //straight from the Catch to the rethrow
+            let rethrow = *reachable.last()?;
+            if reachable.len() != rethrow - target + 1
+                || !matches!(instructions[rethrow].instruction, Instruction::Throw { .. })
+            {
+                return None;
+            }
+            let finally = target + 1..rethrow;
+            //the copy run when the protected code doesn't throw follows it
+            let after = end + finally.len();
+            let is_copy = instructions.get(end..after).is_some_and(|copy| {
+                copy.iter()
+                    .zip(&instructions[finally.clone()])
+                    .all(|(a, b)| a.instruction.name() == b.instruction.name())
+            });
+            Some(TryFinally {
+                start,
+                end,
+                finally,
+                exception_reg,
+                after: is_copy.then_some(after),
+            })
+        })
+        .collect()
+}
+
+/// An exception handler of a function along with the indices of the instructions at its
+/// offsets, which are None when an offset isn't at the start of an instruction
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ExceptionRegion {
+    pub handler: ExceptionHandlerInfo,
+    /// Recognized from the code reachable from the handler, so that try/finally can be told
+    /// from try/catch
+    pub kind: HandlerKind,
+    pub start_index: Option<usize>,
+    /// The instruction right past the protected ones, the instruction count when the region
+    /// reaches the end of the function
+    pub end_index: Option<usize>,
+    pub target_index: Option<usize>,
+}
+
+/// The exception handlers of function `function_id`, see [`BytecodeFile::exception_handlers`]
+pub fn exception_regions(
+    file: &BytecodeFile,
+    buf: &[u8],
+    function_id: usize,
+) -> Result<Vec<ExceptionRegion>, DecompileError> {
+    let instructions = disassemble_function(file, buf, function_id)?;
+    let flow_graph = construct_flow_graph(&instructions)?;
+    //disassemble_function already checked the function exists
+    let header = file
+        .function_header(function_id, &mut Cursor::new(buf))?
+        .unwrap();
+    let index_of =
+        |offset: u32| match instructions.binary_search_by_key(&offset, |info| info.offset) {
+            Ok(index) => Some(index),
+            Err(index) if offset == header.bytecode_size_in_bytes() => Some(index),
+            Err(_) => None,
+        };
+    Ok(file
+        .exception_handlers(function_id)
+        .iter()
+        .map(|handler| ExceptionRegion {
+            handler: *handler,
+            kind: index_of(handler.target).map_or(HandlerKind::Catch, |target| {
+                handler_kind(&instructions, &flow_graph, target)
+            }),
+            start_index: index_of(handler.start),
+            end_index: index_of(handler.end),
+            target_index: index_of(handler.target),
+        })
+        .collect())
+}
+
+/// Offsets of the instructions of function `function_id` that use a string containing
+/// `needle`, ignoring case.
+pub fn string_references(
+    file: &BytecodeFile,
+    buf: &[u8],
+    function_id: usize,
+    needle: &str,
+) -> Result<Vec<u32>, DecompileError> {
+    let needle = needle.to_lowercase();
+    let matches = |index: u32| {
+        index < file.header.string_count
+            && file
+                .get_string(index)
+                .is_some_and(|s| s.to_lowercase().contains(&needle))
+    };
+    Ok(disassemble_function(file, buf, function_id)?
+        .into_iter()
+        .filter(|info| info.instruction.string_operands().into_iter().any(matches))
+        .map(|info| info.offset)
+        .collect())
+}
+
+/// How a function refers to another in the [`call_graph`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum CallGraphEdge {
+    /// Creates a closure (or generator or async function) of it
+    Creates,
+    /// Calls it with `CallDirect`
+    Calls,
+}
+
+impl fmt::Display for CallGraphEdge {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CallGraphEdge::Creates => write!(f, "creates"),
+            CallGraphEdge::Calls => write!(f, "calls"),
+        }
+    }
+}
+
+/// Every `(function id, referenced function id, how)` in the bundle. Functions without
+/// bytecode refer to nothing.
+pub fn call_graph(
+    file: &BytecodeFile,
+    buf: &[u8],
+) -> Result<BTreeSet<(usize, usize, CallGraphEdge)>, DecompileError> {
+    let mut edges = BTreeSet::new();
+    for function_id in 0..file.function_count() {
+        let instructions = match disassemble_function(file, buf, function_id) {
+            Ok(instructions) => instructions,
+            Err(DecompileError::NoBytecode(_)) => continue,
+            Err(e) => return Err(e),
+        };
+        for info in instructions {
+            let kind = match info.instruction {
+                Instruction::CallDirect { .. } | Instruction::CallDirectLongIndex { .. } => {
+                    CallGraphEdge::Calls
+                }
+                _ => CallGraphEdge::Creates,
+            };
+            for target in info.instruction.function_operands() {
+                edges.insert((function_id, target as usize, kind));
+            }
+        }
+    }
+    Ok(edges)
+}
+
+/// Renders [`call_graph`] in graphviz dot format, with a node per function labeled with its
+/// id and name.
+pub fn call_graph_dot(file: &BytecodeFile, buf: &[u8]) -> Result<String, DecompileError> {
+    let mut graph: Graph<String, CallGraphEdge> = Graph::new();
+    for function_id in 0..file.function_count() {
+        let name = file
+            .function_header(function_id, &mut Cursor::new(buf))?
+            .and_then(|header| file.get_string(header.function_name()))
+            .unwrap_or_default();
+        graph.add_node(format!("f{function_id} {name}").trim_end().to_string());
+    }
+    for (from, to, kind) in call_graph(file, buf)? {
+        if to < graph.node_count() {
+            graph.add_edge(NodeIndex::new(from), NodeIndex::new(to), kind);
+        }
+    }
+    Ok(format!("{}", Dot::new(&graph)))
+}
+
+/// How many instructions of the bundle use an opcode, see [`opcode_histogram`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct OpcodeCount {
+    /// The opcode's name, e.g. `Add`
+    pub name: String,
+    pub count: usize,
+    /// Whether the decompiler has no translation for the opcode yet, see
+    /// [`DiagnosticKind::UnsupportedOpcode`]
+    pub is_unimplemented: bool,
+}
+
+/// How many times each opcode is used across all functions, most used first. Opcodes that are
+/// never used aren't listed.
+pub fn opcode_histogram(
+    file: &BytecodeFile,
+    buf: &[u8],
+) -> Result<Vec<OpcodeCount>, DecompileError> {
+    let mut counts: HashMap<Discriminant<Instruction>, OpcodeCount> = HashMap::new();
+    for header in &file.function_headers {
+        let header = header.full_header(&mut Cursor::new(buf))?;
+        if header.kind() == FunctionKind::NoBytecode {
+            continue;
+        }
+        for info in header.instructions::<Instruction, _>(&mut Cursor::new(buf)) {
+            let instruction = info?.instruction;
+            counts
+                .entry(std::mem::discriminant(&instruction))
+                .or_insert_with(|| OpcodeCount {
+                    name: instruction.name(),
+                    count: 0,
+                    is_unimplemented: generate_ast::is_unsupported(file, instruction),
+                })
+                .count += 1;
+        }
+    }
+    let mut counts: Vec<OpcodeCount> = counts.into_values().collect();
+    counts.sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.name.cmp(&b.name)));
+    Ok(counts)
+}
+
+/// Returns the name function `function_id` was declared with, None for anonymous functions and
+/// names that aren't identifiers.
+///
+/// The header of an inner function often has no name. When the bundle kept the function's
+/// source text, see [`BytecodeFile::function_source`], the name it's declared with there is
+/// used instead.
+pub fn function_name(file: &BytecodeFile, buf: &[u8], function_id: usize) -> Option<String> {
+    let header = file
+        .function_header(function_id, &mut Cursor::new(buf))
+        .ok()??;
+    file.get_string(header.function_name())
+        .filter(|name| generate_ast::is_valid_identifier(name))
+        .or_else(|| declared_name(file.function_source(function_id)?))
+        .map(str::to_string)
+}
+
+//`function foo(`, `async function* foo(` or `class Foo` -> the declared name
+fn declared_name(source: &str) -> Option<&str> {
+    let source = source.trim_start();
+    let source = source.strip_prefix("async ").unwrap_or(source).trim_start();
+    let rest = source
+        .strip_prefix("function")
+        .or_else(|| source.strip_prefix("class"))
+        .filter(|rest| rest.starts_with(|c: char| c.is_whitespace() || c == '*'))?;
+    let rest = rest.trim_start().trim_start_matches('*').trim_start();
+    let end = rest
+        .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_' || c == '$'))
+        .unwrap_or(rest.len());
+    Some(&rest[..end]).filter(|name| generate_ast::is_valid_identifier(name))
+}
+
+/// Returns the id of the function implementing CommonJS module `module_id`
+pub fn module_function_id(file: &BytecodeFile, module_id: u32) -> Result<usize, DecompileError> {
+    file.cjs_modules()
+        .into_iter()
+        .find(|(id, _)| *id == module_id)
+        .map(|(_, function_id)| function_id as usize)
+        .ok_or(DecompileError::InvalidModuleId(module_id))
+}
+
+/// Decompiles function `function_id` of `file` to JS source.
+/// `buf` is the whole bundle `file` was parsed from.
+pub fn decompile_function(
+    file: &BytecodeFile,
+    buf: &[u8],
+    function_id: usize,
+) -> Result<DecompileResult, DecompileError> {
+    decompile_function_with(file, buf, function_id, &DecompileOptions::default())
+}
+
+pub fn decompile_function_with(
+    file: &BytecodeFile,
+    buf: &[u8],
+    function_id: usize,
+    options: &DecompileOptions,
+) -> Result<DecompileResult, DecompileError> {
+    decompile_function_details(file, buf, function_id, options).map(|function| DecompileResult {
+        code: function.source,
+        warnings: function.warnings,
+    })
+}
+
+/// A decompiled function's source, along with what couldn't be translated faithfully in it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DecompileResult {
+    pub code: String,
+    pub warnings: Vec<Diagnostic>,
+}
+
+/// Same as [`decompile_function_with`], returning the warnings and what the function reads
+/// along with its source
+pub fn decompile_function_details(
+    file: &BytecodeFile,
+    buf: &[u8],
+    function_id: usize,
+    options: &DecompileOptions,
+) -> Result<DecompiledFunction, DecompileError> {
+    std::thread::scope(|scope| {
+        std::thread::Builder::new()
+            .stack_size(stack_size(options))
+            .spawn_scoped(scope, || {
+                decompile_on_this_thread(file, buf, function_id, options)
+            })?
+            .join()
+            .unwrap_or_else(|payload| std::panic::resume_unwind(payload))
+    })
+}
+
+//stack taken by a level of nesting in the passes and the emitter, which recurse, with room
+//to spare for debug builds
+const STACK_PER_LEVEL: usize = 16 * 1024;
+
+//enough stack to decompile functions nesting up to `options.max_depth`, the generator doesn't
+//go deeper and the passes and the emitter only go as deep as what it generated
+fn stack_size(options: &DecompileOptions) -> usize {
+    (options.max_depth + 64) * STACK_PER_LEVEL
+}
+
+//decompile_function_details on the calling thread, which needs stack_size(options) of stack
+fn decompile_on_this_thread(
+    file: &BytecodeFile,
+    buf: &[u8],
+    function_id: usize,
+    options: &DecompileOptions,
+) -> Result<DecompiledFunction, DecompileError> {
+    let instructions = disassemble_function(file, buf, function_id)?;
+    let header = file
+        .function_header(function_id, &mut Cursor::new(buf))?
+        .ok_or(DecompileError::InvalidFunctionId(function_id))?;
+    let mut output = Vec::new();
+    let (inlined, warnings) = match emit_function(
+        file,
+        buf,
+        function_id,
+        header.frame_size(),
+        header.strict_mode(),
+        &instructions,
+        options,
+        &mut output,
+    ) {
+        //the output has the function as far as it was generated
+        Err(DecompileError::LimitExceeded { limit, .. }) => {
+            return Err(DecompileError::LimitExceeded {
+                limit,
+                partial: String::from_utf8(output).unwrap(),
+            })
+        }
+        result => result?,
+    };
+    Ok(DecompiledFunction {
+        source: String::from_utf8(output).unwrap(),
+        uses_this: uses_this(&instructions),
+        uses_arguments: uses_arguments(&instructions),
+        inlined,
+        warnings,
+    })
+}
+
+/// A decompiled function along with what it reads from the context it's called in
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DecompiledFunction {
+    pub source: String,
+    /// Reads `this`, so it can't be emitted as an arrow function
+    pub uses_this: bool,
+    pub uses_arguments: bool,
+    /// Functions written inline as expressions, see [`DecompileOptions::inline_closures`]
+    pub inlined: Vec<usize>,
+    /// What couldn't be translated faithfully, in this function and the ones inlined into it
+    pub warnings: Vec<Diagnostic>,
+}
+
+/// Returns whether `instructions` read `this`, which the function receives as param 0
+pub fn uses_this(instructions: &[InstructionInfo<Instruction>]) -> bool {
+    instructions.iter().any(|info| {
+        matches!(
+            info.instruction,
+            Instruction::LoadThisNS { .. }
+                | Instruction::CoerceThisNS { .. }
+                | Instruction::LoadParam { param_index: 0, .. }
+                | Instruction::LoadParamLong { param_index: 0, .. }
+        )
+    })
+}
+
+/// Returns whether `instructions` read the `arguments` object
+pub fn uses_arguments(instructions: &[InstructionInfo<Instruction>]) -> bool {
+    instructions.iter().any(|info| {
+        matches!(
+            info.instruction,
+            Instruction::GetArgumentsPropByVal { .. }
+                | Instruction::GetArgumentsLength { .. }
+                | Instruction::ReifyArguments { .. }
+        )
+    })
+}
+
+fn panic_message(payload: &(dyn Any + Send)) -> String {
+    match (
+        payload.downcast_ref::<&str>(),
+        payload.downcast_ref::<String>(),
+    ) {
+        (Some(message), _) => message.to_string(),
+        (_, Some(message)) => message.clone(),
+        _ => "unknown panic".to_string(),
+    }
+}
+
+/// Decompiles every function of `file`, on up to `jobs` threads.
+/// The results are in function id order regardless of `jobs`, a function the decompiler
+/// panics on is [`DecompileError::Panicked`] without affecting the others.
+pub fn decompile_all(
+    file: &BytecodeFile,
+    buf: &[u8],
+    options: &DecompileOptions,
+    jobs: usize,
+) -> Vec<Result<DecompiledFunction, DecompileError>> {
+    decompile_all_with_progress(file, buf, options, jobs, &|_, _| ())
+}
+
+/// Same as [`decompile_all`], calling `on_progress(done, total)` after each function.
+pub fn decompile_all_with_progress(
+    file: &BytecodeFile,
+    buf: &[u8],
+    options: &DecompileOptions,
+    jobs: usize,
+    on_progress: &(dyn Fn(usize, usize) + Sync),
+) -> Vec<Result<DecompiledFunction, DecompileError>> {
+    decompile_range_with_progress(
+        file,
+        buf,
+        options,
+        jobs,
+        0..file.function_count(),
+        on_progress,
+    )
+}
+
+/// Same as [`decompile_all_with_progress`], for the functions with ids in `functions` only.
+/// Result `i` is function `functions.start + i`, ids past the last function are left out.
+pub fn decompile_range_with_progress(
+    file: &BytecodeFile,
+    buf: &[u8],
+    options: &DecompileOptions,
+    jobs: usize,
+    functions: Range<usize>,
+    on_progress: &(dyn Fn(usize, usize) + Sync),
+) -> Vec<Result<DecompiledFunction, DecompileError>> {
+    let functions = functions.start..functions.end.min(file.function_count());
+    let function_count = functions.len();
+    let jobs = jobs.clamp(1, function_count.max(1));
+    let done = AtomicUsize::new(0);
+    let decompile = |function_id| {
+        let result = std::panic::catch_unwind(AssertUnwindSafe(|| {
+            decompile_on_this_thread(file, buf, function_id, options)
+        }))
+        .unwrap_or_else(|payload| Err(DecompileError::Panicked(panic_message(&*payload))));
+        on_progress(done.fetch_add(1, Ordering::Relaxed) + 1, function_count);
+        result
+    };
+    //functions differ a lot in size, so threads take the next function instead of fixed chunks
+    let next_function = AtomicUsize::new(functions.start);
+    let mut results: Vec<Option<Result<DecompiledFunction, DecompileError>>> =
+        (0..function_count).map(|_| None).collect();
+    std::thread::scope(|scope| {
+        let workers: Vec<_> = (0..jobs)
+            .map(|_| {
+                let worker = std::thread::Builder::new().stack_size(stack_size(options));
+                worker
+                    .spawn_scoped(scope, || {
+                        let mut decompiled = Vec::new();
+                        loop {
+                            let function_id = next_function.fetch_add(1, Ordering::Relaxed);
+                            if function_id >= functions.end {
+                                return decompiled;
+                            }
+                            decompiled.push((function_id, decompile(function_id)));
+                        }
+                    })
+                    .expect("failed to spawn thread")
+            })
+            .collect();
+        for worker in workers {
+            for (function_id, result) in worker.join().unwrap() {
+                results[function_id - functions.start] = Some(result);
+            }
+        }
+    });
+    results.into_iter().map(Option::unwrap).collect()
+}
+
+/// Returns the control flow of function `function_id` in graphviz dot format.
+pub fn function_graph_dot(
+    file: &BytecodeFile,
+    buf: &[u8],
+    function_id: usize,
+    kind: GraphKind,
+) -> Result<String, DecompileError> {
+    let instructions = disassemble_function(file, buf, function_id)?;
+    let mut output = Vec::new();
+    write_graph_dot(&instructions, kind, &mut output)?;
+    Ok(String::from_utf8(output).unwrap())
+}
+
+/// Post-passes run over the generated statements before they are emitted
+pub struct DecompileOptions {
+    /// When false no post-pass runs, whatever the other options say, and every instruction
+    /// is emitted as the statement it was translated to
+    pub optimize: bool,
+    /// Replaces reads of `rA` after the move `rA = rB` with `rB` and drops the move, see
+    /// [`passes::propagate_copies`]. The for-in, for-of and spread patterns are matched on the
+    /// propagated statements
+    pub propagate_copies: bool,
+    pub collapse_constructor_calls: bool,
+    pub fold_literals: bool,
+    pub name_environment_slots: bool,
+    /// Writes `globalThis.foo` as a bare `foo` when the global object is loaded just for that
+    /// access. Off by default as reading a missing global throws where the property is undefined
+    pub bare_globals: bool,
+    /// Folds `+` chains concatenating strings into template literals. Off by default as
+    /// templates convert objects with `toString` where `+` tries `valueOf` first
+    pub templates: bool,
+    pub clean: bool,
+    pub reconstruct_for_in: bool,
+    pub reconstruct_for_of: bool,
+    pub rename: bool,
+    /// Writes closures passed as an argument right where they are created as function
+    /// expressions, instead of naming them, see [`passes::inline_closures`]
+    pub inline_closures: bool,
+    /// Names every definition of a register apart, with phi comments where they join, see
+    /// [`passes::version_registers`]. The output is for reading the dataflow, not for running
+    pub ssa: bool,
+    pub keep_profile_points: bool,
+    /// Ends every statement with a comment holding the offset and name of its instruction
+    pub annotate: bool,
+    /// Ends property reads by id with a comment holding the index of their inline cache slot
+    pub show_cache_index: bool,
+    /// Types declared registers and parameters as `any`, making the output valid TypeScript
+    pub declare_any: bool,
+    /// Writes module wrappers as ES modules, with their writes to `exports` as exports, see
+    /// [`passes::module_exports`]
+    pub es_module: bool,
+    /// Names functions after the name they were declared with, `render_f12` instead of `f12`,
+    /// see [`function_name`]
+    pub function_names: bool,
+    pub emit: Emit,
+    /// How deeply control flow may nest before giving up. Functions are decompiled on a thread
+    /// with stack for this many levels of nesting
+    pub max_depth: usize,
+    /// How many blocks may be generated before giving up. Blocks can be generated more than
+    /// once, e.g. the code after ifs whose branches don't rejoin
+    pub max_nodes: usize,
+}
+
+impl Default for DecompileOptions {
+    fn default() -> Self {
+        Self {
+            optimize: true,
+            propagate_copies: true,
+            collapse_constructor_calls: true,
+            fold_literals: true,
+            name_environment_slots: true,
+            bare_globals: false,
+            templates: false,
+            clean: false,
+            reconstruct_for_in: true,
+            reconstruct_for_of: true,
+            rename: false,
+            inline_closures: false,
+            ssa: false,
+            keep_profile_points: false,
+            annotate: false,
+            show_cache_index: false,
+            declare_any: false,
+            es_module: false,
+            function_names: false,
+            emit: Emit::Js,
+            max_depth: 1024,
+            max_nodes: 1_000_000,
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn run_passes(
+    stmts: &mut Vec<Stmt>,
+    options: &DecompileOptions,
+    is_module: bool,
+    register_count: usize,
+    comments: &SingleThreadedComments,
+    first_comment_pos: u32,
+    function_expr: &mut dyn FnMut(u32) -> Option<Box<Expr>>,
+) {
+    if is_module {
+        passes::name_module_params(stmts);
+    }
+    if options.propagate_copies {
+        passes::propagate_copies(stmts);
+    }
+    if options.collapse_constructor_calls {
+        passes::collapse_constructor_calls(stmts);
+    }
+    if options.fold_literals {
+        passes::fold_literals(stmts);
+    }
+    if options.name_environment_slots {
+        passes::name_environment_slots(stmts);
+    }
+    if options.bare_globals {
+        passes::bare_globals(stmts);
+    }
+    if options.templates {
+        passes::fold_templates(stmts);
+    }
+    if options.clean {
+        passes::eliminate_dead_stores(stmts);
+    }
+    //after the passes above, which don't know about for-in and for-of loops
+    if options.reconstruct_for_in {
+        passes::reconstruct_for_in(stmts);
+    }
+    if options.reconstruct_for_of {
+        passes::reconstruct_for_of(stmts);
+    }
+    if options.inline_closures {
+        passes::inline_closures(stmts, function_expr);
+    }
+    //versions aren't registers, so nothing is declared after this
+    if options.ssa {
+        passes::version_registers(stmts, comments, first_comment_pos);
+    }
+    passes::declare_constants(stmts, register_count);
+    passes::declare_registers(stmts, register_count);
+    if options.rename {
+        passes::rename_registers(stmts);
+    }
+}
+
+//Metro wraps every module in a function(global, require, module, exports)
+fn is_module(f: &BytecodeFile, function_id: usize) -> bool {
+    f.cjs_modules()
+        .iter()
+        .any(|(_, module_function_id)| *module_function_id as usize == function_id)
+}
+
+//what the functions nested in the one being emitted are generated from
+struct Nesting<'a> {
+    f: &'a BytecodeFile,
+    buf: &'a [u8],
+    options: &'a DecompileOptions,
+    //the functions being generated, innermost last, which can't be inlined into themselves
+    stack: Vec<usize>,
+    inlined: Vec<usize>,
+    warnings: Vec<Diagnostic>,
+}
+
+impl Nesting<'_> {
+    //function N as an expression to inline, None if it can't be decompiled on its own
+    fn function_expr(&mut self, function_id: u32) -> Option<Box<Expr>> {
+        let function_id = function_id as usize;
+        if self.stack.contains(&function_id)
+            || self.stack.len() > self.options.max_depth
+            || is_module(self.f, function_id)
+        {
+            return None;
+        }
+        let instructions = disassemble_function(self.f, self.buf, function_id).ok()?;
+        let header = self
+            .f
+            .function_header(function_id, &mut Cursor::new(self.buf))
+            .ok()??;
+        //the comments would be at positions of the outer function's, so they are dropped
+        let comments = SingleThreadedComments::default();
+        let budget = Budget::new(self.options.max_depth, self.options.max_nodes);
+        let (inlined, warnings) = (self.inlined.len(), self.warnings.len());
+        self.stack.push(function_id);
+        let body = function_body(
+            self,
+            function_id,
+            header.frame_size(),
+            header.strict_mode(),
+            &instructions,
+            &comments,
+            &budget,
+        );
+        self.stack.pop();
+        let stmts = match body {
+            Ok(stmts) if budget.exceeded().is_none() => stmts,
+            _ => {
+                //the function keeps its own declaration, which reports its warnings
+                self.inlined.truncate(inlined);
+                self.warnings.truncate(warnings);
+                return None;
+            }
+        };
+        self.inlined.push(function_id);
+
+        let body = BlockStmt {
+            span: DUMMY_SP,
+            stmts,
+        };
+        //parameters are read from `arguments`, which an arrow function doesn't have
+        let reads_arguments = uses_arguments(&instructions)
+            || instructions.iter().any(|info| {
+                matches!(
+                    info.instruction,
+                    Instruction::LoadParam { .. } | Instruction::LoadParamLong { .. }
+                )
+            });
+        Some(Box::new(if uses_this(&instructions) || reads_arguments {
+            Expr::Fn(FnExpr {
+                ident: None,
+                function: Box::new(Function {
+                    params: Vec::new(),
+                    decorators: Vec::new(),
+                    span: DUMMY_SP,
+                    body: Some(body),
+                    is_generator: false,
+                    is_async: false,
+                    type_params: None,
+                    return_type: None,
+                }),
+            })
+        } else {
+            Expr::Arrow(ArrowExpr {
+                span: DUMMY_SP,
+                params: Vec::new(),
+                body: Box::new(BlockStmtOrExpr::BlockStmt(body)),
+                is_async: false,
+                is_generator: false,
+                type_params: None,
+                return_type: None,
+            })
+        }))
+    }
+}
+
+//the statements of a function's body, with the passes run over them
+fn function_body(
+    nesting: &mut Nesting,
+    function_id: usize,
+    frame_size: u32,
+    strict: bool,
+    instructions: &[InstructionInfo<Instruction>],
+    comments: &SingleThreadedComments,
+    budget: &Budget,
+) -> Result<Vec<Stmt>, DecompileError> {
+    let (f, buf, options) = (nesting.f, nesting.buf, nesting.options);
+    let names = |function_id: u32| function_name(f, buf, function_id as usize);
+    let flow_graph = construct_flow_graph(instructions)?;
+    let try_finally =
+        try_finally_regions(f.exception_handlers(function_id), instructions, &flow_graph);
+    //no jump leads to the code of the handlers, and the parts of the try statements are
+    //generated apart from each other
+    let roots: Vec<usize> = try_finally
+        .iter()
+        .map(|region| region.finally.start - 1)
+        .collect();
+    let leaders = try_finally
+        .iter()
+        .flat_map(|region| {
+            [region.start, region.end, region.finally.start]
+                .into_iter()
+                .chain(region.after)
+        })
+        .collect();
+    let cfg = construct_cfg_with(&flow_graph, &roots, &leaders);
+    let diagnostics = Diagnostics::new(function_id);
+
+    //a function without instructions has no entry block to generate from
+    let mut stmts: Vec<Stmt> = if cfg.node_count() == 0 {
+        Vec::new()
+    } else {
+        let body = FunctionBody {
+            f,
+            cfg: &cfg,
+            instructions,
+            frame_size,
+            try_finally: &try_finally,
+        };
+        let mut generator = AstGenerator::new(body, NodeIndex::new(0), false, None, None);
+        if options.keep_profile_points {
+            generator = generator.keep_profile_points(comments);
+        }
+        if options.annotate {
+            generator = generator.annotate(comments);
+        }
+        if options.show_cache_index {
+            generator = generator.show_cache_indices(comments);
+        }
+        if options.function_names {
+            generator = generator.function_names(&names);
+        }
+        generator.diagnostics(&diagnostics).budget(budget).collect()
+    };
+    nesting.warnings.extend(diagnostics.into_vec());
+    if options.optimize {
+        //past the positions of the comments about instructions
+        let first_comment_pos = instructions.last().map_or(0, |info| info.offset) + 2;
+        run_passes(
+            &mut stmts,
+            options,
+            is_module(f, function_id),
+            frame_size as usize,
+            comments,
+            first_comment_pos,
+            &mut |function_id| nesting.function_expr(function_id),
+        );
+    }
+    if options.declare_any {
+        passes::declare_any(&mut stmts);
+    }
+    if strict {
+        stmts.insert(
+            0,
+            Stmt::Expr(ExprStmt {
+                span: DUMMY_SP,
+                expr: Box::new(Expr::Lit(Lit::Str(Str {
+                    span: DUMMY_SP,
+                    value: "use strict".into(),
+                    raw: None,
+                }))),
+            }),
+        );
+    }
+    Ok(stmts)
+}
+
+//returns the functions inlined into this one, which need no declaration of their own, and the
+//warnings of this function and them
+#[allow(clippy::too_many_arguments)]
+fn emit_function<W: Write>(
+    f: &BytecodeFile,
+    buf: &[u8],
+    function_id: usize,
+    frame_size: u32,
+    strict: bool,
+    instructions: &[InstructionInfo<Instruction>],
+    options: &DecompileOptions,
+    output: &mut W,
+) -> Result<(Vec<usize>, Vec<Diagnostic>), DecompileError> {
+    let comments = SingleThreadedComments::default();
+    let budget = Budget::new(options.max_depth, options.max_nodes);
+    let mut nesting = Nesting {
+        f,
+        buf,
+        options,
+        stack: vec![function_id],
+        inlined: Vec::new(),
+        warnings: Vec::new(),
+    };
+    let stmts = function_body(
+        &mut nesting,
+        function_id,
+        frame_size,
+        strict,
+        instructions,
+        &comments,
+        &budget,
+    )?;
+    let is_module = is_module(f, function_id);
+    let name = if options.function_names {
+        function_name(f, buf, function_id)
+    } else {
+        None
+    };
+
+    let body = if options.es_module && is_module {
+        passes::module_exports(stmts)
+    } else {
+        Err(stmts)
+    };
+    let program = match body {
+        Ok(body) => Program::Module(Module {
+            span: DUMMY_SP,
+            body,
+            shebang: None,
+        }),
+        Err(stmts) => {
+            let func = FnDecl {
+                ident: Ident::new(
+                    generate_ast::function_ident(name.as_deref(), function_id as u32)
+                        .as_str()
+                        .into(),
+                    DUMMY_SP,
+                ),
+                function: Box::new(Function {
+                    params: if is_module {
+                        passes::MODULE_PARAMS
+                            .iter()
+                            .map(|name| Param {
+                                span: DUMMY_SP,
+                                decorators: Vec::new(),
+                                pat: Pat::Ident(BindingIdent {
+                                    id: Ident::new((*name).into(), DUMMY_SP),
+                                    type_ann: options.declare_any.then(passes::any_type_ann),
+                                }),
+                            })
+                            .collect()
+                    } else {
+                        Vec::new()
+                    },
+                    decorators: Vec::new(),
+                    span: DUMMY_SP,
+                    body: Some(BlockStmt {
+                        span: DUMMY_SP,
+                        stmts,
+                    }),
+                    is_generator: false,
+                    is_async: false,
+                    type_params: None,
+                    return_type: None,
+                }),
+                declare: false,
+            };
+            Program::Script(Script {
+                span: DUMMY_SP,
+                body: vec![Stmt::Decl(Decl::Fn(func))],
+                shebang: None,
+            })
+        }
+    };
+    if options.emit == Emit::AstJson {
+        //comments live outside the AST, so annotations and profile points aren't in the json
+        serde_json::to_writer(&mut *output, &program).map_err(std::io::Error::from)?;
+        writeln!(output)?;
+    } else {
+        let cm = Lrc::new(SourceMap::new(FilePathMapping::empty()));
+        let mut emitter = Emitter {
+            cfg: swc_ecma_codegen::Config {
+                target: EsVersion::Es2022,
+                ascii_only: false,
+                minify: false,
+                omit_last_semi: false,
+            },
+            cm: cm.clone(),
+            comments: Some(&comments),
+            wr: JsWriter::new(cm, "\n", output, None),
+        };
+        emitter.emit_program(&program).unwrap();
+    }
+    //what was generated before running out of budget is written all the same, the passes and
+    //the emitter only recurse as deep as the generators did
+    match budget.exceeded() {
+        Some(limit) => Err(DecompileError::LimitExceeded {
+            limit,
+            partial: String::new(),
+        }),
+        None => Ok((nesting.inlined, nesting.warnings)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        fs::File,
+        io::{Cursor, Read, Write},
+    };
+
+    use petgraph::{stable_graph::NodeIndex, visit::EdgeRef};
+
+    use swc_common::DUMMY_SP;
+    use swc_ecma_ast::{Decl, Expr, ExprStmt, Ident, Pat, Stmt};
+
+    use crate::{
+        bytecode::{v93::Instruction, InstructionSet},
+        construct_cfg, construct_flow_graph, decompile_function, emit_function,
+        generate_ast::is_unsupported,
+        hermes_file_reader::{BytecodeFile, BytecodeFileHeader, InstructionInfo},
+        uses_arguments, uses_this, write_graph_dot, AstGenerator, DecompileError, DecompileOptions,
+        Emit, FunctionBody, GraphKind, Limit,
+    };
+
+    //a bundle without functions that only has a string table
+    fn bytecode_file_with_strings(strings: &[&str]) -> BytecodeFile {
+        let mut bytes = vec![0; std::mem::size_of::<BytecodeFileHeader>()];
+        let storage = strings.concat();
+        bytes[52..56].copy_from_slice(&(strings.len() as u32).to_le_bytes()); //string_count
+        bytes[60..64].copy_from_slice(&(storage.len() as u32).to_le_bytes()); //string_storage_size
+        let mut offset = 0;
+        for s in strings {
+            let entry = ((offset as u32) << 1) | ((s.len() as u32) << 24);
+            bytes.extend_from_slice(&entry.to_le_bytes());
+            offset += s.len();
+        }
+        bytes.extend_from_slice(storage.as_bytes());
+        BytecodeFile::from_reader(&mut Cursor::new(bytes.as_slice())).unwrap()
+    }
+
+    fn decompile(strings: &[&str], instructions: &[InstructionInfo<Instruction>]) -> String {
+        decompile_with(strings, instructions, &DecompileOptions::default())
+    }
+
+    fn decompile_with(
+        strings: &[&str],
+        instructions: &[InstructionInfo<Instruction>],
+        options: &DecompileOptions,
+    ) -> String {
+        //as many registers as a u8 operand can name
+        decompile_in_frame(strings, instructions, 256, options)
+    }
+
+    //for instructions that depend on the frame size, like calls reading their arguments
+    fn decompile_in_frame(
+        strings: &[&str],
+        instructions: &[InstructionInfo<Instruction>],
+        frame_size: u32,
+        options: &DecompileOptions,
+    ) -> String {
+        let mut out = Vec::new();
+        emit_function(
+            &bytecode_file_with_strings(strings),
+            &[],
+            0,
+            frame_size,
+            false,
+            instructions,
+            options,
+            &mut out,
+        )
+        .unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    fn if_instructions() -> Vec<InstructionInfo<Instruction>> {
+        vec![
+            InstructionInfo {
+                offset: 0,
+                instruction: Instruction::LoadConstTrue { dst_reg: 0 },
+            },
+            InstructionInfo {
+                offset: 2,
+                instruction: Instruction::JmpFalse {
+                    relative_offset: 5,
+                    check_value_reg: 0,
+                },
+            },
+            InstructionInfo {
+                offset: 5,
+                instruction: Instruction::LoadConstZero { dst_reg: 1 },
+            },
+            InstructionInfo {
+                offset: 7,
+                instruction: Instruction::Ret { value_reg: 1 },
+            },
+        ]
+    }
+
+    fn dot_node_count(dot: &str) -> usize {
+        dot.lines()
+            .filter(|l| l.contains("label = ") && !l.contains("->"))
+            .count()
+    }
+
+    fn dot_node_containing(dot: &str, text: &str) -> String {
+        let line = dot
+            .lines()
+            .find(|l| l.contains(text) && !l.contains("->"))
+            .unwrap();
+        line.split_whitespace().next().unwrap().to_string()
+    }
+
+    #[test]
+    fn t() {
+        let mut buf = Vec::new();
+        match File::open("../index.android.bundle")
+            .unwrap()
+            .read_to_end(&mut buf)
+        {
+            Ok(_) => (),
+            Err(e) => {
+                println!("Error while reading provided file: {e}");
+                return;
+            }
+        };
+        let mut cursor = Cursor::new(buf.as_slice());
+        let f = BytecodeFile::from_reader(&mut cursor).unwrap();
+        let out = decompile_function(&f, &buf, 12).unwrap().code;
+        File::create("../out.txt")
+            .unwrap()
+            .write_all(out.as_bytes())
+            .unwrap();
+        panic!("{:?}", f.exception_handlers(12));
+    }
+
+    #[test]
+    fn graph_dot_node_count() {
+        let instructions = if_instructions();
+
+        let mut out = Vec::new();
+        write_graph_dot(&instructions, GraphKind::Flow, &mut out).unwrap();
+        assert_eq!(dot_node_count(&String::from_utf8(out).unwrap()), 4);
+
+        let mut out = Vec::new();
+        write_graph_dot(&instructions, GraphKind::Cfg, &mut out).unwrap();
+        assert_eq!(dot_node_count(&String::from_utf8(out).unwrap()), 3);
+    }
+
+    #[test]
+    fn straight_line_is_one_block() {
+        let mut instructions: Vec<_> = (0..10)
+            .map(|i| InstructionInfo {
+                offset: i * 2,
+                instruction: Instruction::LoadConstZero { dst_reg: i as u8 },
+            })
+            .collect();
+        instructions.push(InstructionInfo {
+            offset: 20,
+            instruction: Instruction::Ret { value_reg: 0 },
+        });
+        let flow_graph = construct_flow_graph(&instructions).unwrap();
+        let cfg = construct_cfg(&flow_graph);
+        assert_eq!(cfg.node_count(), 1);
+        assert_eq!(cfg[NodeIndex::new(0)], (0..11).collect::<Vec<_>>());
+
+        //a jump to an instruction nothing else reaches continues the same block
+        instructions[3] = InstructionInfo {
+            offset: 6,
+            instruction: Instruction::Jmp { relative_offset: 4 },
+        };
+        instructions.remove(4);
+        let flow_graph = construct_flow_graph(&instructions).unwrap();
+        let cfg = construct_cfg(&flow_graph);
+        assert_eq!(cfg.node_count(), 1);
+        assert_eq!(cfg[NodeIndex::new(0)], [0, 1, 2, 3, 4, 5, 6, 7, 8, 9]);
+    }
+
+    #[test]
+    fn backward_jump_targets_earlier_instruction() {
+        let instructions = [
+            InstructionInfo {
+                offset: 0,
+                instruction: Instruction::LoadConstZero { dst_reg: 0 },
+            },
+            InstructionInfo {
+                offset: 2,
+                instruction: Instruction::LoadConstZero { dst_reg: 1 },
+            },
+            InstructionInfo {
+                offset: 4,
+                instruction: Instruction::JmpTrue {
+                    relative_offset: -2,
+                    check_value_reg: 0,
+                },
+            },
+            InstructionInfo {
+                offset: 7,
+                instruction: Instruction::Ret { value_reg: 0 },
+            },
+        ];
+        let flow_graph = construct_flow_graph(&instructions).unwrap();
+        let taken = flow_graph
+            .edges(NodeIndex::new(2))
+            .find(|edge| *edge.weight())
+            .unwrap();
+        assert_eq!(taken.target().index(), 1);
+
+        //the loop body is its own block, which jumps back to itself
+        let cfg = construct_cfg(&flow_graph);
+        let body = cfg
+            .node_indices()
+            .find(|node| cfg[*node] == [1, 2])
+            .unwrap();
+        assert!(cfg
+            .edges(body)
+            .any(|edge| *edge.weight() && edge.target() == body));
+    }
+
+    #[test]
+    fn jump_offset_relative_to_jump_start() {
+        let mut instructions: Vec<_> = (0..8)
+            .map(|i| InstructionInfo {
+                offset: i * 2,
+                instruction: Instruction::LoadConstZero { dst_reg: 0 },
+            })
+            .collect();
+        instructions.extend([
+            InstructionInfo {
+                offset: 0x10,
+                instruction: Instruction::Jmp { relative_offset: 5 },
+            },
+            InstructionInfo {
+                offset: 0x12,
+                instruction: Instruction::LoadConstUInt8 {
+                    dst_reg: 0,
+                    value: 1,
+                },
+            },
+            InstructionInfo {
+                offset: 0x15,
+                instruction: Instruction::Ret { value_reg: 0 },
+            },
+            //where the jump would land if it were relative to the end of the Jmp
+            InstructionInfo {
+                offset: 0x17,
+                instruction: Instruction::Ret { value_reg: 0 },
+            },
+        ]);
+        let flow_graph = construct_flow_graph(&instructions).unwrap();
+        let targets: Vec<_> = flow_graph
+            .neighbors(NodeIndex::new(8))
+            .map(|node| instructions[node.index()].offset)
+            .collect();
+        assert_eq!(targets, [0x15]);
+    }
+
+    #[test]
+    fn forward_jump_from_first_instruction() {
+        let instructions = [
+            InstructionInfo {
+                offset: 0,
+                instruction: Instruction::Jmp { relative_offset: 4 },
+            },
+            InstructionInfo {
+                offset: 2,
+                instruction: Instruction::Ret { value_reg: 1 },
+            },
+            InstructionInfo {
+                offset: 4,
+                instruction: Instruction::Ret { value_reg: 0 },
+            },
+        ];
+        let flow_graph = construct_flow_graph(&instructions).unwrap();
+        let targets: Vec<_> = flow_graph.neighbors(NodeIndex::new(0)).collect();
+        assert_eq!(targets, [NodeIndex::new(2)]);
+    }
+
+    #[test]
+    fn unreachable_ends_block() {
+        let instructions = vec![
+            InstructionInfo {
+                offset: 0,
+                instruction: Instruction::LoadConstZero { dst_reg: 0 },
+            },
+            InstructionInfo {
+                offset: 2,
+                instruction: Instruction::Unreachable,
+            },
+            InstructionInfo {
+                offset: 3,
+                instruction: Instruction::Ret { value_reg: 0 },
+            },
+        ];
+        let flow_graph = construct_flow_graph(&instructions).unwrap();
+        assert_eq!(flow_graph.edges(NodeIndex::new(1)).count(), 0);
+        assert_eq!(construct_cfg(&flow_graph).node_count(), 1);
+
+        let out = decompile(&[], &instructions);
+        assert!(out.contains("throw new Error(\"unreachable\");"), "{out}");
+        assert!(!out.contains("return"), "{out}");
+    }
+
+    #[test]
+    fn misaligned_jump_target() {
+        let instructions = vec![
+            InstructionInfo {
+                offset: 0,
+                instruction: Instruction::Jmp { relative_offset: 3 },
+            },
+            InstructionInfo {
+                offset: 2,
+                instruction: Instruction::LoadConstUInt8 {
+                    dst_reg: 0,
+                    value: 1,
+                },
+            },
+            InstructionInfo {
+                offset: 5,
+                instruction: Instruction::Ret { value_reg: 0 },
+            },
+        ];
+        let err = construct_flow_graph(&instructions).unwrap_err();
+        assert_eq!((err.offset, err.target), (0, 3));
+
+        let mut out = Vec::new();
+        assert!(matches!(
+            emit_function(
+                &bytecode_file_with_strings(&[]),
+                &[],
+                0,
+                0,
+                false,
+                &instructions,
+                &DecompileOptions::default(),
+                &mut out,
+            ),
+            Err(DecompileError::InvalidJumpTarget(_))
+        ));
+    }
+
+    #[test]
+    fn graph_dot_conditional_edge_labels() {
+        let instructions = if_instructions();
+
+        let mut out = Vec::new();
+        write_graph_dot(&instructions, GraphKind::Cfg, &mut out).unwrap();
+        let dot = String::from_utf8(out).unwrap();
+        assert!(dot.contains("2: JmpFalse { relative_offset: 5, check_value_reg: 0 }"));
+
+        let jump = dot_node_containing(&dot, "JmpFalse");
+        let taken = dot_node_containing(&dot, "Ret");
+        let fallthrough = dot_node_containing(&dot, "LoadConstZero");
+        assert!(dot.contains(&format!("{jump} -> {taken} [ label = \"true\" ]")));
+        assert!(dot.contains(&format!("{jump} -> {fallthrough} [ label = \"false\" ]")));
+    }
+
+    #[test]
+    fn irreducible_loop_terminates() {
+        //two blocks jumping into each other, both reachable from the entry block
+        let instructions = vec![
+            InstructionInfo {
+                offset: 0,
+                instruction: Instruction::LoadConstTrue { dst_reg: 0 },
+            },
+            InstructionInfo {
+                offset: 2,
+                instruction: Instruction::JmpTrue {
+                    relative_offset: 8,
+                    check_value_reg: 0,
+                },
+            },
+            InstructionInfo {
+                offset: 5,
+                instruction: Instruction::JmpTrue {
+                    relative_offset: 5,
+                    check_value_reg: 1,
+                },
+            },
+            InstructionInfo {
+                offset: 8,
+                instruction: Instruction::Ret { value_reg: 1 },
+            },
+            InstructionInfo {
+                offset: 10,
+                instruction: Instruction::JmpTrue {
+                    relative_offset: -5,
+                    check_value_reg: 2,
+                },
+            },
+            InstructionInfo {
+                offset: 13,
+                instruction: Instruction::Ret { value_reg: 2 },
+            },
+        ];
+        let out = decompile(&[], &instructions);
+        assert!(out.contains("goto("), "{out}");
+    }
+
+    #[test]
+    fn labeled_break_from_inner_loop() {
+        let instructions = vec![
+            InstructionInfo {
+                offset: 0,
+                instruction: Instruction::LoadConstZero { dst_reg: 3 },
+            },
+            //outer loop condition
+            InstructionInfo {
+                offset: 2,
+                instruction: Instruction::JmpTrue {
+                    relative_offset: 13,
+                    check_value_reg: 0,
+                },
+            },
+            //inner loop condition
+            InstructionInfo {
+                offset: 5,
+                instruction: Instruction::JmpTrue {
+                    relative_offset: 8,
+                    check_value_reg: 1,
+                },
+            },
+            //break out of the outer loop
+            InstructionInfo {
+                offset: 8,
+                instruction: Instruction::JmpTrue {
+                    relative_offset: 7,
+                    check_value_reg: 2,
+                },
+            },
+            InstructionInfo {
+                offset: 11,
+                instruction: Instruction::Jmp {
+                    relative_offset: -6,
+                },
+            },
+            InstructionInfo {
+                offset: 13,
+                instruction: Instruction::Jmp {
+                    relative_offset: -11,
+                },
+            },
+            InstructionInfo {
+                offset: 15,
+                instruction: Instruction::Ret { value_reg: 3 },
+            },
+        ];
+        let out = decompile(&[], &instructions);
+        assert!(out.contains("loop1: while"), "{out}");
+        assert!(out.contains("break loop1;"), "{out}");
+    }
+
+    //instructions at the given offsets, for control flow where the jumps have to line up
+    fn at_offsets(instructions: Vec<(u32, Instruction)>) -> Vec<InstructionInfo<Instruction>> {
+        instructions
+            .into_iter()
+            .map(|(offset, instruction)| InstructionInfo {
+                offset,
+                instruction,
+            })
+            .collect()
+    }
+
+    fn mov(dst_reg: u8, src_reg: u8) -> Instruction {
+        Instruction::Mov { dst_reg, src_reg }
+    }
+
+    fn jmp_true(relative_offset: i8, check_value_reg: u8) -> Instruction {
+        Instruction::JmpTrue {
+            relative_offset,
+            check_value_reg,
+        }
+    }
+
+    fn jmp_false(relative_offset: i8, check_value_reg: u8) -> Instruction {
+        Instruction::JmpFalse {
+            relative_offset,
+            check_value_reg,
+        }
+    }
+
+    #[test]
+    fn pre_test_loop_is_while() {
+        let instructions = at_offsets(vec![
+            (0, Instruction::LoadConstZero { dst_reg: 0 }),
+            (2, jmp_false(8, 1)),
+            (5, mov(0, 2)),
+            (
+                8,
+                Instruction::Jmp {
+                    relative_offset: -6,
+                },
+            ),
+            (10, Instruction::Ret { value_reg: 0 }),
+        ]);
+        let out = decompile(&[], &instructions);
+        assert!(out.contains("while(r1){\n        r0 = r2;"), "{out}");
+    }
+
+    #[test]
+    fn post_test_loop_is_do_while() {
+        let instructions = at_offsets(vec![
+            (0, Instruction::LoadConstZero { dst_reg: 0 }),
+            (2, mov(0, 2)),
+            (5, jmp_true(-3, 1)),
+            (8, Instruction::Ret { value_reg: 0 }),
+        ]);
+        let out = decompile(&[], &instructions);
+        assert!(
+            out.contains("do {\n        r0 = r2;\n    }while (r1)"),
+            "{out}"
+        );
+    }
+
+    #[test]
+    fn post_test_loop_at_function_start() {
+        let instructions = at_offsets(vec![
+            (0, mov(0, 2)),
+            (3, jmp_true(-3, 1)),
+            (6, Instruction::Ret { value_reg: 0 }),
+        ]);
+        let out = decompile(&[], &instructions);
+        assert!(
+            out.contains("do {\n        r0 = r2;\n    }while (r1)"),
+            "{out}"
+        );
+    }
+
+    #[test]
+    fn post_test_loop_falling_through_to_header() {
+        //the test is laid out before the body, which is jumped to first
+        let instructions = at_offsets(vec![
+            (0, Instruction::LoadConstZero { dst_reg: 0 }),
+            (2, Instruction::Jmp { relative_offset: 5 }),
+            (4, jmp_true(8, 1)),
+            (7, mov(0, 2)),
+            (
+                10,
+                Instruction::Jmp {
+                    relative_offset: -6,
+                },
+            ),
+            (12, Instruction::Ret { value_reg: 0 }),
+        ]);
+        let out = decompile(&[], &instructions);
+        assert!(
+            out.contains("do {\n        r0 = r2;\n    }while (!r1)"),
+            "{out}"
+        );
+    }
+
+    #[test]
+    fn conditional_continue_stays_while() {
+        //the continue jumps back to the header conditionally, but into the loop otherwise
+        let instructions = at_offsets(vec![
+            (0, Instruction::LoadConstZero { dst_reg: 0 }),
+            (2, jmp_false(14, 1)),
+            (5, mov(0, 2)),
+            (8, jmp_true(-6, 3)),
+            (11, mov(0, 4)),
+            (
+                14,
+                Instruction::Jmp {
+                    relative_offset: -12,
+                },
+            ),
+            (16, Instruction::Ret { value_reg: 0 }),
+        ]);
+        let out = decompile(&[], &instructions);
+        assert!(out.contains("while(r1){"), "{out}");
+        assert!(
+            out.contains("if (r3) {\n            continue;\n        }"),
+            "{out}"
+        );
+        assert!(!out.contains("do {"), "{out}");
+    }
+
+    #[test]
+    fn break_from_do_while() {
+        let instructions = at_offsets(vec![
+            (0, Instruction::LoadConstZero { dst_reg: 0 }),
+            (2, mov(0, 2)),
+            (5, jmp_true(9, 3)),
+            (8, mov(0, 4)),
+            (11, jmp_true(-9, 1)),
+            (14, Instruction::Ret { value_reg: 0 }),
+        ]);
+        let out = decompile(&[], &instructions);
+        assert!(
+            out.contains("if (r3) {\n            break;\n        }"),
+            "{out}"
+        );
+        assert!(out.contains("r0 = r4;\n    }while (r1)"), "{out}");
+    }
+
+    #[test]
+    fn loop_without_test_is_while_true() {
+        //the header's conditional jump stays in the loop, so it isn't the loop's test
+        let instructions = at_offsets(vec![
+            (0, Instruction::LoadConstZero { dst_reg: 0 }),
+            (2, jmp_false(6, 4)),
+            (5, mov(0, 5)),
+            (8, jmp_true(5, 3)),
+            (
+                11,
+                Instruction::Jmp {
+                    relative_offset: -9,
+                },
+            ),
+            (13, Instruction::Ret { value_reg: 0 }),
+        ]);
+        let out = decompile(&[], &instructions);
+        assert!(out.contains("while(true){\n        if (r4) {"), "{out}");
+        assert!(
+            out.contains("if (r3) {\n            break;\n        }"),
+            "{out}"
+        );
+    }
+
+    #[test]
+    fn loop_with_long_back_jump() {
+        let instructions = at_offsets(vec![
+            (0, Instruction::LoadConstZero { dst_reg: 0 }),
+            (2, jmp_false(11, 1)),
+            (5, mov(0, 2)),
+            (
+                8,
+                Instruction::JmpLong {
+                    relative_offset: -6,
+                },
+            ),
+            (13, Instruction::Ret { value_reg: 0 }),
+        ]);
+        let out = decompile(&[], &instructions);
+        assert!(out.contains("while(r1){\n        r0 = r2;"), "{out}");
+    }
+
+    #[test]
+    fn block_jumping_to_itself() {
+        let instructions = at_offsets(vec![
+            (0, Instruction::LoadConstZero { dst_reg: 0 }),
+            (2, mov(0, 2)),
+            (
+                5,
+                Instruction::Jmp {
+                    relative_offset: -3,
+                },
+            ),
+        ]);
+        let out = decompile(&[], &instructions);
+        assert!(out.contains("while(true){"), "{out}");
+    }
+
+    #[test]
+    fn continue_from_inner_loop_runs_update() {
+        //for (r0 = 0; r0 < r2; r0++) { while (r3) { if (r4) continue outer; r5 = r6; } r5 = r7; }
+        let instructions = at_offsets(vec![
+            (0, Instruction::LoadConstZero { dst_reg: 0 }),
+            (
+                2,
+                Instruction::Less {
+                    dst_reg: 1,
+                    arg1_reg: 0,
+                    arg2_reg: 2,
+                },
+            ),
+            (6, jmp_false(22, 1)),
+            (9, jmp_false(11, 3)),
+            (12, jmp_true(11, 4)),
+            (15, mov(5, 6)),
+            (
+                18,
+                Instruction::Jmp {
+                    relative_offset: -9,
+                },
+            ),
+            (20, mov(5, 7)),
+            (
+                23,
+                Instruction::Inc {
+                    dst_reg: 0,
+                    arg_reg: 0,
+                },
+            ),
+            (
+                26,
+                Instruction::Jmp {
+                    relative_offset: -24,
+                },
+            ),
+            (28, Instruction::Ret { value_reg: 0 }),
+        ]);
+        let out = decompile(&[], &instructions);
+        //the header computing the test runs before it's checked again
+        assert!(
+            out.contains("r0++;\n                r1 = r0 < r2;\n                continue loop1;"),
+            "{out}"
+        );
+        assert!(
+            out.contains("r0++;\n        r1 = r0 < r2;\n        continue;\n    }"),
+            "{out}"
+        );
+    }
+
+    #[test]
+    fn throw_and_break_in_loop_with_header() {
+        //while (r0 < r2) { if (r3) throw r4; if (r5) break; r0++; }
+        let instructions = at_offsets(vec![
+            (0, Instruction::LoadConstZero { dst_reg: 0 }),
+            (
+                2,
+                Instruction::Less {
+                    dst_reg: 1,
+                    arg1_reg: 0,
+                    arg2_reg: 2,
+                },
+            ),
+            (6, jmp_false(16, 1)),
+            (9, jmp_false(5, 3)),
+            (12, Instruction::Throw { value_reg: 4 }),
+            (14, jmp_true(8, 5)),
+            (
+                17,
+                Instruction::Inc {
+                    dst_reg: 0,
+                    arg_reg: 0,
+                },
+            ),
+            (
+                20,
+                Instruction::Jmp {
+                    relative_offset: -18,
+                },
+            ),
+            (22, Instruction::Ret { value_reg: 0 }),
+        ]);
+        let out = decompile(&[], &instructions);
+        assert!(
+            out.contains("if (r3) {\n            throw r4;\n        }"),
+            "{out}"
+        );
+        assert!(
+            out.contains("if (r5) {\n            break;\n        }"),
+            "{out}"
+        );
+        assert!(
+            out.contains("r0++;\n        r1 = r0 < r2;\n        continue;"),
+            "{out}"
+        );
+    }
+
+    #[test]
+    fn get_by_val_constant_string_index() {
+        let get = |string_table_index| {
+            vec![
+                InstructionInfo {
+                    offset: 0,
+                    instruction: Instruction::LoadConstString {
+                        dst_reg: 1,
+                        string_table_index,
+                    },
+                },
+                InstructionInfo {
+                    offset: 4,
+                    instruction: Instruction::GetByVal {
+                        dst_reg: 2,
+                        obj_reg: 0,
+                        index_reg: 1,
+                    },
+                },
+                InstructionInfo {
+                    offset: 8,
+                    instruction: Instruction::PutByVal {
+                        dst_obj_reg: 0,
+                        index_reg: 1,
+                        value_reg: 2,
+                    },
+                },
+                InstructionInfo {
+                    offset: 12,
+                    instruction: Instruction::Ret { value_reg: 2 },
+                },
+            ]
+        };
+        let strings = ["foo", "not-ident"];
+
+        let out = decompile(&strings, &get(0));
+        assert!(out.contains("r2 = r0.foo;"), "{out}");
+        assert!(out.contains("r0.foo = r2;"), "{out}");
+
+        let out = decompile(&strings, &get(1));
+        assert!(out.contains("r2 = r0[r1];"), "{out}");
+        assert!(out.contains("r0[r1] = r2;"), "{out}");
+    }
+
+    #[test]
+    fn get_by_val_constant_number_index() {
+        let instructions = vec![
+            InstructionInfo {
+                offset: 0,
+                instruction: Instruction::LoadConstUInt8 {
+                    dst_reg: 1,
+                    value: 3,
+                },
+            },
+            InstructionInfo {
+                offset: 3,
+                instruction: Instruction::GetByVal {
+                    dst_reg: 2,
+                    obj_reg: 0,
+                    index_reg: 1,
+                },
+            },
+            InstructionInfo {
+                offset: 7,
+                instruction: Instruction::PutByVal {
+                    dst_obj_reg: 0,
+                    index_reg: 1,
+                    value_reg: 2,
+                },
+            },
+            InstructionInfo {
+                offset: 11,
+                instruction: Instruction::Ret { value_reg: 2 },
+            },
+        ];
+        let out = decompile(&[], &instructions);
+        assert!(out.contains("r2 = r0[3];"), "{out}");
+        assert!(out.contains("r0[3] = r2;"), "{out}");
+    }
+
+    #[test]
+    fn get_by_val_register_index() {
+        let instructions = vec![
+            InstructionInfo {
+                offset: 0,
+                instruction: Instruction::LoadConstZero { dst_reg: 1 },
+            },
+            //r1 isn't a constant anymore
+            InstructionInfo {
+                offset: 2,
+                instruction: Instruction::Inc {
+                    dst_reg: 1,
+                    arg_reg: 1,
+                },
+            },
+            InstructionInfo {
+                offset: 5,
+                instruction: Instruction::GetByVal {
+                    dst_reg: 2,
+                    obj_reg: 0,
+                    index_reg: 1,
+                },
+            },
+            InstructionInfo {
+                offset: 9,
+                instruction: Instruction::Ret { value_reg: 2 },
+            },
+        ];
+        let out = decompile(&[], &instructions);
+        assert!(out.contains("r2 = r0[r1];"), "{out}");
+    }
+
+    #[test]
+    fn copy_propagation_removes_moves() {
+        let instructions = vec![
+            InstructionInfo {
+                offset: 0,
+                instruction: Instruction::LoadConstZero { dst_reg: 1 },
+            },
+            InstructionInfo {
+                offset: 2,
+                instruction: Instruction::Mov {
+                    dst_reg: 2,
+                    src_reg: 1,
+                },
+            },
+            InstructionInfo {
+                offset: 5,
+                instruction: Instruction::Ret { value_reg: 2 },
+            },
+        ];
+        let unoptimized = decompile_with(
+            &[],
+            &instructions,
+            &DecompileOptions {
+                propagate_copies: false,
+                ..Default::default()
+            },
+        );
+        assert!(unoptimized.contains("r2 = r1;"), "{unoptimized}");
+        let optimized = decompile(&[], &instructions);
+        assert!(optimized.contains("return r1;"), "{optimized}");
+        assert!(!optimized.contains("r2"), "{optimized}");
+        assert!(optimized.matches(';').count() < unoptimized.matches(';').count());
+    }
+
+    #[test]
+    fn no_optimize_emits_statement_per_instruction() {
+        let instructions = [
+            InstructionInfo {
+                offset: 0,
+                instruction: Instruction::LoadConstZero { dst_reg: 1 },
+            },
+            InstructionInfo {
+                offset: 2,
+                instruction: Instruction::Mov {
+                    dst_reg: 2,
+                    src_reg: 1,
+                },
+            },
+            InstructionInfo {
+                offset: 5,
+                instruction: Instruction::Mov {
+                    dst_reg: 3,
+                    src_reg: 2,
+                },
+            },
+            InstructionInfo {
+                offset: 8,
+                instruction: Instruction::Ret { value_reg: 3 },
+            },
+        ];
+        let raw = decompile_with(
+            &[],
+            &instructions,
+            &DecompileOptions {
+                optimize: false,
+                ..Default::default()
+            },
+        );
+        assert_eq!(raw.matches(';').count(), instructions.len(), "{raw}");
+        assert!(!raw.contains("let"), "{raw}");
+        let optimized = decompile(&[], &instructions);
+        assert!(
+            optimized.matches(';').count() < raw.matches(';').count(),
+            "{optimized}"
+        );
+    }
+
+    #[test]
+    fn clean_removes_unused_pure_store() {
+        let instructions = vec![
+            InstructionInfo {
+                offset: 0,
+                instruction: Instruction::LoadParam {
+                    dst_reg: 1,
+                    param_index: 1,
+                },
+            },
+            InstructionInfo {
+                offset: 3,
+                instruction: Instruction::Add {
+                    dst_reg: 3,
+                    arg1_reg: 1,
+                    arg2_reg: 2,
+                },
+            },
+            InstructionInfo {
+                offset: 7,
+                instruction: Instruction::Ret { value_reg: 1 },
+            },
+        ];
+        let out = decompile(&[], &instructions);
+        assert!(out.contains("r3 = r1 + r2;"), "{out}");
+        let out = decompile_with(
+            &[],
+            &instructions,
+            &DecompileOptions {
+                clean: true,
+                ..Default::default()
+            },
+        );
+        assert!(!out.contains("r3"), "{out}");
+        assert!(out.contains("return r1;"), "{out}");
+    }
+
+    #[test]
+    fn clean_keeps_call_with_unused_result() {
+        let instructions = vec![
+            InstructionInfo {
+                offset: 0,
+                instruction: Instruction::Call1 {
+                    dst_reg: 3,
+                    closure_reg: 0,
+                    argument_reg: 1,
+                },
+            },
+            InstructionInfo {
+                offset: 4,
+                instruction: Instruction::Ret { value_reg: 1 },
+            },
+        ];
+        let out = decompile_with(
+            &[],
+            &instructions,
+            &DecompileOptions {
+                clean: true,
+                ..Default::default()
+            },
+        );
+        assert!(!out.contains("r3"), "{out}");
+        assert!(out.contains("r0.bind(r1)"), "{out}");
+    }
+
+    #[test]
+    fn rename_registers_consistently() {
+        let instructions = vec![
+            InstructionInfo {
+                offset: 0,
+                instruction: Instruction::LoadConstZero { dst_reg: 3 },
+            },
+            InstructionInfo {
+                offset: 2,
+                instruction: Instruction::Add {
+                    dst_reg: 1,
+                    arg1_reg: 3,
+                    arg2_reg: 3,
+                },
+            },
+            InstructionInfo {
+                offset: 6,
+                instruction: Instruction::Add {
+                    dst_reg: 3,
+                    arg1_reg: 1,
+                    arg2_reg: 0,
+                },
+            },
+            InstructionInfo {
+                offset: 10,
+                instruction: Instruction::Ret { value_reg: 3 },
+            },
+        ];
+        let out = decompile_with(
+            &[],
+            &instructions,
+            &DecompileOptions {
+                rename: true,
+                ..Default::default()
+            },
+        );
+        assert!(out.contains("v0 = 0;"), "{out}");
+        assert!(out.contains("v1 = v0 + v0;"), "{out}");
+        assert!(out.contains("v0 = v1 + v2;"), "{out}");
+        assert!(out.contains("return v0;"), "{out}");
+        assert!(!(0..4).any(|reg| out.contains(&format!("r{reg}"))), "{out}");
+    }
+
+    #[test]
+    fn rename_registers_from_loaded_names() {
+        let instructions = vec![
+            InstructionInfo {
+                offset: 0,
+                instruction: Instruction::LoadParam {
+                    dst_reg: 0,
+                    param_index: 1,
+                },
+            },
+            InstructionInfo {
+                offset: 3,
+                instruction: Instruction::GetById {
+                    dst_reg: 1,
+                    obj_reg: 0,
+                    cache_index: 0,
+                    string_table_index: 0,
+                },
+            },
+            InstructionInfo {
+                offset: 9,
+                instruction: Instruction::GetById {
+                    dst_reg: 2,
+                    obj_reg: 0,
+                    cache_index: 0,
+                    string_table_index: 1,
+                },
+            },
+            InstructionInfo {
+                offset: 15,
+                instruction: Instruction::LoadThisNS {
+                    dst_this_obj_reg: 3,
+                },
+            },
+            InstructionInfo {
+                offset: 17,
+                instruction: Instruction::Add {
+                    dst_reg: 1,
+                    arg1_reg: 1,
+                    arg2_reg: 2,
+                },
+            },
+            InstructionInfo {
+                offset: 21,
+                instruction: Instruction::Ret { value_reg: 1 },
+            },
+        ];
+        let out = decompile_with(
+            &["count", "arguments"],
+            &instructions,
+            &DecompileOptions {
+                rename: true,
+                ..Default::default()
+            },
+        );
+        assert!(out.contains("param1 = arguments[1];"), "{out}");
+        assert!(out.contains("count = param1.count;"), "{out}");
+        assert!(out.contains("v0 = param1.arguments;"), "{out}");
+        assert!(out.contains("v1 = this;"), "{out}");
+        assert!(out.contains("count = count + v0;"), "{out}");
+    }
+
+    #[test]
+    fn registers_declared_past_unwalkable_statements() {
+        use swc_ecma_ast::{
+            AssignExpr, AssignOp, BindingIdent, BlockStmt, ForInStmt, PatOrExpr, VarDecl,
+            VarDeclKind, VarDeclOrPat, VarDeclarator,
+        };
+        let reg = |name: &str| Box::new(Expr::Ident(Ident::new(name.into(), DUMMY_SP)));
+        //for (const key in r0) {} r2 = r1;
+        let mut stmts = vec![
+            Stmt::ForIn(ForInStmt {
+                span: DUMMY_SP,
+                left: VarDeclOrPat::VarDecl(Box::new(VarDecl {
+                    span: DUMMY_SP,
+                    kind: VarDeclKind::Const,
+                    declare: false,
+                    decls: vec![VarDeclarator {
+                        span: DUMMY_SP,
+                        name: Pat::Ident(BindingIdent {
+                            id: Ident::new("key".into(), DUMMY_SP),
+                            type_ann: None,
+                        }),
+                        init: None,
+                        definite: false,
+                    }],
+                })),
+                right: reg("r0"),
+                body: Box::new(Stmt::Block(BlockStmt {
+                    span: DUMMY_SP,
+                    stmts: Vec::new(),
+                })),
+            }),
+            Stmt::Expr(ExprStmt {
+                span: DUMMY_SP,
+                expr: Box::new(Expr::Assign(AssignExpr {
+                    span: DUMMY_SP,
+                    op: AssignOp::Assign,
+                    left: PatOrExpr::Expr(reg("r2")),
+                    right: reg("r1"),
+                })),
+            }),
+        ];
+        crate::passes::declare_registers(&mut stmts, 3);
+        let Stmt::Decl(Decl::Var(var)) = &stmts[0] else {
+            panic!("{:?}", stmts[0]);
+        };
+        let declared: Vec<_> = var
+            .decls
+            .iter()
+            .map(|decl| match &decl.name {
+                Pat::Ident(binding) => binding.id.sym.to_string(),
+                _ => unreachable!(),
+            })
+            .collect();
+        assert_eq!(declared, ["r0", "r1", "r2"]);
+    }
+
+    #[test]
+    fn rename_ignores_arguments_read_by_register() {
+        let instructions = vec![
+            InstructionInfo {
+                offset: 0,
+                instruction: Instruction::LoadConstZero { dst_reg: 0 },
+            },
+            InstructionInfo {
+                offset: 2,
+                instruction: Instruction::GetArgumentsPropByVal {
+                    dst_reg: 1,
+                    index_reg: 0,
+                    lazy_loaded_reg: 2,
+                },
+            },
+            InstructionInfo {
+                offset: 6,
+                instruction: Instruction::Ret { value_reg: 1 },
+            },
+        ];
+        let out = decompile_with(
+            &[],
+            &instructions,
+            &DecompileOptions {
+                rename: true,
+                ..Default::default()
+            },
+        );
+        assert!(!out.contains("paramr"), "{out}");
+        assert!(!out.contains("paramv"), "{out}");
+    }
+
+    #[test]
+    fn registers_declared_with_let() {
+        let out = decompile(&[], &if_instructions());
+        let body = out.split_once('{').unwrap().1.trim_start();
+        //r1 is assigned inside the if but read after it
+        assert!(body.starts_with("let r1;"), "{out}");
+        assert!(out.contains("const r0 = "), "{out}");
+    }
+
+    #[test]
+    fn single_assignment_declared_const() {
+        let instructions = [
+            InstructionInfo {
+                offset: 0,
+                instruction: Instruction::LoadConstZero { dst_reg: 0 },
+            },
+            InstructionInfo {
+                offset: 2,
+                instruction: Instruction::Ret { value_reg: 0 },
+            },
+        ];
+        let out = decompile(&[], &instructions);
+        assert!(out.contains("const r0 = 0;\n    return r0;"), "{out}");
+        assert!(!out.contains("let"), "{out}");
+    }
+
+    #[test]
+    fn reassigned_register_declared_let() {
+        let instructions = [
+            InstructionInfo {
+                offset: 0,
+                instruction: Instruction::LoadConstZero { dst_reg: 0 },
+            },
+            InstructionInfo {
+                offset: 2,
+                instruction: Instruction::LoadConstUInt8 {
+                    dst_reg: 0,
+                    value: 1,
+                },
+            },
+            InstructionInfo {
+                offset: 5,
+                instruction: Instruction::Ret { value_reg: 0 },
+            },
+        ];
+        let out = decompile(&[], &instructions);
+        let body = out.split_once('{').unwrap().1.trim_start();
+        assert!(body.starts_with("let r0;"), "{out}");
+        assert!(!out.contains("const"), "{out}");
+    }
+
+    #[test]
+    fn registers_declared_up_to_frame_size() {
+        let out = decompile_in_frame(&[], &if_instructions(), 1, &DecompileOptions::default());
+        let body = out.split_once('{').unwrap().1.trim_start();
+        assert!(body.starts_with("const r0 = "), "{out}");
+        assert!(!out.contains("let") && !out.contains("const r1"), "{out}");
+    }
+
+    #[test]
+    fn decompiling_again_gives_identical_output() {
+        let options = DecompileOptions {
+            clean: true,
+            rename: true,
+            ..Default::default()
+        };
+        for instructions in [
+            if_instructions(),
+            for_in_instructions(),
+            for_of_instructions(),
+        ] {
+            let first = decompile_with(&[], &instructions, &options);
+            for _ in 0..50 {
+                assert_eq!(decompile_with(&[], &instructions, &options), first);
+            }
+        }
+    }
+
+    #[test]
+    fn emit_ast_json() {
+        let options = DecompileOptions {
+            emit: Emit::AstJson,
+            ..Default::default()
+        };
+        let out = decompile_with(&[], &if_instructions(), &options);
+        assert!(out.contains(r#""type":"FunctionDeclaration""#), "{out}");
+    }
+
+    #[test]
+    fn declare_any_types_registers() {
+        let options = DecompileOptions {
+            declare_any: true,
+            ..Default::default()
+        };
+        let out = decompile_in_frame(&[], &if_instructions(), 1, &options);
+        let body = out.split_once('{').unwrap().1.trim_start();
+        assert!(body.starts_with("const r0: any = "), "{out}");
+
+        let out = decompile_in_frame(&[], &if_instructions(), 1, &DecompileOptions::default());
+        assert!(!out.contains(": any"), "{out}");
+    }
+
+    #[test]
+    fn templates_fold_string_concatenation() {
+        let load = |offset, dst_reg, string_table_index| InstructionInfo {
+            offset,
+            instruction: Instruction::LoadConstString {
+                dst_reg,
+                string_table_index,
+            },
+        };
+        let add = |offset, dst_reg, arg1_reg, arg2_reg| InstructionInfo {
+            offset,
+            instruction: Instruction::Add {
+                dst_reg,
+                arg1_reg,
+                arg2_reg,
+            },
+        };
+        //"a" + r0 + "b"
+        let instructions = vec![
+            load(0, 1, 0),
+            add(4, 2, 1, 0),
+            load(8, 3, 1),
+            add(12, 4, 2, 3),
+            InstructionInfo {
+                offset: 16,
+                instruction: Instruction::Ret { value_reg: 4 },
+            },
+        ];
+        let options = DecompileOptions {
+            templates: true,
+            ..Default::default()
+        };
+        let out = decompile_with(&["a", "b"], &instructions, &options);
+        assert!(out.contains("r4 = `a${r0}b`;"), "{out}");
+        assert!(!out.contains("r1"), "{out}");
+
+        let out = decompile(&["a", "b"], &instructions);
+        assert!(out.contains("r4 = r2 + r3;"), "{out}");
+
+        //r0 + r1 + "px" adds the numbers before concatenating
+        let instructions = vec![
+            add(0, 2, 0, 1),
+            load(4, 3, 0),
+            add(8, 4, 2, 3),
+            InstructionInfo {
+                offset: 12,
+                instruction: Instruction::Ret { value_reg: 4 },
+            },
+        ];
+        let out = decompile_with(&["px"], &instructions, &options);
+        assert!(out.contains("r4 = `${r2}px`;"), "{out}");
+    }
+
+    #[test]
+    fn conditions_parenthesized_by_precedence() {
+        let info = |offset, instruction| InstructionInfo {
+            offset,
+            instruction,
+        };
+        let zero = |offset, dst_reg| info(offset, Instruction::LoadConstZero { dst_reg });
+        let not_less = |offset, relative_offset| {
+            info(
+                offset,
+                Instruction::JNotLess {
+                    relative_offset,
+                    arg1_value_reg: 0,
+                    arg2_value_reg: 2,
+                },
+            )
+        };
+        let ret = |offset| info(offset, Instruction::Ret { value_reg: 1 });
+
+        //if (!(r0 < r2)) skips the body, which runs if (r0 < r2)
+        let out = decompile(&[], &[not_less(0, 6), zero(4, 1), ret(6)]);
+        assert!(out.contains("if (r0 < r2) {"), "{out}");
+
+        //if (!(r0 === undefined))
+        let jump = Instruction::JmpUndefined {
+            relative_offset: 5,
+            check_value_reg: 0,
+        };
+        let out = decompile(&[], &[info(0, jump), zero(3, 1), ret(5)]);
+        assert!(out.contains("if (!(r0 === undefined)) {"), "{out}");
+
+        //do { ... } while (!(r0 < r2))
+        let out = decompile(&[], &[zero(0, 1), zero(2, 3), not_less(4, -2), ret(8)]);
+        assert!(out.contains("}while (!(r0 < r2))"), "{out}");
+
+        //while (r0 < r2), the jump leaves the loop when !(r0 < r2)
+        let jmp = |offset, relative_offset| info(offset, Instruction::Jmp { relative_offset });
+        let out = decompile(
+            &[],
+            &[zero(0, 1), not_less(2, 8), zero(6, 3), jmp(8, -6), ret(10)],
+        );
+        assert!(out.contains("while(r0 < r2){"), "{out}");
+
+        //while (r0), not !(!r0)
+        let jump = Instruction::JmpFalse {
+            relative_offset: 7,
+            check_value_reg: 0,
+        };
+        let out = decompile(
+            &[],
+            &[zero(0, 1), info(2, jump), zero(5, 3), jmp(7, -5), ret(9)],
+        );
+        assert!(out.contains("while(r0){"), "{out}");
+    }
+
+    #[test]
+    fn swap_via_moves_runs_once_per_pass() {
+        let info = |offset, instruction| InstructionInfo {
+            offset,
+            instruction,
+        };
+        let mov = |offset, dst_reg, src_reg| info(offset, Instruction::Mov { dst_reg, src_reg });
+        let swap = |offset| {
+            [
+                mov(offset, 3, 0),
+                mov(offset + 3, 0, 1),
+                mov(offset + 6, 1, 3),
+            ]
+        };
+        let jump_true = |offset, relative_offset| {
+            info(
+                offset,
+                Instruction::JmpTrue {
+                    relative_offset,
+                    check_value_reg: 4,
+                },
+            )
+        };
+        let jmp = |offset, relative_offset| info(offset, Instruction::Jmp { relative_offset });
+        let add = |offset| {
+            info(
+                offset,
+                Instruction::Add {
+                    dst_reg: 5,
+                    arg1_reg: 0,
+                    arg2_reg: 1,
+                },
+            )
+        };
+        let ret = |offset| info(offset, Instruction::Ret { value_reg: 5 });
+        let count = |out: &str| out.matches("r3 = r0;").count();
+
+        //the moves of one branch stay in it, before the join
+        let jump = Instruction::JmpFalse {
+            relative_offset: 14,
+            check_value_reg: 4,
+        };
+        let mut instructions = vec![info(0, jump)];
+        instructions.extend(swap(3));
+        instructions.extend([jmp(12, 5), mov(14, 0, 2), add(17), ret(21)]);
+        let out = decompile(&[], &instructions);
+        assert_eq!(count(&out), 1, "{out}");
+        assert!(out.find("r3 = r0;") < out.find("r5 = r0 + r1;"), "{out}");
+
+        //a do..while swaps only inside the loop
+        let zero = info(0, Instruction::LoadConstZero { dst_reg: 2 });
+        let mut instructions = vec![zero.clone()];
+        instructions.extend(swap(2));
+        instructions.extend([jump_true(11, -9), add(14), ret(18)]);
+        let out = decompile(&[], &instructions);
+        assert_eq!(count(&out), 1, "{out}");
+        assert!(out.find("do {") < out.find("r3 = r0;"), "{out}");
+
+        //a while swaps before the loop and again before every continue
+        let mut instructions = vec![zero];
+        instructions.extend(swap(2));
+        instructions.extend([jump_true(11, 5), jmp(14, -12), add(16), ret(20)]);
+        let out = decompile(&[], &instructions);
+        assert_eq!(count(&out), 2, "{out}");
+    }
+
+    #[test]
+    fn heap_opcodes_index_typed_array_views() {
+        let instructions = [
+            InstructionInfo {
+                offset: 0,
+                instruction: Instruction::Loadi16 {
+                    dst_reg: 0,
+                    heap_reg: 1,
+                    heap_index_reg: 2,
+                },
+            },
+            InstructionInfo {
+                offset: 4,
+                instruction: Instruction::Loadu8 {
+                    dst_reg: 3,
+                    heap_reg: 1,
+                    heap_index_reg: 2,
+                },
+            },
+            InstructionInfo {
+                offset: 8,
+                instruction: Instruction::Store32 {
+                    heap_reg: 1,
+                    heap_index_reg: 2,
+                    value_reg: 0,
+                },
+            },
+            InstructionInfo {
+                offset: 12,
+                instruction: Instruction::Ret { value_reg: 3 },
+            },
+        ];
+        let out = decompile(&[], &instructions);
+        assert!(
+            out.contains("r0 = new Int16Array(r1.buffer)[r2 >> 1];"),
+            "{out}"
+        );
+        assert!(out.contains("r3 = new Uint8Array(r1.buffer)[r2];"), "{out}");
+        assert!(
+            out.contains("new Int32Array(r1.buffer)[r2 >> 2] = r0;"),
+            "{out}"
+        );
+        let f = bytecode_file_with_strings(&[]);
+        assert!(!instructions
+            .iter()
+            .any(|info| is_unsupported(&f, info.instruction.clone())));
+    }
+
+    #[test]
+    fn ssa_versions_every_definition() {
+        let info = |offset, instruction| InstructionInfo {
+            offset,
+            instruction,
+        };
+        let zero = |offset, dst_reg| info(offset, Instruction::LoadConstZero { dst_reg });
+        let add = |offset, dst_reg, arg1_reg| {
+            info(
+                offset,
+                Instruction::Add {
+                    dst_reg,
+                    arg1_reg,
+                    arg2_reg: 1,
+                },
+            )
+        };
+        let ret = |offset| info(offset, Instruction::Ret { value_reg: 0 });
+        let options = DecompileOptions {
+            ssa: true,
+            ..Default::default()
+        };
+
+        //r0 = 0; r0 = r0 + r1; r0 = r0 + r1
+        let out = decompile_with(
+            &[],
+            &[zero(0, 0), add(2, 0, 0), add(6, 0, 0), ret(10)],
+            &options,
+        );
+        assert!(out.contains("r0_1 = 0;\n    r0_2 = r0_1 + r1_0;\n    r0_3 = r0_2 + r1_0;"));
+        assert!(out.contains("return r0_3;"), "{out}");
+        assert!(!out.contains("let"), "{out}");
+
+        //if (r2) r0 = 0; r0 = r0 + r1
+        let jump = Instruction::JmpFalse {
+            relative_offset: 5,
+            check_value_reg: 2,
+        };
+        let out = decompile_with(
+            &[],
+            &[info(0, jump), zero(3, 0), add(5, 0, 0), ret(9)],
+            &options,
+        );
+        assert!(
+            out.contains("} // r0_2 = phi(r0_1, r0_0)\n    r0_3 = r0_2 + r1_0;"),
+            "{out}"
+        );
+
+        //r0 = 0; while (r2) r0 = r0 + r1
+        let jump = Instruction::JmpFalse {
+            relative_offset: 9,
+            check_value_reg: 2,
+        };
+        let jmp = Instruction::Jmp {
+            relative_offset: -7,
+        };
+        let out = decompile_with(
+            &[],
+            &[
+                zero(0, 0),
+                info(2, jump),
+                add(5, 0, 0),
+                info(9, jmp),
+                ret(11),
+            ],
+            &options,
+        );
+        assert!(
+            out.contains("// r0_2 = phi(r0_1, r0_3)\n    while(r2_0){"),
+            "{out}"
+        );
+        assert!(out.contains("r0_3 = r0_2 + r1_0;"), "{out}");
+        assert!(out.contains("return r0_2;"), "{out}");
+
+        //registers stay as they are by default
+        let out = decompile(&[], &[zero(0, 0), add(2, 0, 0), ret(6)]);
+        assert!(out.contains("r0 = r0 + r1;"), "{out}");
+    }
+
+    #[test]
+    fn doubles_written_like_js() {
+        for (value, raw) in [
+            (-0.0, "-0"),
+            (f64::INFINITY, "Infinity"),
+            (f64::NEG_INFINITY, "-Infinity"),
+            (1e21, "1e+21"),
+            (1.5e300, "1.5e+300"),
+            (123456789012345680000.0, "123456789012345680000"),
+            (9007199254740993.0, "9007199254740992"),
+            (-5.5, "-5.5"),
+            (1e-6, "0.000001"),
+            (1.25e-7, "1.25e-7"),
+        ] {
+            let instructions = vec![
+                InstructionInfo {
+                    offset: 0,
+                    instruction: Instruction::LoadConstDouble { dst_reg: 0, value },
+                },
+                InstructionInfo {
+                    offset: 10,
+                    instruction: Instruction::Ret { value_reg: 0 },
+                },
+            ];
+            let out = decompile(&[], &instructions);
+            assert!(out.contains(&format!("r0 = {raw};")), "{out}");
+        }
+    }
+
+    #[test]
+    fn nan_written_as_global() {
+        let instructions = vec![
+            InstructionInfo {
+                offset: 0,
+                instruction: Instruction::LoadConstDouble {
+                    dst_reg: 0,
+                    value: f64::NAN,
+                },
+            },
+            InstructionInfo {
+                offset: 10,
+                instruction: Instruction::Ret { value_reg: 0 },
+            },
+        ];
+        let out = decompile(&[], &instructions);
+        assert!(out.contains("r0 = NaN;"), "{out}");
+
+        //an identifier rather than a number literal holding NaN
+        let options = DecompileOptions {
+            emit: Emit::AstJson,
+            ..Default::default()
+        };
+        let out = decompile_with(&[], &instructions, &options);
+        assert!(out.contains(r#""type":"Identifier","#), "{out}");
+        assert!(out.contains(r#""value":"NaN""#), "{out}");
+        assert!(!out.contains("NumericLiteral"), "{out}");
+    }
+
+    #[test]
+    fn regexp_literal_with_flags() {
+        let regexp = |flags_string_index| {
+            vec![
+                InstructionInfo {
+                    offset: 0,
+                    instruction: Instruction::CreateRegExp {
+                        dst_reg: 0,
+                        pattern_string_index: 0,
+                        flags_string_index,
+                        regexp_table_index: 0,
+                    },
+                },
+                InstructionInfo {
+                    offset: 14,
+                    instruction: Instruction::Ret { value_reg: 0 },
+                },
+            ]
+        };
+        let strings = ["a.b", "su", "gg"];
+        let out = decompile(&strings, &regexp(1));
+        assert!(out.contains("r0 = /a.b/su;"), "{out}");
+
+        let options = DecompileOptions {
+            emit: Emit::AstJson,
+            ..Default::default()
+        };
+        let out = decompile_with(&strings, &regexp(1), &options);
+        assert!(out.contains(r#""type":"RegExpLiteral","#), "{out}");
+        assert!(out.contains(r#""flags":"su""#), "{out}");
+
+        //invalid flags are kept as they are
+        let out = decompile(&strings, &regexp(2));
+        assert!(out.contains("r0 = /a.b/gg;"), "{out}");
+    }
+
+    #[test]
+    fn bare_globals_replace_global_object_access() {
+        let global = |offset, dst_reg| InstructionInfo {
+            offset,
+            instruction: Instruction::GetGlobalObject { dst_reg },
+        };
+        let get = |offset, dst_reg, obj_reg, string_table_index| InstructionInfo {
+            offset,
+            instruction: Instruction::GetByIdShort {
+                dst_reg,
+                obj_reg,
+                cache_index: 1,
+                string_table_index,
+            },
+        };
+        let instructions = vec![
+            //r1 = globalThis.foo
+            global(0, 0),
+            get(2, 1, 0, 0),
+            //"foo-bar" can't be written as a bare identifier
+            global(7, 2),
+            get(9, 3, 2, 1),
+            //r4 holds the global object for more than one access
+            global(14, 4),
+            get(16, 5, 4, 2),
+            get(21, 6, 4, 0),
+            InstructionInfo {
+                offset: 26,
+                instruction: Instruction::Ret { value_reg: 1 },
+            },
+        ];
+        let options = DecompileOptions {
+            bare_globals: true,
+            ..Default::default()
+        };
+        let out = decompile_with(&["foo", "foo-bar", "bar"], &instructions, &options);
+        assert!(out.contains("r1 = foo;"), "{out}");
+        assert!(!out.contains("r0"), "{out}");
+        assert!(out.contains("r2 = globalThis;"), "{out}");
+        assert!(out.contains("r4 = globalThis;"), "{out}");
+        assert!(out.contains("r5 = r4.bar;"), "{out}");
+        assert!(out.contains("r6 = r4.foo;"), "{out}");
+
+        let out = decompile(&["foo", "foo-bar", "bar"], &instructions);
+        assert!(out.contains("r1 = r0.foo;"), "{out}");
+    }
+
+    #[test]
+    fn deeply_nested_ifs_dont_overflow() {
+        //if (r0) { r1 = 0; if (r0) { r1 = 0; ... } } return r1;
+        let depth = 1000;
+        let mut instructions = Vec::new();
+        for level in 0..depth {
+            instructions.push(InstructionInfo {
+                offset: level * 8,
+                instruction: Instruction::JmpFalseLong {
+                    relative_offset: ((depth - level) * 8) as i32,
+                    check_value_reg: 0,
+                },
+            });
+            instructions.push(InstructionInfo {
+                offset: level * 8 + 6,
+                instruction: Instruction::LoadConstZero { dst_reg: 1 },
+            });
+        }
+        instructions.push(InstructionInfo {
+            offset: depth * 8,
+            instruction: Instruction::Ret { value_reg: 1 },
+        });
+        let flow_graph = construct_flow_graph(&instructions).unwrap();
+        let cfg = construct_cfg(&flow_graph);
+        let f = bytecode_file_with_strings(&[]);
+        let body = FunctionBody {
+            f: &f,
+            cfg: &cfg,
+            instructions: &instructions,
+            frame_size: 256,
+            try_finally: &[],
+        };
+        let stmts: Vec<Stmt> =
+            AstGenerator::new(body, NodeIndex::new(0), false, None, None).collect();
+
+        let mut nesting = 0;
+        let mut stmt = &stmts[0];
+        while let Stmt::If(if_stmt) = stmt {
+            nesting += 1;
+            let Stmt::Block(block) = &*if_stmt.cons else {
+                panic!("{:?}", if_stmt.cons);
+            };
+            stmt = block.stmts.last().unwrap();
+        }
+        assert_eq!(nesting, depth);
+    }
+
+    #[test]
+    fn function_without_instructions() {
+        assert_eq!(decompile(&[], &[]), "function f0() {}\n");
+        let mut out = Vec::new();
+        write_graph_dot(&[], GraphKind::Cfg, &mut out).unwrap();
+    }
+
+    #[test]
+    fn function_only_returning() {
+        let instructions = [InstructionInfo {
+            offset: 0,
+            instruction: Instruction::Ret { value_reg: 0 },
+        }];
+        let out = decompile(&[], &instructions);
+        assert_eq!(out, "function f0() {\n    return;\n}\n");
+    }
+
+    #[test]
+    fn early_returns_share_tail() {
+        let instructions = [
+            (
+                0,
+                Instruction::LoadParam {
+                    dst_reg: 0,
+                    param_index: 1,
+                },
+            ),
+            (
+                3,
+                Instruction::JmpTrue {
+                    relative_offset: 12,
+                    check_value_reg: 0,
+                },
+            ),
+            (
+                6,
+                Instruction::JmpTrue {
+                    relative_offset: 25,
+                    check_value_reg: 1,
+                },
+            ),
+            (
+                9,
+                Instruction::Add {
+                    dst_reg: 5,
+                    arg1_reg: 1,
+                    arg2_reg: 1,
+                },
+            ),
+            (
+                13,
+                Instruction::Jmp {
+                    relative_offset: 12,
+                },
+            ),
+            (
+                15,
+                Instruction::JmpTrue {
+                    relative_offset: 18,
+                    check_value_reg: 2,
+                },
+            ),
+            (
+                18,
+                Instruction::JmpTrue {
+                    relative_offset: 17,
+                    check_value_reg: 3,
+                },
+            ),
+            (
+                21,
+                Instruction::Add {
+                    dst_reg: 5,
+                    arg1_reg: 2,
+                    arg2_reg: 3,
+                },
+            ),
+            (
+                25,
+                Instruction::Add {
+                    dst_reg: 6,
+                    arg1_reg: 5,
+                    arg2_reg: 5,
+                },
+            ),
+            (29, Instruction::Ret { value_reg: 6 }),
+            (31, Instruction::Ret { value_reg: 1 }),
+            (33, Instruction::Ret { value_reg: 2 }),
+            (35, Instruction::Ret { value_reg: 3 }),
+        ]
+        .map(|(offset, instruction)| InstructionInfo {
+            offset,
+            instruction,
+        });
+        let out = decompile(&[], &instructions);
+        assert_eq!(out.matches("r6 = r5 + r5;").count(), 1, "{out}");
+        for early_return in ["return r1;", "return r2;", "return r3;"] {
+            assert!(out.contains(early_return), "{out}");
+        }
+    }
+
+    #[test]
+    fn return_undefined_without_value() {
+        let instructions = vec![
+            InstructionInfo {
+                offset: 0,
+                instruction: Instruction::LoadConstUndefined { dst_reg: 0 },
+            },
+            InstructionInfo {
+                offset: 2,
+                instruction: Instruction::Ret { value_reg: 0 },
+            },
+        ];
+        let out = decompile(&[], &instructions);
+        assert!(out.contains("return;"), "{out}");
+        assert!(!out.contains("return r0;"), "{out}");
+    }
+
+    #[test]
+    fn return_register_value() {
+        let instructions = vec![
+            InstructionInfo {
+                offset: 0,
+                instruction: Instruction::LoadConstUndefined { dst_reg: 0 },
+            },
+            InstructionInfo {
+                offset: 2,
+                instruction: Instruction::LoadConstZero { dst_reg: 0 },
+            },
+            InstructionInfo {
+                offset: 4,
+                instruction: Instruction::Ret { value_reg: 0 },
+            },
+        ];
+        let out = decompile(&[], &instructions);
+        assert!(out.contains("return r0;"), "{out}");
+    }
+
+    fn constructor_call_instructions(select_this_reg: u8) -> Vec<InstructionInfo<Instruction>> {
+        vec![
+            InstructionInfo {
+                offset: 0,
+                instruction: Instruction::GetByIdShort {
+                    dst_reg: 2,
+                    obj_reg: 1,
+                    cache_index: 1,
+                    string_table_index: 0,
+                },
+            },
+            InstructionInfo {
+                offset: 5,
+                instruction: Instruction::CreateThis {
+                    dst_reg: 3,
+                    prototype_reg: 2,
+                    constructor_closure_reg: 1,
+                },
+            },
+            InstructionInfo {
+                offset: 9,
+                instruction: Instruction::Mov {
+                    dst_reg: 5,
+                    src_reg: 3,
+                },
+            },
+            InstructionInfo {
+                offset: 12,
+                instruction: Instruction::LoadConstUInt8 {
+                    dst_reg: 4,
+                    value: 7,
+                },
+            },
+            InstructionInfo {
+                offset: 15,
+                instruction: Instruction::Construct {
+                    dst_reg: 6,
+                    closure_reg: 1,
+                    arguments_len: 2,
+                },
+            },
+            InstructionInfo {
+                offset: 19,
+                instruction: Instruction::SelectObject {
+                    dst_reg: 7,
+                    this_obj_reg: select_this_reg,
+                    return_value_reg: 6,
+                },
+            },
+            InstructionInfo {
+                offset: 23,
+                instruction: Instruction::Ret { value_reg: 7 },
+            },
+        ]
+    }
+
+    #[test]
+    fn constructor_call_collapsed_to_new() {
+        let instructions = constructor_call_instructions(3);
+        for propagate_copies in [true, false] {
+            let out = decompile_in_frame(
+                &["prototype"],
+                &instructions,
+                10,
+                &DecompileOptions {
+                    propagate_copies,
+                    ..Default::default()
+                },
+            );
+            assert!(out.contains("r7 = new r1(r4);"), "{out}");
+            assert!(!out.contains("Object.create"), "{out}");
+            assert!(!out.contains("instanceof"), "{out}");
+        }
+    }
+
+    #[test]
+    fn constructor_call_kept_when_pattern_differs() {
+        //SelectObject falls back to a different object than the one CreateThis made
+        let out = decompile_in_frame(
+            &["prototype"],
+            &constructor_call_instructions(2),
+            10,
+            &DecompileOptions::default(),
+        );
+        assert!(out.contains("Object.create"), "{out}");
+        assert!(out.contains("instanceof"), "{out}");
+    }
+
+    #[test]
+    fn array_literal_from_own_index_stores() {
+        let mut instructions = vec![InstructionInfo {
+            offset: 0,
+            instruction: Instruction::NewArray {
+                dst_reg: 0,
+                size: 3,
+            },
+        }];
+        for index in 0..3 {
+            let offset = 4 + index as u32 * 7;
+            instructions.push(InstructionInfo {
+                offset,
+                instruction: Instruction::LoadConstUInt8 {
+                    dst_reg: 1,
+                    value: index + 1,
+                },
+            });
+            instructions.push(InstructionInfo {
+                offset: offset + 3,
+                instruction: Instruction::PutOwnByIndex {
+                    dst_obj_reg: 0,
+                    value_reg: 1,
+                    index,
+                },
+            });
+        }
+        instructions.push(InstructionInfo {
+            offset: 25,
+            instruction: Instruction::Ret { value_reg: 0 },
+        });
+        let out = decompile(&[], &instructions);
+        assert!(
+            out.replace(char::is_whitespace, "").contains("r0=[1,2,3];"),
+            "{out}"
+        );
+        assert!(!out.contains("r0["), "{out}");
+    }
+
+    #[test]
+    fn long_index_closure() {
+        let create = Instruction::CreateClosureLongIndex {
+            dst_reg: 0,
+            current_environment_reg: 1,
+            function_table_index: 70000,
+        };
+        assert_eq!(create.function_operands(), vec![70000]);
+        let instructions = [
+            InstructionInfo {
+                offset: 0,
+                instruction: create,
+            },
+            InstructionInfo {
+                offset: 7,
+                instruction: Instruction::Ret { value_reg: 0 },
+            },
+        ];
+        let out = decompile(&[], &instructions);
+        assert!(out.contains("r0 = f70000;"), "{out}");
+    }
+
+    fn put_own_by_val(enumerable: bool) -> String {
+        let instructions = [
+            InstructionInfo {
+                offset: 0,
+                instruction: Instruction::PutOwnByVal {
+                    dst_obj_reg: 0,
+                    value_reg: 1,
+                    property_name_reg: 2,
+                    enumerable,
+                },
+            },
+            InstructionInfo {
+                offset: 5,
+                instruction: Instruction::Ret { value_reg: 0 },
+            },
+        ];
+        decompile(&[], &instructions).replace(char::is_whitespace, "")
+    }
+
+    #[test]
+    fn enumerable_own_by_val_store() {
+        let out = put_own_by_val(true);
+        assert!(out.contains("r0[r2]=r1;"), "{out}");
+        assert!(!out.contains("defineProperty"), "{out}");
+    }
+
+    #[test]
+    fn non_enumerable_own_by_val_store() {
+        let out = put_own_by_val(false);
+        assert!(
+            out.contains(
+                "Object.defineProperty(r0,r2,{value:r1,writable:true,enumerable:false,\
+                 configurable:true});"
+            ),
+            "{out}"
+        );
+    }
+
+    #[test]
+    fn object_literal_from_own_id_stores() {
+        let mut instructions = vec![InstructionInfo {
+            offset: 0,
+            instruction: Instruction::NewObject { dst_reg: 0 },
+        }];
+        for key in 0..2 {
+            let offset = 2 + key as u32 * 7;
+            instructions.push(InstructionInfo {
+                offset,
+                instruction: Instruction::LoadConstUInt8 {
+                    dst_reg: 1,
+                    value: key + 1,
+                },
+            });
+            instructions.push(InstructionInfo {
+                offset: offset + 3,
+                instruction: Instruction::PutNewOwnByIdShort {
+                    dst_obj_reg: 0,
+                    value_reg: 1,
+                    string_table_index: key,
+                },
+            });
+        }
+        instructions.push(InstructionInfo {
+            offset: 16,
+            instruction: Instruction::Ret { value_reg: 0 },
+        });
+        let out = decompile(&["a", "b"], &instructions);
+        assert!(
+            out.replace(char::is_whitespace, "")
+                .contains("r0={a:1,b:2};"),
+            "{out}"
+        );
+        assert!(!out.contains("r0.a"), "{out}");
+    }
+
+    #[test]
+    fn profile_point_kept_as_comment() {
+        let instructions = [
+            InstructionInfo {
+                offset: 0,
+                instruction: Instruction::ProfilePoint {
+                    function_local_profile_point_index: 3,
+                },
+            },
+            InstructionInfo {
+                offset: 3,
+                instruction: Instruction::Ret { value_reg: 0 },
+            },
+        ];
+        let out = decompile(&[], &instructions);
+        assert!(!out.contains("profile point"), "{out}");
+        let out = decompile_with(
+            &[],
+            &instructions,
+            &DecompileOptions {
+                keep_profile_points: true,
+                ..Default::default()
+            },
+        );
+        assert!(out.contains("/* profile point 3 */"), "{out}");
+    }
+
+    #[test]
+    fn call_arguments_read_from_frame() {
+        let instructions = [
+            InstructionInfo {
+                offset: 0,
+                instruction: Instruction::LoadParam {
+                    dst_reg: 1,
+                    param_index: 1,
+                },
+            },
+            InstructionInfo {
+                offset: 3,
+                instruction: Instruction::LoadConstUndefined { dst_reg: 3 },
+            },
+            InstructionInfo {
+                offset: 5,
+                instruction: Instruction::ProfilePoint {
+                    function_local_profile_point_index: 0,
+                },
+            },
+            InstructionInfo {
+                offset: 8,
+                instruction: Instruction::LoadConstUInt8 {
+                    dst_reg: 2,
+                    value: 5,
+                },
+            },
+            //unrelated to the call, but the last register write before it
+            InstructionInfo {
+                offset: 11,
+                instruction: Instruction::LoadConstUInt8 {
+                    dst_reg: 0,
+                    value: 9,
+                },
+            },
+            InstructionInfo {
+                offset: 14,
+                instruction: Instruction::Call {
+                    dst_reg: 0,
+                    closure_reg: 1,
+                    arguments_len: 2,
+                },
+            },
+            InstructionInfo {
+                offset: 18,
+                instruction: Instruction::Ret { value_reg: 0 },
+            },
+        ];
+        let out = decompile_in_frame(
+            &[],
+            &instructions,
+            8,
+            &DecompileOptions {
+                keep_profile_points: true,
+                ..Default::default()
+            },
+        );
+        assert!(out.contains("/* profile point 0 */"), "{out}");
+        assert!(out.contains("r0 = r1.bind(r3)(r2);"), "{out}");
+    }
+
+    #[test]
+    fn environment_slots_named_consistently() {
+        //the outer function stores a captured variable, the closure reads it back
+        let outer = [
+            InstructionInfo {
+                offset: 0,
+                instruction: Instruction::CreateEnvironment { dst_reg: 0 },
+            },
+            InstructionInfo {
+                offset: 2,
+                instruction: Instruction::LoadConstUInt8 {
+                    dst_reg: 1,
+                    value: 5,
+                },
+            },
+            InstructionInfo {
+                offset: 5,
+                instruction: Instruction::StoreToEnvironment {
+                    env_reg: 0,
+                    env_slot_index: 0,
+                    value_reg: 1,
+                },
+            },
+            InstructionInfo {
+                offset: 9,
+                instruction: Instruction::LoadFromEnvironment {
+                    dst_reg: 2,
+                    env_reg: 0,
+                    env_slot_index: 0,
+                },
+            },
+            InstructionInfo {
+                offset: 13,
+                instruction: Instruction::Ret { value_reg: 2 },
+            },
+        ];
+        let out = decompile(&[], &outer);
+        assert!(out.contains("env0_slot0 = r1;"), "{out}");
+        assert!(out.contains("r2 = env0_slot0;"), "{out}");
+        assert!(!out.contains(".store("), "{out}");
+
+        let closure = [
+            InstructionInfo {
+                offset: 0,
+                instruction: Instruction::GetEnvironment {
+                    dst_reg: 0,
+                    num_environments: 0,
+                },
+            },
+            InstructionInfo {
+                offset: 3,
+                instruction: Instruction::LoadFromEnvironment {
+                    dst_reg: 1,
+                    env_reg: 0,
+                    env_slot_index: 0,
+                },
+            },
+            InstructionInfo {
+                offset: 7,
+                instruction: Instruction::Ret { value_reg: 1 },
+            },
+        ];
+        let out = decompile(&[], &closure);
+        assert!(out.contains("r0 = get_environment(0);"), "{out}");
+        assert!(out.contains("r1 = env0_slot0;"), "{out}");
+    }
+
+    #[test]
+    fn annotate_with_offsets() {
+        let instructions = [
+            InstructionInfo {
+                offset: 0,
+                instruction: Instruction::LoadConstZero { dst_reg: 1 },
+            },
+            InstructionInfo {
+                offset: 2,
+                instruction: Instruction::LoadConstUInt8 {
+                    dst_reg: 2,
+                    value: 1,
+                },
+            },
+            InstructionInfo {
+                offset: 5,
+                instruction: Instruction::Add {
+                    dst_reg: 0,
+                    arg1_reg: 1,
+                    arg2_reg: 2,
+                },
+            },
+            InstructionInfo {
+                offset: 0x1A,
+                instruction: Instruction::Ret { value_reg: 0 },
+            },
+        ];
+        let out = decompile(&[], &instructions);
+        assert!(!out.contains("@0x"), "{out}");
+        let out = decompile_with(
+            &[],
+            &instructions,
+            &DecompileOptions {
+                annotate: true,
+                ..Default::default()
+            },
+        );
+        assert!(out.contains("const r0 = r1 + r2; // @0x5 Add"), "{out}");
+        assert!(out.contains("return r0; // @0x1A Ret"), "{out}");
+    }
+
+    #[test]
+    fn cache_index_comment_only_when_shown() {
+        let instructions = [
+            InstructionInfo {
+                offset: 0,
+                instruction: Instruction::GetByIdShort {
+                    dst_reg: 0,
+                    obj_reg: 1,
+                    cache_index: 3,
+                    string_table_index: 0,
+                },
+            },
+            InstructionInfo {
+                offset: 4,
+                instruction: Instruction::Ret { value_reg: 0 },
+            },
+        ];
+        let out = decompile(&["foo"], &instructions);
+        assert!(!out.contains("cache index"), "{out}");
+        let out = decompile_with(
+            &["foo"],
+            &instructions,
+            &DecompileOptions {
+                show_cache_index: true,
+                ..Default::default()
+            },
+        );
+        assert!(
+            out.contains("const r0 = r1.foo; /* cache index 3 */"),
+            "{out}"
+        );
+        assert_eq!(out.matches("cache index").count(), 1, "{out}");
+    }
+
+    #[test]
+    fn this_read_as_param_zero() {
+        let load_param = |param_index| {
+            [
+                InstructionInfo {
+                    offset: 0,
+                    instruction: Instruction::LoadParam {
+                        dst_reg: 0,
+                        param_index,
+                    },
+                },
+                InstructionInfo {
+                    offset: 3,
+                    instruction: Instruction::Ret { value_reg: 0 },
+                },
+            ]
+        };
+        assert!(uses_this(&load_param(0)));
+        assert!(!uses_this(&load_param(1)));
+        assert!(!uses_arguments(&load_param(1)));
+        assert!(!uses_this(&if_instructions()));
+        assert!(!uses_arguments(&if_instructions()));
+    }
+
+    fn for_in_instructions() -> Vec<InstructionInfo<Instruction>> {
+        vec![
+            InstructionInfo {
+                offset: 0,
+                instruction: Instruction::GetPNameList {
+                    dst_reg: 1,
+                    obj_reg: 0,
+                    iterating_index_reg: 2,
+                    property_list_size_reg: 3,
+                },
+            },
+            InstructionInfo {
+                offset: 5,
+                instruction: Instruction::JmpUndefined {
+                    relative_offset: 21,
+                    check_value_reg: 1,
+                },
+            },
+            InstructionInfo {
+                offset: 8,
+                instruction: Instruction::GetNextPName {
+                    dst_reg: 4,
+                    properties_array_reg: 1,
+                    obj_reg: 0,
+                    iterating_index_reg: 2,
+                    property_list_size_reg: 3,
+                },
+            },
+            InstructionInfo {
+                offset: 14,
+                instruction: Instruction::JmpUndefined {
+                    relative_offset: 12,
+                    check_value_reg: 4,
+                },
+            },
+            InstructionInfo {
+                offset: 17,
+                instruction: Instruction::Mov {
+                    dst_reg: 5,
+                    src_reg: 4,
+                },
+            },
+            InstructionInfo {
+                offset: 20,
+                instruction: Instruction::GetByVal {
+                    dst_reg: 6,
+                    obj_reg: 0,
+                    index_reg: 5,
+                },
+            },
+            InstructionInfo {
+                offset: 24,
+                instruction: Instruction::Jmp {
+                    relative_offset: -16,
+                },
+            },
+            InstructionInfo {
+                offset: 26,
+                instruction: Instruction::LoadConstUndefined { dst_reg: 7 },
+            },
+            InstructionInfo {
+                offset: 28,
+                instruction: Instruction::Ret { value_reg: 7 },
+            },
+        ]
+    }
+
+    #[test]
+    fn for_in_loop() {
+        let out = decompile(&[], &for_in_instructions());
+        assert!(
+            out.contains("for(r4 in r0){\n        const r6 = r0[r4];\n    }"),
+            "{out}"
+        );
+        assert!(!out.contains("Object.keys"), "{out}");
+        assert!(!out.contains("if"), "{out}");
+        assert!(out.contains("let r0, r4;"), "{out}");
+
+        let out = decompile_with(
+            &[],
+            &for_in_instructions(),
+            &DecompileOptions {
+                reconstruct_for_in: false,
+                ..Default::default()
+            },
+        );
+        assert!(out.contains("Object.keys(r0)"), "{out}");
+    }
+
+    fn for_of_instructions() -> Vec<InstructionInfo<Instruction>> {
+        vec![
+            InstructionInfo {
+                offset: 0,
+                instruction: Instruction::IteratorBegin {
+                    dst_reg: 1,
+                    source_reg: 0,
+                },
+            },
+            InstructionInfo {
+                offset: 3,
+                instruction: Instruction::LoadConstUndefined { dst_reg: 3 },
+            },
+            InstructionInfo {
+                offset: 5,
+                instruction: Instruction::IteratorNext {
+                    dst_reg: 2,
+                    iterator_or_index_reg: 1,
+                    source_reg: 0,
+                },
+            },
+            InstructionInfo {
+                offset: 9,
+                instruction: Instruction::JStrictEqual {
+                    relative_offset: 13,
+                    arg1_value_reg: 1,
+                    arg2_value_reg: 3,
+                },
+            },
+            InstructionInfo {
+                offset: 13,
+                instruction: Instruction::Mov {
+                    dst_reg: 4,
+                    src_reg: 2,
+                },
+            },
+            InstructionInfo {
+                offset: 16,
+                instruction: Instruction::Add {
+                    dst_reg: 5,
+                    arg1_reg: 4,
+                    arg2_reg: 4,
+                },
+            },
+            InstructionInfo {
+                offset: 20,
+                instruction: Instruction::Jmp {
+                    relative_offset: -15,
+                },
+            },
+            InstructionInfo {
+                offset: 22,
+                instruction: Instruction::Ret { value_reg: 3 },
+            },
+        ]
+    }
+
+    #[test]
+    fn for_of_loop() {
+        let out = decompile(&[], &for_of_instructions());
+        assert!(
+            out.contains("for (r2 of r0){\n        const r5 = r2 + r2;\n    }"),
+            "{out}"
+        );
+        assert!(!out.contains("Symbol.iterator"), "{out}");
+        assert!(out.contains("let r0, r2;"), "{out}");
+
+        let out = decompile_with(
+            &[],
+            &for_of_instructions(),
+            &DecompileOptions {
+                reconstruct_for_of: false,
+                ..Default::default()
+            },
+        );
+        assert!(out.contains("r1 = r0[Symbol.iterator]();"), "{out}");
+        assert!(out.contains("r2 = r1.next();"), "{out}");
+        assert!(out.contains("r1 = r2.done ? undefined : r1;"), "{out}");
+        assert!(out.contains("r2 = r2.value;"), "{out}");
+    }
+
+    #[test]
+    fn spread_call() {
+        //r0(...r1): arraySpread copies r1 into a new array that apply passes to r0
+        let instructions = vec![
+            InstructionInfo {
+                offset: 0,
+                instruction: Instruction::NewArray {
+                    dst_reg: 2,
+                    size: 0,
+                },
+            },
+            InstructionInfo {
+                offset: 4,
+                instruction: Instruction::LoadConstUndefined { dst_reg: 7 },
+            },
+            InstructionInfo {
+                offset: 6,
+                instruction: Instruction::Mov {
+                    dst_reg: 6,
+                    src_reg: 2,
+                },
+            },
+            InstructionInfo {
+                offset: 9,
+                instruction: Instruction::Mov {
+                    dst_reg: 5,
+                    src_reg: 1,
+                },
+            },
+            InstructionInfo {
+                offset: 12,
+                instruction: Instruction::LoadConstZero { dst_reg: 4 },
+            },
+            InstructionInfo {
+                offset: 14,
+                instruction: Instruction::CallBuiltin {
+                    dst_reg: 3,
+                    builtin_number: 46,
+                    arguments_len: 4,
+                },
+            },
+            InstructionInfo {
+                offset: 18,
+                instruction: Instruction::LoadConstUndefined { dst_reg: 7 },
+            },
+            InstructionInfo {
+                offset: 20,
+                instruction: Instruction::Mov {
+                    dst_reg: 6,
+                    src_reg: 0,
+                },
+            },
+            InstructionInfo {
+                offset: 23,
+                instruction: Instruction::Mov {
+                    dst_reg: 5,
+                    src_reg: 2,
+                },
+            },
+            InstructionInfo {
+                offset: 26,
+                instruction: Instruction::LoadConstUndefined { dst_reg: 4 },
+            },
+            InstructionInfo {
+                offset: 28,
+                instruction: Instruction::CallBuiltin {
+                    dst_reg: 3,
+                    builtin_number: 47,
+                    arguments_len: 4,
+                },
+            },
+            InstructionInfo {
+                offset: 32,
+                instruction: Instruction::Ret { value_reg: 3 },
+            },
+        ];
+        let out = decompile_in_frame(&[], &instructions, 12, &DecompileOptions::default());
+        assert!(out.contains("r2 = [];\n"), "{out}");
+        assert!(out.contains("r3 = r2.push(...r1);"), "{out}");
+        assert!(out.contains("r3 = r0.bind(r4)(...r2);"), "{out}");
+        assert!(!out.contains("apply"), "{out}");
+    }
+
+    #[test]
+    fn call_builtin() {
+        let instructions = vec![
+            InstructionInfo {
+                offset: 0,
+                instruction: Instruction::CallBuiltin {
+                    dst_reg: 0,
+                    builtin_number: 5,
+                    arguments_len: 2,
+                },
+            },
+            InstructionInfo {
+                offset: 4,
+                instruction: Instruction::Ret { value_reg: 0 },
+            },
+        ];
+        let out = decompile_in_frame(&[], &instructions, 8, &DecompileOptions::default());
+        assert!(out.contains("r0 = Math.abs(r2);"), "{out}");
+    }
+}
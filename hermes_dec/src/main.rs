@@ -1,279 +1,814 @@
-#![feature(cursor_remaining)]
-
 use clap::Parser;
 use clap::Subcommand;
-use generate_ast::AstGenerator;
-use petgraph::stable_graph::NodeIndex;
-use std::fs::File;
-use std::io::Read;
-use std::io::stdout;
+use hermes_dec::call_graph_dot;
+use hermes_dec::decompile_function_with;
+use hermes_dec::decompile_range_with_progress;
+use hermes_dec::exception_regions;
+use hermes_dec::function_graph_dot;
+use hermes_dec::instructions_json;
+use hermes_dec::instructions_listing;
+use hermes_dec::load;
+use hermes_dec::module_function_id;
+use hermes_dec::opcode_histogram;
+use hermes_dec::parse;
+use hermes_dec::string_references;
+use hermes_dec::Bundle;
+use hermes_dec::BytecodeFile;
+use hermes_dec::DecompileError;
+use hermes_dec::DecompileOptions;
+use hermes_dec::DecompileResult;
+use hermes_dec::Emit;
+use hermes_dec::GraphKind;
+use hermes_dec::HandlerKind;
+use hermes_dec::SUPPORTED_VERSIONS;
+use std::collections::HashSet;
 use std::io::BufWriter;
 use std::io::Cursor;
+use std::io::Read;
 use std::io::Write;
+use std::ops::Bound;
+use std::path::Path;
 use std::path::PathBuf;
-use swc_common::sync::Lrc;
-use swc_common::FilePathMapping;
-use swc_common::SourceMap;
-use swc_ecma_ast::{Decl, FnDecl, Ident, Program, Script, Stmt};
-use swc_ecma_codegen::text_writer::JsWriter;
-
-use hermes_file_reader::BytecodeFile;
-use swc_common::DUMMY_SP;
-use swc_ecma_ast::BlockStmt;
-use swc_ecma_ast::EsVersion;
-use swc_ecma_ast::Function;
-use swc_ecma_codegen::Emitter;
-
-use crate::bytecode::v93::Instruction;
-use crate::graphs::construct_cfg;
-use crate::graphs::construct_flow_graph;
-
-mod bytecode;
-mod generate_ast;
-mod graphs;
-mod hermes_file_reader;
 
 fn main() {
     let args = Args::parse();
     let bundle_path = args.bundle_path;
-    if !bundle_path.is_file() {
+    let from_stdin = bundle_path.as_os_str() == "-";
+    if !from_stdin && !bundle_path.is_file() {
         use clap::CommandFactory;
         Args::command().print_help().unwrap();
         return;
     }
-    let mut bundle_file = match File::open(bundle_path.clone()) {
+    let loaded = if from_stdin {
+        let mut buf = Vec::new();
+        std::io::stdin()
+            .read_to_end(&mut buf)
+            .map(|_| Bundle::Read(buf))
+    } else {
+        load(&bundle_path)
+    };
+    let buf = match loaded {
+        Ok(buf) => buf,
+        Err(e) => {
+            println!("Error while reading {}: {}", bundle_path.display(), e);
+            return;
+        }
+    };
+    let f = match parse(&buf) {
         Ok(f) => f,
         Err(e) => {
-            println!("Error while opening {}: {}", bundle_path.display(), e);
+            println!("Error while parsing {}: {}", bundle_path.display(), e);
             return;
         }
     };
+    if args.validate {
+        if let Err(e) = f.validate_string_table() {
+            println!("Invalid string table in {}: {}", bundle_path.display(), e);
+            return;
+        }
+    }
+    //catch typos before they turn into errors deep inside decompilation
+    if let Commands::Disassemble { function_id, .. } | Commands::Cfg { function_id, .. } =
+        args.command
+    {
+        if function_id >= f.function_count() {
+            println!(
+                "function id {function_id} out of range (0..{})",
+                f.function_count()
+            );
+            return;
+        }
+    }
     match args.command {
+        Commands::Header => {
+            let source_hash: String = f
+                .source_hash()
+                .iter()
+                .map(|byte| format!("{byte:02x}"))
+                .collect();
+            println!("Version: {}", f.header.version);
+            println!("Source hash: {source_hash}");
+            println!("File length: {}", f.header.file_length);
+            println!("Global function: {}", f.header.global_code_index);
+            println!("Functions: {}", f.function_count());
+            println!("Strings: {}", f.header.string_count);
+            println!("Modules: {}", f.header.cjs_module_count);
+        }
+        Commands::Version { json } => {
+            let header = &f.header;
+            if json {
+                let version = serde_json::json!({
+                    "version": header.version,
+                    "supported": f.is_supported_version(),
+                    "supported_versions": SUPPORTED_VERSIONS,
+                    "strings": header.string_count,
+                    "big_ints": header.big_int_count,
+                    "reg_exps": header.reg_exp_count,
+                    "debug_info": header.debug_info_offset != 0,
+                });
+                println!("{version}");
+                return;
+            }
+            let yes_no = |present: bool| if present { "yes" } else { "no" };
+            let supported: Vec<String> = SUPPORTED_VERSIONS.iter().map(u32::to_string).collect();
+            println!("Version: {}", header.version);
+            println!(
+                "Supported: {} (supported versions: {})",
+                yes_no(f.is_supported_version()),
+                supported.join(", ")
+            );
+            println!("Strings: {}", header.string_count);
+            println!("BigInts: {}", header.big_int_count);
+            println!("RegExps: {}", header.reg_exp_count);
+            println!("Debug info: {}", yes_no(header.debug_info_offset != 0));
+        }
         Commands::ShowFunctions => {
-            let mut buf = Vec::new();
-                match bundle_file.read_to_end(&mut buf) {
-                    Ok(_) => (),
+            for (i, header) in f.function_headers.iter().enumerate() {
+                //overflowed small headers only hold where their large header is
+                let header = match header.full_header(&mut Cursor::new(&buf[..])) {
+                    Ok(header) => header,
                     Err(e) => {
-                        println!("Error while reading provided file: {e}");
-                        return;
+                        println!("Function {i}: (unreadable header: {e})");
+                        continue;
                     }
                 };
-                let mut cursor = Cursor::new(buf.as_slice());
-                let f = BytecodeFile::from_reader(&mut cursor).unwrap();
-                for (i, header) in f.function_headers.iter().enumerate() {
-                    println!(
-                        "Function {i}: (name: {}, offset: {}, size: {}, param_count: {})",
-                        f.get_string(header.function_name()).unwrap_or_default(),
-                        header.offset(),
-                        header.bytecode_size_in_bytes(),
-                        header.param_count()
-                    )
-                }
-        },
-        Commands::Disassemble { function_id, output_file } => {
-            let mut buf = Vec::new();
-            match bundle_file.read_to_end(&mut buf) {
-                Ok(_) => (),
-                Err(e) => {
-                    println!("Error while reading provided file: {e}");
-                    return;
-                }
-            };
-            let mut cursor = Cursor::new(buf.as_slice());
-            let f = BytecodeFile::from_reader(&mut cursor).unwrap();
-            match output_file{
-                Some(output_path) => {
-                    let mut output_file = match File::create(output_path.clone()) {
-                        Ok(f) => f,
-                        Err(e) => {
-                            println!(
-                                "Error while opening output file {}: {}",
-                                output_path.display(),
-                                e
-                            );
-                            return;
+                println!(
+                    "Function {i}: (name: {}, offset: {}, size: {}, param_count: {}, frame_size: {}, kind: {})",
+                    f.get_string(header.function_name()).unwrap_or_default(),
+                    header.offset(),
+                    header.bytecode_size_in_bytes(),
+                    header.param_count(),
+                    header.frame_size(),
+                    header.kind()
+                )
+            }
+        }
+        Commands::Disassemble {
+            function_id,
+            output_file,
+            json: true,
+            absolute,
+            from_offset,
+            to_offset,
+            ..
+        } => {
+            let from = from_offset.map_or(Bound::Unbounded, Bound::Included);
+            let to = to_offset.map_or(Bound::Unbounded, Bound::Included);
+            match instructions_json(&f, &buf, function_id, absolute, (from, to)) {
+                Ok(json) => write_output(output_file, &(json + "\n")),
+                Err(e) => println!("Error while reading function {function_id}: {e}"),
+            }
+        }
+        Commands::Disassemble {
+            function_id,
+            output_file,
+            listing: true,
+            absolute,
+            from_offset,
+            to_offset,
+            ..
+        } => {
+            let from = from_offset.map_or(Bound::Unbounded, Bound::Included);
+            let to = to_offset.map_or(Bound::Unbounded, Bound::Included);
+            match instructions_listing(&f, &buf, function_id, absolute, (from, to)) {
+                Ok(listing) => write_output(output_file, &listing),
+                Err(e) => println!("Error while reading function {function_id}: {e}"),
+            }
+        }
+        Commands::Disassemble {
+            function_id,
+            output_file,
+            decompile,
+            json: false,
+            listing: false,
+            ..
+        } => {
+            let options = DecompileOptions::from(&decompile);
+            write_decompiled(
+                output_file,
+                decompile_function_with(&f, &buf, function_id, &options),
+                &format!("function {function_id}"),
+            );
+        }
+        Commands::DecompileAll {
+            output_file,
+            split_dir,
+            jobs,
+            decompile,
+            keep_going,
+            max_functions,
+            skip,
+        } => {
+            let options = DecompileOptions::from(&decompile);
+            let emit = decompile.emit;
+            //progress would end up mixed into the decompiled code when it goes to stdout
+            let show_progress = output_file.is_some() || split_dir.is_some();
+            let functions = skip..max_functions.map_or(usize::MAX, |max| skip.saturating_add(max));
+            let results = decompile_range_with_progress(
+                &f,
+                &buf,
+                &options,
+                jobs,
+                functions,
+                &|done, total| {
+                    if show_progress {
+                        eprint!("\rDecompiled {done}/{total} functions");
+                    }
+                },
+            );
+            let attempted = results.len();
+            if show_progress {
+                eprintln!();
+            }
+            //functions inlined into another are already written there
+            let inlined: HashSet<usize> = results
+                .iter()
+                .flat_map(|result| result.iter().flat_map(|function| function.inlined.clone()))
+                .collect();
+            let mut decompiled = Vec::new();
+            let mut warnings = Vec::new();
+            let mut failed = Vec::new();
+            for (function_id, result) in (skip..).zip(results) {
+                match result {
+                    Ok(_) if inlined.contains(&function_id) => (),
+                    Ok(function) => {
+                        decompiled.push((function_id, function.source));
+                        warnings.extend(function.warnings);
+                    }
+                    Err(DecompileError::NoBytecode(_)) => {
+                        eprintln!("Skipping function {function_id}: it has no bytecode");
+                    }
+                    Err(e) if keep_going => {
+                        //a comment would make the json invalid
+                        match (&e, emit) {
+                            (DecompileError::LimitExceeded { partial, .. }, Emit::Js) => {
+                                let stub = format!(
+                                    "/* partially decompiled f{function_id}: {e} */\n{partial}"
+                                );
+                                decompiled.push((function_id, stub));
+                            }
+                            (DecompileError::LimitExceeded { partial, .. }, _) => {
+                                decompiled.push((function_id, partial.clone()));
+                            }
+                            (_, Emit::Js) => {
+                                let stub = format!("/* failed to decompile f{function_id}: {e} */");
+                                decompiled.push((function_id, stub));
+                            }
+                            _ => (),
                         }
-                    };
-                    disassemble_function(&mut cursor, &f, function_id, &mut output_file);
+                        failed.push((function_id, e));
+                    }
+                    Err(e) => {
+                        println!("Error while decompiling function {function_id}: {e}");
+                        return;
+                    }
                 }
+            }
+            match split_dir {
+                Some(split_dir) => write_split(&f, &buf, &split_dir, emit, &decompiled),
                 None => {
-                    disassemble_function(&mut cursor, &f, function_id, &mut stdout());
+                    let decompiled: String = decompiled
+                        .into_iter()
+                        .map(|(_, source)| source + "\n")
+                        .collect();
+                    write_output(output_file, &decompiled)
                 }
             }
-        },
-        Commands::Strings { output_file } => {
-            let mut buf = Vec::new();
-            match bundle_file.read_to_end(&mut buf) {
-                Ok(_) => (),
+            //on stderr like the progress, to keep it apart from code written to stdout
+            for warning in warnings {
+                eprintln!("warning: {warning}");
+            }
+            if !failed.is_empty() {
+                eprintln!(
+                    "Failed to decompile {} of {} functions:",
+                    failed.len(),
+                    attempted
+                );
+                for (function_id, e) in failed {
+                    eprintln!("  f{function_id}: {e}");
+                }
+            }
+        }
+        Commands::Xref { needle } => {
+            for function_id in 0..f.function_count() {
+                let offsets = match string_references(&f, &buf, function_id, &needle) {
+                    Ok(offsets) => offsets,
+                    Err(DecompileError::NoBytecode(_)) => continue,
+                    Err(e) => {
+                        println!("Error while reading function {function_id}: {e}");
+                        continue;
+                    }
+                };
+                if offsets.is_empty() {
+                    continue;
+                }
+                let name = f
+                    .function_header(function_id, &mut Cursor::new(&buf[..]))
+                    .ok()
+                    .flatten()
+                    .and_then(|header| f.get_string(header.function_name()))
+                    .unwrap_or_default();
+                let offsets: Vec<String> = offsets
+                    .iter()
+                    .map(|offset| format!("{offset:#x}"))
+                    .collect();
+                println!(
+                    "Function {function_id}: (name: {name}, offsets: {})",
+                    offsets.join(", ")
+                );
+            }
+        }
+        Commands::Handlers { function_id, json } => {
+            let regions = match exception_regions(&f, &buf, function_id) {
+                Ok(regions) => regions,
                 Err(e) => {
-                    println!("Error while reading provided file: {e}");
+                    println!("Error while reading function {function_id}: {e}");
                     return;
                 }
             };
-            let mut cursor = Cursor::new(buf.as_slice());
-            let f = BytecodeFile::from_reader(&mut cursor).unwrap();
-            match output_file {
-                Some(output_path) => {
-                    let mut output_file = match File::create(output_path.clone()) {
-                        Ok(f) => BufWriter::new(f),
-                        Err(e) => {
-                            println!(
-                                "Error while opening output file {}: {}",
-                                output_path.display(),
-                                e
-                            );
-                            return;
-                        }
+            if json {
+                let regions: Vec<_> = regions
+                    .iter()
+                    .map(|region| {
+                        serde_json::json!({
+                            "start": region.handler.start,
+                            "end": region.handler.end,
+                            "target": region.handler.target,
+                            "start_index": region.start_index,
+                            "end_index": region.end_index,
+                            "target_index": region.target_index,
+                            "kind": region.kind.to_string(),
+                        })
+                    })
+                    .collect();
+                println!("{}", serde_json::Value::from(regions));
+                return;
+            }
+            if regions.is_empty() {
+                println!("Function {function_id} has no exception handlers");
+            }
+            //offsets that aren't at an instruction have no index
+            let index = |index: Option<usize>| index.map_or("?".to_string(), |i| i.to_string());
+            for (i, region) in regions.iter().enumerate() {
+                let handler = &region.handler;
+                let finally = match region.kind {
+                    HandlerKind::Finally => ", runs finally and rethrows",
+                    HandlerKind::Catch => "",
+                };
+                println!(
+                    "Handler {i}: protects {:#x}..{:#x} (instructions {}..{}), catches at {:#x} (instruction {}){finally}",
+                    handler.start,
+                    handler.end,
+                    index(region.start_index),
+                    index(region.end_index),
+                    handler.target,
+                    index(region.target_index)
+                );
+            }
+        }
+        Commands::CallGraph { output_file } => match call_graph_dot(&f, &buf) {
+            Ok(dot) => write_output(output_file, &dot),
+            Err(e) => println!("Error while building the call graph: {e}"),
+        },
+        Commands::Stats { output_file } => match opcode_histogram(&f, &buf) {
+            Ok(histogram) => {
+                let mut out = String::new();
+                for opcode in &histogram {
+                    let todo = if opcode.is_unimplemented {
+                        " (unimplemented)"
+                    } else {
+                        ""
                     };
-                    for s_index in 0..f.header.string_count {
-                        let s = f.get_string(s_index).unwrap_or_default();
-                        match writeln!(output_file, "{s_index}: {s}") {
-                            Ok(_) => (),
-                            Err(e) => {
-                                println!(
-                                    "Error while writing output file {}: {}",
-                                    output_path.display(),
-                                    e
-                                );
-                            }
-                        };
-                    }
-                }
-                None => {
-                    for s_index in 0..f.header.string_count {
-                        let s = f.get_string(s_index).unwrap_or_default();
-                        println!("{s_index}: {s}");
-                    }
+                    out.push_str(&format!("{}: {}{todo}\n", opcode.name, opcode.count));
                 }
+                let unimplemented: Vec<_> = histogram
+                    .iter()
+                    .filter(|opcode| opcode.is_unimplemented)
+                    .collect();
+                out.push_str(&format!(
+                    "Total: {} instructions\nUnimplemented: {} opcodes, {} instructions\n",
+                    histogram.iter().map(|opcode| opcode.count).sum::<usize>(),
+                    unimplemented.len(),
+                    unimplemented
+                        .iter()
+                        .map(|opcode| opcode.count)
+                        .sum::<usize>()
+                ));
+                write_output(output_file, &out)
+            }
+            Err(e) => println!("Error while counting opcodes: {e}"),
+        },
+        Commands::Modules => {
+            for (module_id, function_id) in f.cjs_modules() {
+                let name = f
+                    .function_header(function_id as usize, &mut Cursor::new(&buf[..]))
+                    .ok()
+                    .flatten()
+                    .and_then(|header| f.get_string(header.function_name()))
+                    .unwrap_or_default();
+                println!("Module {module_id}: (function: {function_id}, name: {name})");
             }
+        }
+        Commands::Module {
+            module_id,
+            output_file,
+            decompile,
+        } => {
+            let options = DecompileOptions::from(&decompile);
+            let decompiled = module_function_id(&f, module_id)
+                .and_then(|function_id| decompile_function_with(&f, &buf, function_id, &options));
+            write_decompiled(output_file, decompiled, &format!("module {module_id}"));
+        }
+        Commands::Entry {
+            output_file,
+            decompile,
+        } => {
+            let options = DecompileOptions::from(&decompile);
+            let function_id = f.header.global_code_index as usize;
+            write_decompiled(
+                output_file,
+                decompile_function_with(&f, &buf, function_id, &options),
+                &format!("function {function_id}"),
+            );
+        }
+        Commands::Strings { output_file } => write_strings(&f, output_file),
+        Commands::Cfg {
+            function_id,
+            output_file,
+            kind,
+        } => match function_graph_dot(&f, &buf, function_id, kind) {
+            Ok(dot) => write_output(output_file, &dot),
+            Err(e) => println!("Error while reading function {function_id}: {e}"),
         },
     }
 }
 
-fn disassemble_function<W: Write>(
-    cursor: &mut Cursor<&[u8]>,
-    f: &BytecodeFile,
-    function_id: usize,
-    output: &mut W,
+//writes the code of a decompiled function and lists its warnings on stderr, `what` names it
+//in the error when it failed
+fn write_decompiled(
+    output_file: Option<PathBuf>,
+    decompiled: Result<DecompileResult, DecompileError>,
+    what: &str,
 ) {
-    let header = f.function_headers[function_id];
-    let disassembled = header
-        .disassemble_function::<Instruction, Cursor<&[u8]>>(cursor)
-        .unwrap();
-    let flow_graph = construct_flow_graph(&disassembled);
-    #[cfg(test)]
-    {
-        writeln!(
-            File::create("../out_flow.dot").unwrap(),
-            "{:?}",
-            petgraph::dot::Dot::new(&flow_graph)
-        )
-        .unwrap();
+    match decompiled {
+        Ok(decompiled) => {
+            write_output(output_file, &decompiled.code);
+            for warning in decompiled.warnings {
+                eprintln!("warning: {warning}");
+            }
+        }
+        Err(e) => {
+            //what was generated before giving up is written all the same
+            if let DecompileError::LimitExceeded { partial, .. } = &e {
+                write_output(output_file, partial);
+            }
+            println!("Error while decompiling {what}: {e}");
+        }
     }
+}
 
-    let cfg = construct_cfg(&flow_graph);
-    #[cfg(test)]
-    {
-        writeln!(
-            File::create("../out_cfg.dot").unwrap(),
-            "{:?}",
-            petgraph::dot::Dot::new(&cfg)
-        )
-        .unwrap();
+fn write_output(output_file: Option<PathBuf>, s: &str) {
+    match output_file {
+        Some(output_path) => {
+            if let Err(e) = std::fs::write(&output_path, s) {
+                println!(
+                    "Error while writing output file {}: {}",
+                    output_path.display(),
+                    e
+                );
+            }
+        }
+        None => print!("{s}"),
     }
+}
 
-    let func = FnDecl {
-        ident: Ident::new(format!("f{function_id}").as_str().into(), DUMMY_SP),
-        function: Box::new(Function {
-            params: Vec::new(),
-            decorators: Vec::new(),
-            span: DUMMY_SP,
-            body: Some(BlockStmt {
-                span: DUMMY_SP,
-                stmts: AstGenerator::new(
-                    f,
-                    &cfg,
-                    &disassembled,
-                    NodeIndex::new(0),
-                    false,
-                    None,
-                    None,
-                )
-                .collect(),
-            }),
-            is_generator: false,
-            is_async: false,
-            type_params: None,
-            return_type: None,
-        }),
-        declare: false,
-    };
-    let cm = Lrc::new(SourceMap::new(FilePathMapping::empty()));
-    let mut emitter = Emitter {
-        cfg: swc_ecma_codegen::Config {
-            target: EsVersion::Es2022,
-            ascii_only: false,
-            minify: false,
-            omit_last_semi: false,
-        },
-        cm: cm.clone(),
-        comments: None,
-        wr: JsWriter::new(cm, "\n", output, None),
+//keeps names like `render` or `_$foo-bar` readable and replaces separators and anything else a
+//filesystem could reject
+fn sanitize_file_stem(name: &str) -> String {
+    name.chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || matches!(c, '_' | '$' | '-') {
+                c
+            } else {
+                '_'
+            }
+        })
+        .take(100)
+        .collect()
+}
+
+/// Writes every function to `split_dir/{name}.js`, or `f{id}.js` when it has no name or the
+/// name is already taken ignoring case, `f{id}_2.js` and so on if that is taken too, plus an
+/// `index.js` listing which file holds which function.
+/// Functions emitted as json get a `.json` extension instead.
+fn write_split(
+    f: &BytecodeFile,
+    buf: &[u8],
+    split_dir: &Path,
+    emit: Emit,
+    decompiled: &[(usize, String)],
+) {
+    let extension = match emit {
+        Emit::Js => "js",
+        Emit::AstJson => "json",
     };
-    let program = Program::Script(Script {
-        span: DUMMY_SP,
-        body: vec![Stmt::Decl(Decl::Fn(func))],
-        shebang: None,
-    });
-    emitter.emit_program(&program).unwrap();
+    if let Err(e) = std::fs::create_dir_all(split_dir) {
+        println!(
+            "Error while creating directory {}: {}",
+            split_dir.display(),
+            e
+        );
+        return;
+    }
+    let mut used = HashSet::from(["index".to_string()]);
+    let mut index = String::new();
+    for (function_id, source) in decompiled {
+        let name = f
+            .function_header(*function_id, &mut Cursor::new(buf))
+            .ok()
+            .flatten()
+            .and_then(|header| f.get_string(header.function_name()))
+            .unwrap_or_default();
+        //names are compared lowercased, case-insensitive file systems would overwrite the file otherwise
+        let fallback = format!("f{function_id}");
+        let mut stem = sanitize_file_stem(name);
+        let mut suffix = 1;
+        while stem.is_empty() || used.contains(&stem.to_lowercase()) {
+            stem = match suffix {
+                1 => fallback.clone(),
+                _ => format!("{fallback}_{suffix}"),
+            };
+            suffix += 1;
+        }
+        used.insert(stem.to_lowercase());
+        let file_name = format!("{stem}.{extension}");
+        index.push_str(&format!("// f{function_id} ({name}): {file_name}\n"));
+        let path = split_dir.join(&file_name);
+        if let Err(e) = std::fs::write(&path, source) {
+            println!("Error while writing output file {}: {}", path.display(), e);
+            return;
+        }
+    }
+    write_output(Some(split_dir.join("index.js")), &index);
+}
+
+fn write_strings(f: &BytecodeFile, output_file: Option<PathBuf>) {
+    match output_file {
+        Some(output_path) => {
+            let mut output_file = match std::fs::File::create(output_path.clone()) {
+                Ok(f) => BufWriter::new(f),
+                Err(e) => {
+                    println!(
+                        "Error while opening output file {}: {}",
+                        output_path.display(),
+                        e
+                    );
+                    return;
+                }
+            };
+            for s_index in 0..f.header.string_count {
+                let s = f.get_string(s_index).unwrap_or_default();
+                if let Err(e) = writeln!(output_file, "{s_index}: {s}") {
+                    println!(
+                        "Error while writing output file {}: {}",
+                        output_path.display(),
+                        e
+                    );
+                    return;
+                }
+            }
+        }
+        None => {
+            for s_index in 0..f.header.string_count {
+                let s = f.get_string(s_index).unwrap_or_default();
+                println!("{s_index}: {s}");
+            }
+        }
+    }
 }
 
 #[derive(Parser)]
 struct Args {
-    /// Path to an index.android.bundle from unpacked hermes application
+    /// Path to an index.android.bundle from unpacked hermes application, or - to read stdin
     bundle_path: PathBuf,
 
+    /// Check the string table is well formed before running the command, bundles patched by
+    /// obfuscators can have string tables that decode to garbage
+    #[arg(long, global = true)]
+    validate: bool,
+
     #[command(subcommand)]
     command: Commands
 }
 
+/// How the decompile commands generate and write functions
+#[derive(clap::Args)]
+struct DecompileArgs {
+    /// Remove assignments to registers that are never read
+    #[arg(long)]
+    clean: bool,
+
+    /// Rename registers to variables named after the values they hold
+    #[arg(long)]
+    rename: bool,
+
+    /// Write closures passed as an argument right after they are created as function
+    /// expressions at the call instead of by name
+    #[arg(long)]
+    inline_closures: bool,
+
+    /// Name every definition of a register apart (`r0_1`, `r0_2`, ...) with phi comments
+    /// where they join, to follow the dataflow. The output no longer runs
+    #[arg(long, conflicts_with = "rename")]
+    ssa: bool,
+
+    /// Keep profiling instrumentation as `/* profile point N */` comments
+    #[arg(long)]
+    keep_profile_points: bool,
+
+    /// Emit one statement per instruction without running any post-pass
+    #[arg(long)]
+    no_optimize: bool,
+
+    /// Keep register moves like `r2 = r1` instead of reading `r1` where `r2` is read
+    #[arg(long)]
+    no_propagate_copies: bool,
+
+    /// End every statement with a comment holding the offset and name of its instruction
+    #[arg(long)]
+    annotate: bool,
+
+    /// End property reads by id with a `/* cache index N */` comment naming the inline cache
+    /// slot they use
+    #[arg(long)]
+    show_cache_index: bool,
+
+    /// Type registers and parameters as `any` so the output is valid TypeScript
+    #[arg(long)]
+    declare_any: bool,
+
+    /// Write module wrappers as ES modules, with `exports.foo = x` as `export const foo = x`
+    #[arg(long)]
+    es_module: bool,
+
+    /// Name functions after the name they were declared with, `render_f12` instead of `f12`
+    #[arg(long)]
+    function_names: bool,
+
+    /// Fold string concatenation into template literals, `"a" + x` becomes `` `a${x}` ``
+    #[arg(long)]
+    templates: bool,
+
+    /// Write properties of the global object as bare globals, `globalThis.foo` becomes `foo`
+    #[arg(long)]
+    bare_globals: bool,
+
+    /// Write JS source, or the syntax tree as json for other tools to consume
+    #[arg(long, value_enum, default_value_t = Emit::Js)]
+    emit: Emit,
+}
+
+impl From<&DecompileArgs> for DecompileOptions {
+    fn from(args: &DecompileArgs) -> Self {
+        DecompileOptions {
+            optimize: !args.no_optimize,
+            propagate_copies: !args.no_propagate_copies,
+            clean: args.clean,
+            rename: args.rename,
+            inline_closures: args.inline_closures,
+            ssa: args.ssa,
+            keep_profile_points: args.keep_profile_points,
+            annotate: args.annotate,
+            show_cache_index: args.show_cache_index,
+            declare_any: args.declare_any,
+            es_module: args.es_module,
+            function_names: args.function_names,
+            templates: args.templates,
+            bare_globals: args.bare_globals,
+            emit: args.emit,
+            ..Default::default()
+        }
+    }
+}
+
 #[derive(Subcommand)]
 enum Commands {
+    /// Prints the bundle header: version, source hash and table sizes
+    Header,
+    /// Prints the bytecode version, whether it's supported and which tables the bundle has
+    Version {
+        /// Print a json object instead, for scripts
+        #[arg(long)]
+        json: bool,
+    },
     ShowFunctions,
     Disassemble {
         function_id: usize,
-        output_file: Option<PathBuf>
+        output_file: Option<PathBuf>,
+
+        #[command(flatten)]
+        decompile: DecompileArgs,
+
+        /// Instead of decompiling, list the decoded instructions as json with the offset,
+        /// opcode name and operands of each
+        #[arg(long, group = "raw")]
+        json: bool,
+
+        /// Instead of decompiling, list the decoded instructions as text an assembler can read
+        /// back, like `0x10: GetById r0, r1, 3, "foo"@42`
+        #[arg(long, group = "raw")]
+        listing: bool,
+
+        /// With --json or --listing, list offsets as positions in the file instead of relative
+        /// to the start of the function
+        #[arg(long, requires = "raw")]
+        absolute: bool,
+
+        /// With --json or --listing, only list instructions at this offset or after it
+        #[arg(long, requires = "raw")]
+        from_offset: Option<u32>,
+
+        /// With --json or --listing, only list instructions at this offset or before it
+        #[arg(long, requires = "raw")]
+        to_offset: Option<u32>,
     },
-    Strings {
-        output_file: Option<PathBuf>
-    }
-}
+    /// Decompiles every function in the bundle
+    DecompileAll {
+        output_file: Option<PathBuf>,
 
-#[cfg(test)]
-mod tests {
-    use std::{
-        fs::File,
-        io::{Cursor, Read},
-    };
+        /// Write every function to its own file in this directory instead of to output_file
+        #[arg(long, conflicts_with = "output_file")]
+        split_dir: Option<PathBuf>,
 
-    use crate::{disassemble_function, hermes_file_reader::BytecodeFile};
+        /// Number of threads to decompile functions on
+        #[arg(long, default_value_t = 1)]
+        jobs: usize,
 
-    #[test]
-    fn t() {
-        let mut buf = Vec::new();
-        match File::open("../index.android.bundle")
-            .unwrap()
-            .read_to_end(&mut buf)
-        {
-            Ok(_) => (),
-            Err(e) => {
-                println!("Error while reading provided file: {e}");
-                return;
-            }
-        };
-        let mut cursor = Cursor::new(buf.as_slice());
-        let f = BytecodeFile::from_reader(&mut cursor).unwrap();
-        disassemble_function(&mut cursor, &f, 12, &mut File::create("../out.txt").unwrap());
-        panic!("{:?}", f.exception_handler_map.get(&12).unwrap());
-    }
+        #[command(flatten)]
+        decompile: DecompileArgs,
+
+        /// Write functions that fail to decompile as a comment with the error instead of
+        /// stopping, and list the failures at the end
+        #[arg(long)]
+        keep_going: bool,
+
+        /// Decompile at most N functions, to sample a large bundle
+        #[arg(long)]
+        max_functions: Option<usize>,
+
+        /// Start at function id M, skipping the ones before it
+        #[arg(long, default_value_t = 0)]
+        skip: usize,
+    },
+    /// Lists the functions using a string that contains `needle`, ignoring case, and the
+    /// offsets of the instructions using it
+    Xref {
+        needle: String,
+    },
+    /// Lists the exception handlers of a function: the offsets it protects and where it
+    /// catches, along with the indices of the instructions there
+    Handlers {
+        function_id: usize,
+
+        /// Print a json array instead, for scripts
+        #[arg(long)]
+        json: bool,
+    },
+    /// Outputs which functions create closures of or directly call which, in graphviz dot format
+    CallGraph {
+        output_file: Option<PathBuf>,
+    },
+    /// Counts how many instructions use each opcode across all functions, most used first,
+    /// marking the opcodes the decompiler doesn't support yet
+    Stats {
+        output_file: Option<PathBuf>,
+    },
+    /// Lists which function implements each CommonJS module
+    Modules,
+    /// Decompiles the function implementing the CommonJS module with require id `module_id`
+    Module {
+        module_id: u32,
+        output_file: Option<PathBuf>,
+
+        #[command(flatten)]
+        decompile: DecompileArgs,
+    },
+    /// Decompiles the global function, which runs first when the bundle is loaded
+    Entry {
+        output_file: Option<PathBuf>,
+
+        #[command(flatten)]
+        decompile: DecompileArgs,
+    },
+    Strings {
+        output_file: Option<PathBuf>
+    },
+    /// Dumps the control flow of a function in graphviz dot format
+    Cfg {
+        function_id: usize,
+        output_file: Option<PathBuf>,
+
+        #[arg(long, value_enum, default_value_t = GraphKind::Cfg)]
+        kind: GraphKind,
+    },
 }
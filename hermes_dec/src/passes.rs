@@ -0,0 +1,2824 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+
+use swc_common::comments::{Comment, CommentKind, Comments, SingleThreadedComments};
+use swc_common::{BytePos, Span, DUMMY_SP};
+use swc_ecma_ast::{
+    ArrayLit, AssignExpr, AssignOp, BinExpr, BinaryOp, BindingIdent, BlockStmt, Callee,
+    ContinueStmt, Decl, ExportDecl, ExportDefaultExpr, Expr, ExprOrSpread, ExprStmt, ForInStmt,
+    ForOfStmt, Ident, Invalid, KeyValueProp, LabeledStmt, Lit, MemberProp, ModuleDecl, ModuleItem,
+    NewExpr, ObjectLit, Pat, PatOrExpr, Prop, PropName, PropOrSpread, Stmt, Tpl, TplElement,
+    TsKeywordType, TsKeywordTypeKind, TsType, TsTypeAnn, UnaryOp, UpdateOp, VarDecl, VarDeclKind,
+    VarDeclOrPat, VarDeclarator,
+};
+
+use crate::generate_ast::{is_valid_identifier, number_literal, EMPTY};
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Access {
+    Read,
+    Write,
+    //r++ or r += x, the register is read and written by the same expression
+    ReadWrite,
+}
+
+pub fn is_register(sym: &str) -> bool {
+    sym.len() > 1 && sym.starts_with('r') && sym[1..].bytes().all(|b| b.is_ascii_digit())
+}
+
+/// Calls `f` for every identifier `expr` reads or writes, in evaluation order.
+/// Returns false if `expr` contains an expression kind we don't know how to walk,
+/// in which case passes have to assume it can touch any register.
+pub fn walk_expr(expr: &mut Expr, f: &mut dyn FnMut(&mut Ident, Access)) -> bool {
+    match expr {
+        Expr::Ident(ident) => {
+            f(ident, Access::Read);
+            true
+        }
+        Expr::Lit(_) | Expr::This(_) => true,
+        //closures have their own registers
+        Expr::Fn(_) | Expr::Arrow(_) => true,
+        Expr::Paren(paren) => walk_expr(&mut paren.expr, f),
+        Expr::Unary(unary) => walk_expr(&mut unary.arg, f),
+        Expr::Update(update) => match &mut *update.arg {
+            Expr::Ident(ident) => {
+                f(ident, Access::ReadWrite);
+                true
+            }
+            arg => walk_expr(arg, f),
+        },
+        Expr::Bin(bin) => walk_expr(&mut bin.left, f) && walk_expr(&mut bin.right, f),
+        Expr::Cond(cond) => {
+            walk_expr(&mut cond.test, f)
+                && walk_expr(&mut cond.cons, f)
+                && walk_expr(&mut cond.alt, f)
+        }
+        Expr::Seq(seq) => seq.exprs.iter_mut().all(|e| walk_expr(e, f)),
+        Expr::Member(member) => {
+            walk_expr(&mut member.obj, f)
+                && match &mut member.prop {
+                    MemberProp::Computed(computed) => walk_expr(&mut computed.expr, f),
+                    _ => true,
+                }
+        }
+        Expr::Call(call) => {
+            (match &mut call.callee {
+                Callee::Expr(callee) => walk_expr(callee, f),
+                _ => true,
+            }) && call.args.iter_mut().all(|arg| walk_expr(&mut arg.expr, f))
+        }
+        Expr::New(new) => {
+            walk_expr(&mut new.callee, f)
+                && new
+                    .args
+                    .iter_mut()
+                    .flatten()
+                    .all(|arg| walk_expr(&mut arg.expr, f))
+        }
+        Expr::Array(array) => array
+            .elems
+            .iter_mut()
+            .flatten()
+            .all(|elem| walk_expr(&mut elem.expr, f)),
+        Expr::Object(object) => object.props.iter_mut().all(|prop| match prop {
+            PropOrSpread::Spread(spread) => walk_expr(&mut spread.expr, f),
+            PropOrSpread::Prop(prop) => match &mut **prop {
+                Prop::Shorthand(ident) => {
+                    f(ident, Access::Read);
+                    true
+                }
+                Prop::KeyValue(key_value) => {
+                    (match &mut key_value.key {
+                        PropName::Computed(computed) => walk_expr(&mut computed.expr, f),
+                        _ => true,
+                    }) && walk_expr(&mut key_value.value, f)
+                }
+                _ => false,
+            },
+        }),
+        Expr::Tpl(tpl) => tpl.exprs.iter_mut().all(|e| walk_expr(e, f)),
+        Expr::Assign(assign) => {
+            let access = if assign.op == AssignOp::Assign {
+                Access::Write
+            } else {
+                Access::ReadWrite
+            };
+            let target = match &mut assign.left {
+                PatOrExpr::Expr(target) => &mut **target,
+                PatOrExpr::Pat(pat) => match &mut **pat {
+                    Pat::Ident(binding) => {
+                        return walk_expr(&mut assign.right, f) && {
+                            f(&mut binding.id, access);
+                            true
+                        };
+                    }
+                    Pat::Expr(target) => &mut **target,
+                    _ => return false,
+                },
+            };
+            match target {
+                //the value is evaluated before the register gets written
+                Expr::Ident(ident) => {
+                    walk_expr(&mut assign.right, f) && {
+                        f(ident, access);
+                        true
+                    }
+                }
+                target => walk_expr(target, f) && walk_expr(&mut assign.right, f),
+            }
+        }
+        _ => false,
+    }
+}
+
+/// Walks the expressions a statement evaluates, but not nested statements.
+/// Returns None for statements with nested statements.
+fn walk_simple_stmt(stmt: &mut Stmt, f: &mut dyn FnMut(&mut Ident, Access)) -> Option<bool> {
+    match stmt {
+        Stmt::Expr(expr_stmt) => Some(walk_expr(&mut expr_stmt.expr, f)),
+        Stmt::Return(ret) => Some(match &mut ret.arg {
+            Some(arg) => walk_expr(arg, f),
+            None => true,
+        }),
+        Stmt::Throw(throw) => Some(walk_expr(&mut throw.arg, f)),
+        Stmt::Break(_) | Stmt::Continue(_) | Stmt::Debugger(_) | Stmt::Empty(_) => Some(true),
+        _ => None,
+    }
+}
+
+/// Calls `f` for every identifier read or written anywhere in `stmts`, including nested statements.
+/// Returns false if some part of `stmts` couldn't be walked.
+pub fn walk_stmts(stmts: &mut [Stmt], f: &mut dyn FnMut(&mut Ident, Access)) -> bool {
+    stmts.iter_mut().all(|stmt| walk_stmt(stmt, f))
+}
+
+pub fn walk_stmt(stmt: &mut Stmt, f: &mut dyn FnMut(&mut Ident, Access)) -> bool {
+    if let Some(walked) = walk_simple_stmt(stmt, f) {
+        return walked;
+    }
+    match stmt {
+        Stmt::Block(block) => walk_stmts(&mut block.stmts, f),
+        Stmt::Labeled(labeled) => walk_stmt(&mut labeled.body, f),
+        Stmt::If(if_stmt) => {
+            walk_expr(&mut if_stmt.test, f)
+                && walk_stmt(&mut if_stmt.cons, f)
+                && match &mut if_stmt.alt {
+                    Some(alt) => walk_stmt(alt, f),
+                    None => true,
+                }
+        }
+        Stmt::While(while_stmt) => {
+            walk_expr(&mut while_stmt.test, f) && walk_stmt(&mut while_stmt.body, f)
+        }
+        Stmt::DoWhile(do_while) => {
+            walk_stmt(&mut do_while.body, f) && walk_expr(&mut do_while.test, f)
+        }
+        Stmt::ForIn(for_in) => {
+            walk_expr(&mut for_in.right, f)
+                && match &mut for_in.left {
+                    VarDeclOrPat::Pat(pat) => match &mut **pat {
+                        Pat::Ident(binding) => {
+                            f(&mut binding.id, Access::Write);
+                            true
+                        }
+                        _ => false,
+                    },
+                    VarDeclOrPat::VarDecl(_) => false,
+                }
+                && walk_stmt(&mut for_in.body, f)
+        }
+        Stmt::ForOf(for_of) => {
+            walk_expr(&mut for_of.right, f)
+                && match &mut for_of.left {
+                    VarDeclOrPat::Pat(pat) => match &mut **pat {
+                        Pat::Ident(binding) => {
+                            f(&mut binding.id, Access::Write);
+                            true
+                        }
+                        _ => false,
+                    },
+                    VarDeclOrPat::VarDecl(_) => false,
+                }
+                && walk_stmt(&mut for_of.body, f)
+        }
+        //the finally block runs after the protected one, whether it threw or not
+        Stmt::Try(try_stmt) => {
+            try_stmt.handler.is_none()
+                && walk_stmts(&mut try_stmt.block.stmts, f)
+                && match &mut try_stmt.finalizer {
+                    Some(finalizer) => walk_stmts(&mut finalizer.stmts, f),
+                    None => true,
+                }
+        }
+        Stmt::Decl(Decl::Var(var)) => var.decls.iter_mut().all(|decl| {
+            (match &mut decl.init {
+                Some(init) => walk_expr(init, f),
+                None => true,
+            }) && match &mut decl.name {
+                Pat::Ident(binding) => {
+                    f(&mut binding.id, Access::Write);
+                    true
+                }
+                _ => false,
+            }
+        }),
+        _ => false,
+    }
+}
+
+//rA = rB
+fn as_register_copy(stmt: &Stmt) -> Option<(String, String)> {
+    if let Stmt::Expr(ExprStmt { expr, .. }) = stmt {
+        if let Expr::Assign(AssignExpr {
+            op: AssignOp::Assign,
+            left: PatOrExpr::Expr(left),
+            right,
+            ..
+        }) = &**expr
+        {
+            if let (Expr::Ident(dst), Expr::Ident(src)) = (&**left, &**right) {
+                if is_register(&dst.sym) && is_register(&src.sym) {
+                    return Some((dst.sym.to_string(), src.sym.to_string()));
+                }
+            }
+        }
+    }
+    None
+}
+
+/// Conservative copy propagation.
+///
+/// Reads of `rA` after `rA = rB` are replaced with `rB` until either register is written again,
+/// but only inside the straight-line run of statements the copy is in. Copies whose destination
+/// isn't read anywhere in the function afterwards are dropped.
+pub fn propagate_copies(stmts: &mut Vec<Stmt>) {
+    propagate_copies_in_block(stmts);
+
+    //removing a copy can make the copy feeding it unused as well
+    loop {
+        let mut reads: HashMap<String, usize> = HashMap::new();
+        if !walk_stmts(stmts, &mut |ident, access| {
+            if access != Access::Write {
+                *reads.entry(ident.sym.to_string()).or_default() += 1;
+            }
+        }) {
+            return;
+        }
+        if !remove_unread_copies(stmts, &reads) {
+            return;
+        }
+    }
+}
+
+fn propagate_copies_in_block(stmts: &mut Vec<Stmt>) {
+    let mut i = 0;
+    while i < stmts.len() {
+        let Some((dst, src)) = as_register_copy(&stmts[i]) else {
+            //nested blocks are propagated on their own
+            match &mut stmts[i] {
+                Stmt::Block(block) => propagate_copies_in_block(&mut block.stmts),
+                Stmt::Labeled(labeled) => propagate_copies_in_stmt(&mut labeled.body),
+                Stmt::If(if_stmt) => {
+                    propagate_copies_in_stmt(&mut if_stmt.cons);
+                    if let Some(alt) = &mut if_stmt.alt {
+                        propagate_copies_in_stmt(alt);
+                    }
+                }
+                Stmt::While(while_stmt) => propagate_copies_in_stmt(&mut while_stmt.body),
+                Stmt::DoWhile(do_while) => propagate_copies_in_stmt(&mut do_while.body),
+                Stmt::Try(try_stmt) => {
+                    propagate_copies_in_block(&mut try_stmt.block.stmts);
+                    if let Some(finalizer) = &mut try_stmt.finalizer {
+                        propagate_copies_in_block(&mut finalizer.stmts);
+                    }
+                }
+                _ => (),
+            }
+            i += 1;
+            continue;
+        };
+        if dst == src {
+            stmts.remove(i);
+            continue;
+        }
+
+        let mut is_overwritten = false;
+        for stmt in &mut stmts[i + 1..] {
+            let mut dst_written = false;
+            let mut src_written = false;
+            let mut blocked = false;
+            let walked = walk_simple_stmt(stmt, &mut |ident, access| {
+                if blocked || dst_written || src_written {
+                    return;
+                }
+                let sym = ident.sym.to_string();
+                match access {
+                    Access::Read if sym == dst => ident.sym = src.as_str().into(),
+                    Access::Read => (),
+                    Access::Write if sym == dst => dst_written = true,
+                    Access::Write if sym == src => src_written = true,
+                    Access::Write => (),
+                    Access::ReadWrite if sym == dst || sym == src => blocked = true,
+                    Access::ReadWrite => (),
+                }
+            });
+            if walked != Some(true) || blocked || src_written {
+                break;
+            }
+            if dst_written {
+                is_overwritten = true;
+                break;
+            }
+            if matches!(
+                stmt,
+                Stmt::Return(_) | Stmt::Throw(_) | Stmt::Break(_) | Stmt::Continue(_)
+            ) {
+                break;
+            }
+        }
+
+        if is_overwritten {
+            stmts.remove(i);
+        } else {
+            i += 1;
+        }
+    }
+}
+
+fn propagate_copies_in_stmt(stmt: &mut Stmt) {
+    match stmt {
+        Stmt::Block(block) => propagate_copies_in_block(&mut block.stmts),
+        _ => {
+            let mut stmts = vec![stmt.clone()];
+            propagate_copies_in_block(&mut stmts);
+            if stmts.len() == 1 {
+                *stmt = stmts.pop().unwrap();
+            }
+        }
+    }
+}
+
+fn remove_unread_copies(stmts: &mut Vec<Stmt>, reads: &HashMap<String, usize>) -> bool {
+    let mut removed = false;
+    stmts.retain_mut(|stmt| {
+        if let Some((dst, _)) = as_register_copy(stmt) {
+            if !reads.contains_key(&dst) {
+                removed = true;
+                return false;
+            }
+        }
+        match stmt {
+            Stmt::Block(block) => removed |= remove_unread_copies(&mut block.stmts, reads),
+            Stmt::Labeled(labeled) => {
+                removed |= remove_unread_copies_in_stmt(&mut labeled.body, reads)
+            }
+            Stmt::If(if_stmt) => {
+                removed |= remove_unread_copies_in_stmt(&mut if_stmt.cons, reads);
+                if let Some(alt) = &mut if_stmt.alt {
+                    removed |= remove_unread_copies_in_stmt(alt, reads);
+                }
+            }
+            Stmt::While(while_stmt) => {
+                removed |= remove_unread_copies_in_stmt(&mut while_stmt.body, reads)
+            }
+            Stmt::DoWhile(do_while) => {
+                removed |= remove_unread_copies_in_stmt(&mut do_while.body, reads)
+            }
+            _ => (),
+        }
+        true
+    });
+    removed
+}
+
+fn remove_unread_copies_in_stmt(stmt: &mut Stmt, reads: &HashMap<String, usize>) -> bool {
+    match stmt {
+        Stmt::Block(block) => remove_unread_copies(&mut block.stmts, reads),
+        _ => false,
+    }
+}
+
+//expressions that can be dropped if their value isn't used
+fn is_pure(expr: &Expr) -> bool {
+    match expr {
+        Expr::Ident(_) | Expr::Lit(_) | Expr::This(_) | Expr::Fn(_) | Expr::Arrow(_) => true,
+        Expr::Paren(paren) => is_pure(&paren.expr),
+        Expr::Unary(unary) => unary.op != UnaryOp::Delete && is_pure(&unary.arg),
+        Expr::Bin(bin) => is_pure(&bin.left) && is_pure(&bin.right),
+        Expr::Cond(cond) => is_pure(&cond.test) && is_pure(&cond.cons) && is_pure(&cond.alt),
+        Expr::Seq(seq) => seq.exprs.iter().all(|e| is_pure(e)),
+        Expr::Tpl(tpl) => tpl.exprs.iter().all(|e| is_pure(e)),
+        Expr::Array(array) => array
+            .elems
+            .iter()
+            .flatten()
+            .all(|elem| elem.spread.is_none() && is_pure(&elem.expr)),
+        Expr::Object(object) => object.props.iter().all(|prop| match prop {
+            PropOrSpread::Prop(prop) => match &**prop {
+                Prop::Shorthand(_) => true,
+                Prop::KeyValue(key_value) => {
+                    !matches!(key_value.key, PropName::Computed(_)) && is_pure(&key_value.value)
+                }
+                _ => false,
+            },
+            PropOrSpread::Spread(_) => false,
+        }),
+        _ => false,
+    }
+}
+
+//rX = expr
+fn as_register_store(stmt: &mut Stmt) -> Option<(String, &mut Box<Expr>)> {
+    if let Stmt::Expr(ExprStmt { expr, .. }) = stmt {
+        if let Expr::Assign(AssignExpr {
+            op: AssignOp::Assign,
+            left: PatOrExpr::Expr(left),
+            right,
+            ..
+        }) = &mut **expr
+        {
+            if let Expr::Ident(dst) = &**left {
+                if is_register(&dst.sym) {
+                    return Some((dst.sym.to_string(), right));
+                }
+            }
+        }
+    }
+    None
+}
+
+fn is_goto(stmt: &Stmt) -> bool {
+    if let Stmt::Expr(ExprStmt { expr, .. }) = stmt {
+        if let Expr::Call(call) = &**expr {
+            if let Callee::Expr(callee) = &call.callee {
+                return matches!(&**callee, Expr::Ident(ident) if &*ident.sym == "goto");
+            }
+        }
+    }
+    false
+}
+
+fn add_reads(stmt: &mut Stmt, live: &mut HashSet<String>) {
+    walk_stmt(stmt, &mut |ident, access| {
+        if access != Access::Write {
+            live.insert(ident.sym.to_string());
+        }
+    });
+}
+
+fn add_expr_reads(expr: &mut Expr, live: &mut HashSet<String>) {
+    walk_expr(expr, &mut |ident, access| {
+        if access != Access::Write {
+            live.insert(ident.sym.to_string());
+        }
+    });
+}
+
+/// Dead-store elimination.
+///
+/// Runs a backward liveness analysis over the structured statements and removes `rX = expr`
+/// where `rX` isn't read before being overwritten or the function exiting. If `expr` has side
+/// effects only the assignment target is dropped. Loops are handled by treating everything read
+/// anywhere in the loop as live throughout it, and jumps (`break`, `continue`, `goto`) by treating
+/// everything read anywhere in the function as live.
+pub fn eliminate_dead_stores(stmts: &mut Vec<Stmt>) {
+    let mut all_reads = HashSet::new();
+    if !walk_stmts(stmts, &mut |ident, access| {
+        if access != Access::Write {
+            all_reads.insert(ident.sym.to_string());
+        }
+    }) {
+        return;
+    }
+    let mut live = HashSet::new();
+    eliminate_dead_stores_in_block(stmts, &mut live, &all_reads);
+}
+
+//`live` holds the registers live after `stmts` and is updated to the ones live before them
+fn eliminate_dead_stores_in_block(
+    stmts: &mut Vec<Stmt>,
+    live: &mut HashSet<String>,
+    all_reads: &HashSet<String>,
+) {
+    let mut i = stmts.len();
+    while i > 0 {
+        i -= 1;
+        if let Some((dst, value)) = as_register_store(&mut stmts[i]) {
+            if live.remove(&dst) {
+                add_expr_reads(value, live);
+            } else if is_pure(value) {
+                stmts.remove(i);
+            } else {
+                let mut value =
+                    std::mem::replace(value, Box::new(Expr::Invalid(Invalid { span: DUMMY_SP })));
+                add_expr_reads(&mut value, live);
+                stmts[i] = Stmt::Expr(ExprStmt {
+                    span: DUMMY_SP,
+                    expr: value,
+                });
+            }
+            continue;
+        }
+        eliminate_dead_stores_in_stmt(&mut stmts[i], live, all_reads);
+    }
+}
+
+fn eliminate_dead_stores_in_stmt(
+    stmt: &mut Stmt,
+    live: &mut HashSet<String>,
+    all_reads: &HashSet<String>,
+) {
+    match stmt {
+        Stmt::Return(_) | Stmt::Throw(_) => {
+            live.clear();
+            add_reads(stmt, live);
+        }
+        Stmt::Break(_) | Stmt::Continue(_) => *live = all_reads.clone(),
+        _ if is_goto(stmt) => *live = all_reads.clone(),
+        Stmt::Block(block) => eliminate_dead_stores_in_block(&mut block.stmts, live, all_reads),
+        Stmt::Labeled(labeled) => eliminate_dead_stores_in_stmt(&mut labeled.body, live, all_reads),
+        Stmt::If(if_stmt) => {
+            let mut cons_live = live.clone();
+            eliminate_dead_stores_in_stmt(&mut if_stmt.cons, &mut cons_live, all_reads);
+            if let Some(alt) = &mut if_stmt.alt {
+                eliminate_dead_stores_in_stmt(alt, live, all_reads);
+            }
+            live.extend(cons_live);
+            add_expr_reads(&mut if_stmt.test, live);
+        }
+        Stmt::While(_) | Stmt::DoWhile(_) => {
+            add_reads(stmt, live);
+            let body = match stmt {
+                Stmt::While(while_stmt) => &mut while_stmt.body,
+                Stmt::DoWhile(do_while) => &mut do_while.body,
+                _ => unreachable!(),
+            };
+            let mut body_live = live.clone();
+            eliminate_dead_stores_in_stmt(body, &mut body_live, all_reads);
+        }
+        _ => add_reads(stmt, live),
+    }
+}
+
+//names that can't be used for a variable even though they are valid identifiers
+const RESERVED_NAMES: &[&str] = &[
+    "arguments",
+    "await",
+    "break",
+    "case",
+    "catch",
+    "class",
+    "const",
+    "continue",
+    "debugger",
+    "default",
+    "delete",
+    "do",
+    "else",
+    "enum",
+    "eval",
+    "export",
+    "extends",
+    "false",
+    "finally",
+    "for",
+    "function",
+    "if",
+    "implements",
+    "import",
+    "in",
+    "instanceof",
+    "interface",
+    "let",
+    "new",
+    "null",
+    "package",
+    "private",
+    "protected",
+    "public",
+    "return",
+    "static",
+    "super",
+    "switch",
+    "this",
+    "throw",
+    "true",
+    "try",
+    "typeof",
+    "undefined",
+    "var",
+    "void",
+    "while",
+    "with",
+    "yield",
+];
+
+fn for_each_register_store(stmts: &[Stmt], f: &mut dyn FnMut(&str, &Expr)) {
+    for stmt in stmts {
+        for_each_register_store_in_stmt(stmt, f);
+    }
+}
+
+fn for_each_register_store_in_stmt(stmt: &Stmt, f: &mut dyn FnMut(&str, &Expr)) {
+    match stmt {
+        Stmt::Expr(ExprStmt { expr, .. }) => {
+            if let Expr::Assign(AssignExpr {
+                op: AssignOp::Assign,
+                left: PatOrExpr::Expr(left),
+                right,
+                ..
+            }) = &**expr
+            {
+                if let Expr::Ident(dst) = &**left {
+                    if is_register(&dst.sym) {
+                        f(&dst.sym, right);
+                    }
+                }
+            }
+        }
+        Stmt::Decl(Decl::Var(var)) => {
+            for decl in &var.decls {
+                if let (Pat::Ident(binding), Some(init)) = (&decl.name, &decl.init) {
+                    if is_register(&binding.id.sym) {
+                        f(&binding.id.sym, init);
+                    }
+                }
+            }
+        }
+        Stmt::Block(block) => for_each_register_store(&block.stmts, f),
+        Stmt::Labeled(labeled) => for_each_register_store_in_stmt(&labeled.body, f),
+        Stmt::If(if_stmt) => {
+            for_each_register_store_in_stmt(&if_stmt.cons, f);
+            if let Some(alt) = &if_stmt.alt {
+                for_each_register_store_in_stmt(alt, f);
+            }
+        }
+        Stmt::While(while_stmt) => for_each_register_store_in_stmt(&while_stmt.body, f),
+        Stmt::DoWhile(do_while) => for_each_register_store_in_stmt(&do_while.body, f),
+        Stmt::ForIn(for_in) => for_each_register_store_in_stmt(&for_in.body, f),
+        Stmt::ForOf(for_of) => for_each_register_store_in_stmt(&for_of.body, f),
+        _ => (),
+    }
+}
+
+//a name describing the value, r0 = this.count -> count, r0 = arguments[1] -> param1
+fn name_hint(value: &Expr) -> Option<String> {
+    let Expr::Member(member) = value else {
+        return None;
+    };
+    let name = match (&*member.obj, &member.prop) {
+        (Expr::Ident(obj), MemberProp::Computed(computed)) if &*obj.sym == "arguments" => {
+            //LoadParam writes the index as an identifier of digits, a register index would leak
+            //the register's name into the hint
+            match &*computed.expr {
+                Expr::Ident(index) if index.sym.bytes().all(|b| b.is_ascii_digit()) => {
+                    format!("param{}", index.sym)
+                }
+                Expr::Lit(Lit::Num(index)) => format!("param{}", index.value),
+                _ => return None,
+            }
+        }
+        (_, MemberProp::Ident(prop)) => prop.sym.to_string(),
+        _ => return None,
+    };
+    if is_valid_identifier(&name) && !RESERVED_NAMES.contains(&name.as_str()) {
+        Some(name)
+    } else {
+        None
+    }
+}
+
+/// Renames registers to variables.
+///
+/// Registers get `v0`, `v1`, ... in the order they are first assigned, unless their first
+/// assignment loads a named property or a parameter, in which case that name is used.
+/// Names never collide with other identifiers already used in the function.
+pub fn rename_registers(stmts: &mut [Stmt]) {
+    let mut taken = HashSet::new();
+    let mut defined = Vec::new();
+    let mut read = Vec::new();
+    let mut collect = |ident: &mut Ident, access| {
+        let sym = ident.sym.to_string();
+        if !is_register(&sym) {
+            taken.insert(sym);
+        } else if access == Access::Read {
+            if !read.contains(&sym) {
+                read.push(sym);
+            }
+        } else if !defined.contains(&sym) {
+            defined.push(sym);
+        }
+    };
+    //declarations without a value don't count as the first assignment
+    if !stmts
+        .iter_mut()
+        .filter(|stmt| match stmt {
+            Stmt::Decl(Decl::Var(var)) => var.decls.iter().any(|decl| decl.init.is_some()),
+            _ => true,
+        })
+        .all(|stmt| walk_stmt(stmt, &mut collect))
+    {
+        return;
+    }
+
+    let mut hints = HashMap::new();
+    for_each_register_store(stmts, &mut |dst, value| {
+        if !hints.contains_key(dst) {
+            hints.insert(dst.to_string(), name_hint(value));
+        }
+    });
+
+    //registers that are only read (e.g. used before being assigned) go last
+    for reg in read {
+        if !defined.contains(&reg) {
+            defined.push(reg);
+        }
+    }
+    let mut names = HashMap::new();
+    let mut next_var = 0;
+    for reg in defined {
+        let name = match hints.get(&reg).cloned().flatten() {
+            Some(hint) => {
+                let mut name = hint.clone();
+                let mut suffix = 1;
+                while taken.contains(&name) {
+                    name = format!("{hint}{suffix}");
+                    suffix += 1;
+                }
+                name
+            }
+            None => loop {
+                let name = format!("v{next_var}");
+                next_var += 1;
+                if !taken.contains(&name) {
+                    break name;
+                }
+            },
+        };
+        taken.insert(name.clone());
+        names.insert(reg, name);
+    }
+
+    walk_stmts(stmts, &mut |ident, _| {
+        if let Some(name) = names.get(&*ident.sym) {
+            ident.sym = name.as_str().into();
+        }
+    });
+}
+
+//the version each register has at some point of the function, registers missing are rN_0
+type Versions = HashMap<String, String>;
+
+//walks some identifiers with the callback it's given, like walk_stmt
+type Walk<'a> = dyn FnMut(&mut dyn FnMut(&mut Ident, Access)) -> bool + 'a;
+
+fn version_of(versions: &Versions, reg: &str) -> String {
+    versions
+        .get(reg)
+        .cloned()
+        .unwrap_or_else(|| format!("{reg}_0"))
+}
+
+//a statement break or continue can jump to, collecting the versions they jump with
+struct JumpTarget {
+    label: Option<String>,
+    is_loop: bool,
+    breaks: Vec<Versions>,
+    continues: Vec<Versions>,
+}
+
+struct Versioner {
+    counts: HashMap<String, usize>,
+    targets: Vec<JumpTarget>,
+    //the phi comments, and whether they trail the statement at their position or lead it
+    phis: Vec<(BytePos, bool, String)>,
+    next_pos: u32,
+}
+
+impl Versioner {
+    fn define(&mut self, versions: &mut Versions, reg: &str) -> String {
+        let count = self.counts.entry(reg.to_string()).or_default();
+        *count += 1;
+        let version = format!("{reg}_{count}");
+        versions.insert(reg.to_string(), version.clone());
+        version
+    }
+
+    //renames the registers `walk` reads to their versions, then versions the ones it writes
+    fn version_accesses(&mut self, versions: &mut Versions, walk: &mut Walk) -> Option<()> {
+        let read = walk(&mut |ident, access| {
+            if access == Access::Read && is_register(&ident.sym) {
+                ident.sym = version_of(versions, &ident.sym).as_str().into();
+            }
+        });
+        //reads are versioned already, which leaves the writes as registers
+        let written = walk(&mut |ident, _| {
+            if is_register(&ident.sym) {
+                ident.sym = self.define(versions, &ident.sym).as_str().into();
+            }
+        });
+        (read && written).then_some(())
+    }
+
+    //a position for comments about `span`'s statement
+    fn comment_span(&mut self, span: &mut Span) -> BytePos {
+        let pos = BytePos(self.next_pos);
+        self.next_pos += 1;
+        *span = Span::new(pos, pos, Default::default());
+        pos
+    }
+
+    //the versions where `incoming` flow together, phis are made for registers they disagree on
+    fn join(&mut self, incoming: &[Versions], phis: &mut Vec<String>) -> Versions {
+        let regs: BTreeSet<&String> = incoming
+            .iter()
+            .flat_map(|versions| versions.keys())
+            .collect();
+        let mut joined = Versions::new();
+        for reg in regs {
+            let mut sources = Vec::new();
+            for versions in incoming {
+                let version = version_of(versions, reg);
+                if !sources.contains(&version) {
+                    sources.push(version);
+                }
+            }
+            if let [version] = &sources[..] {
+                joined.insert(reg.clone(), version.clone());
+            } else {
+                let version = self.define(&mut joined, reg);
+                phis.push(format!("{version} = phi({})", sources.join(", ")));
+            }
+        }
+        joined
+    }
+
+    //phis of a join after `span`'s statement
+    fn add_join_phis(&mut self, span: &mut Span, phis: Vec<String>) {
+        if phis.is_empty() {
+            return;
+        }
+        let pos = self.comment_span(span);
+        self.phis
+            .extend(phis.into_iter().map(|phi| (pos, true, phi)));
+    }
+
+    fn target(&mut self, label: Option<&Ident>, is_continue: bool) -> Option<&mut JumpTarget> {
+        self.targets.iter_mut().rev().find(|target| match label {
+            Some(label) => target.label.as_deref() == Some(&*label.sym),
+            //unlabeled breaks leave the innermost loop, we don't generate switches
+            None => target.is_loop,
+        } && (!is_continue || target.is_loop))
+    }
+
+    //versions the statements, returns whether control can flow past their end
+    fn version_stmts(&mut self, stmts: &mut [Stmt], versions: &mut Versions) -> Option<bool> {
+        let mut falls_through = true;
+        for stmt in stmts {
+            //what follows a jump is dead, but versioned all the same
+            falls_through &= self.version_stmt(stmt, versions)?;
+        }
+        Some(falls_through)
+    }
+
+    fn version_stmt(&mut self, stmt: &mut Stmt, versions: &mut Versions) -> Option<bool> {
+        match stmt {
+            Stmt::Expr(expr_stmt) => {
+                expand_update(&mut expr_stmt.expr);
+                self.version_accesses(versions, &mut |f| walk_expr(&mut expr_stmt.expr, f))?;
+                Some(true)
+            }
+            Stmt::Return(_) | Stmt::Throw(_) => {
+                self.version_accesses(versions, &mut |f| walk_stmt(stmt, f))?;
+                Some(false)
+            }
+            Stmt::Decl(Decl::Var(_)) => {
+                self.version_accesses(versions, &mut |f| walk_stmt(stmt, f))?;
+                Some(true)
+            }
+            Stmt::Debugger(_) | Stmt::Empty(_) => Some(true),
+            Stmt::Break(break_stmt) => {
+                let versions = versions.clone();
+                self.target(break_stmt.label.as_ref(), false)?
+                    .breaks
+                    .push(versions);
+                Some(false)
+            }
+            Stmt::Continue(continue_stmt) => {
+                let versions = versions.clone();
+                self.target(continue_stmt.label.as_ref(), true)?
+                    .continues
+                    .push(versions);
+                Some(false)
+            }
+            Stmt::Block(block) => self.version_stmts(&mut block.stmts, versions),
+            Stmt::If(if_stmt) => {
+                self.version_accesses(versions, &mut |f| walk_expr(&mut if_stmt.test, f))?;
+                let mut alt_versions = versions.clone();
+                let mut incoming = Vec::new();
+                if self.version_stmt(&mut if_stmt.cons, versions)? {
+                    incoming.push(versions.clone());
+                }
+                let alt_falls_through = match &mut if_stmt.alt {
+                    Some(alt) => self.version_stmt(alt, &mut alt_versions)?,
+                    None => true,
+                };
+                if alt_falls_through {
+                    incoming.push(alt_versions);
+                }
+                if incoming.is_empty() {
+                    return Some(false);
+                }
+                let mut phis = Vec::new();
+                *versions = self.join(&incoming, &mut phis);
+                self.add_join_phis(&mut if_stmt.span, phis);
+                Some(true)
+            }
+            Stmt::Labeled(labeled) => {
+                let label = Some(labeled.label.sym.to_string());
+                if matches!(
+                    &*labeled.body,
+                    Stmt::While(_) | Stmt::DoWhile(_) | Stmt::ForIn(_) | Stmt::ForOf(_)
+                ) {
+                    return self.version_loop(&mut labeled.body, label, versions);
+                }
+                self.targets.push(JumpTarget {
+                    label,
+                    is_loop: false,
+                    breaks: Vec::new(),
+                    continues: Vec::new(),
+                });
+                let falls_through = self.version_stmt(&mut labeled.body, versions);
+                let mut incoming = self.targets.pop().unwrap().breaks;
+                if falls_through? {
+                    incoming.push(versions.clone());
+                }
+                if incoming.is_empty() {
+                    return Some(false);
+                }
+                let mut phis = Vec::new();
+                *versions = self.join(&incoming, &mut phis);
+                self.add_join_phis(&mut labeled.span, phis);
+                Some(true)
+            }
+            Stmt::While(_) | Stmt::DoWhile(_) | Stmt::ForIn(_) | Stmt::ForOf(_) => {
+                self.version_loop(stmt, None, versions)
+            }
+            _ => None,
+        }
+    }
+
+    //the registers a loop writes get a version at its header, joining the versions they enter
+    //the loop with and the ones they have when it jumps back
+    fn version_loop(
+        &mut self,
+        stmt: &mut Stmt,
+        label: Option<String>,
+        versions: &mut Versions,
+    ) -> Option<bool> {
+        let mut written = Vec::new();
+        if !walk_stmt(&mut stmt.clone(), &mut |ident, access| {
+            let reg = ident.sym.to_string();
+            if access != Access::Read && is_register(&reg) && !written.contains(&reg) {
+                written.push(reg);
+            }
+        }) {
+            return None;
+        }
+        //what's iterated is evaluated once, before the loop
+        if let Stmt::ForIn(ForInStmt { right, .. }) | Stmt::ForOf(ForOfStmt { right, .. }) = stmt {
+            self.version_accesses(versions, &mut |f| walk_expr(right, f))?;
+        }
+        let entry = versions.clone();
+        let header: Vec<(String, String)> = written
+            .into_iter()
+            .map(|reg| {
+                let version = self.define(versions, &reg);
+                (reg, version)
+            })
+            .collect();
+        self.targets.push(JumpTarget {
+            label,
+            is_loop: true,
+            breaks: Vec::new(),
+            continues: Vec::new(),
+        });
+
+        let mut phis = Vec::new();
+        let (back, exits) = match stmt {
+            Stmt::While(while_stmt) => {
+                self.version_accesses(versions, &mut |f| walk_expr(&mut while_stmt.test, f))?;
+                let exit = versions.clone();
+                let falls_through = self.version_stmt(&mut while_stmt.body, versions)?;
+                let mut back = std::mem::take(&mut self.targets.last_mut().unwrap().continues);
+                if falls_through {
+                    back.push(versions.clone());
+                }
+                (back, vec![exit])
+            }
+            Stmt::DoWhile(do_while) => {
+                let falls_through = self.version_stmt(&mut do_while.body, versions)?;
+                let mut to_test = std::mem::take(&mut self.targets.last_mut().unwrap().continues);
+                if falls_through {
+                    to_test.push(versions.clone());
+                }
+                if to_test.is_empty() {
+                    (Vec::new(), Vec::new())
+                } else {
+                    //continues join the end of the body at the condition
+                    *versions = self.join(&to_test, &mut phis);
+                    self.version_accesses(versions, &mut |f| walk_expr(&mut do_while.test, f))?;
+                    (vec![versions.clone()], vec![versions.clone()])
+                }
+            }
+            Stmt::ForIn(ForInStmt { left, body, .. })
+            | Stmt::ForOf(ForOfStmt { left, body, .. }) => {
+                //the loop ends at its header, when nothing is left to iterate
+                let exit = versions.clone();
+                let VarDeclOrPat::Pat(pat) = left else {
+                    return None;
+                };
+                let Pat::Ident(binding) = &mut **pat else {
+                    return None;
+                };
+                if is_register(&binding.id.sym) {
+                    binding.id.sym = self.define(versions, &binding.id.sym).as_str().into();
+                }
+                let falls_through = self.version_stmt(body, versions)?;
+                let mut back = std::mem::take(&mut self.targets.last_mut().unwrap().continues);
+                if falls_through {
+                    back.push(versions.clone());
+                }
+                (back, vec![exit])
+            }
+            _ => unreachable!(),
+        };
+
+        let mut exits = exits;
+        exits.append(&mut self.targets.pop().unwrap().breaks);
+        let falls_through = !exits.is_empty();
+        if falls_through {
+            *versions = self.join(&exits, &mut phis);
+        }
+
+        let mut loop_phis: Vec<(bool, String)> = header
+            .iter()
+            .map(|(reg, version)| {
+                let mut sources = vec![version_of(&entry, reg)];
+                for versions in &back {
+                    let source = version_of(versions, reg);
+                    if !sources.contains(&source) {
+                        sources.push(source);
+                    }
+                }
+                (false, format!("{version} = phi({})", sources.join(", ")))
+            })
+            .collect();
+        //the joins at the condition of a do..while and at the exit follow the loop
+        loop_phis.extend(phis.into_iter().map(|phi| (true, phi)));
+        if !loop_phis.is_empty() {
+            let span = match stmt {
+                Stmt::While(while_stmt) => &mut while_stmt.span,
+                Stmt::DoWhile(do_while) => &mut do_while.span,
+                Stmt::ForIn(for_in) => &mut for_in.span,
+                Stmt::ForOf(for_of) => &mut for_of.span,
+                _ => unreachable!(),
+            };
+            let pos = self.comment_span(span);
+            self.phis.extend(
+                loop_phis
+                    .into_iter()
+                    .map(|(trailing, phi)| (pos, trailing, phi)),
+            );
+        }
+        Some(falls_through)
+    }
+}
+
+//r++ becomes r = r + 1 and r += x becomes r = r + x, so the read and the write are versioned
+//apart. Registers updated inside a larger expression only get a new version
+fn expand_update(expr: &mut Expr) {
+    let (reg, op, value) = match expr {
+        Expr::Update(update) => {
+            let Expr::Ident(reg) = &*update.arg else {
+                return;
+            };
+            let op = match update.op {
+                UpdateOp::PlusPlus => BinaryOp::Add,
+                UpdateOp::MinusMinus => BinaryOp::Sub,
+            };
+            (
+                reg.clone(),
+                op,
+                Box::new(Expr::Lit(Lit::Num(number_literal(1.0)))),
+            )
+        }
+        Expr::Assign(assign) => {
+            let (Some(op), PatOrExpr::Expr(left)) = (assign.op.to_update(), &assign.left) else {
+                return;
+            };
+            let Expr::Ident(reg) = &**left else {
+                return;
+            };
+            (reg.clone(), op, assign.right.clone())
+        }
+        _ => return,
+    };
+    if !is_register(&reg.sym) {
+        return;
+    }
+    *expr = Expr::Assign(AssignExpr {
+        span: DUMMY_SP,
+        op: AssignOp::Assign,
+        left: PatOrExpr::Expr(Box::new(Expr::Ident(reg.clone()))),
+        right: Box::new(Expr::Bin(BinExpr {
+            span: DUMMY_SP,
+            op,
+            left: Box::new(Expr::Ident(reg)),
+            right: value,
+        })),
+    });
+}
+
+/// Names every definition of a register apart, for reading the dataflow in near-SSA form:
+/// `r0 = 1; r0 = r0 + 1;` becomes `r0_1 = 1; r0_2 = r0_1 + 1;`, and registers read before
+/// being written are `rN_0`.
+///
+/// Where control flow joins with different versions of a register, the join gets a new one
+/// noted in a `// r0_3 = phi(r0_1, r0_2)` comment, after an `if` or before a loop for the
+/// versions flowing into its header. Versions made by phis are never assigned, so the output
+/// is for reading only. The comments are put at positions from `first_pos` on.
+pub fn version_registers(stmts: &mut [Stmt], comments: &SingleThreadedComments, first_pos: u32) {
+    let mut versioner = Versioner {
+        counts: HashMap::new(),
+        targets: Vec::new(),
+        phis: Vec::new(),
+        next_pos: first_pos,
+    };
+    let mut versioned = stmts.to_vec();
+    if versioner
+        .version_stmts(&mut versioned, &mut Versions::new())
+        .is_none()
+    {
+        return;
+    }
+    stmts.clone_from_slice(&versioned);
+    for (pos, trailing, phi) in versioner.phis {
+        let comment = Comment {
+            kind: CommentKind::Line,
+            span: DUMMY_SP,
+            text: format!(" {phi}").into(),
+        };
+        if trailing {
+            comments.add_trailing(pos, comment);
+        } else {
+            comments.add_leading(pos, comment);
+        }
+    }
+}
+
+/// Declares registers that are assigned once as `const` where they are assigned:
+/// `r0 = x; f(r0);` becomes `const r0 = x; f(r0);`.
+///
+/// A register only qualifies when its assignment is a statement of a block and every read
+/// of it comes after that statement in the same block, so the declaration is in scope for
+/// all of them. The rest are left to [`declare_registers`].
+pub fn declare_constants(stmts: &mut Vec<Stmt>, register_count: usize) {
+    let mut writes: HashMap<String, usize> = HashMap::new();
+    let mut reads: HashMap<String, usize> = HashMap::new();
+    if !walk_stmts(stmts, &mut |ident, access| {
+        let sym = ident.sym.to_string();
+        match access {
+            Access::Read => *reads.entry(sym).or_default() += 1,
+            Access::Write => *writes.entry(sym).or_default() += 1,
+            //never a const
+            Access::ReadWrite => *writes.entry(sym).or_default() += 2,
+        }
+    }) {
+        return;
+    }
+    let is_candidate = |reg: &str| {
+        is_register(reg)
+            && reg[1..]
+                .parse::<usize>()
+                .is_ok_and(|reg| reg < register_count)
+            && writes.get(reg) == Some(&1)
+    };
+
+    for_each_block(stmts, &mut |stmts| {
+        //reads of every register in the statements after the current one
+        let mut later_reads: HashMap<String, usize> = HashMap::new();
+        for stmt in stmts.iter_mut().rev() {
+            let mut stmt_reads = Vec::new();
+            walk_stmt(stmt, &mut |ident, access| {
+                if access != Access::Write {
+                    stmt_reads.push(ident.sym.to_string());
+                }
+            });
+            if let Some((dst, value)) = register_store(stmt) {
+                let all_later = reads.get(dst) == later_reads.get(dst);
+                if is_candidate(dst) && all_later && !stmt_reads.iter().any(|r| r == dst) {
+                    //annotations are attached to the statement's span
+                    let span = match &*stmt {
+                        Stmt::Expr(expr_stmt) => expr_stmt.span,
+                        _ => DUMMY_SP,
+                    };
+                    *stmt = Stmt::Decl(Decl::Var(Box::new(VarDecl {
+                        span,
+                        kind: VarDeclKind::Const,
+                        declare: false,
+                        decls: vec![VarDeclarator {
+                            span: DUMMY_SP,
+                            name: Pat::Ident(BindingIdent {
+                                id: Ident::new(dst.into(), DUMMY_SP),
+                                type_ann: None,
+                            }),
+                            init: Some(Box::new(value.clone())),
+                            definite: false,
+                        }],
+                    })));
+                }
+            }
+            for reg in stmt_reads {
+                *later_reads.entry(reg).or_default() += 1;
+            }
+        }
+    });
+}
+
+/// Prepends `let r0, r1, ...;` declaring every register used in the function that
+/// [`declare_constants`] didn't already declare.
+/// Only the function's `register_count` registers are declared, other `rN` names aren't registers.
+/// When some statement can't be walked all of them are.
+pub fn declare_registers(stmts: &mut Vec<Stmt>, register_count: usize) {
+    let mut declared = HashSet::new();
+    for_each_block(stmts, &mut |stmts| {
+        for stmt in stmts.iter() {
+            if let Stmt::Decl(Decl::Var(var)) = stmt {
+                for decl in &var.decls {
+                    if let Pat::Ident(binding) = &decl.name {
+                        declared.insert(binding.id.sym.to_string());
+                    }
+                }
+            }
+        }
+    });
+    let mut registers = Vec::new();
+    let walked = walk_stmts(stmts, &mut |ident, _| {
+        if is_register(&ident.sym) && !declared.contains(&*ident.sym) {
+            let reg: usize = ident.sym[1..].parse().unwrap();
+            if reg < register_count && !registers.contains(&reg) {
+                registers.push(reg);
+            }
+        }
+    });
+    if !walked {
+        //the walk stopped at a statement it can't look into, the registers used past it are
+        //unknown so all of them are declared
+        registers = (0..register_count)
+            .filter(|reg| !declared.contains(&format!("r{reg}")))
+            .collect();
+    }
+    if registers.is_empty() {
+        return;
+    }
+    registers.sort();
+    stmts.insert(
+        0,
+        Stmt::Decl(Decl::Var(Box::new(VarDecl {
+            span: DUMMY_SP,
+            kind: VarDeclKind::Let,
+            declare: false,
+            decls: registers
+                .into_iter()
+                .map(|reg| VarDeclarator {
+                    span: DUMMY_SP,
+                    name: Pat::Ident(BindingIdent {
+                        id: Ident::new(format!("r{reg}").as_str().into(), DUMMY_SP),
+                        type_ann: None,
+                    }),
+                    init: None,
+                    definite: false,
+                })
+                .collect(),
+        }))),
+    );
+}
+
+/// `: any`
+pub fn any_type_ann() -> Box<TsTypeAnn> {
+    Box::new(TsTypeAnn {
+        span: DUMMY_SP,
+        type_ann: Box::new(TsType::TsKeywordType(TsKeywordType {
+            span: DUMMY_SP,
+            kind: TsKeywordTypeKind::TsAnyKeyword,
+        })),
+    })
+}
+
+/// Types every declared variable as `any`, so `let r0, r1;` becomes `let r0: any, r1: any;`
+/// and the output is valid TypeScript.
+pub fn declare_any(stmts: &mut Vec<Stmt>) {
+    for_each_block(stmts, &mut |stmts| {
+        for stmt in stmts.iter_mut() {
+            let Stmt::Decl(Decl::Var(var)) = stmt else {
+                continue;
+            };
+            for decl in &mut var.decls {
+                if let Pat::Ident(binding) = &mut decl.name {
+                    binding.type_ann = Some(any_type_ann());
+                }
+            }
+        }
+    });
+}
+
+fn register_store(stmt: &Stmt) -> Option<(&str, &Expr)> {
+    if let Stmt::Expr(ExprStmt { expr, .. }) = stmt {
+        if let Expr::Assign(AssignExpr {
+            op: AssignOp::Assign,
+            left: PatOrExpr::Expr(left),
+            right,
+            ..
+        }) = &**expr
+        {
+            if let Expr::Ident(dst) = &**left {
+                if is_register(&dst.sym) {
+                    return Some((&dst.sym, right));
+                }
+            }
+        }
+    }
+    None
+}
+
+fn as_ident(expr: &Expr) -> Option<&str> {
+    match expr {
+        Expr::Ident(ident) => Some(&ident.sym),
+        _ => None,
+    }
+}
+
+//Object.create(rP, {constructor: {value: rC}}) -> rC
+fn create_this_closure(value: &Expr) -> Option<&str> {
+    let Expr::Call(call) = value else {
+        return None;
+    };
+    let Callee::Expr(callee) = &call.callee else {
+        return None;
+    };
+    let Expr::Member(member) = &**callee else {
+        return None;
+    };
+    match (as_ident(&member.obj), &member.prop) {
+        (Some("Object"), MemberProp::Ident(prop)) if &*prop.sym == "create" => (),
+        _ => return None,
+    }
+    let [_, descriptor] = call.args.as_slice() else {
+        return None;
+    };
+    let mut value = &*descriptor.expr;
+    //{constructor: {value: rC}}
+    for key in ["constructor", "value"] {
+        let Expr::Object(object) = value else {
+            return None;
+        };
+        let [PropOrSpread::Prop(prop)] = object.props.as_slice() else {
+            return None;
+        };
+        let Prop::KeyValue(key_value) = &**prop else {
+            return None;
+        };
+        match &key_value.key {
+            PropName::Ident(ident) if &*ident.sym == key => value = &key_value.value,
+            _ => return None,
+        }
+    }
+    as_ident(value)
+}
+
+//rR instanceof Object ? rR : rT -> (rR, rT)
+fn select_object(value: &Expr) -> Option<(&str, &str)> {
+    let Expr::Cond(cond) = value else {
+        return None;
+    };
+    let Expr::Bin(test) = &*cond.test else {
+        return None;
+    };
+    let result = as_ident(&cond.cons)?;
+    if test.op != BinaryOp::InstanceOf
+        || as_ident(&test.left) != Some(result)
+        || as_ident(&test.right) != Some("Object")
+    {
+        return None;
+    }
+    Some((result, as_ident(&cond.alt)?))
+}
+
+fn for_each_block(stmts: &mut Vec<Stmt>, f: &mut dyn FnMut(&mut Vec<Stmt>)) {
+    f(stmts);
+    for stmt in stmts {
+        for_each_block_in_stmt(stmt, f);
+    }
+}
+
+fn for_each_block_in_stmt(stmt: &mut Stmt, f: &mut dyn FnMut(&mut Vec<Stmt>)) {
+    match stmt {
+        Stmt::Block(block) => for_each_block(&mut block.stmts, f),
+        Stmt::Labeled(labeled) => for_each_block_in_stmt(&mut labeled.body, f),
+        Stmt::If(if_stmt) => {
+            for_each_block_in_stmt(&mut if_stmt.cons, f);
+            if let Some(alt) = &mut if_stmt.alt {
+                for_each_block_in_stmt(alt, f);
+            }
+        }
+        Stmt::While(while_stmt) => for_each_block_in_stmt(&mut while_stmt.body, f),
+        Stmt::DoWhile(do_while) => for_each_block_in_stmt(&mut do_while.body, f),
+        Stmt::ForIn(for_in) => for_each_block_in_stmt(&mut for_in.body, f),
+        Stmt::ForOf(for_of) => for_each_block_in_stmt(&mut for_of.body, f),
+        Stmt::Try(try_stmt) => {
+            for_each_block(&mut try_stmt.block.stmts, f);
+            if let Some(handler) = &mut try_stmt.handler {
+                for_each_block(&mut handler.body.stmts, f);
+            }
+            if let Some(finalizer) = &mut try_stmt.finalizer {
+                for_each_block(&mut finalizer.stmts, f);
+            }
+        }
+        _ => (),
+    }
+}
+
+//removes the copies of a while loop's condition block that run before each continue of it
+fn strip_loop_header(body: &mut Vec<Stmt>, header: &[Stmt]) {
+    for_each_block(body, &mut |stmts| {
+        let mut k = header.len();
+        while k < stmts.len() {
+            if matches!(&stmts[k], Stmt::Continue(ContinueStmt { label: None, .. }))
+                && stmts[k - header.len()..k] == *header
+            {
+                stmts.drain(k - header.len()..k);
+                k -= header.len();
+            }
+            k += 1;
+        }
+    });
+}
+
+/// Collapses the sequence Hermes emits for `new X(...)`:
+///
+/// ```text
+/// rT = Object.create(rP, {constructor: {value: rX}});
+/// rA = rT;
+/// rR = new rX(rA, ...args);
+/// rD = rR instanceof Object ? rR : rT;
+/// ```
+///
+/// into `rD = new rX(...args);`. Only applied when the temporaries aren't read anywhere else.
+pub fn collapse_constructor_calls(stmts: &mut Vec<Stmt>) {
+    let mut reads: HashMap<String, usize> = HashMap::new();
+    if !walk_stmts(stmts, &mut |ident, access| {
+        if access != Access::Write {
+            *reads.entry(ident.sym.to_string()).or_default() += 1;
+        }
+    }) {
+        return;
+    }
+    let read_count = |reg: &str| reads.get(reg).copied().unwrap_or(0);
+
+    for_each_block(stmts, &mut |stmts| {
+        let mut j = 0;
+        while j + 1 < stmts.len() {
+            match collapse_constructor_call(stmts, j, &read_count) {
+                Some(new_j) => j = new_j,
+                None => j += 1,
+            }
+        }
+    });
+}
+
+//tries to collapse the constructor call at stmts[j], returns the index of the collapsed statement
+fn collapse_constructor_call(
+    stmts: &mut Vec<Stmt>,
+    j: usize,
+    read_count: &dyn Fn(&str) -> usize,
+) -> Option<usize> {
+    let (result, construct) = register_store(&stmts[j])?;
+    let Expr::New(new) = construct else {
+        return None;
+    };
+    let closure = as_ident(&new.callee)?;
+    let args = new.args.as_ref()?;
+    let this_arg = as_ident(&args.first()?.expr)?;
+    let (dst, select) = register_store(&stmts[j + 1])?;
+    let (select_result, this) = select_object(select)?;
+    if select_result != result || read_count(result) != 2 {
+        return None;
+    }
+
+    //find CreateThis and the move of its result into the this argument, if there is one
+    let mut create_this = None;
+    let mut this_move = None;
+    for i in (0..j).rev() {
+        let mut written = Vec::new();
+        let walked = walk_simple_stmt(&mut stmts[i].clone(), &mut |ident, access| {
+            if access != Access::Read {
+                written.push(ident.sym.to_string());
+            }
+        });
+        if walked != Some(true) {
+            return None;
+        }
+        if let Some((target, value)) = register_store(&stmts[i]) {
+            if target == this {
+                if create_this_closure(value) != Some(closure) {
+                    return None;
+                }
+                create_this = Some(i);
+                break;
+            }
+            if target == this_arg && this_move.is_none() {
+                if as_ident(value) != Some(this) {
+                    return None;
+                }
+                this_move = Some(i);
+                continue;
+            }
+        }
+        if written
+            .iter()
+            .any(|w| w == closure || w == this || w == this_arg)
+        {
+            return None;
+        }
+    }
+    let create_this = create_this?;
+    match this_move {
+        //rT is read by the move and by SelectObject, the moved register only by the call
+        Some(_) if read_count(this) != 2 || read_count(this_arg) != 1 => return None,
+        None if this_arg != this || read_count(this) != 2 => return None,
+        _ => (),
+    }
+
+    let collapsed = Stmt::Expr(ExprStmt {
+        span: DUMMY_SP,
+        expr: Box::new(Expr::Assign(AssignExpr {
+            span: DUMMY_SP,
+            op: AssignOp::Assign,
+            left: PatOrExpr::Expr(Box::new(Expr::Ident(Ident::new(dst.into(), DUMMY_SP)))),
+            right: Box::new(Expr::New(NewExpr {
+                args: Some(args[1..].to_vec()),
+                ..new.clone()
+            })),
+        })),
+    });
+    stmts[j] = collapsed;
+    stmts.remove(j + 1);
+    let mut removed = vec![create_this];
+    removed.extend(this_move);
+    removed.sort();
+    for i in removed.iter().rev() {
+        stmts.remove(*i);
+    }
+    Some(j - removed.len())
+}
+
+//values that can be evaluated earlier than where they were loaded
+fn is_constant(expr: &Expr) -> bool {
+    match expr {
+        Expr::Lit(Lit::Num(_) | Lit::Str(_) | Lit::Bool(_) | Lit::Null(_)) => true,
+        Expr::Ident(ident) => &*ident.sym == "undefined" || &*ident.sym == EMPTY,
+        Expr::Unary(unary) => unary.op == UnaryOp::Minus && is_constant(&unary.arg),
+        _ => false,
+    }
+}
+
+enum LiteralStore<'a> {
+    //rO[index] = value
+    Index(f64, &'a str),
+    //rO.key = value
+    Key(&'a Ident, &'a str),
+}
+
+fn literal_store<'a>(stmt: &'a Stmt, obj: &str) -> Option<LiteralStore<'a>> {
+    let Stmt::Expr(ExprStmt { expr, .. }) = stmt else {
+        return None;
+    };
+    let Expr::Assign(AssignExpr {
+        op: AssignOp::Assign,
+        left: PatOrExpr::Expr(left),
+        right,
+        ..
+    }) = &**expr
+    else {
+        return None;
+    };
+    let Expr::Member(member) = &**left else {
+        return None;
+    };
+    if as_ident(&member.obj) != Some(obj) {
+        return None;
+    }
+    let value = as_ident(right)?;
+    if value == obj {
+        return None;
+    }
+    match &member.prop {
+        MemberProp::Ident(key) => Some(LiteralStore::Key(key, value)),
+        MemberProp::Computed(computed) => match &*computed.expr {
+            Expr::Lit(Lit::Num(index)) => Some(LiteralStore::Index(index.value, value)),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+fn accesses(stmt: &Stmt, reg: &str) -> Option<Vec<Access>> {
+    let mut accesses = Vec::new();
+    let walked = walk_simple_stmt(&mut stmt.clone(), &mut |ident, access| {
+        if &*ident.sym == reg {
+            accesses.push(access);
+        }
+    });
+    (walked == Some(true)).then_some(accesses)
+}
+
+/// Folds stores into a fresh array or object back into its literal:
+/// `r0 = []; r1 = 1; r0[0] = r1; r1 = 2; r0[1] = r1;` becomes `r0 = [1, 2];`.
+///
+/// Folding stops at the first statement that uses the array or object in any other way.
+pub fn fold_literals(stmts: &mut Vec<Stmt>) {
+    for_each_block(stmts, &mut |stmts| {
+        let mut i = 0;
+        while i < stmts.len() {
+            fold_literal(stmts, i);
+            i += 1;
+        }
+    });
+}
+
+fn fold_literal(stmts: &mut Vec<Stmt>, i: usize) {
+    let Some((obj, init)) = register_store(&stmts[i]) else {
+        return;
+    };
+    let is_array = match init {
+        Expr::Array(array) if array.elems.is_empty() => true,
+        Expr::Object(object) if object.props.is_empty() => false,
+        _ => return,
+    };
+    let obj = obj.to_string();
+
+    let mut elems = Vec::new();
+    let mut props = Vec::new();
+    let mut folded = Vec::new();
+    //loads of constants that were inlined into the literal
+    let mut inlined = Vec::new();
+    for k in i + 1..stmts.len() {
+        let (key, value_reg) = match literal_store(&stmts[k], &obj) {
+            Some(LiteralStore::Index(index, value)) if is_array && index == elems.len() as f64 => {
+                (None, value)
+            }
+            Some(LiteralStore::Key(key, value)) if !is_array => (Some(key.clone()), value),
+            Some(_) => break,
+            None => match accesses(&stmts[k], &obj) {
+                Some(accesses) if accesses.is_empty() => continue,
+                _ => break,
+            },
+        };
+
+        //the value has to be available where the literal is created
+        let last_store = (i + 1..k).rev().find(
+            |m| matches!(register_store(&stmts[*m]), Some((target, _)) if target == value_reg),
+        );
+        let value = match last_store {
+            Some(m) => {
+                let value = register_store(&stmts[m]).unwrap().1;
+                if !is_constant(value) {
+                    break;
+                }
+                if !inlined.contains(&m) {
+                    inlined.push(m);
+                }
+                value.clone()
+            }
+            None if (i + 1..k).any(|m| {
+                !matches!(accesses(&stmts[m], value_reg),
+                    Some(a) if a.iter().all(|a| *a == Access::Read))
+            }) =>
+            {
+                break
+            }
+            None => Expr::Ident(Ident::new(value_reg.into(), DUMMY_SP)),
+        };
+        props.extend(key);
+        elems.push(Some(ExprOrSpread {
+            spread: None,
+            expr: Box::new(value),
+        }));
+        folded.push(k);
+    }
+    if folded.is_empty() {
+        return;
+    }
+
+    //constant loads whose register is overwritten before being read again are no longer needed
+    let mut removed = folded.clone();
+    for m in inlined {
+        let Some((reg, _)) = register_store(&stmts[m]) else {
+            continue;
+        };
+        let reg = reg.to_string();
+        let next_access = (m + 1..stmts.len())
+            .filter(|k| !folded.contains(k))
+            .find_map(|k| match accesses(&stmts[k], &reg) {
+                Some(accesses) if accesses.is_empty() => None,
+                accesses => Some(accesses),
+            });
+        if let Some(Some(accesses)) = next_access {
+            if accesses[0] == Access::Write {
+                removed.push(m);
+            }
+        }
+    }
+
+    let Stmt::Expr(ExprStmt { expr, .. }) = &mut stmts[i] else {
+        unreachable!()
+    };
+    let Expr::Assign(AssignExpr { right, .. }) = &mut **expr else {
+        unreachable!()
+    };
+    **right = if is_array {
+        Expr::Array(ArrayLit {
+            span: DUMMY_SP,
+            elems,
+        })
+    } else {
+        Expr::Object(ObjectLit {
+            span: DUMMY_SP,
+            props: props
+                .into_iter()
+                .zip(elems)
+                .map(|(key, value)| {
+                    PropOrSpread::Prop(Box::new(Prop::KeyValue(KeyValueProp {
+                        key: PropName::Ident(key),
+                        value: value.unwrap().expr,
+                    })))
+                })
+                .collect(),
+        })
+    };
+    removed.sort();
+    for k in removed.iter().rev() {
+        stmts.remove(*k);
+    }
+}
+
+/// Parameters of the function Metro wraps every module in, `arguments[0]` being `this`
+pub const MODULE_PARAMS: [&str; 4] = ["global", "require", "module", "exports"];
+
+//rX = arguments[N] -> (rX, N)
+fn as_param_load(stmt: &Stmt) -> Option<(&str, usize)> {
+    let (reg, value) = register_store(stmt)?;
+    let Expr::Member(member) = value else {
+        return None;
+    };
+    if as_ident(&member.obj) != Some("arguments") {
+        return None;
+    }
+    let MemberProp::Computed(computed) = &member.prop else {
+        return None;
+    };
+    let index = match &*computed.expr {
+        Expr::Ident(index) => index.sym.parse().ok()?,
+        Expr::Lit(Lit::Num(index)) => index.value as usize,
+        _ => return None,
+    };
+    Some((reg, index))
+}
+
+/// Replaces loads of the module wrapper's parameters with the parameter names.
+///
+/// A register that is only ever assigned the parameter is replaced by the name everywhere,
+/// so `r1 = arguments[2]; r1(r2);` becomes `require(r2);`.
+pub fn name_module_params(stmts: &mut Vec<Stmt>) {
+    let mut writes: HashMap<String, usize> = HashMap::new();
+    let walked = walk_stmts(stmts, &mut |ident, access| {
+        if access != Access::Read {
+            *writes.entry(ident.sym.to_string()).or_default() += 1;
+        }
+    });
+
+    let mut names = HashMap::new();
+    for_each_block(stmts, &mut |stmts| {
+        stmts.retain_mut(|stmt| {
+            let Some((reg, index)) = as_param_load(stmt) else {
+                return true;
+            };
+            let reg = reg.to_string();
+            let Some(name) = index.checked_sub(1).and_then(|i| MODULE_PARAMS.get(i)) else {
+                return true;
+            };
+            if walked && writes.get(&reg) == Some(&1) {
+                names.insert(reg, *name);
+                return false;
+            }
+            if let Some((_, value)) = as_register_store(stmt) {
+                **value = Expr::Ident(Ident::new((*name).into(), DUMMY_SP));
+            }
+            true
+        });
+    });
+    walk_stmts(stmts, &mut |ident, _| {
+        if let Some(name) = names.get(&*ident.sym) {
+            ident.sym = (*name).into();
+        }
+    });
+}
+
+//exports.foo = e -> ("foo", e), module.exports = e -> ("default", e)
+fn as_export(stmt: &Stmt) -> Option<(&str, &Expr)> {
+    let Stmt::Expr(ExprStmt { expr, .. }) = stmt else {
+        return None;
+    };
+    let Expr::Assign(AssignExpr {
+        op: AssignOp::Assign,
+        left: PatOrExpr::Expr(left),
+        right,
+        ..
+    }) = &**expr
+    else {
+        return None;
+    };
+    let Expr::Member(member) = &**left else {
+        return None;
+    };
+    let MemberProp::Ident(prop) = &member.prop else {
+        return None;
+    };
+    match (as_ident(&member.obj)?, &*prop.sym) {
+        ("exports", name) => Some((name, right)),
+        ("module", "exports") => Some(("default", right)),
+        _ => None,
+    }
+}
+
+fn contains_return(stmt: &Stmt) -> bool {
+    match stmt {
+        Stmt::Return(_) => true,
+        Stmt::Block(block) => block.stmts.iter().any(contains_return),
+        Stmt::Labeled(labeled) => contains_return(&labeled.body),
+        Stmt::If(if_stmt) => {
+            contains_return(&if_stmt.cons) || if_stmt.alt.as_deref().is_some_and(contains_return)
+        }
+        Stmt::With(with) => contains_return(&with.body),
+        Stmt::While(while_stmt) => contains_return(&while_stmt.body),
+        Stmt::DoWhile(do_while) => contains_return(&do_while.body),
+        Stmt::For(for_stmt) => contains_return(&for_stmt.body),
+        Stmt::ForIn(for_in) => contains_return(&for_in.body),
+        Stmt::ForOf(for_of) => contains_return(&for_of.body),
+        Stmt::Switch(switch) => switch
+            .cases
+            .iter()
+            .any(|case| case.cons.iter().any(contains_return)),
+        Stmt::Try(try_stmt) => {
+            try_stmt.block.stmts.iter().any(contains_return)
+                || try_stmt
+                    .handler
+                    .as_ref()
+                    .is_some_and(|handler| handler.body.stmts.iter().any(contains_return))
+                || try_stmt
+                    .finalizer
+                    .as_ref()
+                    .is_some_and(|finalizer| finalizer.stmts.iter().any(contains_return))
+        }
+        _ => false,
+    }
+}
+
+/// Turns the body of a module wrapper into the top level of an ES module, writing
+/// `exports.foo = e;` as `export const foo = e;` and `module.exports = e;` as
+/// `export default e;` where they run at the top level.
+///
+/// The value the wrapper returns at its end is dropped, as the module system ignores it.
+/// Bodies returning anywhere else are handed back, there's nothing a module can return from.
+/// Names exported more than once, or used as a variable, stay writes to `exports`. The
+/// exported values aren't put on the exports object anymore, so the output is for reading
+/// and for tools importing it, not for running next to modules reading `exports.foo`.
+pub fn module_exports(mut stmts: Vec<Stmt>) -> Result<Vec<ModuleItem>, Vec<Stmt>> {
+    let last = match stmts.pop() {
+        Some(Stmt::Return(ret)) => Some(ret),
+        Some(stmt) => {
+            stmts.push(stmt);
+            None
+        }
+        None => None,
+    };
+    if stmts.iter().any(contains_return) {
+        stmts.extend(last.map(Stmt::Return));
+        return Err(stmts);
+    }
+    if let Some(arg) = last.and_then(|ret| ret.arg) {
+        if !matches!(*arg, Expr::Ident(_) | Expr::Lit(_)) {
+            stmts.push(Stmt::Expr(ExprStmt {
+                span: DUMMY_SP,
+                expr: arg,
+            }));
+        }
+    }
+
+    let mut used = HashSet::new();
+    let walked = walk_stmts(&mut stmts, &mut |ident, _| {
+        used.insert(ident.sym.to_string());
+    });
+    let mut exported: HashMap<String, usize> = HashMap::new();
+    for (name, _) in stmts.iter().filter_map(as_export) {
+        *exported.entry(name.to_string()).or_default() += 1;
+    }
+    let exportable = |name: &str| {
+        exported.get(name) == Some(&1)
+            && (name == "default"
+                || walked
+                    && is_valid_identifier(name)
+                    && !RESERVED_NAMES.contains(&name)
+                    && !used.contains(name))
+    };
+
+    Ok(stmts
+        .into_iter()
+        .map(|stmt| {
+            let Some((name, value)) = as_export(&stmt).filter(|(name, _)| exportable(name)) else {
+                return ModuleItem::Stmt(stmt);
+            };
+            let value = Box::new(value.clone());
+            ModuleItem::ModuleDecl(if name == "default" {
+                ModuleDecl::ExportDefaultExpr(ExportDefaultExpr {
+                    span: DUMMY_SP,
+                    expr: value,
+                })
+            } else {
+                ModuleDecl::ExportDecl(ExportDecl {
+                    span: DUMMY_SP,
+                    decl: Decl::Var(Box::new(VarDecl {
+                        span: DUMMY_SP,
+                        kind: VarDeclKind::Const,
+                        declare: false,
+                        decls: vec![VarDeclarator {
+                            span: DUMMY_SP,
+                            name: Pat::Ident(BindingIdent {
+                                id: Ident::new(name.into(), DUMMY_SP),
+                                type_ann: None,
+                            }),
+                            init: Some(value),
+                            definite: false,
+                        }],
+                    })),
+                })
+            })
+        })
+        .collect())
+}
+
+//rE.method(N, ...args) -> (rE, N, args)
+fn as_environment_call<'a>(
+    expr: &'a Expr,
+    method: &str,
+) -> Option<(&'a str, f64, &'a [ExprOrSpread])> {
+    let Expr::Call(call) = expr else {
+        return None;
+    };
+    let Callee::Expr(callee) = &call.callee else {
+        return None;
+    };
+    let Expr::Member(member) = &**callee else {
+        return None;
+    };
+    match &member.prop {
+        MemberProp::Ident(prop) if &*prop.sym == method => (),
+        _ => return None,
+    }
+    let env = as_ident(&member.obj).filter(|env| is_register(env))?;
+    let (slot, args) = call.args.split_first()?;
+    let Expr::Lit(Lit::Num(slot)) = &*slot.expr else {
+        return None;
+    };
+    Some((env, slot.value, args))
+}
+
+fn slot_ident(env: &str, slot: f64) -> Ident {
+    Ident::new(
+        format!("env{}_slot{slot}", &env[1..]).as_str().into(),
+        DUMMY_SP,
+    )
+}
+
+/// Replaces environment slot accesses with variables named after the environment register
+/// and slot, so `r1 = r0.get(2)` becomes `r1 = env0_slot2` and `r0.store(2, r1)` becomes
+/// `env0_slot2 = r1`.
+pub fn name_environment_slots(stmts: &mut Vec<Stmt>) {
+    for_each_block(stmts, &mut |stmts| {
+        for stmt in stmts.iter_mut() {
+            let Stmt::Expr(ExprStmt { expr, .. }) = stmt else {
+                continue;
+            };
+            if let Some((env, slot, [value])) = as_environment_call(expr, "store") {
+                let slot = slot_ident(env, slot);
+                let value = value.expr.clone();
+                **expr = Expr::Assign(AssignExpr {
+                    span: DUMMY_SP,
+                    op: AssignOp::Assign,
+                    left: PatOrExpr::Expr(Box::new(Expr::Ident(slot))),
+                    right: value,
+                });
+                continue;
+            }
+            if let Expr::Assign(AssignExpr {
+                op: AssignOp::Assign,
+                right,
+                ..
+            }) = &mut **expr
+            {
+                if let Some((env, slot, [])) = as_environment_call(right, "get") {
+                    **right = Expr::Ident(slot_ident(env, slot));
+                }
+            }
+        }
+    });
+}
+
+//rG.name where the name can be written as a bare identifier -> name
+fn global_name<'a>(expr: &'a Expr, global: &str) -> Option<&'a str> {
+    let Expr::Member(member) = expr else {
+        return None;
+    };
+    let MemberProp::Ident(prop) = &member.prop else {
+        return None;
+    };
+    let name = &*prop.sym;
+    (as_ident(&member.obj) == Some(global)
+        && is_valid_identifier(name)
+        && !RESERVED_NAMES.contains(&name)
+        && !is_register(name))
+    .then_some(name)
+}
+
+//the side of `x = rG.name` or `rG.name = x` accessing the global, with its name
+fn global_access<'a>(stmt: &'a mut Stmt, global: &str) -> Option<(&'a mut Expr, String)> {
+    let Stmt::Expr(ExprStmt { expr, .. }) = stmt else {
+        return None;
+    };
+    let Expr::Assign(AssignExpr {
+        left: PatOrExpr::Expr(left),
+        right,
+        ..
+    }) = &mut **expr
+    else {
+        return None;
+    };
+    if let Some(name) = global_name(left, global) {
+        let name = name.to_string();
+        return Some((left, name));
+    }
+    let name = global_name(right, global)?.to_string();
+    Some((right, name))
+}
+
+/// Replaces properties of the global object with bare references to the globals,
+/// `r0 = globalThis; r1 = r0.foo` becomes `r1 = foo`, when the register holding the global
+/// object is used for nothing else. Names the function assigns itself are kept as
+/// `globalThis.foo`, as a bare reference would mean the local instead.
+pub fn bare_globals(stmts: &mut Vec<Stmt>) {
+    let mut reads: HashMap<String, usize> = HashMap::new();
+    let mut writes: HashMap<String, usize> = HashMap::new();
+    if !walk_stmts(stmts, &mut |ident, access| {
+        let counts = if access == Access::Read {
+            &mut reads
+        } else {
+            &mut writes
+        };
+        *counts.entry(ident.sym.to_string()).or_default() += 1;
+    }) {
+        return;
+    }
+
+    for_each_block(stmts, &mut |stmts| {
+        let mut i = 0;
+        while i < stmts.len() {
+            let global = match register_store(&stmts[i]) {
+                Some((reg, value)) if as_ident(value) == Some("globalThis") => reg.to_string(),
+                _ => {
+                    i += 1;
+                    continue;
+                }
+            };
+            let used_once = reads.get(&global) == Some(&1) && writes.get(&global) == Some(&1);
+            let access = (i + 1..stmts.len()).find_map(|j| {
+                let (_, name) = global_access(&mut stmts[j], &global)?;
+                Some((j, name))
+            });
+            match access {
+                Some((j, name)) if used_once && !writes.contains_key(&name) => {
+                    let (expr, _) = global_access(&mut stmts[j], &global).unwrap();
+                    *expr = Expr::Ident(Ident::new(name.as_str().into(), DUMMY_SP));
+                    stmts.remove(i);
+                }
+                _ => i += 1,
+            }
+        }
+    });
+}
+
+//rA === undefined
+fn as_undefined_check(expr: &Expr) -> Option<&str> {
+    match expr {
+        Expr::Paren(paren) => as_undefined_check(&paren.expr),
+        Expr::Bin(bin)
+            if bin.op == BinaryOp::EqEqEq && as_ident(&bin.right) == Some("undefined") =>
+        {
+            as_ident(&bin.left)
+        }
+        _ => None,
+    }
+}
+
+//rL = Object.keys(rO) -> (rL, rO)
+fn as_property_list(stmt: &Stmt) -> Option<(&str, &Expr)> {
+    let (list, value) = register_store(stmt)?;
+    let Expr::Call(call) = value else {
+        return None;
+    };
+    let Callee::Expr(callee) = &call.callee else {
+        return None;
+    };
+    let Expr::Member(member) = &**callee else {
+        return None;
+    };
+    match (as_ident(&member.obj), &member.prop, &call.args[..]) {
+        (Some("Object"), MemberProp::Ident(keys), [obj]) if &*keys.sym == "keys" => {
+            Some((list, &obj.expr))
+        }
+        _ => None,
+    }
+}
+
+//the first access to each of `regs` in `stmts`, None if `stmts` couldn't be walked
+fn first_accesses(stmts: &[Stmt], regs: &[&str]) -> Option<BTreeMap<String, Access>> {
+    let mut first = BTreeMap::new();
+    let walked = walk_stmts(&mut stmts.to_vec(), &mut |ident, access| {
+        if regs.contains(&&*ident.sym) && !first.contains_key(&*ident.sym) {
+            first.insert(ident.sym.to_string(), access);
+        }
+    });
+    walked.then_some(first)
+}
+
+/// Rebuilds the `for (rK in rO)` loops Hermes lowers to a list of property names:
+///
+/// ```text
+/// rI = 0;
+/// rL = Object.keys(rO);
+/// rS = rL.length;
+/// if (rL === undefined) { ... }
+/// rK = rL[rI];
+/// rI++;
+/// while (!(rK === undefined)) { ... }
+/// ```
+///
+/// The `if` is dropped when its body is a copy of what follows the loop, as it is when the
+/// object has no properties to iterate, or when it is `if (!(rL === undefined))` wrapping the
+/// loop. Only applied when the list, index and size registers
+/// aren't used anywhere else.
+pub fn reconstruct_for_in(stmts: &mut Vec<Stmt>) {
+    for_each_block(stmts, &mut |stmts| {
+        let mut i = 0;
+        while i < stmts.len() {
+            reconstruct_for_in_at(stmts, i);
+            i += 1;
+        }
+    });
+}
+
+fn reconstruct_for_in_at(stmts: &mut Vec<Stmt>, i: usize) -> Option<()> {
+    let (index, start) = register_store(stmts.get(i)?)?;
+    if !matches!(start, Expr::Lit(Lit::Num(zero)) if zero.value == 0.0) {
+        return None;
+    }
+    let (list, obj) = as_property_list(stmts.get(i + 1)?)?;
+    let mut j = i + 2;
+    let mut size = None;
+    if let Some((reg, Expr::Member(member))) = stmts.get(j).and_then(register_store) {
+        if as_ident(&member.obj) == Some(list)
+            && matches!(&member.prop, MemberProp::Ident(length) if &*length.sym == "length")
+        {
+            size = Some(reg);
+            j += 1;
+        }
+    }
+    //the guard can also wrap the loop when what follows it isn't duplicated into an early return
+    if let Some(Stmt::If(if_stmt)) = stmts.get(j) {
+        if let (None, Expr::Unary(unary)) = (&if_stmt.alt, &*if_stmt.test) {
+            if unary.op == UnaryOp::Bang && as_undefined_check(&unary.arg) == Some(list) {
+                let mut inlined = stmts[..j].to_vec();
+                match &*if_stmt.cons {
+                    Stmt::Block(block) => inlined.extend(block.stmts.iter().cloned()),
+                    cons => inlined.push(cons.clone()),
+                }
+                inlined.extend(stmts[j + 1..].iter().cloned());
+                reconstruct_for_in_at(&mut inlined, i)?;
+                *stmts = inlined;
+                return Some(());
+            }
+        }
+    }
+    let mut guard = None;
+    if let Some(Stmt::If(if_stmt)) = stmts.get(j) {
+        if if_stmt.alt.is_none() && as_undefined_check(&if_stmt.test) == Some(list) {
+            guard = Some(j);
+            j += 1;
+        }
+    }
+
+    let (key, next) = register_store(stmts.get(j)?)?;
+    let Expr::Member(member) = next else {
+        return None;
+    };
+    let MemberProp::Computed(computed) = &member.prop else {
+        return None;
+    };
+    if as_ident(&member.obj) != Some(list) || as_ident(&computed.expr) != Some(index) {
+        return None;
+    }
+    let Some(Stmt::Expr(ExprStmt { expr, .. })) = stmts.get(j + 1) else {
+        return None;
+    };
+    if !matches!(&**expr, Expr::Update(update) if as_ident(&update.arg) == Some(index)) {
+        return None;
+    }
+    let (label, loop_stmt) = match stmts.get(j + 2)? {
+        Stmt::Labeled(labeled) => (Some(labeled.label.clone()), &*labeled.body),
+        stmt => (None, stmt),
+    };
+    let Stmt::While(while_stmt) = loop_stmt else {
+        return None;
+    };
+    let Expr::Unary(unary) = &*while_stmt.test else {
+        return None;
+    };
+    if unary.op != UnaryOp::Bang || as_undefined_check(&unary.arg) != Some(key) {
+        return None;
+    }
+
+    let mut body = match &*while_stmt.body {
+        Stmt::Block(block) => block.stmts.clone(),
+        body => vec![body.clone()],
+    };
+    strip_loop_header(&mut body, &stmts[j..j + 2]);
+    //the loop reads the key instead of the list, and nothing after it may read the list
+    let regs: Vec<&str> = [Some(index), Some(list), size]
+        .into_iter()
+        .flatten()
+        .collect();
+    if !first_accesses(&body, &regs)?.is_empty()
+        || first_accesses(&stmts[j + 3..], &regs)?
+            .values()
+            .any(|access| *access != Access::Write)
+    {
+        return None;
+    }
+    if matches!(
+        body.last(),
+        Some(Stmt::Continue(ContinueStmt { label: None, .. }))
+    ) {
+        body.pop();
+    }
+    let for_in = Stmt::ForIn(ForInStmt {
+        span: DUMMY_SP,
+        left: VarDeclOrPat::Pat(Box::new(Pat::Ident(BindingIdent {
+            id: Ident::new(key.into(), DUMMY_SP),
+            type_ann: None,
+        }))),
+        right: Box::new(obj.clone()),
+        body: Box::new(Stmt::Block(BlockStmt {
+            span: DUMMY_SP,
+            stmts: body,
+        })),
+    });
+    let for_in = match label {
+        Some(label) => Stmt::Labeled(LabeledStmt {
+            span: DUMMY_SP,
+            label,
+            body: Box::new(for_in),
+        }),
+        None => for_in,
+    };
+
+    //iterating undefined runs no iteration, so a guard doing what follows the loop is redundant
+    let keep_guard = guard.is_some_and(|guard| {
+        let Stmt::If(if_stmt) = &stmts[guard] else {
+            unreachable!()
+        };
+        match &*if_stmt.cons {
+            Stmt::Block(block) => block.stmts[..] != stmts[j + 3..],
+            cons => std::slice::from_ref(cons) != &stmts[j + 3..],
+        }
+    });
+    let guard = guard
+        .filter(|_| keep_guard)
+        .map(|guard| stmts[guard].clone());
+    stmts.splice(i..j + 3, guard.into_iter().chain([for_in]));
+    Some(())
+}
+
+//rI = rS[Symbol.iterator]() -> (rI, rS)
+fn as_iterator_begin(stmt: &Stmt) -> Option<(&str, &Expr)> {
+    let (iterator, value) = register_store(stmt)?;
+    let Expr::Call(call) = value else {
+        return None;
+    };
+    let Callee::Expr(callee) = &call.callee else {
+        return None;
+    };
+    let Expr::Member(member) = &**callee else {
+        return None;
+    };
+    let MemberProp::Computed(computed) = &member.prop else {
+        return None;
+    };
+    let Expr::Member(symbol) = &*computed.expr else {
+        return None;
+    };
+    match (as_ident(&symbol.obj), &symbol.prop) {
+        (Some("Symbol"), MemberProp::Ident(prop))
+            if &*prop.sym == "iterator" && call.args.is_empty() =>
+        {
+            Some((iterator, &member.obj))
+        }
+        _ => None,
+    }
+}
+
+//rA.prop
+fn as_member_of<'a>(expr: &'a Expr, prop: &str) -> Option<&'a str> {
+    let Expr::Member(member) = expr else {
+        return None;
+    };
+    match &member.prop {
+        MemberProp::Ident(ident) if &*ident.sym == prop => as_ident(&member.obj),
+        _ => None,
+    }
+}
+
+//rV = rI.next(); rI = rV.done ? undefined : rI; rV = rV.value; -> rV
+fn as_iterator_next<'a>(stmts: &'a [Stmt], iterator: &str) -> Option<&'a str> {
+    let [next, done, value, ..] = stmts else {
+        return None;
+    };
+    let (result, Expr::Call(call)) = register_store(next)? else {
+        return None;
+    };
+    let Callee::Expr(callee) = &call.callee else {
+        return None;
+    };
+    if as_member_of(callee, "next") != Some(iterator) || !call.args.is_empty() {
+        return None;
+    }
+    let (iterator_reg, Expr::Cond(cond)) = register_store(done)? else {
+        return None;
+    };
+    if iterator_reg != iterator
+        || as_member_of(&cond.test, "done") != Some(result)
+        || as_ident(&cond.cons) != Some("undefined")
+        || as_ident(&cond.alt) != Some(iterator)
+    {
+        return None;
+    }
+    let (value_reg, value) = register_store(value)?;
+    (value_reg == result && as_member_of(value, "value") == Some(result)).then_some(result)
+}
+
+//rI === undefined || rI.return == null || rI.return();
+fn is_iterator_close(stmt: &Stmt, iterator: &str) -> bool {
+    let Stmt::Expr(ExprStmt { expr, .. }) = stmt else {
+        return false;
+    };
+    let Expr::Bin(bin) = &**expr else {
+        return false;
+    };
+    let Expr::Call(call) = &*bin.right else {
+        return false;
+    };
+    let Callee::Expr(callee) = &call.callee else {
+        return false;
+    };
+    bin.op == BinaryOp::LogicalOr && as_member_of(callee, "return") == Some(iterator)
+}
+
+/// Rebuilds the `for (rV of rS)` loops Hermes lowers to `IteratorBegin`/`IteratorNext`:
+///
+/// ```text
+/// rI = rS[Symbol.iterator]();
+/// rV = rI.next();
+/// rI = rV.done ? undefined : rI;
+/// rV = rV.value;
+/// while (!(rI === rU)) { ... }
+/// ```
+///
+/// where rU holds undefined. The `IteratorClose` cleanups Hermes emits before leaving the loop
+/// early are dropped from the body, since `for-of` closes the iterator itself. Only applied
+/// when the iterator register isn't used anywhere else.
+pub fn reconstruct_for_of(stmts: &mut Vec<Stmt>) {
+    for_each_block(stmts, &mut |stmts| {
+        let mut i = 0;
+        while i < stmts.len() {
+            reconstruct_for_of_at(stmts, i);
+            i += 1;
+        }
+    });
+}
+
+fn reconstruct_for_of_at(stmts: &mut Vec<Stmt>, i: usize) -> Option<()> {
+    let (iterator, source) = as_iterator_begin(stmts.get(i)?)?;
+    let iterator = iterator.to_string();
+    //statements hoisted between IteratorBegin and the first IteratorNext stay before the loop,
+    //so they may not touch the iterator or what's being iterated
+    let regs: Vec<&str> = [Some(&*iterator), as_ident(source)]
+        .into_iter()
+        .flatten()
+        .collect();
+    let mut j = i + 1;
+    let value = loop {
+        if let Some(value) = as_iterator_next(&stmts[j..], &iterator) {
+            break value;
+        }
+        if !first_accesses(std::slice::from_ref(stmts.get(j)?), &regs)?.is_empty() {
+            return None;
+        }
+        j += 1;
+    };
+
+    let (label, loop_stmt) = match stmts.get(j + 3)? {
+        Stmt::Labeled(labeled) => (Some(labeled.label.clone()), &*labeled.body),
+        stmt => (None, stmt),
+    };
+    let Stmt::While(while_stmt) = loop_stmt else {
+        return None;
+    };
+    let Expr::Unary(unary) = &*while_stmt.test else {
+        return None;
+    };
+    let test = match &*unary.arg {
+        Expr::Paren(paren) => &*paren.expr,
+        test => test,
+    };
+    let Expr::Bin(test) = test else {
+        return None;
+    };
+    if unary.op != UnaryOp::Bang
+        || test.op != BinaryOp::EqEqEq
+        || as_ident(&test.left) != Some(&iterator)
+    {
+        return None;
+    }
+    let end = as_ident(&test.right)?;
+    let holds_undefined = stmts[..j]
+        .iter()
+        .rev()
+        .find_map(|stmt| register_store(stmt).filter(|(reg, _)| *reg == end))
+        .is_some_and(|(_, value)| as_ident(value) == Some("undefined"));
+    if end != "undefined" && !holds_undefined {
+        return None;
+    }
+
+    let mut body = match &*while_stmt.body {
+        Stmt::Block(block) => block.stmts.clone(),
+        body => vec![body.clone()],
+    };
+    strip_loop_header(&mut body, &stmts[j..j + 3]);
+    for_each_block(&mut body, &mut |stmts| {
+        stmts.retain(|stmt| !is_iterator_close(stmt, &iterator));
+    });
+    //the loop and what follows it may only overwrite the iterator
+    if !first_accesses(&body, &[&iterator])?.is_empty()
+        || first_accesses(&stmts[j + 4..], &[&iterator])?
+            .values()
+            .any(|access| *access != Access::Write)
+    {
+        return None;
+    }
+    if matches!(
+        body.last(),
+        Some(Stmt::Continue(ContinueStmt { label: None, .. }))
+    ) {
+        body.pop();
+    }
+    let for_of = Stmt::ForOf(ForOfStmt {
+        span: DUMMY_SP,
+        is_await: false,
+        left: VarDeclOrPat::Pat(Box::new(Pat::Ident(BindingIdent {
+            id: Ident::new(value.into(), DUMMY_SP),
+            type_ann: None,
+        }))),
+        right: Box::new(source.clone()),
+        body: Box::new(Stmt::Block(BlockStmt {
+            span: DUMMY_SP,
+            stmts: body,
+        })),
+    });
+    let for_of = match label {
+        Some(label) => Stmt::Labeled(LabeledStmt {
+            span: DUMMY_SP,
+            label,
+            body: Box::new(for_of),
+        }),
+        None => for_of,
+    };
+    let hoisted: Vec<Stmt> = stmts[i + 1..j].to_vec();
+    stmts.splice(i..j + 4, hoisted.into_iter().chain([for_of]));
+    Some(())
+}
+
+/// Folds chains of `+` that concatenate strings into template literals:
+/// `r1 = "a"; r2 = r1 + r0; r3 = "b"; r4 = r2 + r3;` becomes ``r4 = `a${r0}b`;``.
+///
+/// A chain is only folded when one of its first two operands is a string, so that every `+`
+/// in it concatenates; `r0 + r1 + "px"` keeps the numeric addition as ``${r0 + r1}px``.
+/// String loads that are only read by the chain are inlined into it.
+///
+/// Opt-in, as the result isn't always equivalent: `+` converts objects with
+/// `valueOf` first while templates use `toString`, which differs for e.g. `Date`s.
+pub fn fold_templates(stmts: &mut Vec<Stmt>) {
+    let mut reads: HashMap<String, usize> = HashMap::new();
+    if !walk_stmts(stmts, &mut |ident, access| {
+        if access != Access::Write {
+            *reads.entry(ident.sym.to_string()).or_default() += 1;
+        }
+    }) {
+        return;
+    }
+    let read_count = |reg: &str| reads.get(reg).copied().unwrap_or(0);
+
+    for_each_block(stmts, &mut |stmts| {
+        let mut i = 0;
+        while i < stmts.len() {
+            i = fold_template(stmts, i, &read_count) + 1;
+        }
+    });
+}
+
+//tries to fold the chain stored by stmts[i], returns the index of the folded statement
+fn fold_template(stmts: &mut Vec<Stmt>, i: usize, read_count: &dyn Fn(&str) -> usize) -> usize {
+    let Some((_, value)) = register_store(&stmts[i]) else {
+        return i;
+    };
+    if !matches!(value, Expr::Bin(bin) if bin.op == BinaryOp::Add) {
+        return i;
+    }
+    let mut operands = Vec::new();
+    flatten_add(value, &mut operands);
+
+    //strings loaded into registers that only the chain reads
+    let mut removed = Vec::new();
+    for operand in &mut operands {
+        let Some(reg) = as_ident(operand).filter(|reg| is_register(reg)) else {
+            continue;
+        };
+        if read_count(reg) != 1 {
+            continue;
+        }
+        if let Some((j, string)) = string_load(stmts, i, reg, &removed) {
+            *operand = string;
+            removed.push(j);
+        }
+    }
+    let Some(first_string) = operands.iter().position(is_string) else {
+        return i;
+    };
+    if first_string >= 2 {
+        //the operands before the first string are added as numbers
+        let prefix = operands.drain(..first_string).reduce(add).unwrap();
+        operands.insert(0, prefix);
+    }
+
+    let mut exprs = Vec::new();
+    let mut quasis = Vec::new();
+    let mut quasi = String::new();
+    for operand in operands {
+        match operand {
+            Expr::Lit(Lit::Str(string)) => quasi += &template_raw(&string.value),
+            Expr::Ident(ident) if &*ident.sym == "\"\"" => (),
+            Expr::Tpl(tpl) => {
+                let mut parts = tpl.quasis.into_iter();
+                quasi += &parts.next().unwrap().raw;
+                for (expr, part) in tpl.exprs.into_iter().zip(parts) {
+                    quasis.push(std::mem::take(&mut quasi));
+                    exprs.push(expr);
+                    quasi += &part.raw;
+                }
+            }
+            operand => {
+                quasis.push(std::mem::take(&mut quasi));
+                exprs.push(Box::new(operand));
+            }
+        }
+    }
+    quasis.push(quasi);
+    let count = quasis.len();
+    let tpl = Expr::Tpl(Tpl {
+        span: DUMMY_SP,
+        exprs,
+        quasis: quasis
+            .into_iter()
+            .enumerate()
+            .map(|(n, raw)| TplElement {
+                span: DUMMY_SP,
+                tail: n + 1 == count,
+                cooked: None,
+                raw: raw.as_str().into(),
+            })
+            .collect(),
+    });
+    if let Some((_, value)) = as_register_store(&mut stmts[i]) {
+        **value = tpl;
+    }
+
+    removed.sort();
+    for j in removed.iter().rev() {
+        stmts.remove(*j);
+    }
+    i - removed.len()
+}
+
+//a + b + c -> [a, b, c]
+fn flatten_add(expr: &Expr, operands: &mut Vec<Expr>) {
+    match expr {
+        Expr::Bin(bin) if bin.op == BinaryOp::Add => {
+            flatten_add(&bin.left, operands);
+            operands.push((*bin.right).clone());
+        }
+        expr => operands.push(expr.clone()),
+    }
+}
+
+fn add(left: Expr, right: Expr) -> Expr {
+    Expr::Bin(BinExpr {
+        span: DUMMY_SP,
+        op: BinaryOp::Add,
+        left: Box::new(left),
+        right: Box::new(right),
+    })
+}
+
+//AddEmptyString is lowered to `"" + rX` with `""` as an identifier
+fn is_string(expr: &Expr) -> bool {
+    match expr {
+        Expr::Lit(Lit::Str(_)) | Expr::Tpl(_) => true,
+        Expr::Ident(ident) => &*ident.sym == "\"\"",
+        _ => false,
+    }
+}
+
+//finds the store of a string into reg before stmts[i], which can be moved down to stmts[i]
+//if only constants are loaded in between
+fn string_load(stmts: &[Stmt], i: usize, reg: &str, removed: &[usize]) -> Option<(usize, Expr)> {
+    let mut written = Vec::new();
+    for j in (0..i).rev() {
+        if removed.contains(&j) {
+            continue;
+        }
+        let (target, value) = register_store(&stmts[j])?;
+        if target == reg {
+            let mut value = value.clone();
+            let mut clobbered = false;
+            let walked = walk_expr(&mut value, &mut |ident, _| {
+                clobbered |= written.iter().any(|w| *w == &*ident.sym);
+            });
+            return (is_string(&value) && walked && !clobbered).then_some((j, value));
+        }
+        if !is_constant(value) {
+            return None;
+        }
+        written.push(target);
+    }
+    None
+}
+
+//escapes what would end or interpolate the template, and characters that are hard to read
+fn template_raw(value: &str) -> String {
+    let mut raw = String::new();
+    let mut chars = value.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => raw += "\\\\",
+            '`' => raw += "\\`",
+            '$' if chars.peek() == Some(&'{') => raw += "\\$",
+            '\n' => raw += "\\n",
+            '\r' => raw += "\\r",
+            '\t' => raw += "\\t",
+            c if c.is_control() => raw += &format!("\\u{{{:x}}}", c as u32),
+            c => raw.push(c),
+        }
+    }
+    raw
+}
+
+//the argument of a call or construction in `expr` that is just `reg`
+fn call_argument<'a>(expr: &'a mut Expr, reg: &str) -> Option<&'a mut Box<Expr>> {
+    let is_reg = |arg: &ExprOrSpread| arg.spread.is_none() && as_ident(&arg.expr) == Some(reg);
+    match expr {
+        Expr::Call(call) => {
+            if let Some(arg) = call.args.iter_mut().find(|arg| is_reg(arg)) {
+                return Some(&mut arg.expr);
+            }
+            match &mut call.callee {
+                Callee::Expr(callee) => call_argument(callee, reg),
+                _ => None,
+            }
+        }
+        Expr::New(new) => {
+            let args = new.args.as_mut()?;
+            if let Some(arg) = args.iter_mut().find(|arg| is_reg(arg)) {
+                return Some(&mut arg.expr);
+            }
+            call_argument(&mut new.callee, reg)
+        }
+        Expr::Assign(assign) => call_argument(&mut assign.right, reg),
+        Expr::Member(member) => call_argument(&mut member.obj, reg),
+        Expr::Paren(paren) => call_argument(&mut paren.expr, reg),
+        Expr::Unary(unary) => call_argument(&mut unary.arg, reg),
+        Expr::Await(await_expr) => call_argument(&mut await_expr.arg, reg),
+        Expr::Seq(seq) => seq.exprs.iter_mut().find_map(|e| call_argument(e, reg)),
+        _ => None,
+    }
+}
+
+//fN or name_fN -> N, see generate_ast::function_ident
+fn as_closure(expr: &Expr) -> Option<u32> {
+    let (name, function_id) = as_ident(expr)?.rsplit_once('f')?;
+    if !name.is_empty() && !name.ends_with('_') {
+        return None;
+    }
+    function_id.parse().ok()
+}
+
+/// Replaces closures passed as an argument right after they are created with the function
+/// itself, `r1 = f3; r0.addEventListener("click", r1)` becomes
+/// `r0.addEventListener("click", function() {...})`.
+///
+/// `function_expr` returns the expression of function N, or None if it can't be inlined.
+/// Closures stored in a register that is read or written more than once keep their name,
+/// as do ones created more than once since they can't be told apart anymore.
+pub fn inline_closures(
+    stmts: &mut Vec<Stmt>,
+    function_expr: &mut dyn FnMut(u32) -> Option<Box<Expr>>,
+) {
+    let mut reads: HashMap<String, usize> = HashMap::new();
+    let mut writes: HashMap<String, usize> = HashMap::new();
+    let mut created: HashMap<u32, usize> = HashMap::new();
+    if !walk_stmts(stmts, &mut |ident, access| {
+        if access != Access::Write {
+            *reads.entry(ident.sym.to_string()).or_default() += 1;
+        }
+        if access != Access::Read {
+            *writes.entry(ident.sym.to_string()).or_default() += 1;
+        }
+    }) {
+        return;
+    }
+    for_each_register_store(stmts, &mut |_, value| {
+        if let Some(function_id) = as_closure(value) {
+            *created.entry(function_id).or_default() += 1;
+        }
+    });
+
+    for_each_block(stmts, &mut |stmts| {
+        let mut i = 0;
+        while i < stmts.len() {
+            let closure = register_store(&stmts[i]).and_then(|(reg, value)| {
+                let function_id = as_closure(value)?;
+                let single_use = reads.get(reg) == Some(&1)
+                    && writes.get(reg) == Some(&1)
+                    && created[&function_id] == 1;
+                single_use.then(|| (reg.to_string(), function_id))
+            });
+            let Some((reg, function_id)) = closure else {
+                i += 1;
+                continue;
+            };
+            //the only read, when it's in this block, comes after the only write
+            let arg = stmts[i + 1..].iter_mut().find_map(|stmt| match stmt {
+                Stmt::Expr(expr_stmt) => call_argument(&mut expr_stmt.expr, &reg),
+                Stmt::Return(ret) => call_argument(ret.arg.as_mut()?, &reg),
+                _ => None,
+            });
+            let Some(arg) = arg else {
+                i += 1;
+                continue;
+            };
+            match function_expr(function_id) {
+                Some(function) => {
+                    *arg = function;
+                    stmts.remove(i);
+                }
+                None => i += 1,
+            }
+        }
+    });
+}
@@ -0,0 +1,383 @@
+use std::io::Cursor;
+
+use hermes_dec::{
+    bytecode::{v93::Instruction, InstructionSet},
+    decompile_function,
+    hermes_file_reader::{ExceptionHandlerInfo, SmallStringTableEntry},
+    BytecodeError, BytecodeFile, BytecodeFileHeader, TryFromBytesError,
+};
+
+mod common;
+
+#[test]
+fn read_function_header() {
+    let bytecode = [120, 0, 90, 0];
+    let buf = common::bundle_with_function(&["global", "main"], 1, 2, &bytecode);
+    let file = BytecodeFile::from_reader(&mut Cursor::new(buf.as_slice())).unwrap();
+    assert_eq!(file.header.function_count, 1);
+
+    let header = file
+        .function_header(0, &mut Cursor::new(buf.as_slice()))
+        .unwrap()
+        .unwrap();
+    assert_eq!(file.get_string(header.function_name()), Some("main"));
+    assert_eq!(header.param_count(), 2);
+    assert_eq!(header.frame_size(), common::FRAME_SIZE);
+    assert_eq!(header.register_count(), common::FRAME_SIZE as usize);
+    assert_eq!(header.bytecode_size_in_bytes(), bytecode.len() as u32);
+    assert_eq!(
+        &buf[header.offset() as usize..][..bytecode.len()],
+        bytecode.as_slice()
+    );
+
+    let instructions = header
+        .disassemble_function::<Instruction, _>(&mut Cursor::new(buf.as_slice()))
+        .unwrap();
+    assert!(matches!(
+        instructions[0].instruction,
+        Instruction::LoadConstZero { dst_reg: 0 }
+    ));
+    assert_eq!(instructions[1].offset, 2);
+    assert!(file
+        .function_header(1, &mut Cursor::new(buf.as_slice()))
+        .unwrap()
+        .is_none());
+}
+
+#[test]
+fn instruction_iterator_matches_disassembly() {
+    #[rustfmt::skip]
+    let bytecode = [
+        120, 0, //LoadConstZero r0
+        52, 1, 0, 7, 1, //GetByIdShort r1, r0, cache 7, string 1
+        90, 1, //Ret r1
+    ];
+    let buf = common::bundle_with_function(&["global", "main"], 1, 1, &bytecode);
+    let file = BytecodeFile::from_reader(&mut Cursor::new(buf.as_slice())).unwrap();
+    let header = file
+        .function_header(0, &mut Cursor::new(buf.as_slice()))
+        .unwrap()
+        .unwrap();
+
+    let streamed = header
+        .instructions::<Instruction, _>(&mut Cursor::new(buf.as_slice()))
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap();
+    let collected = header
+        .disassemble_function::<Instruction, _>(&mut Cursor::new(buf.as_slice()))
+        .unwrap();
+    assert_eq!(streamed.len(), 3);
+    assert_eq!(format!("{streamed:?}"), format!("{collected:?}"));
+}
+
+#[test]
+fn get_by_id_operands_keep_stream_aligned() {
+    #[rustfmt::skip]
+    let bytecode = [
+        52, 1, 0, 7, 1, //GetByIdShort r1, r0, cache 7, string 1
+        53, 2, 0, 8, 2, 0, //GetById r2, r0, cache 8, string 2
+        54, 3, 0, 9, 3, 0, 0, 0, //GetByIdLong r3, r0, cache 9, string 3
+        90, 3, //Ret r3
+    ];
+    let buf = common::bundle_with_function(&[], 0, 1, &bytecode);
+    let file = BytecodeFile::from_reader(&mut Cursor::new(buf.as_slice())).unwrap();
+    let header = file
+        .function_header(0, &mut Cursor::new(buf.as_slice()))
+        .unwrap()
+        .unwrap();
+    let instructions = header
+        .disassemble_function::<Instruction, _>(&mut Cursor::new(buf.as_slice()))
+        .unwrap();
+
+    let offsets: Vec<u32> = instructions.iter().map(|i| i.offset).collect();
+    assert_eq!(offsets, [0, 5, 11, 19]);
+    let mut expected_offset = 0;
+    for (info, opcode) in instructions.iter().zip([52, 53, 54, 90]) {
+        assert_eq!(info.offset, expected_offset);
+        //opcode byte + operands
+        expected_offset += 1 + u32::from(Instruction::get_bytecode_size(opcode));
+    }
+    assert_eq!(expected_offset, header.bytecode_size_in_bytes());
+
+    assert!(matches!(
+        instructions[0].instruction,
+        Instruction::GetByIdShort {
+            dst_reg: 1,
+            obj_reg: 0,
+            cache_index: 7,
+            string_table_index: 1
+        }
+    ));
+    assert!(matches!(
+        instructions[1].instruction,
+        Instruction::GetById {
+            dst_reg: 2,
+            obj_reg: 0,
+            cache_index: 8,
+            string_table_index: 2
+        }
+    ));
+    assert!(matches!(
+        instructions[2].instruction,
+        Instruction::GetByIdLong {
+            dst_reg: 3,
+            obj_reg: 0,
+            cache_index: 9,
+            string_table_index: 3
+        }
+    ));
+    assert!(matches!(
+        instructions[3].instruction,
+        Instruction::Ret { value_reg: 3 }
+    ));
+}
+
+#[test]
+fn truncated_instruction_is_size_mismatch() {
+    //GetById needs 6 bytes but the function ends after 5
+    let bytecode = [53, 2, 0, 8, 2];
+    let buf = common::bundle_with_function(&[], 0, 1, &bytecode);
+    let file = BytecodeFile::from_reader(&mut Cursor::new(buf.as_slice())).unwrap();
+    let result = file.function_headers[0]
+        .disassemble_function::<Instruction, _>(&mut Cursor::new(buf.as_slice()));
+    assert!(matches!(
+        result,
+        Err(BytecodeError::SizeMismatch {
+            expected: 5,
+            actual: 6
+        })
+    ));
+}
+
+#[test]
+fn cjs_modules_map_module_ids_to_functions() {
+    let bytecode = [120, 0, 90, 0];
+    let functions = [0, 1, 2].map(|name| common::TestFunction {
+        name,
+        param_count: 1,
+        bytecode: &bytecode,
+    });
+
+    let buf = common::bundle_with_modules(
+        &["global", "a.js", "b.js"],
+        &functions,
+        &[(42, 2), (7, 1)],
+        true,
+    );
+    let file = BytecodeFile::from_reader(&mut Cursor::new(buf.as_slice())).unwrap();
+    assert_eq!(file.cjs_modules(), vec![(42, 2), (7, 1)]);
+
+    //without static resolution the table holds filenames and modules are numbered in order
+    let buf = common::bundle_with_modules(
+        &["global", "a.js", "b.js"],
+        &functions,
+        &[(1, 2), (2, 1)],
+        false,
+    );
+    let file = BytecodeFile::from_reader(&mut Cursor::new(buf.as_slice())).unwrap();
+    assert_eq!(file.cjs_modules(), vec![(0, 2), (1, 1)]);
+
+    let buf = common::bundle_with_functions(&["global"], &functions[..1]);
+    let file = BytecodeFile::from_reader(&mut Cursor::new(buf.as_slice())).unwrap();
+    assert!(file.cjs_modules().is_empty());
+}
+
+#[test]
+fn header_from_too_short_buffer() {
+    //magic takes the first 8 bytes, version needs 4 more
+    let err = BytecodeFileHeader::try_from_bytes(&[0; 10]).unwrap_err();
+    assert_eq!(
+        err,
+        TryFromBytesError {
+            field: "version",
+            offset: 8,
+            len: 10,
+        }
+    );
+
+    let buf = common::bundle_with_function(&[], 0, 0, &[90, 0]);
+    let header = BytecodeFileHeader::try_from_bytes(&buf).unwrap();
+    assert_eq!(header.function_count, 1);
+}
+
+#[test]
+fn header_from_unaligned_buffer() {
+    let buf = common::bundle_with_function(&[], 0, 0, &[90, 0]);
+    let mut shifted = vec![0];
+    shifted.extend_from_slice(&buf);
+    let header = BytecodeFileHeader::try_from_bytes(&shifted[1..]).unwrap();
+    assert_eq!(header.function_count, 1);
+}
+
+#[test]
+fn overflowed_header_read_from_large_header() {
+    //too many bytes for the small header's 15 bit size field
+    let mut large = [120, 0].repeat(20000); //LoadConstZero r0
+    large.extend_from_slice(&[90, 0]); //Ret r0
+    let functions = [
+        common::TestFunction {
+            name: 1,
+            param_count: 3,
+            bytecode: &large,
+        },
+        common::TestFunction {
+            name: 0,
+            param_count: 1,
+            bytecode: &[120, 0, 90, 0],
+        },
+    ];
+    let buf = common::bundle_with_functions(&["global", "big"], &functions);
+    let file = BytecodeFile::from_reader(&mut Cursor::new(buf.as_slice())).unwrap();
+
+    let header = file
+        .function_header(0, &mut Cursor::new(buf.as_slice()))
+        .unwrap()
+        .unwrap();
+    assert_eq!(header.bytecode_size_in_bytes(), large.len() as u32);
+    assert_eq!(header.param_count(), 3);
+    assert_eq!(header.frame_size(), common::FRAME_SIZE);
+    assert_eq!(file.get_string(header.function_name()).unwrap(), "big");
+
+    let instructions = file.function_headers[0]
+        .disassemble_function::<Instruction, _>(&mut Cursor::new(buf.as_slice()))
+        .unwrap();
+    assert_eq!(instructions.len(), 20001);
+    assert!(matches!(
+        instructions.last().unwrap().instruction,
+        Instruction::Ret { value_reg: 0 }
+    ));
+
+    //the function after it still starts where its small header says
+    let instructions = file.function_headers[1]
+        .disassemble_function::<Instruction, _>(&mut Cursor::new(buf.as_slice()))
+        .unwrap();
+    assert_eq!(instructions.len(), 2);
+}
+
+#[test]
+fn long_string_read_from_overflow_table() {
+    let long = "0123456789abcdef".repeat(20);
+    let buf = common::bundle_with_function(&["short", &long, "after"], 1, 0, &[120, 0, 90, 0]);
+    let file = BytecodeFile::from_reader(&mut Cursor::new(buf.as_slice())).unwrap();
+
+    assert_eq!(file.string_table_overflow_entries.len(), 1);
+    assert_eq!(file.get_string(0), Some("short"));
+    assert_eq!(file.get_string(1), Some(long.as_str()));
+    assert_eq!(file.get_string(2), Some("after"));
+
+    let file = BytecodeFile::from_bytes(&buf);
+    assert_eq!(file.get_string(1), Some(long.as_str()));
+}
+
+#[test]
+fn corrupted_string_table_fails_validation() {
+    let long = "0123456789abcdef".repeat(20);
+    let buf = common::bundle_with_function(&["short", &long], 0, 0, &[120, 0, 90, 0]);
+    let mut file = BytecodeFile::from_reader(&mut Cursor::new(buf.as_slice())).unwrap();
+    assert!(file.validate_string_table().is_ok());
+
+    //offset 1000, length 4, in storage a few hundred bytes long
+    let valid = file.string_table_entries[0];
+    file.string_table_entries[0] = SmallStringTableEntry::from((1000 << 1) | (4 << 24));
+    assert!(matches!(
+        file.validate_string_table(),
+        Err(BytecodeError::StringOutOfBounds {
+            index: 0,
+            end: 1004,
+            ..
+        })
+    ));
+    file.string_table_entries[0] = valid;
+
+    //the long string points at an overflow entry past the first one
+    let overflowed = u32::from(file.string_table_entries[1]);
+    file.string_table_entries[1] = SmallStringTableEntry::from(overflowed | (1 << 1));
+    assert!(matches!(
+        file.validate_string_table(),
+        Err(BytecodeError::OverflowEntryMismatch {
+            index: 1,
+            overflow_index: 1,
+            expected: 0
+        })
+    ));
+}
+
+#[test]
+fn functions_read_in_any_order() {
+    //LoadConstUInt8 r0, value; Ret r0, with bodies of different sizes
+    let bytecodes: Vec<Vec<u8>> = (0..4u8)
+        .map(|value| {
+            let mut bytecode = vec![108, 0, value];
+            bytecode.extend(std::iter::repeat_n([108, 1, value], value.into()).flatten());
+            bytecode.extend([90, 0]);
+            bytecode
+        })
+        .collect();
+    let functions: Vec<_> = bytecodes
+        .iter()
+        .map(|bytecode| common::TestFunction {
+            name: 0,
+            param_count: 0,
+            bytecode,
+        })
+        .collect();
+    let buf = common::bundle_with_functions(&[], &functions);
+    let file = BytecodeFile::from_reader(&mut Cursor::new(buf.as_slice())).unwrap();
+
+    let in_order: Vec<String> = (0..4)
+        .map(|function_id| decompile_function(&file, &buf, function_id).unwrap().code)
+        .collect();
+    for function_id in [3, 0, 2, 1, 3] {
+        assert_eq!(
+            decompile_function(&file, &buf, function_id).unwrap().code,
+            in_order[function_id]
+        );
+    }
+
+    //one reader, left wherever the previous read ended or somewhere else entirely
+    let mut reader = Cursor::new(buf.as_slice());
+    for (function_id, position) in [(3, 0), (0, buf.len()), (2, 7), (1, 0), (3, 1)] {
+        reader.set_position(position as u64);
+        let instructions = file.function_headers[function_id]
+            .disassemble_function::<Instruction, _>(&mut reader)
+            .unwrap();
+        assert_eq!(instructions.len(), function_id + 2);
+        assert!(matches!(
+            instructions[0].instruction,
+            Instruction::LoadConstUInt8 { dst_reg: 0, value } if usize::from(value) == function_id
+        ));
+    }
+}
+
+#[test]
+fn exception_handler_spans() {
+    //try { LoadConstZero r0; Throw r0 } catch (r1) { Ret r1 }
+    let bytecode = [120, 0, 93, 0, 91, 1, 90, 1];
+    let mut buf = common::bundle_with_functions(
+        &[],
+        &[
+            common::TestFunction {
+                name: 0,
+                param_count: 0,
+                bytecode: &[120, 0, 90, 0],
+            },
+            common::TestFunction {
+                name: 0,
+                param_count: 0,
+                bytecode: &bytecode,
+            },
+        ],
+    );
+    common::add_exception_handlers(&mut buf, 1, &[(0, 4, 4)]);
+    let file = BytecodeFile::from_reader(&mut Cursor::new(buf.as_slice())).unwrap();
+    assert_eq!(
+        file.exception_handlers(1),
+        [ExceptionHandlerInfo {
+            start: 0,
+            end: 4,
+            target: 4
+        }]
+    );
+    assert!(file.exception_handlers(0).is_empty());
+    assert!(file.exception_handlers(2).is_empty());
+}
@@ -0,0 +1,589 @@
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+mod common;
+
+//LoadConstZero r0; Ret r0
+const BYTECODE: &[u8] = &[120, 0, 90, 0];
+
+fn write_bundle(name: &str) -> std::path::PathBuf {
+    let functions = [
+        common::TestFunction {
+            name: 0,
+            param_count: 0,
+            bytecode: BYTECODE,
+        },
+        common::TestFunction {
+            name: 0,
+            param_count: 0,
+            bytecode: BYTECODE,
+        },
+    ];
+    let path = std::env::temp_dir().join(format!("hermes_dec_{name}_{}.hbc", std::process::id()));
+    std::fs::write(&path, common::bundle_with_functions(&[], &functions)).unwrap();
+    path
+}
+
+#[test]
+fn decompile_all_progress_goes_to_stderr() {
+    let bundle = write_bundle("progress");
+    let output_path = bundle.with_extension("js");
+    let output = Command::new(env!("CARGO_BIN_EXE_hermes_dec"))
+        .arg(&bundle)
+        .arg("decompile-all")
+        .arg(&output_path)
+        .output()
+        .unwrap();
+    let js = std::fs::read_to_string(&output_path).unwrap();
+    std::fs::remove_file(&bundle).unwrap();
+    std::fs::remove_file(&output_path).unwrap();
+
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("2/2"), "{stderr}");
+    assert!(js.contains("function f1()"), "{js}");
+    assert!(!js.contains("2/2"), "{js}");
+}
+
+#[test]
+fn bundle_read_from_stdin() {
+    //show-functions looks up every name, so the bundle needs a string table
+    let function = common::TestFunction {
+        name: 0,
+        param_count: 0,
+        bytecode: BYTECODE,
+    };
+    let bundle = std::env::temp_dir().join(format!("hermes_dec_stdin_{}.hbc", std::process::id()));
+    std::fs::write(
+        &bundle,
+        common::bundle_with_functions(&["main"], &[function]),
+    )
+    .unwrap();
+    let from_file = Command::new(env!("CARGO_BIN_EXE_hermes_dec"))
+        .arg(&bundle)
+        .arg("show-functions")
+        .output()
+        .unwrap();
+    let mut child = Command::new(env!("CARGO_BIN_EXE_hermes_dec"))
+        .arg("-")
+        .arg("show-functions")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(&std::fs::read(&bundle).unwrap())
+        .unwrap();
+    let from_stdin = child.wait_with_output().unwrap();
+    std::fs::remove_file(&bundle).unwrap();
+
+    let stdout = String::from_utf8(from_stdin.stdout).unwrap();
+    assert!(stdout.contains("Function 0: (name: main"), "{stdout}");
+    assert_eq!(stdout, String::from_utf8(from_file.stdout).unwrap());
+}
+
+#[test]
+fn out_of_range_function_id() {
+    let bundle = write_bundle("out_of_range");
+    let run = |command: &str| {
+        let output = Command::new(env!("CARGO_BIN_EXE_hermes_dec"))
+            .arg(&bundle)
+            .arg(command)
+            .arg("2")
+            .output()
+            .unwrap();
+        String::from_utf8(output.stdout).unwrap()
+    };
+    let disassembled = run("disassemble");
+    let cfg = run("cfg");
+    std::fs::remove_file(&bundle).unwrap();
+
+    assert!(
+        disassembled.contains("function id 2 out of range (0..2)"),
+        "{disassembled}"
+    );
+    assert!(cfg.contains("function id 2 out of range (0..2)"), "{cfg}");
+}
+
+#[test]
+fn header_shows_source_hash() {
+    let mut bytes = common::bundle_with_function(&[], 0, 0, BYTECODE);
+    //source_hash follows the magic and version
+    for (i, byte) in bytes[12..32].iter_mut().enumerate() {
+        *byte = i as u8;
+    }
+    let bundle = std::env::temp_dir().join(format!("hermes_dec_header_{}.hbc", std::process::id()));
+    std::fs::write(&bundle, &bytes).unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_hermes_dec"))
+        .arg(&bundle)
+        .arg("header")
+        .output()
+        .unwrap();
+    std::fs::remove_file(&bundle).unwrap();
+
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(
+        stdout.contains("Source hash: 000102030405060708090a0b0c0d0e0f10111213\n"),
+        "{stdout}"
+    );
+    assert!(stdout.contains("Functions: 1\n"), "{stdout}");
+}
+
+#[test]
+fn version_matches_header() {
+    let mut bytes = common::bundle_with_function(&["main"], 0, 0, BYTECODE);
+    let bundle =
+        std::env::temp_dir().join(format!("hermes_dec_version_{}.hbc", std::process::id()));
+    let run = |bytes: &[u8], json: bool| {
+        std::fs::write(&bundle, bytes).unwrap();
+        let mut command = Command::new(env!("CARGO_BIN_EXE_hermes_dec"));
+        command.arg(&bundle).arg("version");
+        if json {
+            command.arg("--json");
+        }
+        String::from_utf8(command.output().unwrap().stdout).unwrap()
+    };
+    let unsupported = run(&bytes, false);
+    //the version follows the magic
+    bytes[..8].copy_from_slice(&0x1F19_03C1_03BC_1FC6u64.to_le_bytes());
+    bytes[8..12].copy_from_slice(&93u32.to_le_bytes());
+    let supported = run(&bytes, false);
+    let json = run(&bytes, true);
+    std::fs::remove_file(&bundle).unwrap();
+
+    assert!(unsupported.contains("Version: 0\n"), "{unsupported}");
+    assert!(
+        unsupported.contains("Supported: no (supported versions: 93)\n"),
+        "{unsupported}"
+    );
+    assert!(supported.starts_with("Version: 93\n"), "{supported}");
+    assert!(supported.contains("Supported: yes"), "{supported}");
+    assert!(supported.contains("Strings: 1\n"), "{supported}");
+    let json: serde_json::Value = serde_json::from_str(&json).unwrap();
+    assert_eq!(json["version"], 93);
+    assert_eq!(json["supported"], true);
+    assert_eq!(json["strings"], 1);
+}
+
+#[test]
+fn decompile_all_to_stdout_has_no_progress() {
+    let bundle = write_bundle("stdout");
+    let output = Command::new(env!("CARGO_BIN_EXE_hermes_dec"))
+        .arg(&bundle)
+        .arg("decompile-all")
+        .output()
+        .unwrap();
+    std::fs::remove_file(&bundle).unwrap();
+
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("function f1()"), "{stdout}");
+    assert!(output.stderr.is_empty());
+}
+
+#[test]
+fn module_decompiles_resolved_function() {
+    //the module's function loads 5 so it can't be mistaken for function 0
+    let functions = [BYTECODE, &[108, 0, 5, 90, 0]].map(|bytecode| common::TestFunction {
+        name: 0,
+        param_count: 0,
+        bytecode,
+    });
+    let bundle = std::env::temp_dir().join(format!("hermes_dec_module_{}.hbc", std::process::id()));
+    std::fs::write(
+        &bundle,
+        common::bundle_with_modules(&[], &functions, &[(42, 1)], true),
+    )
+    .unwrap();
+    let run = |args: &[&str]| {
+        let output = Command::new(env!("CARGO_BIN_EXE_hermes_dec"))
+            .arg(&bundle)
+            .args(args)
+            .output()
+            .unwrap();
+        String::from_utf8(output.stdout).unwrap()
+    };
+    let module = run(&["module", "42"]);
+    let function = run(&["disassemble", "1"]);
+    let es_module = run(&["module", "42", "--es-module"]);
+    let es_function = run(&["disassemble", "1", "--es-module"]);
+    let missing = run(&["module", "7"]);
+    std::fs::remove_file(&bundle).unwrap();
+
+    assert!(
+        module.contains("function f1(global, require, module, exports)"),
+        "{module}"
+    );
+    assert!(module.contains("r0 = 5"), "{module}");
+    assert_eq!(module, function);
+    assert!(!es_module.contains("function f1"), "{es_module}");
+    assert!(es_module.contains("r0 = 5"), "{es_module}");
+    assert_eq!(es_module, es_function);
+    assert!(missing.contains("no module with id 7"), "{missing}");
+}
+
+#[test]
+fn function_without_bytecode_shown_and_skipped() {
+    let functions = [
+        common::TestFunction {
+            name: 0,
+            param_count: 0,
+            bytecode: BYTECODE,
+        },
+        common::TestFunction {
+            name: 0,
+            param_count: 0,
+            bytecode: &[],
+        },
+    ];
+    let bundle =
+        std::env::temp_dir().join(format!("hermes_dec_no_bytecode_{}.hbc", std::process::id()));
+    std::fs::write(&bundle, common::bundle_with_functions(&["f"], &functions)).unwrap();
+    let run = |command: &str| {
+        let output = Command::new(env!("CARGO_BIN_EXE_hermes_dec"))
+            .arg(&bundle)
+            .arg(command)
+            .output()
+            .unwrap();
+        (
+            String::from_utf8(output.stdout).unwrap(),
+            String::from_utf8(output.stderr).unwrap(),
+        )
+    };
+    let (functions, _) = run("show-functions");
+    let (decompiled, errors) = run("decompile-all");
+    std::fs::remove_file(&bundle).unwrap();
+
+    assert!(
+        functions.contains("Function 1: (name: f, offset: "),
+        "{functions}"
+    );
+    assert!(functions.contains("kind: no bytecode)"), "{functions}");
+    assert!(decompiled.contains("function f0()"), "{decompiled}");
+    assert!(!decompiled.contains("Skipping"), "{decompiled}");
+    assert!(
+        errors.contains("Skipping function 1: it has no bytecode"),
+        "{errors}"
+    );
+}
+
+#[test]
+fn entry_decompiles_global_function() {
+    //LoadConstUInt8 r0, 5; GetNewTarget r1; Ret r0, with an opcode that has no translation
+    let functions = [BYTECODE, &[108, 0, 5, 49, 1, 90, 0]].map(|bytecode| common::TestFunction {
+        name: 0,
+        param_count: 0,
+        bytecode,
+    });
+    let mut buf = common::bundle_with_functions(&[], &functions);
+    buf[36..40].copy_from_slice(&1u32.to_le_bytes()); //global_code_index
+    let bundle = std::env::temp_dir().join(format!("hermes_dec_entry_{}.hbc", std::process::id()));
+    std::fs::write(&bundle, buf).unwrap();
+    let run = |args: &[&str]| {
+        let output = Command::new(env!("CARGO_BIN_EXE_hermes_dec"))
+            .arg(&bundle)
+            .args(args)
+            .output()
+            .unwrap();
+        (
+            String::from_utf8(output.stdout).unwrap(),
+            String::from_utf8(output.stderr).unwrap(),
+        )
+    };
+    let (entry, warnings) = run(&["entry"]);
+    let (function, _) = run(&["disassemble", "1"]);
+    let (annotated, _) = run(&["entry", "--annotate"]);
+    std::fs::remove_file(&bundle).unwrap();
+
+    assert!(entry.contains("function f1()"), "{entry}");
+    assert!(entry.contains("r0 = 5"), "{entry}");
+    assert_eq!(entry, function);
+    assert!(
+        annotated.contains("r0 = 5; // @0x0 LoadConstUInt8"),
+        "{annotated}"
+    );
+    assert!(
+        warnings.contains("warning: f1 @0x3: unsupported opcode: GetNewTarget"),
+        "{warnings}"
+    );
+}
+
+#[test]
+fn decompile_all_split_into_files() {
+    let functions = [0, 1, 0, 2, 4, 3].map(|name| common::TestFunction {
+        name,
+        param_count: 0,
+        bytecode: BYTECODE,
+    });
+    let bundle = std::env::temp_dir().join(format!("hermes_dec_split_{}.hbc", std::process::id()));
+    let split_dir = bundle.with_extension("d");
+    std::fs::write(
+        &bundle,
+        common::bundle_with_functions(&["render", "../a b", "", "Render", "F5"], &functions),
+    )
+    .unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_hermes_dec"))
+        .arg(&bundle)
+        .arg("decompile-all")
+        .arg("--split-dir")
+        .arg(&split_dir)
+        .output()
+        .unwrap();
+    std::fs::remove_file(&bundle).unwrap();
+    let mut files: Vec<String> = std::fs::read_dir(&split_dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+        .collect();
+    files.sort();
+    let render = std::fs::read_to_string(split_dir.join("render.js")).unwrap();
+    let index = std::fs::read_to_string(split_dir.join("index.js")).unwrap();
+    std::fs::remove_dir_all(&split_dir).unwrap();
+
+    assert!(output.status.success());
+    assert_eq!(
+        files,
+        [
+            "F5.js",
+            "___a_b.js",
+            "f2.js",
+            "f3.js",
+            "f5_2.js",
+            "index.js",
+            "render.js"
+        ],
+        "{}",
+        String::from_utf8_lossy(&output.stdout)
+    );
+    assert!(render.contains("function f0()"), "{render}");
+    assert!(index.contains("// f2 (render): f2.js"), "{index}");
+    assert!(index.contains("// f5 (Render): f5_2.js"), "{index}");
+}
+
+#[test]
+fn xref_lists_functions_using_string() {
+    //LoadConstZero r1; LoadConstString r0, 1; Ret r0, and the same loading string 2
+    let functions = [[120, 1, 113, 0, 1, 0, 90, 0], [120, 1, 113, 0, 2, 0, 90, 0]]
+        .map(|bytecode| bytecode.to_vec());
+    let functions = functions.each_ref().map(|bytecode| common::TestFunction {
+        name: 0,
+        param_count: 0,
+        bytecode,
+    });
+    let bundle = std::env::temp_dir().join(format!("hermes_dec_xref_{}.hbc", std::process::id()));
+    std::fs::write(
+        &bundle,
+        common::bundle_with_functions(&["f", "my_api_key", "other"], &functions),
+    )
+    .unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_hermes_dec"))
+        .arg(&bundle)
+        .arg("xref")
+        .arg("API_KEY")
+        .output()
+        .unwrap();
+    std::fs::remove_file(&bundle).unwrap();
+
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let found: Vec<&str> = stdout
+        .lines()
+        .filter(|line| line.starts_with("Function"))
+        .collect();
+    assert_eq!(found, ["Function 0: (name: f, offsets: 0x2)"], "{stdout}");
+}
+
+#[test]
+fn decompile_all_keeps_going_past_failures() {
+    let functions = [
+        common::TestFunction {
+            name: 0,
+            param_count: 0,
+            bytecode: BYTECODE,
+        },
+        //an opcode byte no instruction has, decoding it panics
+        common::TestFunction {
+            name: 0,
+            param_count: 0,
+            bytecode: &[255],
+        },
+        common::TestFunction {
+            name: 0,
+            param_count: 0,
+            bytecode: BYTECODE,
+        },
+    ];
+    let bundle =
+        std::env::temp_dir().join(format!("hermes_dec_keep_going_{}.hbc", std::process::id()));
+    std::fs::write(&bundle, common::bundle_with_functions(&[], &functions)).unwrap();
+    let run = |keep_going: bool| {
+        let mut command = Command::new(env!("CARGO_BIN_EXE_hermes_dec"));
+        command.arg(&bundle).arg("decompile-all");
+        if keep_going {
+            command.arg("--keep-going");
+        }
+        command.output().unwrap()
+    };
+    let stopped = run(false);
+    let kept_going = run(true);
+    std::fs::remove_file(&bundle).unwrap();
+
+    let stdout = String::from_utf8(stopped.stdout).unwrap();
+    assert!(
+        stdout.contains("Error while decompiling function 1"),
+        "{stdout}"
+    );
+    assert!(!stdout.contains("function f2()"), "{stdout}");
+
+    let stdout = String::from_utf8(kept_going.stdout).unwrap();
+    assert!(stdout.contains("function f0()"), "{stdout}");
+    assert!(
+        stdout.contains("/* failed to decompile f1: decompiler panicked"),
+        "{stdout}"
+    );
+    assert!(stdout.contains("function f2()"), "{stdout}");
+    let stderr = String::from_utf8(kept_going.stderr).unwrap();
+    assert!(
+        stderr.contains("Failed to decompile 1 of 3 functions:"),
+        "{stderr}"
+    );
+    assert!(stderr.contains("  f1: decompiler panicked"), "{stderr}");
+}
+
+#[test]
+fn decompile_all_pages_through_functions() {
+    let functions: Vec<_> = (0..8)
+        .map(|_| common::TestFunction {
+            name: 0,
+            param_count: 0,
+            bytecode: BYTECODE,
+        })
+        .collect();
+    let bundle = std::env::temp_dir().join(format!(
+        "hermes_dec_max_functions_{}.hbc",
+        std::process::id()
+    ));
+    std::fs::write(&bundle, common::bundle_with_functions(&[], &functions)).unwrap();
+    let run = |args: &[&str]| {
+        let output = Command::new(env!("CARGO_BIN_EXE_hermes_dec"))
+            .arg(&bundle)
+            .arg("decompile-all")
+            .args(args)
+            .output()
+            .unwrap();
+        let stdout = String::from_utf8(output.stdout).unwrap();
+        let ids: Vec<usize> = (0..functions.len())
+            .filter(|function_id| stdout.contains(&format!("function f{function_id}()")))
+            .collect();
+        (stdout.matches("function f").count(), ids)
+    };
+    let first = run(&["--max-functions", "5"]);
+    let page = run(&["--skip", "4", "--max-functions", "2"]);
+    let rest = run(&["--skip", "6", "--max-functions", "5", "--jobs", "2"]);
+    std::fs::remove_file(&bundle).unwrap();
+
+    assert_eq!(first, (5, vec![0, 1, 2, 3, 4]));
+    assert_eq!(page, (2, vec![4, 5]));
+    assert_eq!(rest, (2, vec![6, 7]));
+}
+
+#[test]
+fn handlers_listed_with_instruction_indices() {
+    //try { LoadConstZero r0; Throw r0 } catch (r1) { Ret r1 }
+    let mut bytes = common::bundle_with_function(&[], 0, 0, &[120, 0, 93, 0, 91, 1, 90, 1]);
+    //the second region starts inside LoadConstZero and reaches the end of the function
+    common::add_exception_handlers(&mut bytes, 0, &[(0, 4, 4), (1, 8, 4)]);
+    let bundle =
+        std::env::temp_dir().join(format!("hermes_dec_handlers_{}.hbc", std::process::id()));
+    std::fs::write(&bundle, bytes).unwrap();
+    let run = |json: bool| {
+        let mut command = Command::new(env!("CARGO_BIN_EXE_hermes_dec"));
+        command.arg(&bundle).arg("handlers").arg("0");
+        if json {
+            command.arg("--json");
+        }
+        String::from_utf8(command.output().unwrap().stdout).unwrap()
+    };
+    let text = run(false);
+    let json = run(true);
+    std::fs::remove_file(&bundle).unwrap();
+
+    assert!(
+        text.contains(
+            "Handler 0: protects 0x0..0x4 (instructions 0..2), catches at 0x4 (instruction 2)\n"
+        ),
+        "{text}"
+    );
+    assert!(
+        text.contains(
+            "Handler 1: protects 0x1..0x8 (instructions ?..4), catches at 0x4 (instruction 2)\n"
+        ),
+        "{text}"
+    );
+    //the json follows the warnings about the fixture's header
+    let json: serde_json::Value = serde_json::from_str(json.lines().last().unwrap()).unwrap();
+    assert_eq!(json[0]["end"], 4);
+    assert_eq!(json[0]["end_index"], 2);
+    assert_eq!(json[1]["start_index"], serde_json::Value::Null);
+    assert_eq!(json[1]["end_index"], 4);
+}
+
+#[test]
+fn disassemble_writes_partial_result() {
+    //if (r0) { r1 = 0; if (r0) { r1 = 0; ... } } return r1, nested deeper than the default limit
+    let depth: i32 = 1100;
+    let mut bytecode = Vec::new();
+    for level in 0..depth {
+        //JmpFalseLong to the Ret, LoadConstZero r1
+        bytecode.push(145);
+        bytecode.extend_from_slice(&((depth - level) * 8).to_le_bytes());
+        bytecode.extend_from_slice(&[0, 120, 1]);
+    }
+    bytecode.extend_from_slice(&[90, 1]);
+    let bundle =
+        std::env::temp_dir().join(format!("hermes_dec_partial_{}.hbc", std::process::id()));
+    std::fs::write(&bundle, common::bundle_with_function(&[], 0, 0, &bytecode)).unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_hermes_dec"))
+        .arg(&bundle)
+        .arg("disassemble")
+        .arg("0")
+        .output()
+        .unwrap();
+    std::fs::remove_file(&bundle).unwrap();
+
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(stdout.matches("if (r0) {").count(), 1024, "{stdout}");
+    assert!(
+        stdout.contains(
+            "Error while decompiling function 0: gave up after exceeding the nesting depth limit"
+        ),
+        "{stdout}"
+    );
+}
+
+#[test]
+fn disassemble_keeps_moves_without_propagation() {
+    //LoadConstZero r1; Mov r2, r1; Ret r2
+    let functions = [&[120, 1, 8, 2, 1, 90, 2][..]].map(|bytecode| common::TestFunction {
+        name: 0,
+        param_count: 0,
+        bytecode,
+    });
+    let bundle = std::env::temp_dir().join(format!("hermes_dec_moves_{}.hbc", std::process::id()));
+    std::fs::write(&bundle, common::bundle_with_functions(&[], &functions)).unwrap();
+    let run = |args: &[&str]| {
+        let output = Command::new(env!("CARGO_BIN_EXE_hermes_dec"))
+            .arg(&bundle)
+            .args(args)
+            .output()
+            .unwrap();
+        String::from_utf8(output.stdout).unwrap()
+    };
+    let propagated = run(&["disassemble", "0"]);
+    let moves = run(&["disassemble", "0", "--no-propagate-copies"]);
+    std::fs::remove_file(&bundle).unwrap();
+
+    assert!(propagated.contains("return r1;"), "{propagated}");
+    assert!(!propagated.contains("r2"), "{propagated}");
+    assert!(moves.contains("r2 = r1;"), "{moves}");
+    assert!(moves.contains("return r2;"), "{moves}");
+}
@@ -0,0 +1,176 @@
+#![allow(dead_code)]
+
+const HEADER_SIZE: usize = 128;
+const FUNCTION_HEADER_SIZE: usize = 16;
+const LARGE_FUNCTION_HEADER_SIZE: usize = 31;
+/// Registers every test function gets, enough for the handful their bytecode uses
+pub const FRAME_SIZE: u32 = 16;
+const STRING_ENTRY_SIZE: usize = 4;
+const OVERFLOW_STRING_ENTRY_SIZE: usize = 8;
+/// Strings at least this long don't fit in a small string table entry
+const OVERFLOW_STRING_LENGTH: usize = 255;
+const CJS_MODULE_ENTRY_SIZE: usize = 8;
+const FUNCTION_SOURCE_ENTRY_SIZE: usize = 8;
+
+/// A function of a synthetic bundle: name string index, param count and bytecode
+pub struct TestFunction<'a> {
+    pub name: u32,
+    pub param_count: u32,
+    pub bytecode: &'a [u8],
+}
+
+/// A bundle with a single function named `strings[name]` whose body is `bytecode`
+pub fn bundle_with_function(
+    strings: &[&str],
+    name: u32,
+    param_count: u32,
+    bytecode: &[u8],
+) -> Vec<u8> {
+    bundle_with_functions(
+        strings,
+        &[TestFunction {
+            name,
+            param_count,
+            bytecode,
+        }],
+    )
+}
+
+pub fn bundle_with_functions(strings: &[&str], functions: &[TestFunction]) -> Vec<u8> {
+    bundle_with_modules(strings, functions, &[], false)
+}
+
+/// A bundle that also has a CommonJS module table, made of `(module_id, function_id)` pairs
+/// when `statically_resolved` and `(filename string, function_id)` pairs otherwise
+pub fn bundle_with_modules(
+    strings: &[&str],
+    functions: &[TestFunction],
+    cjs_modules: &[(u32, u32)],
+    statically_resolved: bool,
+) -> Vec<u8> {
+    bundle(strings, functions, cjs_modules, statically_resolved, &[])
+}
+
+/// A bundle that also keeps the source text of some functions, made of
+/// `(function_id, source string)` pairs
+pub fn bundle_with_function_sources(
+    strings: &[&str],
+    functions: &[TestFunction],
+    function_sources: &[(u32, u32)],
+) -> Vec<u8> {
+    bundle(strings, functions, &[], false, function_sources)
+}
+
+fn bundle(
+    strings: &[&str],
+    functions: &[TestFunction],
+    cjs_modules: &[(u32, u32)],
+    statically_resolved: bool,
+    function_sources: &[(u32, u32)],
+) -> Vec<u8> {
+    let storage = strings.concat();
+    let overflow_count = strings
+        .iter()
+        .filter(|s| s.len() >= OVERFLOW_STRING_LENGTH)
+        .count();
+    let mut bytes = vec![0; HEADER_SIZE];
+    bytes[40..44].copy_from_slice(&(functions.len() as u32).to_le_bytes()); //function_count
+    bytes[52..56].copy_from_slice(&(strings.len() as u32).to_le_bytes()); //string_count
+    bytes[56..60].copy_from_slice(&(overflow_count as u32).to_le_bytes()); //overflow_string_count
+    bytes[60..64].copy_from_slice(&(storage.len() as u32).to_le_bytes()); //string_storage_size
+    bytes[96..100].copy_from_slice(&(cjs_modules.len() as u32).to_le_bytes()); //cjs_module_count
+    bytes[100..104].copy_from_slice(&(function_sources.len() as u32).to_le_bytes()); //function_source_count
+    bytes[108] = (statically_resolved as u8) << 1; //options
+
+    let mut offset = HEADER_SIZE
+        + functions.len() * FUNCTION_HEADER_SIZE
+        + strings.len() * STRING_ENTRY_SIZE
+        + overflow_count * OVERFLOW_STRING_ENTRY_SIZE
+        + storage.len()
+        + cjs_modules.len() * CJS_MODULE_ENTRY_SIZE
+        + function_sources.len() * FUNCTION_SOURCE_ENTRY_SIZE;
+    //functions that don't fit in a small header get a large one after all the bytecode
+    let mut large_header_offset =
+        offset + functions.iter().map(|f| f.bytecode.len()).sum::<usize>();
+    let mut large_headers = Vec::new();
+    for function in functions {
+        let function_header = if needs_large_header(function) {
+            large_headers.extend_from_slice(&(offset as u32).to_le_bytes());
+            large_headers.extend_from_slice(&function.param_count.to_le_bytes());
+            large_headers.extend_from_slice(&(function.bytecode.len() as u32).to_le_bytes());
+            large_headers.extend_from_slice(&function.name.to_le_bytes());
+            large_headers.extend_from_slice(&0u32.to_le_bytes()); //info_offset
+            large_headers.extend_from_slice(&FRAME_SIZE.to_le_bytes());
+            large_headers.extend_from_slice(&[0; 7]); //environment size up to flags
+            let small_header = (large_header_offset as u128 & 0xFFFF)
+                | ((large_header_offset as u128 >> 16) << 64)
+                | (1 << 125); //overflowed
+            large_header_offset += LARGE_FUNCTION_HEADER_SIZE;
+            small_header
+        } else {
+            offset as u128
+                | ((function.param_count as u128) << 25)
+                | ((function.bytecode.len() as u128) << 32)
+                | ((function.name as u128) << 47)
+                | ((FRAME_SIZE as u128) << 89)
+        };
+        bytes.extend_from_slice(&function_header.to_le_bytes());
+        offset += function.bytecode.len();
+    }
+
+    let mut string_offset = 0;
+    let mut overflow_entries = Vec::new();
+    for s in strings {
+        let entry = if s.len() >= OVERFLOW_STRING_LENGTH {
+            let overflow_index = overflow_entries.len() / OVERFLOW_STRING_ENTRY_SIZE;
+            overflow_entries.extend_from_slice(&(string_offset as u32).to_le_bytes());
+            overflow_entries.extend_from_slice(&(s.len() as u32).to_le_bytes());
+            ((overflow_index as u32) << 1) | ((OVERFLOW_STRING_LENGTH as u32) << 24)
+        } else {
+            ((string_offset as u32) << 1) | ((s.len() as u32) << 24)
+        };
+        bytes.extend_from_slice(&entry.to_le_bytes());
+        string_offset += s.len();
+    }
+    bytes.extend_from_slice(&overflow_entries);
+    bytes.extend_from_slice(storage.as_bytes());
+    for (first, function_id) in cjs_modules {
+        bytes.extend_from_slice(&first.to_le_bytes());
+        bytes.extend_from_slice(&function_id.to_le_bytes());
+    }
+    for (function_id, source) in function_sources {
+        bytes.extend_from_slice(&function_id.to_le_bytes());
+        bytes.extend_from_slice(&source.to_le_bytes());
+    }
+    for function in functions {
+        bytes.extend_from_slice(function.bytecode);
+    }
+    bytes.extend_from_slice(&large_headers);
+    bytes
+}
+
+/// Appends an exception handler table of `(start, end, target)` offsets to `bundle` and points
+/// function `function_id` at it. Only for functions with a small header
+pub fn add_exception_handlers(
+    bundle: &mut Vec<u8>,
+    function_id: usize,
+    handlers: &[(u32, u32, u32)],
+) {
+    let info_offset = bundle.len() as u128;
+    bundle.extend_from_slice(&(handlers.len() as u32).to_le_bytes());
+    for (start, end, target) in handlers {
+        for offset in [start, end, target] {
+            bundle.extend_from_slice(&offset.to_le_bytes());
+        }
+    }
+    let header_offset = HEADER_SIZE + function_id * FUNCTION_HEADER_SIZE;
+    let header = &mut bundle[header_offset..][..FUNCTION_HEADER_SIZE];
+    let mut small_header = u128::from_le_bytes(header.try_into().unwrap());
+    small_header |= (info_offset << 64) | (1 << 123); //has_exception_handler
+    header.copy_from_slice(&small_header.to_le_bytes());
+}
+
+//the widths of the small header's offset, param count, bytecode size and name fields
+fn needs_large_header(function: &TestFunction) -> bool {
+    function.param_count >= 1 << 7 || function.bytecode.len() >= 1 << 15 || function.name >= 1 << 17
+}
@@ -0,0 +1,742 @@
+use hermes_dec::{
+    call_graph, call_graph_dot, decompile_all, decompile_function, decompile_function_with,
+    disassemble_function, exception_regions, function_name, instructions_json, opcode_histogram,
+    parse, CallGraphEdge, DecompileError, DecompileOptions, Diagnostic, DiagnosticKind,
+    FunctionKind, HandlerKind, Limit,
+};
+
+mod common;
+
+#[test]
+fn decompile_fixture_function() {
+    //LoadConstZero r0; Ret r0
+    let buf = common::bundle_with_function(&[], 0, 0, &[120, 0, 90, 0]);
+    let file = parse(&buf).unwrap();
+    let out = decompile_function(&file, &buf, 0).unwrap().code;
+    assert!(out.contains("function f0()"), "{out}");
+    assert!(out.contains("r0 = 0;"), "{out}");
+    assert!(out.contains("return r0;"), "{out}");
+}
+
+#[test]
+fn negative_int_constant() {
+    //LoadConstInt r0, -5; Ret r0
+    let mut bytecode = vec![109, 0];
+    bytecode.extend_from_slice(&(-5i32).to_le_bytes());
+    bytecode.extend_from_slice(&[90, 0]);
+    let buf = common::bundle_with_function(&[], 0, 0, &bytecode);
+    let file = parse(&buf).unwrap();
+    let out = decompile_function(&file, &buf, 0).unwrap().code;
+    assert!(out.contains("r0 = -5;"), "{out}");
+    assert!(!out.contains("4294967291"), "{out}");
+}
+
+#[test]
+fn instructions_as_json() {
+    //LoadConstZero r0; Ret r0
+    let buf = common::bundle_with_function(&[], 0, 0, &[120, 0, 90, 0]);
+    let file = parse(&buf).unwrap();
+    let json = instructions_json(&file, &buf, 0, false, ..).unwrap();
+    let compact: String = json.split_whitespace().collect();
+    assert_eq!(
+        compact,
+        concat!(
+            r#"[{"offset":0,"opcode":"LoadConstZero","dst_reg":0},"#,
+            r#"{"offset":2,"opcode":"Ret","value_reg":0}]"#
+        )
+    );
+}
+
+#[test]
+fn instructions_json_absolute_offsets() {
+    //LoadConstZero r0; Ret r0
+    let buf = common::bundle_with_function(&[], 0, 0, &[120, 0, 90, 0]);
+    let file = parse(&buf).unwrap();
+    let function_offset = file
+        .function_header(0, &mut std::io::Cursor::new(&buf[..]))
+        .unwrap()
+        .unwrap()
+        .offset();
+    let second_offset = |absolute| {
+        let json = instructions_json(&file, &buf, 0, absolute, ..).unwrap();
+        let instructions: serde_json::Value = serde_json::from_str(&json).unwrap();
+        instructions[1]["offset"].as_u64().unwrap() as u32
+    };
+    assert_eq!(second_offset(false), 2);
+    assert_eq!(second_offset(true), function_offset + 2);
+    assert_ne!(function_offset, 0);
+}
+
+#[test]
+fn instructions_json_offset_range() {
+    //LoadConstZero r0; LoadConstZero r1; LoadConstZero r2; Ret r0
+    let buf = common::bundle_with_function(&[], 0, 0, &[120, 0, 120, 1, 120, 2, 90, 0]);
+    let file = parse(&buf).unwrap();
+    let json = instructions_json(&file, &buf, 0, false, 2..=4).unwrap();
+    let compact: String = json.split_whitespace().collect();
+    assert_eq!(
+        compact,
+        concat!(
+            r#"[{"offset":2,"opcode":"LoadConstZero","dst_reg":1},"#,
+            r#"{"offset":4,"opcode":"LoadConstZero","dst_reg":2}]"#
+        )
+    );
+}
+
+#[test]
+fn decompile_invalid_function_id() {
+    let buf = common::bundle_with_function(&[], 0, 0, &[90, 0]);
+    let file = parse(&buf).unwrap();
+    assert!(matches!(
+        decompile_function(&file, &buf, 1),
+        Err(DecompileError::InvalidFunctionId(1))
+    ));
+}
+
+#[test]
+fn decompile_all_in_parallel_keeps_order() {
+    let bytecodes: Vec<Vec<u8>> = (0..16u8)
+        .map(|value| vec![108, 0, value, 90, 0]) //LoadConstUInt8 r0, value; Ret r0
+        .collect();
+    let functions: Vec<_> = bytecodes
+        .iter()
+        .map(|bytecode| common::TestFunction {
+            name: 0,
+            param_count: 0,
+            bytecode,
+        })
+        .collect();
+    let buf = common::bundle_with_functions(&[], &functions);
+    let file = parse(&buf).unwrap();
+
+    let serial: Vec<String> = decompile_all(&file, &buf, &DecompileOptions::default(), 1)
+        .into_iter()
+        .map(|result| result.unwrap().source)
+        .collect();
+    let parallel: Vec<String> = decompile_all(&file, &buf, &DecompileOptions::default(), 4)
+        .into_iter()
+        .map(|result| result.unwrap().source)
+        .collect();
+    assert_eq!(serial, parallel);
+    for (function_id, out) in parallel.iter().enumerate() {
+        assert!(out.contains(&format!("function f{function_id}()")), "{out}");
+        assert!(out.contains(&format!("r0 = {function_id};")), "{out}");
+    }
+}
+
+#[test]
+fn decompile_all_catches_panics() {
+    let functions = [
+        //an opcode byte no instruction has, decoding it panics
+        common::TestFunction {
+            name: 0,
+            param_count: 0,
+            bytecode: &[255],
+        },
+        //LoadConstZero r0; Ret r0
+        common::TestFunction {
+            name: 0,
+            param_count: 0,
+            bytecode: &[120, 0, 90, 0],
+        },
+    ];
+    let buf = common::bundle_with_functions(&[], &functions);
+    let file = parse(&buf).unwrap();
+    for jobs in [1, 2] {
+        let results = decompile_all(&file, &buf, &DecompileOptions::default(), jobs);
+        assert!(matches!(results[0], Err(DecompileError::Panicked(_))));
+        assert!(results[1]
+            .as_ref()
+            .unwrap()
+            .source
+            .contains("function f1()"));
+    }
+}
+
+#[test]
+fn unsupported_opcode_reported() {
+    //LoadConstZero r0; GetNewTarget r1; Ret r1
+    let buf = common::bundle_with_function(&[], 0, 0, &[120, 0, 49, 1, 90, 1]);
+    let file = parse(&buf).unwrap();
+    let options = DecompileOptions {
+        optimize: false,
+        ..Default::default()
+    };
+    let function = decompile_function_with(&file, &buf, 0, &options).unwrap();
+    assert!(
+        function.code.contains("unsupported(\"GetNewTarget\");"),
+        "{}",
+        function.code
+    );
+    assert_eq!(
+        function.warnings,
+        [Diagnostic {
+            kind: DiagnosticKind::UnsupportedOpcode,
+            function_id: 0,
+            offset: 2,
+            message: "GetNewTarget has no translation yet".to_string(),
+        }]
+    );
+}
+
+#[test]
+fn missing_string_reported() {
+    //LoadConstZero r0; GetById r1, r0, 0, 5 of a string the bundle doesn't have; Ret r1
+    let buf = common::bundle_with_function(&[], 0, 0, &[120, 0, 53, 1, 0, 0, 5, 0, 90, 1]);
+    let file = parse(&buf).unwrap();
+    let options = DecompileOptions {
+        optimize: false,
+        ..Default::default()
+    };
+    let function = decompile_function_with(&file, &buf, 0, &options).unwrap();
+    assert!(
+        function.code.contains("r1 = r0.__string5;"),
+        "{}",
+        function.code
+    );
+    assert_eq!(
+        function.warnings,
+        [Diagnostic {
+            kind: DiagnosticKind::MissingString,
+            function_id: 0,
+            offset: 2,
+            message: "string 5 isn't in the string table".to_string(),
+        }]
+    );
+}
+
+#[test]
+fn call_arguments_counted_down_from_frame_end() {
+    //hermesc passes `this` in the last register before the callee's frame header and the
+    //arguments below it: with FRAME_SIZE 16 that's this in r11, then r10, r9
+    #[rustfmt::skip]
+    let bytecode = [
+        106, 1, 1, //LoadParam r1, 1
+        116, 11, //LoadConstUndefined r11
+        108, 9, 7, //LoadConstUInt8 r9, 7
+        108, 12, 1, //LoadConstUInt8 r12, 1
+        108, 10, 5, //LoadConstUInt8 r10, 5
+        77, 0, 1, 3, //Call r0, r1, 3
+        90, 0, //Ret r0
+    ];
+    let buf = common::bundle_with_function(&["f"], 0, 2, &bytecode);
+    let file = parse(&buf).unwrap();
+    let out = decompile_function(&file, &buf, 0).unwrap().code;
+    assert!(out.contains("r0 = r1.bind(r11)(r10, r9);"), "{out}");
+}
+
+#[test]
+fn module_params_named() {
+    #[rustfmt::skip]
+    let bytecode = [
+        106, 1, 2, //LoadParam r1, 2
+        116, 3, //LoadConstUndefined r3
+        108, 2, 5, //LoadConstUInt8 r2, 5
+        81, 0, 1, 3, 2, //Call2 r0, r1, r3, r2
+        90, 0, //Ret r0
+    ];
+    let functions = [0, 1].map(|name| common::TestFunction {
+        name,
+        param_count: 5,
+        bytecode: &bytecode,
+    });
+    let buf = common::bundle_with_modules(&["module", "helper"], &functions, &[(0, 0)], true);
+    let file = parse(&buf).unwrap();
+
+    let module = decompile_function(&file, &buf, 0).unwrap().code;
+    assert!(
+        module.contains("function f0(global, require, module, exports)"),
+        "{module}"
+    );
+    assert!(module.contains("r0 = require"), "{module}");
+    assert!(!module.contains("arguments"), "{module}");
+
+    let helper = decompile_function(&file, &buf, 1).unwrap().code;
+    assert!(helper.contains("function f1()"), "{helper}");
+    assert!(helper.contains("arguments[2]"), "{helper}");
+}
+
+#[test]
+fn module_exports_as_es_module() {
+    #[rustfmt::skip]
+    let bytecode = [
+        106, 1, 4, //LoadParam r1, 4
+        108, 0, 5, //LoadConstUInt8 r0, 5
+        57, 1, 0, 1, 1, 0, //PutById r1, r0, cache 1, "bar"
+        116, 2, //LoadConstUndefined r2
+        90, 2, //Ret r2
+    ];
+    let functions = [common::TestFunction {
+        name: 0,
+        param_count: 5,
+        bytecode: &bytecode,
+    }];
+    let buf = common::bundle_with_modules(&["module", "bar"], &functions, &[(0, 0)], true);
+    let file = parse(&buf).unwrap();
+
+    let options = DecompileOptions {
+        es_module: true,
+        ..Default::default()
+    };
+    let out = decompile_function_with(&file, &buf, 0, &options)
+        .unwrap()
+        .code;
+    assert!(out.contains("export const bar = "), "{out}");
+    assert!(!out.contains("exports.bar"), "{out}");
+    assert!(!out.contains("function f0"), "{out}");
+    assert!(!out.contains("return"), "{out}");
+
+    let script = decompile_function(&file, &buf, 0).unwrap().code;
+    assert!(script.contains("exports.bar = "), "{script}");
+}
+
+#[test]
+fn functions_named_from_header_and_source() {
+    let functions = [
+        //CreateClosure r0, r1, 1; CreateClosure r2, r1, 2; Ret r0
+        common::TestFunction {
+            name: 0,
+            param_count: 0,
+            bytecode: &[98, 0, 1, 1, 0, 98, 2, 1, 2, 0, 90, 0],
+        },
+        //LoadConstZero r0; Ret r0, named only in its source
+        common::TestFunction {
+            name: 2,
+            param_count: 0,
+            bytecode: &[120, 0, 90, 0],
+        },
+        //LoadConstZero r0; Ret r0, anonymous
+        common::TestFunction {
+            name: 2,
+            param_count: 0,
+            bytecode: &[120, 0, 90, 0],
+        },
+    ];
+    let strings = ["outer", "function inner() { return 0; }", ""];
+    let buf = common::bundle_with_function_sources(&strings, &functions, &[(1, 1)]);
+    let file = parse(&buf).unwrap();
+    assert_eq!(function_name(&file, &buf, 0).as_deref(), Some("outer"));
+    assert_eq!(function_name(&file, &buf, 1).as_deref(), Some("inner"));
+    assert_eq!(function_name(&file, &buf, 2), None);
+
+    let options = DecompileOptions {
+        function_names: true,
+        ..Default::default()
+    };
+    let outer = decompile_function_with(&file, &buf, 0, &options)
+        .unwrap()
+        .code;
+    assert!(outer.contains("function outer_f0()"), "{outer}");
+    assert!(outer.contains("r0 = inner_f1;"), "{outer}");
+    assert!(outer.contains("r2 = f2;"), "{outer}");
+    let inner = decompile_function_with(&file, &buf, 1, &options)
+        .unwrap()
+        .code;
+    assert!(inner.contains("function inner_f1()"), "{inner}");
+
+    let unnamed = decompile_function(&file, &buf, 0).unwrap().code;
+    assert!(unnamed.contains("function f0()"), "{unnamed}");
+    assert!(unnamed.contains("r0 = f1;"), "{unnamed}");
+}
+
+#[test]
+fn empty_checked_apart_from_undefined() {
+    #[rustfmt::skip]
+    let bytecode = [
+        115, 0, //LoadConstEmpty r0
+        94, 1, 0, //ThrowIfEmpty r1, r0
+        116, 2, //LoadConstUndefined r2
+        94, 3, 2, //ThrowIfEmpty r3, r2
+        90, 3, //Ret r3
+    ];
+    let buf = common::bundle_with_function(&[], 0, 0, &bytecode);
+    let file = parse(&buf).unwrap();
+    let options = DecompileOptions {
+        optimize: false,
+        ..Default::default()
+    };
+    let out = decompile_function_with(&file, &buf, 0, &options)
+        .unwrap()
+        .code;
+    assert!(out.contains("r0 = __empty;"), "{out}");
+    assert!(out.contains("r2 = undefined;"), "{out}");
+    for reg in [0, 2] {
+        let check = format!("if (r{reg} === __empty) throw new ReferenceError(");
+        assert!(out.contains(&check), "{out}");
+    }
+    assert!(out.contains("r1 = r0;"), "{out}");
+    assert!(out.contains("r3 = r2;"), "{out}");
+}
+
+#[test]
+fn decompile_all_reports_this_and_arguments_use() {
+    let bytecodes: [&[u8]; 3] = [
+        &[108, 0, 1, 90, 0], //LoadConstUInt8 r0, 1; Ret r0
+        &[122, 0, 90, 0],    //LoadThisNS r0; Ret r0
+        &[128, 0, 1, 90, 0], //GetArgumentsLength r0, r1; Ret r0
+    ];
+    let functions = bytecodes.map(|bytecode| common::TestFunction {
+        name: 0,
+        param_count: 1,
+        bytecode,
+    });
+    let buf = common::bundle_with_functions(&[], &functions);
+    let file = parse(&buf).unwrap();
+
+    let usage: Vec<(bool, bool)> = decompile_all(&file, &buf, &DecompileOptions::default(), 1)
+        .into_iter()
+        .map(Result::unwrap)
+        .map(|function| (function.uses_this, function.uses_arguments))
+        .collect();
+    assert_eq!(usage, [(false, false), (true, false), (false, true)]);
+}
+
+#[test]
+fn long_index_string_above_u16() {
+    let strings: Vec<String> = (0..=0x10000).map(|i| format!("s{i}")).collect();
+    let strings: Vec<&str> = strings.iter().map(String::as_str).collect();
+    #[rustfmt::skip]
+    let bytecode = [
+        114, 0, 0x00, 0x00, 0x01, 0x00, //LoadConstStringLongIndex r0, 0x10000
+        90, 0, //Ret r0
+    ];
+    let buf = common::bundle_with_function(&strings, 0, 1, &bytecode);
+    let file = parse(&buf).unwrap();
+
+    let out = decompile_function(&file, &buf, 0).unwrap().code;
+    assert!(out.contains(r#"r0 = "s65536";"#), "{out}");
+}
+
+#[test]
+fn function_without_bytecode() {
+    let buf = common::bundle_with_function(&[], 0, 0, &[]);
+    let file = parse(&buf).unwrap();
+
+    let header = file.function_headers[0]
+        .full_header(&mut std::io::Cursor::new(buf.as_slice()))
+        .unwrap();
+    assert_eq!(header.kind(), FunctionKind::NoBytecode);
+    assert!(matches!(
+        decompile_function(&file, &buf, 0),
+        Err(DecompileError::NoBytecode(0))
+    ));
+}
+
+#[test]
+fn call_graph_edges_to_created_and_called_functions() {
+    let functions = [
+        //CreateClosure r0, r1, 1; CallDirect r0, 1, 1; Ret r0
+        common::TestFunction {
+            name: 0,
+            param_count: 0,
+            bytecode: &[98, 0, 1, 1, 0, 80, 0, 1, 1, 0, 90, 0],
+        },
+        //LoadConstZero r0; Ret r0
+        common::TestFunction {
+            name: 1,
+            param_count: 0,
+            bytecode: &[120, 0, 90, 0],
+        },
+    ];
+    let buf = common::bundle_with_functions(&["global", "inner"], &functions);
+    let file = parse(&buf).unwrap();
+
+    let edges: Vec<_> = call_graph(&file, &buf).unwrap().into_iter().collect();
+    assert_eq!(
+        edges,
+        [(0, 1, CallGraphEdge::Creates), (0, 1, CallGraphEdge::Calls)]
+    );
+    let dot = call_graph_dot(&file, &buf).unwrap();
+    assert!(dot.contains("label = \"f1 inner\""), "{dot}");
+    assert!(dot.contains("0 -> 1 [ label = \"creates\" ]"), "{dot}");
+}
+
+#[test]
+fn opcode_histogram_counts_every_instruction() {
+    let functions = [
+        //CreateClosure r0, r1, 1; CallDirect r0, 1, 1; Ret r0
+        common::TestFunction {
+            name: 0,
+            param_count: 0,
+            bytecode: &[98, 0, 1, 1, 0, 80, 0, 1, 1, 0, 90, 0],
+        },
+        //LoadConstZero r0; LoadConstZero r1; Ret r0
+        common::TestFunction {
+            name: 1,
+            param_count: 0,
+            bytecode: &[120, 0, 120, 1, 90, 0],
+        },
+    ];
+    let buf = common::bundle_with_functions(&["global", "inner"], &functions);
+    let file = parse(&buf).unwrap();
+
+    let histogram = opcode_histogram(&file, &buf).unwrap();
+    let instruction_count: usize = (0..functions.len())
+        .map(|function_id| {
+            disassemble_function(&file, &buf, function_id)
+                .unwrap()
+                .len()
+        })
+        .sum();
+    assert_eq!(
+        histogram.iter().map(|opcode| opcode.count).sum::<usize>(),
+        instruction_count
+    );
+    assert_eq!(
+        (histogram[0].name.as_str(), histogram[0].count),
+        ("LoadConstZero", 2)
+    );
+    assert_eq!((histogram[1].name.as_str(), histogram[1].count), ("Ret", 2));
+    let unimplemented: Vec<_> = histogram
+        .iter()
+        .filter(|opcode| opcode.is_unimplemented)
+        .collect();
+    assert_eq!(unimplemented.len(), 1);
+    assert_eq!(unimplemented[0].name, "CallDirect");
+}
+
+//if (r0) { r1 = 0; if (r0) { r1 = 0; ... } } return r1;
+fn nested_ifs(depth: u32) -> Vec<u8> {
+    let mut bytecode = Vec::new();
+    for level in 0..depth {
+        //JmpFalseLong to the Ret, LoadConstZero r1
+        bytecode.push(145);
+        bytecode.extend_from_slice(&(((depth - level) * 8) as i32).to_le_bytes());
+        bytecode.extend_from_slice(&[0, 120, 1]);
+    }
+    bytecode.extend_from_slice(&[90, 1]);
+    bytecode
+}
+
+#[test]
+fn deep_nesting_decompiles() {
+    //the passes and the emitter recurse into every level, on a thread with stack for as many
+    //levels as the generator may make
+    let buf = common::bundle_with_function(&[], 0, 0, &nested_ifs(1000));
+    let file = parse(&buf).unwrap();
+    let out = decompile_function(&file, &buf, 0).unwrap().code;
+    assert_eq!(out.matches("if (r0) {").count(), 1000, "{out}");
+
+    let options = DecompileOptions {
+        max_depth: 100,
+        ..Default::default()
+    };
+    match decompile_function_with(&file, &buf, 0, &options) {
+        Err(DecompileError::LimitExceeded {
+            limit: Limit::Depth,
+            partial,
+        }) => assert!(partial.contains("if (r0) {"), "{partial}"),
+        result => panic!("{result:?}"),
+    }
+
+    let buf = common::bundle_with_function(&[], 0, 0, &nested_ifs(10));
+    let file = parse(&buf).unwrap();
+    assert!(decompile_function(&file, &buf, 0).is_ok());
+    let options = DecompileOptions {
+        max_nodes: 5,
+        ..Default::default()
+    };
+    assert!(matches!(
+        decompile_function_with(&file, &buf, 0, &options),
+        Err(DecompileError::LimitExceeded {
+            limit: Limit::Nodes,
+            ..
+        })
+    ));
+}
+
+#[test]
+fn sequential_blocks_dont_count_as_nesting() {
+    //if (r0) { r1 = 0; } one after another, more times than blocks may nest
+    let count = DecompileOptions::default().max_depth + 100;
+    let mut bytecode = Vec::new();
+    for _ in 0..count {
+        //JmpFalse past the LoadConstZero, LoadConstZero r1
+        bytecode.extend_from_slice(&[144, 5, 0, 120, 1]);
+    }
+    bytecode.extend_from_slice(&[90, 1]);
+    let buf = common::bundle_with_function(&[], 0, 0, &bytecode);
+    let file = parse(&buf).unwrap();
+    let out = decompile_function(&file, &buf, 0).unwrap().code;
+    assert_eq!(out.matches("if (r0) {").count(), count, "{out}");
+}
+
+#[test]
+fn derived_constructor_calls_parent() {
+    //v93 has no class syntax, `super(x)` is lowered to `Parent.call(this, x)`:
+    //LoadParam r2, 1; LoadThisNS r0; GetGlobalObject r3; GetByIdShort r1, r3, "Parent";
+    //Call2 r4, r1, r0, r2; Ret r0
+    let bytecode = [
+        106, 2, 1, 122, 0, 48, 3, 52, 1, 3, 1, 0, 81, 4, 1, 0, 2, 90, 0,
+    ];
+    let buf = common::bundle_with_function(&["Parent"], 0, 2, &bytecode);
+    let file = parse(&buf).unwrap();
+    let out = decompile_function(&file, &buf, 0).unwrap().code;
+    assert!(out.contains("r1 = r3.Parent;"), "{out}");
+    assert!(out.contains("r4 = r1.bind(r0)(r2);"), "{out}");
+    assert!(out.contains("return r0;"), "{out}");
+}
+
+#[test]
+fn create_this_opcodes() {
+    //v93 only has CreateThis and SelectObject, CreateThisForNew and CreateThisForSuper came
+    //with later versions. Opcodes are numbered by their position, so LoadParam must follow.
+    //CreateThis r3, r2, r1; SelectObject r7, r3, r6; LoadParam r0, 1; Ret r7
+    let bytecode = [104, 3, 2, 1, 105, 7, 3, 6, 106, 0, 1, 90, 7];
+    let buf = common::bundle_with_function(&[], 0, 0, &bytecode);
+    let file = parse(&buf).unwrap();
+    let json = instructions_json(&file, &buf, 0, false, ..).unwrap();
+    let instructions: serde_json::Value = serde_json::from_str(&json).unwrap();
+    let opcodes: Vec<&str> = instructions
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|instruction| instruction["opcode"].as_str().unwrap())
+        .collect();
+    assert_eq!(opcodes, ["CreateThis", "SelectObject", "LoadParam", "Ret"]);
+    assert_eq!(instructions[0]["constructor_closure_reg"], 1);
+}
+
+#[test]
+fn strict_function_has_directive() {
+    //LoadConstZero r0; Ret r0
+    let mut buf = common::bundle_with_function(&[], 0, 0, &[120, 0, 90, 0]);
+    let file = parse(&buf).unwrap();
+    let out = decompile_function(&file, &buf, 0).unwrap().code;
+    assert!(!out.contains("use strict"), "{out}");
+
+    //the flags are the last byte of the function header, right after the file header
+    buf[128 + 15] |= 1 << 2;
+    let file = parse(&buf).unwrap();
+    let header = file
+        .function_header(0, &mut std::io::Cursor::new(&buf[..]))
+        .unwrap()
+        .unwrap();
+    assert!(header.strict_mode());
+    let out = decompile_function(&file, &buf, 0).unwrap().code;
+    assert!(
+        out.contains("function f0() {\n    \"use strict\";\n    const r0 = 0;"),
+        "{out}"
+    );
+}
+
+#[test]
+fn finally_handler_recognized() {
+    //try { LoadConstZero r0; Throw r0 } finally { LoadConstZero r2 } Ret r2, where the finally
+    //block is copied after the protected code and into the handler, which rethrows
+    let bytecode = [120, 0, 93, 0, 120, 2, 140, 8, 91, 1, 120, 2, 93, 1, 90, 2];
+    let mut buf = common::bundle_with_function(&[], 0, 0, &bytecode);
+    common::add_exception_handlers(&mut buf, 0, &[(0, 4, 8)]);
+    let file = parse(&buf).unwrap();
+    let regions = exception_regions(&file, &buf, 0).unwrap();
+    assert_eq!(regions.len(), 1);
+    assert_eq!(regions[0].kind, HandlerKind::Finally);
+    assert_eq!(regions[0].target_index, Some(4));
+
+    //try { LoadConstZero r0; Throw r0 } catch (r1) { Ret r1 }
+    let mut buf = common::bundle_with_function(&[], 0, 0, &[120, 0, 93, 0, 91, 1, 90, 1]);
+    common::add_exception_handlers(&mut buf, 0, &[(0, 4, 4)]);
+    let file = parse(&buf).unwrap();
+    assert_eq!(
+        exception_regions(&file, &buf, 0).unwrap()[0].kind,
+        HandlerKind::Catch
+    );
+
+    //the handler is followed past its conditional jump, where it returns instead of rethrowing:
+    //Catch r1; JmpTrue 0xB, r0; Throw r1; Ret r0
+    #[rustfmt::skip]
+    let bytecode = [120, 0, 93, 0, 91, 1, 142, 5, 0, 93, 1, 90, 0];
+    let mut buf = common::bundle_with_function(&[], 0, 0, &bytecode);
+    common::add_exception_handlers(&mut buf, 0, &[(0, 4, 4)]);
+    let file = parse(&buf).unwrap();
+    assert_eq!(
+        exception_regions(&file, &buf, 0).unwrap()[0].kind,
+        HandlerKind::Catch
+    );
+}
+
+#[test]
+fn try_finally_generated() {
+    #[rustfmt::skip]
+    let bytecode = [
+        120, 0, //LoadConstZero r0, protected
+        120, 2, //LoadConstZero r2, the finally block run when nothing is thrown
+        140, 8, //Jmp 0xC
+        91, 1, //Catch r1
+        120, 2, //LoadConstZero r2, the finally block run when something is thrown
+        93, 1, //Throw r1
+        90, 2, //Ret r2
+    ];
+    let mut buf = common::bundle_with_function(&[], 0, 0, &bytecode);
+    common::add_exception_handlers(&mut buf, 0, &[(0, 2, 6)]);
+    let file = parse(&buf).unwrap();
+    let out = decompile_function(&file, &buf, 0).unwrap().code;
+    assert!(
+        out.contains(
+            "try {\n        const r0 = 0;\n    } finally{\n        r2 = 0;\n    }\n    return r2;"
+        ),
+        "{out}"
+    );
+    assert!(!out.contains("throw"), "{out}");
+
+    //the protected code throwing, the finally block after it is never run
+    let bytecode = [120, 0, 93, 0, 120, 2, 140, 8, 91, 1, 120, 2, 93, 1, 90, 2];
+    let mut buf = common::bundle_with_function(&[], 0, 0, &bytecode);
+    common::add_exception_handlers(&mut buf, 0, &[(0, 4, 8)]);
+    let file = parse(&buf).unwrap();
+    let out = decompile_function(&file, &buf, 0).unwrap().code;
+    assert!(out.contains("throw r0;\n    } finally{\n"), "{out}");
+    assert!(!out.contains("throw r1"), "{out}");
+}
+
+#[test]
+fn single_use_closure_inlined() {
+    #[rustfmt::skip]
+    let functions = [
+        common::TestFunction {
+            name: 0,
+            param_count: 1,
+            bytecode: &[
+                48, 0, //GetGlobalObject r0
+                53, 1, 0, 0, 0, 0, //GetById r1, r0, 0, "addEventListener"
+                113, 2, 1, 0, //LoadConstString r2, "click"
+                98, 3, 0, 1, 0, //CreateClosure r3, r0, 1
+                82, 4, 1, 0, 2, 3, //Call3 r4, r1, r0, r2, r3
+                98, 5, 0, 2, 0, //CreateClosure r5, r0, 2
+                82, 4, 1, 0, 2, 5, //Call3 r4, r1, r0, r2, r5
+                82, 4, 1, 0, 2, 5, //Call3 r4, r1, r0, r2, r5
+                90, 4, //Ret r4
+            ],
+        },
+        //LoadConstZero r0; Ret r0
+        common::TestFunction {
+            name: 0,
+            param_count: 0,
+            bytecode: &[120, 0, 90, 0],
+        },
+        //LoadThisNS r0; Ret r0
+        common::TestFunction {
+            name: 0,
+            param_count: 0,
+            bytecode: &[122, 0, 90, 0],
+        },
+    ];
+    let buf = common::bundle_with_functions(&["addEventListener", "click"], &functions);
+    let file = parse(&buf).unwrap();
+    let options = DecompileOptions {
+        inline_closures: true,
+        ..Default::default()
+    };
+
+    let out = decompile_function_with(&file, &buf, 0, &options)
+        .unwrap()
+        .code;
+    assert!(out.contains("r4 = r1.bind(r0)(r2, ()=>{"), "{out}");
+    assert!(out.contains("const r0 = 0;"), "{out}");
+    //created once but passed twice, so it stays a reference
+    assert!(out.contains("const r5 = f2;"), "{out}");
+    assert!(!out.contains("f1"), "{out}");
+
+    let functions = decompile_all(&file, &buf, &options, 1);
+    assert_eq!(functions[0].as_ref().unwrap().inlined, [1]);
+}
@@ -0,0 +1,268 @@
+//! Checks the text `instructions_listing` writes against a reference tokenizer of its grammar,
+//! which an assembler reading the listing back can start from.
+
+use hermes_dec::{disassemble_function, instructions_listing, parse};
+
+mod common;
+
+/// A token of a listing line
+#[derive(Debug, PartialEq)]
+enum Token {
+    /// The offset starting the line, or a jump target
+    Offset(u32),
+    Opcode(String),
+    Register(u32),
+    /// The string and its index in the string table
+    String(String, u32),
+    /// The name the function was declared with, if it's named, and its id
+    Function(Option<String>, u32),
+    BigInt(u32),
+    Integer(i64),
+    Double(f64),
+    Bool(bool),
+}
+
+fn offset(text: &str) -> Result<u32, String> {
+    let digits = text
+        .strip_prefix("0x")
+        .ok_or(format!("expected an offset: {text}"))?;
+    if digits.is_empty()
+        || !digits
+            .bytes()
+            .all(|b| b.is_ascii_digit() || b.is_ascii_uppercase())
+    {
+        return Err(format!("expected uppercase hex digits: {text}"));
+    }
+    u32::from_str_radix(digits, 16).map_err(|e| e.to_string())
+}
+
+fn index(text: &str) -> Result<u32, String> {
+    if text.is_empty() || !text.bytes().all(|b| b.is_ascii_digit()) {
+        return Err(format!("expected a table index: {text}"));
+    }
+    text.parse().map_err(|e| format!("{e}: {text}"))
+}
+
+fn is_identifier_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '_' || c == '$'
+}
+
+//an operand at the start of `text`, and what follows it
+fn operand(text: &str) -> Result<(Token, &str), String> {
+    if text.starts_with('"') {
+        let mut strings = serde_json::Deserializer::from_str(text).into_iter::<String>();
+        let string = strings.next().unwrap().map_err(|e| e.to_string())?;
+        let rest = text[strings.byte_offset()..]
+            .strip_prefix('@')
+            .ok_or(format!("expected the string's index: {text}"))?;
+        let end = rest.find(',').unwrap_or(rest.len());
+        return Ok((Token::String(string, index(&rest[..end])?), &rest[end..]));
+    }
+    let end = text.find(',').unwrap_or(text.len());
+    let (word, rest) = text.split_at(end);
+    let token = if word.starts_with("0x") {
+        Token::Offset(offset(word)?)
+    } else if let Some(table_index) = word.strip_prefix("bigint@") {
+        Token::BigInt(index(table_index)?)
+    } else if word.starts_with(|c: char| c == '-' || c.is_ascii_digit()) && word != "-inf" {
+        if word.contains(['.', 'e']) {
+            Token::Double(word.parse().map_err(|e| format!("{e}: {word}"))?)
+        } else {
+            Token::Integer(word.parse().map_err(|e| format!("{e}: {word}"))?)
+        }
+    } else {
+        match word {
+            "true" => Token::Bool(true),
+            "false" => Token::Bool(false),
+            "NaN" | "inf" | "-inf" => Token::Double(word.parse().unwrap()),
+            _ if !word.chars().all(is_identifier_char) => {
+                return Err(format!("unexpected operand: {word}"))
+            }
+            _ if word.starts_with('r') && index(&word[1..]).is_ok() => {
+                Token::Register(index(&word[1..])?)
+            }
+            _ => {
+                let (name, id) = word
+                    .rsplit_once('f')
+                    .ok_or(format!("unexpected operand: {word}"))?;
+                let name = match name {
+                    "" => None,
+                    _ => Some(
+                        name.strip_suffix('_')
+                            .ok_or(format!("unexpected operand: {word}"))?,
+                    ),
+                };
+                Token::Function(name.map(str::to_string), index(id)?)
+            }
+        }
+    };
+    Ok((token, rest))
+}
+
+/// Splits a line of a listing into its tokens, failing on anything off the grammar
+fn tokenize(line: &str) -> Result<Vec<Token>, String> {
+    let (line_offset, rest) = line
+        .split_once(": ")
+        .ok_or(format!("expected an offset: {line}"))?;
+    let (opcode, operands) = match rest.split_once(' ') {
+        Some((opcode, operands)) => (opcode, Some(operands)),
+        None => (rest, None),
+    };
+    if opcode.is_empty() || !opcode.chars().all(is_identifier_char) {
+        return Err(format!("expected an opcode: {line}"));
+    }
+    let mut tokens = vec![
+        Token::Offset(offset(line_offset)?),
+        Token::Opcode(opcode.to_string()),
+    ];
+    if let Some(mut rest) = operands {
+        loop {
+            let (token, after) = operand(rest)?;
+            tokens.push(token);
+            if after.is_empty() {
+                break;
+            }
+            rest = after
+                .strip_prefix(", ")
+                .filter(|next| !next.is_empty())
+                .ok_or(format!("expected \", \" between operands: {line}"))?;
+        }
+    }
+    Ok(tokens)
+}
+
+/// Writes `tokens` back as a line, the way the listing is expected to write them
+fn write(tokens: &[Token]) -> String {
+    let words: Vec<String> = tokens
+        .iter()
+        .map(|token| match token {
+            Token::Offset(offset) => format!("{offset:#X}"),
+            Token::Opcode(opcode) => opcode.clone(),
+            Token::Register(register) => format!("r{register}"),
+            Token::String(string, index) => {
+                format!("{}@{index}", serde_json::Value::from(&**string))
+            }
+            Token::Function(Some(name), id) => format!("{name}_f{id}"),
+            Token::Function(None, id) => format!("f{id}"),
+            Token::BigInt(index) => format!("bigint@{index}"),
+            Token::Integer(value) => value.to_string(),
+            Token::Double(value) => format!("{value:?}"),
+            Token::Bool(value) => value.to_string(),
+        })
+        .collect();
+    match words.as_slice() {
+        [line_offset, opcode] => format!("{line_offset}: {opcode}"),
+        [line_offset, opcode, operands @ ..] => {
+            format!("{line_offset}: {opcode} {}", operands.join(", "))
+        }
+        _ => unreachable!(),
+    }
+}
+
+fn sample_bundle() -> Vec<u8> {
+    let mut bytecode = vec![
+        118, 0, //LoadConstTrue r0
+        142, 38, 0, //JmpTrue 0x28, r0
+        53, 1, 0, 3, 0, 0, //GetById r1, r0, 3, "foo"
+        113, 2, 1, 0, //LoadConstString r2, "say \"hi\"\n"
+        98, 3, 0, 1, 0, //CreateClosure r3, r0, 1
+        110, 4, //LoadConstDouble r4, 1.5
+    ];
+    bytecode.extend_from_slice(&1.5f64.to_le_bytes());
+    bytecode.extend_from_slice(&[109, 5]); //LoadConstInt r5, -5
+    bytecode.extend_from_slice(&(-5i32).to_le_bytes());
+    bytecode.extend_from_slice(&[
+        111, 6, 2, 0, //LoadConstBigInt r6, 2
+        90, 0, //Ret r0
+    ]);
+    let functions = [
+        common::TestFunction {
+            name: 0,
+            param_count: 0,
+            bytecode: &bytecode,
+        },
+        //LoadConstZero r0; Ret r0
+        common::TestFunction {
+            name: 2,
+            param_count: 0,
+            bytecode: &[120, 0, 90, 0],
+        },
+    ];
+    common::bundle_with_functions(&["foo", "say \"hi\"\n", "render"], &functions)
+}
+
+#[test]
+fn listing_format_is_stable() {
+    let buf = sample_bundle();
+    let file = parse(&buf).unwrap();
+    let listing = instructions_listing(&file, &buf, 0, false, ..).unwrap();
+    assert_eq!(
+        listing,
+        concat!(
+            "0x0: LoadConstTrue r0\n",
+            "0x2: JmpTrue 0x28, r0\n",
+            "0x5: GetById r1, r0, 3, \"foo\"@0\n",
+            "0xB: LoadConstString r2, \"say \\\"hi\\\"\\n\"@1\n",
+            "0xF: CreateClosure r3, r0, render_f1\n",
+            "0x14: LoadConstDouble r4, 1.5\n",
+            "0x1E: LoadConstInt r5, -5\n",
+            "0x24: LoadConstBigInt r6, bigint@2\n",
+            "0x28: Ret r0\n",
+        )
+    );
+}
+
+#[test]
+fn listing_parses_back() {
+    let buf = sample_bundle();
+    let file = parse(&buf).unwrap();
+    let listing = instructions_listing(&file, &buf, 0, false, ..).unwrap();
+    let lines: Vec<Vec<Token>> = listing
+        .lines()
+        .map(|line| {
+            let tokens = tokenize(line).unwrap_or_else(|e| panic!("{e}"));
+            assert_eq!(write(&tokens), line);
+            tokens
+        })
+        .collect();
+    let instructions = disassemble_function(&file, &buf, 0).unwrap();
+    assert_eq!(lines.len(), instructions.len());
+    for (tokens, info) in lines.iter().zip(&instructions) {
+        assert_eq!(tokens[0], Token::Offset(info.offset));
+        assert_eq!(tokens[1], Token::Opcode(info.instruction.name()));
+    }
+    assert_eq!(lines[1][2], Token::Offset(0x28));
+    assert_eq!(lines[3][3], Token::String("say \"hi\"\n".to_string(), 1));
+    assert_eq!(lines[4][4], Token::Function(Some("render".to_string()), 1));
+    assert_eq!(lines[5][3], Token::Double(1.5));
+    assert_eq!(lines[6][3], Token::Integer(-5));
+    assert_eq!(lines[7][3], Token::BigInt(2));
+}
+
+#[test]
+fn listing_absolute_range() {
+    let buf = sample_bundle();
+    let file = parse(&buf).unwrap();
+    let function_offset = file
+        .function_header(0, &mut std::io::Cursor::new(&buf[..]))
+        .unwrap()
+        .unwrap()
+        .offset();
+    let listing = instructions_listing(
+        &file,
+        &buf,
+        0,
+        true,
+        function_offset + 2..=function_offset + 5,
+    )
+    .unwrap();
+    assert_eq!(
+        listing,
+        format!(
+            "{:#X}: JmpTrue {:#X}, r0\n{:#X}: GetById r1, r0, 3, \"foo\"@0\n",
+            function_offset + 2,
+            function_offset + 0x28,
+            function_offset + 5
+        )
+    );
+}
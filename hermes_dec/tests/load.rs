@@ -0,0 +1,37 @@
+use hermes_dec::{load, parse, BytecodeFile};
+
+mod common;
+
+fn function_summaries(f: &BytecodeFile) -> Vec<(Option<&str>, u32, u32, u32)> {
+    f.function_headers
+        .iter()
+        .map(|header| {
+            (
+                f.get_string(header.function_name()),
+                header.offset(),
+                header.bytecode_size_in_bytes(),
+                header.param_count(),
+            )
+        })
+        .collect()
+}
+
+#[test]
+fn load_matches_read_to_end() {
+    let bytes = common::bundle_with_function(&["global", "main"], 1, 2, &[120, 0, 90, 0]);
+    let path = std::env::temp_dir().join(format!("hermes_dec_load_{}.hbc", std::process::id()));
+    std::fs::write(&path, &bytes).unwrap();
+
+    let loaded = load(&path).unwrap();
+    let read = std::fs::read(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    //without the feature load reads the file too, and this only checks it's read whole
+    #[cfg(feature = "mmap")]
+    assert!(matches!(loaded, hermes_dec::Bundle::Mapped(_)));
+    assert_eq!(&*loaded, read.as_slice());
+    assert_eq!(
+        function_summaries(&parse(&loaded).unwrap()),
+        function_summaries(&parse(&read).unwrap())
+    );
+}
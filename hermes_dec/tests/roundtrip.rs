@@ -0,0 +1,94 @@
+//! Compiles JS snippets with `hermesc`, decompiles them and checks that running the decompiled
+//! code gives the same result as running the snippet. Needs `hermesc` (or the path in the
+//! `HERMESC` env var) and `node`, and is skipped when either is missing.
+
+use std::path::PathBuf;
+use std::process::Command;
+
+use hermes_dec::{decompile_all, parse, DecompileOptions};
+
+fn hermesc() -> PathBuf {
+    std::env::var_os("HERMESC").map_or_else(|| "hermesc".into(), PathBuf::from)
+}
+
+fn available(program: &PathBuf, version_arg: &str) -> bool {
+    Command::new(program).arg(version_arg).output().is_ok()
+}
+
+/// Runs `source` as a script with node and returns what the last expression evaluated to
+fn evaluate(source: &str) -> String {
+    let output = Command::new("node").arg("-p").arg(source).output().unwrap();
+    assert!(
+        output.status.success(),
+        "{}\n{source}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    String::from_utf8(output.stdout).unwrap()
+}
+
+fn roundtrip(name: &str, snippet: &str) {
+    let hermesc = hermesc();
+    if !available(&hermesc, "-version") || !available(&"node".into(), "--version") {
+        eprintln!("skipping roundtrip of {name}: hermesc or node isn't installed");
+        return;
+    }
+    let dir = std::env::temp_dir();
+    let js = dir.join(format!(
+        "hermes_dec_roundtrip_{name}_{}.js",
+        std::process::id()
+    ));
+    let hbc = js.with_extension("hbc");
+    std::fs::write(&js, snippet).unwrap();
+    let status = Command::new(&hermesc)
+        .arg("-emit-binary")
+        .arg("-out")
+        .arg(&hbc)
+        .arg(&js)
+        .status()
+        .unwrap();
+    let buf = std::fs::read(&hbc);
+    std::fs::remove_file(&js).unwrap();
+    let _ = std::fs::remove_file(&hbc);
+    assert!(status.success(), "hermesc failed on {name}");
+    let buf = buf.unwrap();
+
+    let file = parse(&buf).unwrap();
+    let mut decompiled = String::new();
+    for result in decompile_all(&file, &buf, &DecompileOptions::default(), 1) {
+        decompiled.push_str(&result.unwrap().source);
+        decompiled.push('\n');
+    }
+    //the global function returns the value of the script's last expression
+    decompiled.push_str(&format!("f{}();\n", file.header.global_code_index));
+
+    assert_eq!(evaluate(&decompiled), evaluate(snippet), "{decompiled}");
+}
+
+#[test]
+fn arithmetic() {
+    roundtrip("arithmetic", "var a = 6; var b = 7; a * b + 1 - a / b;");
+}
+
+#[test]
+fn loop_sum() {
+    roundtrip(
+        "loop_sum",
+        "var sum = 0; for (var i = 0; i < 10; i++) { if (i % 3) { sum += i; } } sum;",
+    );
+}
+
+#[test]
+fn object_properties() {
+    roundtrip(
+        "object_properties",
+        "var o = {x: 1, y: true}; o.z = o.x + 1; o.flag = !o.y; JSON.stringify(o);",
+    );
+}
+
+#[test]
+fn for_in_keys() {
+    roundtrip(
+        "for_in_keys",
+        "var o = {a: 1, b: 2}; var keys = ''; for (var k in o) { keys += k + o[k]; } keys;",
+    );
+}